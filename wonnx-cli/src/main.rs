@@ -175,6 +175,15 @@ fn print_output(
                         }
                     }
                 }
+                wonnx::utils::OutputTensor::F16(fs) => {
+                    for i in fs {
+                        if print_newlines {
+                            println!("{:.3}", i);
+                        } else {
+                            print!("{:.3} ", i);
+                        }
+                    }
+                }
                 wonnx::utils::OutputTensor::I32(ints) => {
                     for i in ints {
                         if print_newlines {
@@ -202,6 +211,51 @@ fn print_output(
                         }
                     }
                 }
+                wonnx::utils::OutputTensor::F64(values) => {
+                    for i in values {
+                        if print_newlines {
+                            println!("{:.3}", i);
+                        } else {
+                            print!("{:.3} ", i);
+                        }
+                    }
+                }
+                wonnx::utils::OutputTensor::I8(values) => {
+                    for i in values {
+                        if print_newlines {
+                            println!("{}", i);
+                        } else {
+                            print!("{} ", i);
+                        }
+                    }
+                }
+                wonnx::utils::OutputTensor::U16(values) => {
+                    for i in values {
+                        if print_newlines {
+                            println!("{}", i);
+                        } else {
+                            print!("{} ", i);
+                        }
+                    }
+                }
+                wonnx::utils::OutputTensor::I16(values) => {
+                    for i in values {
+                        if print_newlines {
+                            println!("{}", i);
+                        } else {
+                            print!("{} ", i);
+                        }
+                    }
+                }
+                wonnx::utils::OutputTensor::Bool(values) => {
+                    for i in values {
+                        if print_newlines {
+                            println!("{}", i);
+                        } else {
+                            print!("{} ", i);
+                        }
+                    }
+                }
             }
         }
     }
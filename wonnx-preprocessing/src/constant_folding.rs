@@ -5,6 +5,7 @@ use thiserror::Error;
 
 use wonnx::{
     constant_of_shape_output,
+    half::f16,
     onnx::{
         GraphProto, NodeProto, TensorProto, TensorShapeProto, TensorShapeProto_Dimension,
         TypeProto, TypeProto_Tensor, ValueInfoProto,
@@ -52,6 +53,9 @@ pub(crate) async fn calculate_constant_node_outputs<'a>(
 
             let output_tensor = match (input_tensor, cast_to_type) {
                 (InputTensor::F32(v), ScalarType::F32) => OutputTensor::F32(v.to_vec()),
+                (InputTensor::F32(v), ScalarType::F16) => {
+                    OutputTensor::F16(v.iter().map(|x| f16::from_f32(*x)).collect())
+                }
                 (InputTensor::F32(v), ScalarType::I64) => {
                     OutputTensor::I64(v.iter().map(|x| *x as i64).collect())
                 }
@@ -61,9 +65,25 @@ pub(crate) async fn calculate_constant_node_outputs<'a>(
                 (InputTensor::F32(v), ScalarType::U8) => {
                     OutputTensor::U8(v.iter().map(|x| *x as u8).collect())
                 }
+                (InputTensor::F16(v), ScalarType::F32) => {
+                    OutputTensor::F32(v.iter().map(|x| x.to_f32()).collect())
+                }
+                (InputTensor::F16(v), ScalarType::F16) => OutputTensor::F16(v.to_vec()),
+                (InputTensor::F16(v), ScalarType::I64) => {
+                    OutputTensor::I64(v.iter().map(|x| x.to_f32() as i64).collect())
+                }
+                (InputTensor::F16(v), ScalarType::I32) => {
+                    OutputTensor::I32(v.iter().map(|x| x.to_f32() as i32).collect())
+                }
+                (InputTensor::F16(v), ScalarType::U8) => {
+                    OutputTensor::U8(v.iter().map(|x| x.to_f32() as u8).collect())
+                }
                 (InputTensor::I32(v), ScalarType::F32) => {
                     OutputTensor::F32(v.iter().map(|x| *x as f32).collect())
                 }
+                (InputTensor::I32(v), ScalarType::F16) => {
+                    OutputTensor::F16(v.iter().map(|x| f16::from_f32(*x as f32)).collect())
+                }
                 (InputTensor::I32(v), ScalarType::I64) => {
                     OutputTensor::I64(v.iter().map(|x| *x as i64).collect())
                 }
@@ -74,6 +94,9 @@ pub(crate) async fn calculate_constant_node_outputs<'a>(
                 (InputTensor::I64(v), ScalarType::F32) => {
                     OutputTensor::F32(v.iter().map(|x| *x as f32).collect())
                 }
+                (InputTensor::I64(v), ScalarType::F16) => {
+                    OutputTensor::F16(v.iter().map(|x| f16::from_f32(*x as f32)).collect())
+                }
                 (InputTensor::I64(v), ScalarType::I64) => OutputTensor::I64(v.to_vec()),
                 (InputTensor::I64(v), ScalarType::I32) => {
                     OutputTensor::I32(v.iter().map(|x| *x as i32).collect())
@@ -84,6 +107,9 @@ pub(crate) async fn calculate_constant_node_outputs<'a>(
                 (InputTensor::U8(v), ScalarType::F32) => {
                     OutputTensor::F32(v.iter().map(|x| *x as f32).collect())
                 }
+                (InputTensor::U8(v), ScalarType::F16) => {
+                    OutputTensor::F16(v.iter().map(|x| f16::from(*x)).collect())
+                }
                 (InputTensor::U8(v), ScalarType::I64) => {
                     OutputTensor::I64(v.iter().map(|x| *x as i64).collect())
                 }
@@ -91,6 +117,10 @@ pub(crate) async fn calculate_constant_node_outputs<'a>(
                     OutputTensor::I32(v.iter().map(|x| *x as i32).collect())
                 }
                 (InputTensor::U8(v), ScalarType::U8) => OutputTensor::U8(v.to_vec()),
+                // Other combinations involve types that are less common; these are converted through f64
+                (input_tensor, cast_to_type) => {
+                    cast_values(input_values(input_tensor), cast_to_type)
+                }
             };
 
             Some(vec![output_tensor])
@@ -200,6 +230,39 @@ pub(crate) async fn calculate_constant_node_outputs<'a>(
     })
 }
 
+/// The values of a tensor as f64 (booleans become 0 or 1)
+fn input_values(input: &InputTensor) -> Vec<f64> {
+    match input {
+        InputTensor::F32(v) => v.iter().map(|x| *x as f64).collect(),
+        InputTensor::F16(v) => v.iter().map(|x| x.to_f64()).collect(),
+        InputTensor::F64(v) => v.to_vec(),
+        InputTensor::I32(v) => v.iter().map(|x| *x as f64).collect(),
+        InputTensor::I64(v) => v.iter().map(|x| *x as f64).collect(),
+        InputTensor::U8(v) => v.iter().map(|x| *x as f64).collect(),
+        InputTensor::I8(v) => v.iter().map(|x| *x as f64).collect(),
+        InputTensor::U16(v) => v.iter().map(|x| *x as f64).collect(),
+        InputTensor::I16(v) => v.iter().map(|x| *x as f64).collect(),
+        InputTensor::Bool(v) => v.iter().map(|x| *x as u8 as f64).collect(),
+    }
+}
+
+/// Converts values to a tensor of the specified type (non-zero values become true when casting to bool)
+fn cast_values(values: Vec<f64>, scalar_type: ScalarType) -> OutputTensor {
+    let values = values.into_iter();
+    match scalar_type {
+        ScalarType::F32 => OutputTensor::F32(values.map(|x| x as f32).collect()),
+        ScalarType::F16 => OutputTensor::F16(values.map(f16::from_f64).collect()),
+        ScalarType::F64 => OutputTensor::F64(values.collect()),
+        ScalarType::I32 => OutputTensor::I32(values.map(|x| x as i32).collect()),
+        ScalarType::I64 => OutputTensor::I64(values.map(|x| x as i64).collect()),
+        ScalarType::U8 => OutputTensor::U8(values.map(|x| x as u8).collect()),
+        ScalarType::I8 => OutputTensor::I8(values.map(|x| x as i8).collect()),
+        ScalarType::U16 => OutputTensor::U16(values.map(|x| x as u16).collect()),
+        ScalarType::I16 => OutputTensor::I16(values.map(|x| x as i16).collect()),
+        ScalarType::Bool => OutputTensor::Bool(values.map(|x| x != 0.0).collect()),
+    }
+}
+
 fn input_to_value_info(shape: &Shape, name: &str) -> ValueInfoProto {
     let mut ttp = TypeProto_Tensor::new();
     ttp.set_elem_type(shape.data_type.to_datatype().value());
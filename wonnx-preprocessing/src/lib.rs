@@ -3,6 +3,7 @@ use wonnx::utils::InputTensor;
 
 pub mod constant_folding;
 pub mod image;
+pub mod session;
 pub mod shape_inference;
 pub mod text;
 
@@ -0,0 +1,319 @@
+//! Inference session for models with dynamic (parametrized) input dimensions
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+use wonnx::{
+    onnx::{ModelProto, TypeProto_oneof_value, ValueInfoProto},
+    utils::{get_opset_version, InputTensor, OpsetError, OutputTensor},
+    Session, SessionConfig, SessionError,
+};
+
+use crate::shape_inference::{apply_dynamic_dimensions, infer_shapes, ShapeInferenceError};
+
+#[derive(Error, Debug)]
+pub enum DynamicSessionError {
+    #[error("the model did not reference a specific version of the ONNX opset")]
+    UnknownOnnxOpsetVersion,
+
+    #[error("opset error: {0}")]
+    OpsetError(#[from] OpsetError),
+
+    #[error("inference input not found: '{0}'")]
+    InferenceInputMissing(String),
+
+    #[error("could not determine the value of dimension parameter '{0}'; specify it explicitly")]
+    UnresolvedDimension(String),
+
+    #[error(
+        "inference input '{name}' has {actual} elements, which does not match its shape {dims:?}"
+    )]
+    InvalidInputLength {
+        name: String,
+        dims: Vec<i64>,
+        actual: usize,
+    },
+
+    #[error("shape inference failed: {0}")]
+    ShapeInferenceError(#[from] ShapeInferenceError),
+
+    #[error("session error: {0}")]
+    SessionError(#[from] SessionError),
+}
+
+/// A dimension of a model input: either a fixed value or a parameter (e.g. `batch_size`)
+#[derive(Clone, Debug)]
+enum Dimension {
+    Value(i64),
+    Param(String),
+}
+
+/// An inference session for a model whose inputs have dynamic (parametrized) dimensions. The model is prepared (dynamic
+/// dimensions are replaced and shapes are inferred) and compiled the first time it is used with a particular set of
+/// input shapes. Compiled variants are cached, keyed by the input shapes.
+pub struct DynamicSession {
+    model: ModelProto,
+    config: SessionConfig,
+    onnx_opset_version: i64,
+    inputs: Vec<(String, Vec<Dimension>)>,
+    variants: Mutex<HashMap<Vec<Vec<i64>>, Arc<Session>>>,
+}
+
+impl DynamicSession {
+    /// Create a session for the specified model. Variants of the model are created using the specified config.
+    pub fn new(model: ModelProto, config: SessionConfig) -> Result<Self, DynamicSessionError> {
+        let onnx_opset_version =
+            get_opset_version(&model)?.ok_or(DynamicSessionError::UnknownOnnxOpsetVersion)?;
+
+        // Inputs that are initializers are not supplied at inference time
+        let graph = model.get_graph();
+        let inputs = graph
+            .get_input()
+            .iter()
+            .filter(|input| {
+                !graph
+                    .get_initializer()
+                    .iter()
+                    .any(|i| i.get_name() == input.get_name())
+            })
+            .map(|input| (input.get_name().to_string(), input_dimensions(input)))
+            .collect();
+
+        Ok(DynamicSession {
+            model,
+            config,
+            onnx_opset_version,
+            inputs,
+            variants: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Perform inference. The values of dimension parameters are derived from the length of the inputs, which is only
+    /// possible when an input has a single dimension parameter (use [`DynamicSession::run_with_dimensions`] otherwise).
+    pub async fn run<'a>(
+        &self,
+        inputs: &HashMap<String, InputTensor<'a>>,
+    ) -> Result<HashMap<String, OutputTensor>, DynamicSessionError> {
+        self.run_with_dimensions(inputs, &HashMap::new()).await
+    }
+
+    /// Perform inference, using the specified values for dimension parameters. Values for parameters that are not
+    /// specified are derived from the length of the inputs.
+    pub async fn run_with_dimensions<'a>(
+        &self,
+        inputs: &HashMap<String, InputTensor<'a>>,
+        dynamic_dims: &HashMap<String, i64>,
+    ) -> Result<HashMap<String, OutputTensor>, DynamicSessionError> {
+        let dynamic_dims = self.resolve_dimensions(inputs, dynamic_dims)?;
+        let session = self.session_for(&dynamic_dims).await?;
+        Ok(session.run(inputs).await?)
+    }
+
+    /// Returns the session for the variant of the model with the specified values for dimension parameters, preparing
+    /// and compiling it if it was not used before.
+    pub async fn session_for(
+        &self,
+        dynamic_dims: &HashMap<String, i64>,
+    ) -> Result<Arc<Session>, DynamicSessionError> {
+        let input_shapes: Vec<Vec<i64>> = self
+            .inputs
+            .iter()
+            .map(|(_, dims)| concrete_dimensions(dims, dynamic_dims))
+            .collect::<Result<_, _>>()?;
+
+        if let Some(session) = self.variants.lock().unwrap().get(&input_shapes) {
+            return Ok(session.clone());
+        }
+
+        log::info!("preparing model variant for input shapes {input_shapes:?}");
+        let mut model = self.model.clone();
+        let graph = model.mut_graph();
+        apply_dynamic_dimensions(graph, dynamic_dims);
+        infer_shapes(graph, true, self.onnx_opset_version).await?;
+        let session = Arc::new(Session::from_model_with_config(model, &self.config).await?);
+
+        // Another task may have compiled the same variant in the meantime; use whichever was stored first
+        Ok(self
+            .variants
+            .lock()
+            .unwrap()
+            .entry(input_shapes)
+            .or_insert(session)
+            .clone())
+    }
+
+    /// The number of variants of the model that have been compiled
+    pub fn variant_count(&self) -> usize {
+        self.variants.lock().unwrap().len()
+    }
+
+    /// Determine the values of all dimension parameters that appear in the inputs of the model, given the explicitly
+    /// specified values and the inference inputs.
+    fn resolve_dimensions(
+        &self,
+        inputs: &HashMap<String, InputTensor>,
+        dynamic_dims: &HashMap<String, i64>,
+    ) -> Result<HashMap<String, i64>, DynamicSessionError> {
+        let mut resolved = dynamic_dims.clone();
+
+        // Repeatedly look for an input with a single unknown parameter, which then follows from the input's length
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for (name, dims) in &self.inputs {
+                let input = inputs
+                    .get(name)
+                    .ok_or_else(|| DynamicSessionError::InferenceInputMissing(name.to_string()))?;
+
+                let mut known_product = 1;
+                let mut unknown = vec![];
+                for dim in dims {
+                    match dim {
+                        Dimension::Value(value) => known_product *= value,
+                        Dimension::Param(param) => match resolved.get(param) {
+                            Some(value) => known_product *= value,
+                            None => unknown.push(param),
+                        },
+                    }
+                }
+
+                if let [param] = unknown[..] {
+                    let length = input_length(input) as i64;
+                    if known_product == 0 || length % known_product != 0 {
+                        return Err(DynamicSessionError::InvalidInputLength {
+                            name: name.to_string(),
+                            dims: concrete_dimensions(dims, &resolved).unwrap_or_default(),
+                            actual: length as usize,
+                        });
+                    }
+                    resolved.insert(param.to_string(), length / known_product);
+                    progress = true;
+                }
+            }
+        }
+
+        // Check that all inputs match the resulting shapes
+        for (name, dims) in &self.inputs {
+            let dims = concrete_dimensions(dims, &resolved)?;
+            let length = input_length(&inputs[name]);
+            if dims.iter().product::<i64>() as usize != length {
+                return Err(DynamicSessionError::InvalidInputLength {
+                    name: name.to_string(),
+                    dims,
+                    actual: length,
+                });
+            }
+        }
+
+        Ok(resolved)
+    }
+}
+
+fn input_dimensions(input: &ValueInfoProto) -> Vec<Dimension> {
+    match &input.get_field_type().value {
+        Some(TypeProto_oneof_value::tensor_type(tensor_type)) => tensor_type
+            .get_shape()
+            .get_dim()
+            .iter()
+            .map(|dim| {
+                if dim.has_dim_value() {
+                    Dimension::Value(dim.get_dim_value())
+                } else {
+                    Dimension::Param(dim.get_dim_param().to_string())
+                }
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn concrete_dimensions(
+    dims: &[Dimension],
+    dynamic_dims: &HashMap<String, i64>,
+) -> Result<Vec<i64>, DynamicSessionError> {
+    dims.iter()
+        .map(|dim| match dim {
+            Dimension::Value(value) => Ok(*value),
+            Dimension::Param(param) => dynamic_dims
+                .get(param)
+                .copied()
+                .ok_or_else(|| DynamicSessionError::UnresolvedDimension(param.to_string())),
+        })
+        .collect()
+}
+
+fn input_length(input: &InputTensor) -> usize {
+    match input {
+        InputTensor::F32(data) => data.len(),
+        InputTensor::F16(data) => data.len(),
+        InputTensor::I32(data) => data.len(),
+        InputTensor::I64(data) => data.len(),
+        InputTensor::U8(data) => data.len(),
+        InputTensor::F64(data) => data.len(),
+        InputTensor::I8(data) => data.len(),
+        InputTensor::U16(data) => data.len(),
+        InputTensor::I16(data) => data.len(),
+        InputTensor::Bool(data) => data.len(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use wonnx::{
+        onnx::{TensorShapeProto_Dimension, ValueInfoProto},
+        utils::{graph, model, node, tensor, OutputTensor},
+        Backend, SessionConfig,
+    };
+
+    use super::{DynamicSession, DynamicSessionError};
+
+    fn parametrized_tensor(name: &str, param: &str, size: i64) -> ValueInfoProto {
+        let mut value_info = tensor(name, &[1, size]);
+        let mut dim = TensorShapeProto_Dimension::new();
+        dim.set_dim_param(param.to_string());
+        value_info
+            .mut_field_type()
+            .mut_tensor_type()
+            .mut_shape()
+            .mut_dim()[0] = dim;
+        value_info
+    }
+
+    #[test]
+    pub fn test_dynamic_session() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // Model: X (N x 4) -> Neg -> Y (N x 4)
+        let model = model(graph(
+            vec![parametrized_tensor("X", "N", 4)],
+            vec![parametrized_tensor("Y", "N", 4)],
+            vec![],
+            vec![],
+            vec![node(vec!["X"], vec!["Y"], "neg", "Neg", vec![])],
+        ));
+        // The CPU backend is used so that this test does not require a GPU
+        let config = SessionConfig::new().with_backend(Backend::Cpu);
+        let session = DynamicSession::new(model, config).unwrap();
+
+        for n in [2, 3, 2] {
+            let data: Vec<f32> = (0..(n * 4)).map(|x| x as f32).collect();
+            let inputs = HashMap::from([("X".to_string(), data.as_slice().into())]);
+            let result = pollster::block_on(session.run(&inputs)).unwrap();
+            let expected: Vec<f32> = data.iter().map(|x| -x).collect();
+            assert_eq!(result["Y"], OutputTensor::F32(expected));
+        }
+        assert_eq!(session.variant_count(), 2);
+
+        // Input length that does not fit the shape
+        let data = vec![0.0f32; 7];
+        let inputs = HashMap::from([("X".to_string(), data.as_slice().into())]);
+        assert!(matches!(
+            pollster::block_on(session.run(&inputs)),
+            Err(DynamicSessionError::InvalidInputLength { .. })
+        ));
+    }
+}
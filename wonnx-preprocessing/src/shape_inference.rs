@@ -10,6 +10,7 @@ use wonnx::{
     utils::{
         AttributeNotFoundError, DataTypeError, InputTensor, NodeAttributes, ScalarType, Shape,
     },
+    EinsumEquation,
 };
 
 use crate::constant_folding::{calculate_constant_node_outputs, ConstantFoldingError};
@@ -34,6 +35,26 @@ fn div_ceil(num: i64, div: i64) -> i64 {
     num / div + (num % div != 0) as i64
 }
 
+/// Reads the axis attribute of a node and resolves negative values relative to the rank of the input
+fn normalized_axis_attribute(
+    node: &NodeProto,
+    input_shape: &Shape,
+    default_axis: i64,
+) -> Result<usize, ShapeInferenceError> {
+    let rank = input_shape.rank() as i64;
+    let axis: i64 = node
+        .get_attribute_value("axis", Some(default_axis))
+        .map_err(ShapeInferenceError::MissingAttribute)?;
+    let normalized_axis = if axis < 0 { axis + rank } else { axis };
+    if normalized_axis < 0 || normalized_axis >= rank {
+        return Err(ShapeInferenceError::InvalidNode(
+            node.get_name().to_string(),
+            format!("axis {} is out of bounds for input of rank {}", axis, rank),
+        ));
+    }
+    Ok(normalized_axis as usize)
+}
+
 /// Retrieve the value of the initializer with the given name as a vector if i64 values.
 fn static_initializer_value_i64<'a>(
     initializers: &'a HashMap<String, Cow<'a, TensorProto>>,
@@ -291,16 +312,16 @@ pub async fn infer_shapes(
         );
 
         // Do shape inference if this node has at least one output for which the shape is not yet known
-        if node
-            .get_output()
-            .iter()
-            .any(|output_name| !shapes.contains_key(output_name.as_str()))
-        {
+        if node.get_output().iter().any(|output_name| {
+            !output_name.is_empty() && !shapes.contains_key(output_name.as_str())
+        }) {
             log::debug!("node needs shape inference: {}", node.get_name());
 
+            // Optional inputs that are left out have an empty name
             let input_shapes: Vec<&Shape> = node
                 .get_input()
                 .iter()
+                .filter(|name| !name.is_empty())
                 .map(|name| {
                     shapes
                         .get(name)
@@ -337,6 +358,9 @@ pub async fn infer_shapes(
 
             // Cache the inferred shapes and write to model
             for (output_idx, output_name) in node.get_output().iter().enumerate() {
+                if output_name.is_empty() {
+                    continue;
+                }
                 let output_shape = &output_shapes[output_idx];
                 shapes.insert(output_name.clone(), output_shape.clone());
                 let mut vip = ValueInfoProto::new();
@@ -484,10 +508,15 @@ pub(crate) fn infer_output_shapes(
         | (
             "Identity" | "Sqrt" | "Relu" | "LeakyRelu" | "Abs" | "Acos" | "Acosh" | "Asin" | "Sin"
             | "Asinh" | "Atan" | "Atanh" | "Cos" | "Cosh" | "Elu" | "Erf" | "Exp" | "Log" | "Neg"
-            | "Ceil" | "Floor" | "Reciprocal" | "Celu" | "Sign",
+            | "Ceil" | "Floor" | "Reciprocal" | "Celu" | "Sign" | "Gelu",
             1,
             1,
-        ) => Ok(vec![input_shapes[0].clone()]),
+        )
+        | ("LayerNormalization", 1..=3, 1)
+        | ("InstanceNormalization" | "GroupNormalization", 3, 1)
+        | ("LpNormalization", 1, 1)
+        | ("SkipLayerNormalization", 3..=5, 1)
+        | ("BiasGelu", 2, 1) => Ok(vec![input_shapes[0].clone()]),
 
         ("Cast", 1, 1) => {
             let to_value: i64 = node
@@ -591,6 +620,136 @@ pub(crate) fn infer_output_shapes(
             )])
         }
 
+        ("Einsum", 1.., 1) => {
+            let equation: String = node
+                .get_attribute_value("equation", None)
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+            let equation = EinsumEquation::parse(&equation, input_shapes).map_err(|error| {
+                ShapeInferenceError::InvalidNode(node.get_name().to_string(), error.to_string())
+            })?;
+            let dims: Vec<i64> = equation
+                .dims(equation.output())
+                .iter()
+                .map(|dim| *dim as i64)
+                .collect();
+            Ok(vec![Shape::from(input_shapes[0].data_type, &dims)])
+        }
+
+        ("GatherElements", 2, 1) => {
+            // The output has the shape of the indices and the type of the data
+            let r = input_shapes[0].rank() as i64;
+            let axis = node
+                .get_attribute_value("axis", Some(0))
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+            if r < 1 || input_shapes[1].rank() as i64 != r || axis >= r || axis < -r {
+                return Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    format!(
+                        "indices ({}) must have the same rank as data ({}) and axis {axis} must be in range",
+                        input_shapes[1], input_shapes[0]
+                    ),
+                ));
+            }
+            Ok(vec![Shape::from(
+                input_shapes[0].data_type,
+                &input_shapes[1]
+                    .dims
+                    .iter()
+                    .map(|d| *d as i64)
+                    .collect::<Vec<i64>>(),
+            )])
+        }
+
+        ("GatherND", 2, 1) => {
+            // https://github.com/onnx/onnx/blob/main/docs/Operators.md#GatherND
+            let r = input_shapes[0].rank();
+            let q = input_shapes[1].rank();
+            let batch_dims = node
+                .get_attribute_value("batch_dims", Some(0))
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+            if q < 1 || batch_dims < 0 || batch_dims as usize >= q.min(r) {
+                return Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    format!(
+                        "batch_dims {batch_dims} is invalid for data ({}) and indices ({})",
+                        input_shapes[0], input_shapes[1]
+                    ),
+                ));
+            }
+            let batch_dims = batch_dims as usize;
+            let k = input_shapes[1].dim(q - 1) as usize;
+            if k < 1 || batch_dims + k > r {
+                return Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    format!("last dimension of indices ({}) must be between 1 and the rank of data ({}) minus batch_dims", input_shapes[1], input_shapes[0]),
+                ));
+            }
+            let dims: Vec<i64> = input_shapes[1].dims[0..(q - 1)]
+                .iter()
+                .chain(&input_shapes[0].dims[(batch_dims + k)..])
+                .map(|d| *d as i64)
+                .collect();
+            Ok(vec![Shape::from(input_shapes[0].data_type, &dims)])
+        }
+
+        ("ScatterElements" | "ScatterND", 3, 1) => {
+            // The output has the shape and type of the data
+            Ok(vec![input_shapes[0].clone()])
+        }
+
+        ("NonMaxSuppression", 2..=5, 1) => {
+            // Boxes are [batch, box, 4] and scores are [batch, class, box]. The number of selected boxes depends on the
+            // data; the output shape is an upper bound, which is lower when the maximum number of boxes per class is known.
+            let (boxes, scores) = (input_shapes[0], input_shapes[1]);
+            if boxes.rank() != 3 || scores.rank() != 3 || boxes.dim(1) != scores.dim(2) {
+                return Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    format!("boxes ({boxes}) and scores ({scores}) have incompatible shapes"),
+                ));
+            }
+            let max_output_boxes_per_class = match node.get_input().get(2) {
+                Some(name) if !name.is_empty() => {
+                    match static_initializer_value_i64(initializers, name) {
+                        Ok([max, ..]) => (*max).max(0) as u64,
+                        _ => boxes.dim(1),
+                    }
+                }
+                _ => 0,
+            };
+            let max_selected =
+                scores.dim(0) * scores.dim(1) * max_output_boxes_per_class.min(boxes.dim(1));
+            Ok(vec![Shape::from(
+                ScalarType::I64,
+                &[max_selected as i64, 3],
+            )])
+        }
+
+        ("RoiAlign", 3, 1) => {
+            // Output is [num_rois, C, output_height, output_width]
+            let (x, rois) = (input_shapes[0], input_shapes[1]);
+            if x.rank() != 4 || rois.rank() != 2 {
+                return Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    format!("X ({x}) must have rank 4 and rois ({rois}) must have rank 2"),
+                ));
+            }
+            let output_height: i64 = node
+                .get_attribute_value("output_height", Some(1))
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+            let output_width: i64 = node
+                .get_attribute_value("output_width", Some(1))
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+            Ok(vec![Shape::from(
+                x.data_type,
+                &[
+                    rois.dim(0) as i64,
+                    x.dim(1) as i64,
+                    output_height,
+                    output_width,
+                ],
+            )])
+        }
+
         ("Shape", 1, 1) => {
             let rank = input_shapes[0].rank() as i64;
             let mut start: i64 = node.get_attribute_value("start", Some(0)).unwrap();
@@ -764,10 +923,80 @@ pub(crate) fn infer_output_shapes(
             )])
         }
 
-        ("Sub" | "Pow" | "Add" | "Div" | "Mul" | "Mod", 2, 1) => {
-            if let Some(output_shape) =
+        ("ArgMax" | "ArgMin", 1, 1) => {
+            let axis = normalized_axis_attribute(node, input_shapes[0], 0)?;
+            let mut dims: Vec<i64> = input_shapes[0].dims.iter().map(|d| *d as i64).collect();
+            let keep_dims = node
+                .get_attribute_value("keepdims", Some(1))
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+            if keep_dims == 1 {
+                dims[axis] = 1;
+            } else {
+                dims.remove(axis);
+            }
+            Ok(vec![Shape::from(ScalarType::I64, &dims)])
+        }
+
+        ("TopK", 1..=2, 2) => {
+            // Since opset 10, K is a (static) input; before that, it is an attribute
+            let k = match input_shapes.len() {
+                2 => static_initializer_value_i64(initializers, &node.get_input()[1])?
+                    .first()
+                    .copied()
+                    .ok_or_else(|| {
+                        ShapeInferenceError::InvalidNode(
+                            node.get_name().to_string(),
+                            "K must have one element".to_string(),
+                        )
+                    })?,
+                _ => node
+                    .get_attribute_value("k", None)
+                    .map_err(ShapeInferenceError::MissingAttribute)?,
+            };
+            let axis = normalized_axis_attribute(node, input_shapes[0], -1)?;
+            let mut dims: Vec<i64> = input_shapes[0].dims.iter().map(|d| *d as i64).collect();
+            dims[axis] = k;
+            Ok(vec![
+                Shape::from(input_shapes[0].data_type, &dims),
+                Shape::from(ScalarType::I64, &dims),
+            ])
+        }
+
+        ("Sum" | "Mean" | "Max" | "Min", 1.., 1) => {
+            let input_shapes: Vec<Shape> =
+                input_shapes.iter().map(|shape| (*shape).clone()).collect();
+            match Shape::multi_broadcast(&input_shapes) {
+                Some(output_shape) => Ok(vec![output_shape]),
+                None => Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    format!(
+                        "inputs ({}) must be broadcastable",
+                        input_shapes
+                            .iter()
+                            .map(|shape| shape.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ),
+                )),
+            }
+        }
+
+        (
+            op @ ("Sub" | "Pow" | "Add" | "Div" | "Mul" | "Mod" | "And" | "Or" | "Equal"
+            | "Greater" | "GreaterOrEqual" | "Less" | "LessOrEqual"),
+            2,
+            1,
+        ) => {
+            if let Some(mut output_shape) =
                 Shape::multi_broadcast(&[input_shapes[0].clone(), input_shapes[1].clone()])
             {
+                // Comparisons produce booleans
+                if matches!(
+                    op,
+                    "Equal" | "Greater" | "GreaterOrEqual" | "Less" | "LessOrEqual"
+                ) {
+                    output_shape.data_type = ScalarType::Bool;
+                }
                 Ok(vec![output_shape])
             } else {
                 Err(ShapeInferenceError::InvalidNode(
@@ -961,6 +1190,240 @@ pub(crate) fn infer_output_shapes(
                 .collect())
         }
 
+        ("ConvTranspose", 2..=3, 1) => {
+            // https://github.com/onnx/onnx/blob/main/docs/Operators.md#ConvTranspose
+            let (input_shape, weights_shape) = (input_shapes[0], input_shapes[1]);
+            if input_shape.rank() < 3 || weights_shape.rank() != input_shape.rank() {
+                return Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    "input and weights must have the same rank of at least three".to_string(),
+                ));
+            }
+            let num_input_dims = input_shape.rank() - 2;
+            let spatial_attribute = |name: &str, default: i64| {
+                node.get_attribute_value(name, Some(vec![default; num_input_dims]))
+                    .map_err(ShapeInferenceError::MissingAttribute)
+                    .and_then(|values: Vec<i64>| {
+                        if values.len() == num_input_dims {
+                            Ok(values)
+                        } else {
+                            Err(ShapeInferenceError::InvalidNode(
+                                node.get_name().to_string(),
+                                format!("attribute {} has incorrect size", name),
+                            ))
+                        }
+                    })
+            };
+            let strides = spatial_attribute("strides", 1)?;
+            let dilations = spatial_attribute("dilations", 1)?;
+            let output_padding = spatial_attribute("output_padding", 0)?;
+            let kernel_shape: Vec<i64> = node
+                .get_attribute_value(
+                    "kernel_shape",
+                    Some(weights_shape.dims[2..].iter().map(|d| *d as i64).collect()),
+                )
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+            let pads: Vec<i64> = node
+                .get_attribute_value("pads", Some(vec![0; num_input_dims * 2]))
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+            let auto_pad = node
+                .get_attribute_value("auto_pad", Some("NOTSET".to_string()))
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+            let group: i64 = node
+                .get_attribute_value("group", Some(1))
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+
+            let mut output_dims = vec![
+                input_shape.dim(0) as i64,
+                weights_shape.dim(1) as i64 * group,
+            ];
+            if let Ok(output_shape) = node.get_attribute_value::<Vec<i64>>("output_shape", None) {
+                // The output shape may or may not include the batch and channel dimensions
+                output_dims
+                    .extend_from_slice(&output_shape[(output_shape.len() - num_input_dims)..]);
+            } else if auto_pad == "SAME_UPPER" || auto_pad == "SAME_LOWER" {
+                output_dims.extend(
+                    (0..num_input_dims).map(|i| input_shape.dim(2 + i) as i64 * strides[i]),
+                );
+            } else {
+                output_dims.extend((0..num_input_dims).map(|i| {
+                    strides[i] * (input_shape.dim(2 + i) as i64 - 1)
+                        + output_padding[i]
+                        + (kernel_shape[i] - 1) * dilations[i]
+                        + 1
+                        - pads[i]
+                        - pads[i + num_input_dims]
+                }));
+            }
+            Ok(vec![Shape::from(input_shape.data_type, &output_dims)])
+        }
+
+        ("LSTM" | "GRU" | "RNN", 3..=8, 1..=3) => {
+            // https://github.com/onnx/onnx/blob/main/docs/Operators.md#LSTM
+            let (input_shape, recurrence_weights_shape) = (input_shapes[0], input_shapes[2]);
+            if input_shape.rank() != 3 || recurrence_weights_shape.rank() != 3 {
+                return Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    "input and recurrence weights must have rank three".to_string(),
+                ));
+            }
+            let layout: i64 = node
+                .get_attribute_value("layout", Some(0))
+                .map_err(ShapeInferenceError::MissingAttribute)?;
+            let (sequence_length, batch_size) = if layout == 0 {
+                (input_shape.dim(0), input_shape.dim(1))
+            } else {
+                (input_shape.dim(1), input_shape.dim(0))
+            };
+            let num_directions = recurrence_weights_shape.dim(0);
+            let hidden_size = recurrence_weights_shape.dim(2);
+            let (output_dims, state_dims) = if layout == 0 {
+                (
+                    [sequence_length, num_directions, batch_size, hidden_size],
+                    [num_directions, batch_size, hidden_size],
+                )
+            } else {
+                (
+                    [batch_size, sequence_length, num_directions, hidden_size],
+                    [batch_size, num_directions, hidden_size],
+                )
+            };
+            let to_shape = |dims: &[u64]| {
+                Shape::from(
+                    input_shape.data_type,
+                    &dims.iter().map(|d| *d as i64).collect::<Vec<i64>>(),
+                )
+            };
+            let mut output_shapes = vec![to_shape(&output_dims)];
+            output_shapes.resize(node.get_output().len(), to_shape(&state_dims));
+            Ok(output_shapes)
+        }
+
+        ("Expand", 2, 1) => {
+            let shape = static_initializer_value_i64(initializers, &node.get_input()[1])?;
+            let broadcast = Shape::multi_broadcast(&[
+                input_shapes[0].clone(),
+                Shape::from(input_shapes[0].data_type, shape),
+            ]);
+            match broadcast {
+                Some(output_shape) => Ok(vec![output_shape]),
+                None => Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    format!(
+                        "input shape {} cannot be expanded to {:?}",
+                        input_shapes[0], shape
+                    ),
+                )),
+            }
+        }
+
+        ("Tile", 2, 1) => {
+            let repeats = static_initializer_value_i64(initializers, &node.get_input()[1])?;
+            if repeats.len() != input_shapes[0].rank() {
+                return Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    format!(
+                        "number of repeats ({}) must be equal to the input rank ({})",
+                        repeats.len(),
+                        input_shapes[0].rank()
+                    ),
+                ));
+            }
+            let output_dims: Vec<i64> = input_shapes[0]
+                .dims
+                .iter()
+                .zip(repeats)
+                .map(|(dim, repeat)| *dim as i64 * repeat)
+                .collect();
+            Ok(vec![Shape::from(input_shapes[0].data_type, &output_dims)])
+        }
+
+        ("Where", 3, 1) => {
+            // The condition is a boolean tensor; the output has the type of the other inputs
+            let data_type = input_shapes[1].data_type;
+            let shapes: Vec<Shape> = input_shapes
+                .iter()
+                .map(|shape| Shape {
+                    data_type,
+                    dims: shape.dims.clone(),
+                })
+                .collect();
+            Shape::multi_broadcast(&shapes)
+                .map(|output_shape| vec![output_shape])
+                .ok_or_else(|| {
+                    ShapeInferenceError::InvalidNode(
+                        node.get_name().to_string(),
+                        "inputs must be broadcastable".to_string(),
+                    )
+                })
+        }
+
+        ("QuantizeLinear", 2..=3, 1) => {
+            // The output type is that of the zero point, if specified, or the output_dtype attribute (default uint8)
+            let data_type = match input_shapes.get(2) {
+                Some(zero_point_shape) => zero_point_shape.data_type,
+                None => {
+                    let output_dtype = node
+                        .get_attribute_value("output_dtype", Some(0))
+                        .map_err(ShapeInferenceError::MissingAttribute)?;
+                    if output_dtype == 0 {
+                        ScalarType::U8
+                    } else {
+                        ScalarType::from_i32(output_dtype as i32)
+                            .map_err(ShapeInferenceError::UnsupportedDataType)?
+                    }
+                }
+            };
+            Ok(vec![Shape {
+                data_type,
+                dims: input_shapes[0].dims.clone(),
+            }])
+        }
+
+        ("DequantizeLinear", 2..=3, 1) => Ok(vec![Shape {
+            data_type: input_shapes[1].data_type,
+            dims: input_shapes[0].dims.clone(),
+        }]),
+
+        ("MatMulInteger", 2..=4, 1) => Ok(vec![Shape::from(
+            ScalarType::I32,
+            &matmul_output_dims(node, input_shapes[0], input_shapes[1])?,
+        )]),
+
+        ("QLinearMatMul", 8, 1) => Ok(vec![Shape::from(
+            input_shapes[7].data_type,
+            &matmul_output_dims(node, input_shapes[0], input_shapes[3])?,
+        )]),
+
+        ("QLinearConv", 8..=9, 1) => {
+            // The output has the shape of a regular convolution of the input (x) and weights (w)
+            let mut conv = node.clone();
+            conv.set_op_type("Conv".to_string());
+            let mut output_shapes =
+                infer_output_shapes(&conv, &[input_shapes[0], input_shapes[3]], initializers)?;
+            output_shapes[0].data_type = input_shapes[7].data_type;
+            Ok(output_shapes)
+        }
+
+        ("Attention", 3..=4, 1) => {
+            // The output has shape (batch_size, sequence_length, hidden_size); weights are (input_size, 3 * hidden_size)
+            let (input_shape, weights_shape) = (input_shapes[0], input_shapes[1]);
+            if input_shape.rank() != 3 || weights_shape.rank() != 2 {
+                return Err(ShapeInferenceError::InvalidNode(
+                    node.get_name().to_string(),
+                    "input must be of rank 3 and weights of rank 2".to_string(),
+                ));
+            }
+            Ok(vec![Shape::from(
+                input_shape.data_type,
+                &[
+                    input_shape.dim(0) as i64,
+                    input_shape.dim(1) as i64,
+                    weights_shape.dim(1) as i64 / 3,
+                ],
+            )])
+        }
+
         ("ConstantOfShape", 1, 1) => {
             let shape = static_initializer_value_i64(initializers, &node.get_input()[0])?;
 
@@ -1210,7 +1673,8 @@ pub(crate) fn infer_output_shapes(
             | "Atan" | "Atanh" | "Cos" | "Cosh" | "Elu" | "Erf" | "Exp" | "Log" | "Neg" | "Ceil"
             | "Reciprocal" | "Floor" | "Mod" | "Celu" | "ReduceSum" | "ReduceMin" | "ReduceMax"
             | "ReduceSumSquare" | "ReduceLogSumExp" | "ReduceLogSum" | "ReduceL2" | "ReduceL1"
-            | "ReduceProd" | "Size" | "Sign",
+            | "ReduceProd" | "Size" | "Sign" | "Sum" | "Mean" | "Max" | "Min" | "GatherElements"
+            | "GatherND" | "ScatterElements" | "ScatterND" | "NonMaxSuppression" | "RoiAlign",
             _,
             _,
         ) => Err(ShapeInferenceError::InvalidNode(
@@ -1229,6 +1693,50 @@ pub(crate) fn infer_output_shapes(
     }
 }
 
+/// Output dimensions of a matrix multiplication (as in numpy.matmul) of tensors with the specified shapes
+fn matmul_output_dims(
+    node: &NodeProto,
+    a: &Shape,
+    b: &Shape,
+) -> Result<Vec<i64>, ShapeInferenceError> {
+    let invalid = || {
+        ShapeInferenceError::InvalidNode(
+            node.get_name().to_string(),
+            format!("inputs with shapes {} and {} cannot be multiplied", a, b),
+        )
+    };
+    if a.rank() == 0 || b.rank() == 0 {
+        return Err(invalid());
+    }
+
+    // 1-D arguments are promoted to matrices; the added dimensions are removed from the output
+    let a_dims: Vec<i64> = a.dims.iter().map(|d| *d as i64).collect();
+    let b_dims: Vec<i64> = b.dims.iter().map(|d| *d as i64).collect();
+    let (a_stack, a_matrix) = a_dims.split_at(a.rank().saturating_sub(2));
+    let (b_stack, b_matrix) = b_dims.split_at(b.rank().saturating_sub(2));
+    let k = a_matrix[a_matrix.len() - 1];
+    if b_matrix[0] != k {
+        return Err(invalid());
+    }
+
+    let mut output_dims = Shape::multi_broadcast(&[
+        Shape::from(a.data_type, a_stack),
+        Shape::from(b.data_type, b_stack),
+    ])
+    .ok_or_else(invalid)?
+    .dims
+    .iter()
+    .map(|d| *d as i64)
+    .collect::<Vec<i64>>();
+    if a_matrix.len() == 2 {
+        output_dims.push(a_matrix[0]);
+    }
+    if b_matrix.len() == 2 {
+        output_dims.push(b_matrix[1]);
+    }
+    Ok(output_dims)
+}
+
 /// https://github.com/onnx/onnx/blob/fb80e3ade84e9f406711aa41b9f3665753158371/onnx/defs/tensor/defs.cc#L814
 fn process_slice_inputs(
     input_rank: i64,
@@ -1274,9 +1782,17 @@ fn fix_raw_tensor(tensor: &mut TensorProto) -> Result<(), ShapeInferenceError> {
             .map_err(ShapeInferenceError::UnsupportedDataType)?
         {
             ScalarType::F32 => tensor.set_float_data(bytemuck::cast_slice(&raw_data[..]).to_vec()),
+            // There is no separate field for f16 data, other than int32_data (which takes more space)
+            ScalarType::F16 => tensor.set_raw_data(raw_data),
             ScalarType::I64 => tensor.set_int64_data(bytemuck::cast_slice(&raw_data[..]).to_vec()),
             ScalarType::I32 => tensor.set_int32_data(bytemuck::cast_slice(&raw_data[..]).to_vec()),
-            ScalarType::U8 => tensor.set_raw_data(bytemuck::cast_slice(&raw_data[..]).to_vec()),
+            ScalarType::F64 => tensor.set_double_data(bytemuck::pod_collect_to_vec(&raw_data[..])),
+            // Small integer types and booleans would take more space in int32_data
+            ScalarType::U8
+            | ScalarType::I8
+            | ScalarType::U16
+            | ScalarType::I16
+            | ScalarType::Bool => tensor.set_raw_data(raw_data),
         }
     }
     Ok(())
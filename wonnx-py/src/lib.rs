@@ -16,9 +16,19 @@ impl IntoPy<PyObject> for PyOutputTensor {
     fn into_py(self, py: Python) -> PyObject {
         match self.0 {
             OutputTensor::F32(fs) => fs.into_py(py),
+            OutputTensor::F16(fs) => fs
+                .iter()
+                .map(|x| x.to_f32())
+                .collect::<Vec<f32>>()
+                .into_py(py),
             OutputTensor::I32(fs) => fs.into_py(py),
             OutputTensor::I64(fs) => fs.into_py(py),
             OutputTensor::U8(fs) => fs.into_py(py),
+            OutputTensor::F64(fs) => fs.into_py(py),
+            OutputTensor::I8(fs) => fs.into_py(py),
+            OutputTensor::U16(fs) => fs.into_py(py),
+            OutputTensor::I16(fs) => fs.into_py(py),
+            OutputTensor::Bool(fs) => fs.into_py(py),
         }
     }
 }
@@ -94,8 +94,18 @@ impl Session {
 pub fn tensor_to_js_value(tensor: OutputTensor) -> JsValue {
     match tensor {
         OutputTensor::F32(fs) => serde_wasm_bindgen::to_value(&fs).unwrap(),
+        // JavaScript has no (widely supported) half-precision float type
+        OutputTensor::F16(fs) => {
+            let fs: Vec<f32> = fs.iter().map(|x| x.to_f32()).collect();
+            serde_wasm_bindgen::to_value(&fs).unwrap()
+        }
         OutputTensor::I32(ints) => serde_wasm_bindgen::to_value(&ints).unwrap(),
         OutputTensor::I64(ints) => serde_wasm_bindgen::to_value(&ints).unwrap(),
         OutputTensor::U8(ints) => serde_wasm_bindgen::to_value(&ints).unwrap(),
+        OutputTensor::F64(fs) => serde_wasm_bindgen::to_value(&fs).unwrap(),
+        OutputTensor::I8(ints) => serde_wasm_bindgen::to_value(&ints).unwrap(),
+        OutputTensor::U16(ints) => serde_wasm_bindgen::to_value(&ints).unwrap(),
+        OutputTensor::I16(ints) => serde_wasm_bindgen::to_value(&ints).unwrap(),
+        OutputTensor::Bool(bools) => serde_wasm_bindgen::to_value(&bools).unwrap(),
     }
 }
@@ -0,0 +1,249 @@
+//! Compiled models: optimized models that can be stored and loaded into a [`crate::Session`] without optimizing them and
+//! generating shaders again.
+//!
+//! A compiled model is a regular ONNX model containing the graph as it is after optimization. Each op node carries the
+//! WGSL shader generated for it, along with the number of workgroups to dispatch, as attributes. The metadata of the
+//! model records the version of the format and of wonnx; a compiled model can only be loaded by the version that
+//! created it. Buffers are planned when the model is loaded, as for any other model (this is deterministic and cheap).
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::compiler::{CompileError, CompiledNode};
+use crate::gpu::{compile_op, GpuError};
+use crate::ir::{
+    op_forwards_input, op_has_data_dependent_output, Input, Node, NodeDefinition, NodeIdentifier,
+};
+use crate::onnx::{
+    GraphProto, ModelProto, NodeProto, StringStringEntryProto, TensorProto, ValueInfoProto,
+};
+use crate::utils::{model_with_opset, NodeAttributes, ScalarType, Shape};
+use crate::SessionError;
+
+const FORMAT_KEY: &str = "wonnx.format";
+const FORMAT_VERSION: &str = "1";
+const VERSION_KEY: &str = "wonnx.version";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const SHADER_ATTRIBUTE: &str = "wonnx.shader";
+const THREADS_ATTRIBUTE: &str = "wonnx.threads";
+
+/// Returns whether the model is a compiled model, or an error when it was compiled by an incompatible version of wonnx.
+pub(crate) fn is_compiled(model: &ModelProto) -> Result<bool, SessionError> {
+    let metadata = |key: &str| {
+        model
+            .get_metadata_props()
+            .iter()
+            .find(|prop| prop.get_key() == key)
+            .map(|prop| prop.get_value())
+    };
+
+    match (metadata(FORMAT_KEY), metadata(VERSION_KEY)) {
+        (None, None) => Ok(false),
+        (Some(FORMAT_VERSION), Some(VERSION)) => Ok(true),
+        (format, version) => Err(SessionError::IncompatibleCompiledModel {
+            format: format.unwrap_or_default().to_string(),
+            version: version.unwrap_or_default().to_string(),
+        }),
+    }
+}
+
+/// Returns the shader stored in a node of a compiled model, if any.
+pub(crate) fn stored_shader(node: &NodeProto) -> Option<CompiledNode> {
+    let shader: String = node.get_attribute_value(SHADER_ATTRIBUTE, None).ok()?;
+    let threads: Vec<i64> = node.get_attribute_value(THREADS_ATTRIBUTE, None).ok()?;
+    match threads[..] {
+        [x, y, z] => Some(CompiledNode {
+            shader,
+            threads: (x as u32, y as u32, z as u32),
+        }),
+        _ => None,
+    }
+}
+
+/// Convert an (optimized) intermediate representation graph to a compiled model.
+pub(crate) fn to_model(root: &Arc<Node>, onnx_opset_version: i64) -> Result<ModelProto, GpuError> {
+    let mut sorted_nodes = vec![];
+    root.topological_sort(&mut HashSet::new(), &mut sorted_nodes);
+
+    // Optimization may have created values with the same name, so each value is assigned a unique name. Graph inputs
+    // and outputs keep their names.
+    let mut value_names = HashMap::new();
+    let mut used_names = HashSet::from([String::new()]);
+    for node in &sorted_nodes {
+        match &node.definition {
+            NodeDefinition::Input(input) => {
+                used_names.insert(input.get_name().to_string());
+                value_names.insert((node.identifier(), 0), input.get_name().to_string());
+            }
+            NodeDefinition::Outputs { names } => {
+                for (input, name) in node.inputs.iter().zip(names) {
+                    let key = (input.source_node.identifier(), input.output_index);
+                    if matches!(input.source_node.definition, NodeDefinition::Operator(_))
+                        && !value_names.contains_key(&key)
+                        && used_names.insert(name.clone())
+                    {
+                        value_names.insert(key, name.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut graph = GraphProto::new();
+    let mut value_shapes = HashMap::new();
+    for node in &sorted_nodes {
+        match &node.definition {
+            NodeDefinition::Input(input) => {
+                value_shapes.insert((node.identifier(), 0), input.get_shape()?);
+                graph.mut_input().push((*input).clone());
+            }
+            NodeDefinition::Tensor(tensor) => {
+                let name = unique_name(&mut used_names, tensor.get_name());
+                let mut tensor: TensorProto = tensor.as_ref().clone().into_owned();
+                tensor.set_name(name.clone());
+                value_shapes.insert(
+                    (node.identifier(), 0),
+                    Shape::from(
+                        ScalarType::from_i32(tensor.get_data_type())?,
+                        tensor.get_dims(),
+                    ),
+                );
+                value_names.insert((node.identifier(), 0), name);
+                graph.mut_initializer().push(tensor);
+            }
+            NodeDefinition::Operator(op_def) => {
+                // The graphs in attributes of the op would have to be compiled (and renamed) as well
+                if !op_def.subgraphs.is_empty() {
+                    return Err(GpuError::CompileError {
+                        node: op_def.proto.get_name().to_string(),
+                        error: CompileError::UnimplementedVariant {
+                            variant: "subgraphs in compiled models".to_string(),
+                            op: op_def.proto.get_op_type().to_string(),
+                        },
+                    });
+                }
+                let mut proto = op_def.proto.clone().into_owned();
+                let output_names: Vec<String> = proto
+                    .get_output()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, name)| {
+                        value_names
+                            .entry((node.identifier(), index))
+                            .or_insert_with(|| unique_name(&mut used_names, name))
+                            .clone()
+                    })
+                    .collect();
+                let input_names = node
+                    .inputs
+                    .iter()
+                    .map(|input| value_name(&value_names, input))
+                    .collect();
+
+                // Optional inputs that are missing have no shape; such ops cannot be run on the GPU. Ops that forward
+                // their input or are executed on the host do not have a shader.
+                let input_shapes: Option<Vec<&Shape>> = node
+                    .inputs
+                    .iter()
+                    .map(|input| {
+                        value_shapes.get(&(input.source_node.identifier(), input.output_index))
+                    })
+                    .collect();
+                let op_type = proto.get_op_type();
+                if let (Some(input_shapes), false) = (
+                    input_shapes,
+                    op_forwards_input(op_type) || op_has_data_dependent_output(op_type),
+                ) {
+                    let output_shapes: Vec<&Shape> = op_def.output_shapes.iter().collect();
+                    let CompiledNode { shader, threads } = compile_op(
+                        &proto,
+                        &input_shapes,
+                        &output_shapes,
+                        onnx_opset_version,
+                        true,
+                        false,
+                    )?;
+                    let mut shader_attribute = crate::onnx::AttributeProto::from(shader);
+                    shader_attribute.set_name(SHADER_ATTRIBUTE.to_string());
+                    let mut threads_attribute = crate::onnx::AttributeProto::from(vec![
+                        threads.0 as i64,
+                        threads.1 as i64,
+                        threads.2 as i64,
+                    ]);
+                    threads_attribute.set_name(THREADS_ATTRIBUTE.to_string());
+                    proto.mut_attribute().push(shader_attribute);
+                    proto.mut_attribute().push(threads_attribute);
+                }
+
+                for (index, (name, shape)) in
+                    output_names.iter().zip(&op_def.output_shapes).enumerate()
+                {
+                    graph.mut_value_info().push(value_info(name, shape));
+                    value_shapes.insert((node.identifier(), index), shape.clone());
+                }
+                proto.set_input(input_names);
+                proto.set_output(output_names.into());
+                graph.mut_node().push(proto);
+            }
+            NodeDefinition::Outputs { names } => {
+                for (input, name) in node.inputs.iter().zip(names) {
+                    let shape =
+                        &value_shapes[&(input.source_node.identifier(), input.output_index)];
+                    let source_name = value_name(&value_names, input);
+
+                    // Graph outputs can only be read from op nodes, so values that come from elsewhere or have already
+                    // been assigned another name are passed through an Identity node
+                    if &source_name != name {
+                        let mut identity = NodeProto::new();
+                        identity.set_op_type("Identity".to_string());
+                        identity.set_name(format!("{name}_identity"));
+                        identity.set_input(vec![source_name].into());
+                        identity.set_output(vec![name.clone()].into());
+                        graph.mut_node().push(identity);
+                    }
+                    graph.mut_output().push(value_info(name, shape));
+                }
+            }
+            NodeDefinition::Missing => {}
+        }
+    }
+
+    let mut model = model_with_opset(graph, onnx_opset_version);
+    for (key, value) in [(FORMAT_KEY, FORMAT_VERSION), (VERSION_KEY, VERSION)] {
+        let mut prop = StringStringEntryProto::new();
+        prop.set_key(key.to_string());
+        prop.set_value(value.to_string());
+        model.mut_metadata_props().push(prop);
+    }
+    Ok(model)
+}
+
+/// The name of the value that is used as input (missing optional inputs have an empty name).
+fn value_name<'model>(
+    value_names: &HashMap<(NodeIdentifier<'model>, usize), String>,
+    input: &Input<'model>,
+) -> String {
+    value_names
+        .get(&(input.source_node.identifier(), input.output_index))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Returns `name`, or `name` with a numeric suffix if it is already in use, and marks the result as used.
+fn unique_name(used_names: &mut HashSet<String>, name: &str) -> String {
+    let mut unique = name.to_string();
+    let mut suffix = 1;
+    while !used_names.insert(unique.clone()) {
+        unique = format!("{name}_{suffix}");
+        suffix += 1;
+    }
+    unique
+}
+
+fn value_info(name: &str, shape: &Shape) -> ValueInfoProto {
+    let mut value_info = ValueInfoProto::new();
+    value_info.set_name(name.to_string());
+    value_info.set_shape(shape);
+    value_info
+}
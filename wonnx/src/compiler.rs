@@ -1,6 +1,7 @@
 //! Compiles individual ONNX ops to a WebGPU shader using WGSL templates
 use std::sync::OnceLock;
 
+use crate::einsum::{EinsumEquation, EinsumError};
 use crate::utils::{
     ceil, AttributeNotFoundError, DataTypeError, MultiType, NodeAttributes, ScalarType, Shape,
 };
@@ -15,8 +16,14 @@ pub const MAX_COMPUTE_WORKGROUPS_PER_DIMENSION: u32 = 65535;
 /// The maximum workgroup size per dimension (see <https://www.w3.org/TR/webgpu/#dom-supported-limits-maxcomputeworkgroupsizex>)
 pub const MAX_WORKGROUP_SIZE_X: u32 = 256;
 pub const MAX_WORKGROUP_SIZE_Y: u32 = 256;
+
 // pub const MAX_WORKGROUP_SIZE_Z: u32 = 64;
 
+/// The largest hidden size supported for recurrent ops. The hidden state and (for GRU) the reset hidden state are shared
+/// f32 arrays, which at 2048 units take up 16 KiB of workgroup memory: the default limit of WebGPU (see
+/// <https://www.w3.org/TR/webgpu/#dom-supported-limits-maxcomputeworkgroupstoragesize>)
+const MAX_RECURRENT_HIDDEN_SIZE: u64 = 2048;
+
 static TEMPLATES: OnceLock<Tera> = OnceLock::new();
 
 fn get_templates() -> &'static Tera {
@@ -42,6 +49,26 @@ fn get_templates() -> &'static Tera {
             include_str!("../templates/endomorphism/softmax.wgsl"),
         )
         .unwrap();
+        tera.add_raw_template(
+            "endomorphism/gelu.wgsl",
+            include_str!("../templates/endomorphism/gelu.wgsl"),
+        )
+        .unwrap();
+        tera.add_raw_template(
+            "endomorphism/groupnormalization.wgsl",
+            include_str!("../templates/endomorphism/groupnormalization.wgsl"),
+        )
+        .unwrap();
+        tera.add_raw_template(
+            "endomorphism/layernormalization.wgsl",
+            include_str!("../templates/endomorphism/layernormalization.wgsl"),
+        )
+        .unwrap();
+        tera.add_raw_template(
+            "endomorphism/lpnormalization.wgsl",
+            include_str!("../templates/endomorphism/lpnormalization.wgsl"),
+        )
+        .unwrap();
         tera.add_raw_template(
             "endomorphism/map.wgsl",
             include_str!("../templates/endomorphism/map.wgsl"),
@@ -52,6 +79,11 @@ fn get_templates() -> &'static Tera {
             include_str!("../templates/endomorphism/cast.wgsl"),
         )
         .unwrap();
+        tera.add_raw_template(
+            "matrix/attention.wgsl",
+            include_str!("../templates/matrix/attention.wgsl"),
+        )
+        .unwrap();
         tera.add_raw_template(
             "matrix/concat.wgsl",
             include_str!("../templates/matrix/concat.wgsl"),
@@ -72,11 +104,21 @@ fn get_templates() -> &'static Tera {
             include_str!("../templates/matrix/pad.wgsl"),
         )
         .unwrap();
+        tera.add_raw_template(
+            "matrix/recurrent.wgsl",
+            include_str!("../templates/matrix/recurrent.wgsl"),
+        )
+        .unwrap();
         tera.add_raw_template(
             "matrix/resize.wgsl",
             include_str!("../templates/matrix/resize.wgsl"),
         )
         .unwrap();
+        tera.add_raw_template(
+            "matrix/slice.wgsl",
+            include_str!("../templates/matrix/slice.wgsl"),
+        )
+        .unwrap();
         tera.add_raw_template(
             "matrix/split.wgsl",
             include_str!("../templates/matrix/split.wgsl"),
@@ -112,6 +154,16 @@ fn get_templates() -> &'static Tera {
             include_str!("../templates/pool/reduce.wgsl"),
         )
         .unwrap();
+        tera.add_raw_template(
+            "pool/roialign.wgsl",
+            include_str!("../templates/pool/roialign.wgsl"),
+        )
+        .unwrap();
+        tera.add_raw_template(
+            "pool/topk.wgsl",
+            include_str!("../templates/pool/topk.wgsl"),
+        )
+        .unwrap();
         tera.add_raw_template("structs.wgsl", include_str!("../templates/structs.wgsl"))
             .unwrap();
         tera.add_raw_template(
@@ -129,6 +181,26 @@ fn get_templates() -> &'static Tera {
             include_str!("../templates/endomorphism/gather.wgsl"),
         )
         .unwrap();
+        tera.add_raw_template(
+            "endomorphism/gather_elements.wgsl",
+            include_str!("../templates/endomorphism/gather_elements.wgsl"),
+        )
+        .unwrap();
+        tera.add_raw_template(
+            "endomorphism/gather_nd.wgsl",
+            include_str!("../templates/endomorphism/gather_nd.wgsl"),
+        )
+        .unwrap();
+        tera.add_raw_template(
+            "endomorphism/scatter_elements.wgsl",
+            include_str!("../templates/endomorphism/scatter_elements.wgsl"),
+        )
+        .unwrap();
+        tera.add_raw_template(
+            "endomorphism/scatter_nd.wgsl",
+            include_str!("../templates/endomorphism/scatter_nd.wgsl"),
+        )
+        .unwrap();
         tera.add_raw_template(
             "endomorphism/onehot.wgsl",
             include_str!("../templates/endomorphism/onehot.wgsl"),
@@ -139,6 +211,21 @@ fn get_templates() -> &'static Tera {
             include_str!("../templates/endomorphism/broadcast.wgsl"),
         )
         .unwrap();
+        tera.add_raw_template(
+            "endomorphism/quantize.wgsl",
+            include_str!("../templates/endomorphism/quantize.wgsl"),
+        )
+        .unwrap();
+        tera.add_raw_template(
+            "matrix/qmatmul.wgsl",
+            include_str!("../templates/matrix/qmatmul.wgsl"),
+        )
+        .unwrap();
+        tera.add_raw_template(
+            "pool/qlinearconv.wgsl",
+            include_str!("../templates/pool/qlinearconv.wgsl"),
+        )
+        .unwrap();
         tera
     })
 }
@@ -175,6 +262,9 @@ pub enum CompileError {
         opset_version: i64,
     },
 
+    #[error("{0}")]
+    Einsum(#[from] EinsumError),
+
     #[error("input {input_index} has invalid shape {input_shape}")]
     InvalidInputShape {
         input_index: usize,
@@ -240,11 +330,383 @@ fn agreed_type(
         }
     }
 
-    if let Some(ScalarType::I64) = data_type {
-        data_type = Some(ScalarType::I32);
+    data_type
+        .map(|data_type| data_type.gpu_type())
+        .ok_or(CompileError::TypeUnderspecified)
+}
+
+/// Returns the range of values that can be represented by a quantized data type
+pub(crate) fn quantized_range(data_type: ScalarType, op: &str) -> Result<(i64, i64), CompileError> {
+    match data_type {
+        ScalarType::U8 => Ok((u8::MIN as i64, u8::MAX as i64)),
+        ScalarType::I8 => Ok((i8::MIN as i64, i8::MAX as i64)),
+        ScalarType::U16 => Ok((u16::MIN as i64, u16::MAX as i64)),
+        ScalarType::I16 => Ok((i16::MIN as i64, i16::MAX as i64)),
+        _ => Err(CompileError::UnimplementedVariant {
+            variant: format!("with quantized data type {}", data_type),
+            op: op.to_string(),
+        }),
+    }
+}
+
+/// Returns the padding at the start of the height and width dimensions of a ConvTranspose node. When the output shape is
+/// specified (explicitly or through `auto_pad`), the padding follows from the difference with the full output size.
+pub(crate) fn conv_transpose_pads(
+    node: &crate::onnx::NodeProto,
+    input_shape: &Shape,
+    output_shape: &Shape,
+    kernel_shape: &[i64],
+    strides: &[i64],
+    dilations: &[i64],
+) -> Result<Vec<i64>, CompileError> {
+    let auto_pad = node.get_attribute_value("auto_pad", Some("NOTSET".to_string()))?;
+    let output_padding = node.get_attribute_value("output_padding", Some(vec![0, 0]))?;
+    if auto_pad == "NOTSET" && !node.has_attribute("output_shape") {
+        let pads = node.get_attribute_value("pads", Some(vec![0, 0, 0, 0]))?;
+        return Ok(pads[0..2].to_vec());
+    }
+    if !matches!(
+        auto_pad.as_str(),
+        "NOTSET" | "SAME_UPPER" | "SAME_LOWER" | "VALID"
+    ) {
+        return Err(CompileError::UnimplementedVariant {
+            op: "ConvTranspose".to_string(),
+            variant: format!("auto_pad={}", auto_pad),
+        });
     }
 
-    data_type.ok_or(CompileError::TypeUnderspecified)
+    Ok((0..2)
+        .map(|i| {
+            let total_padding = strides[i] * (input_shape.dim(2 + i) as i64 - 1)
+                + output_padding[i]
+                + (kernel_shape[i] - 1) * dilations[i]
+                + 1
+                - output_shape.dim(2 + i) as i64;
+            if auto_pad == "SAME_UPPER" {
+                total_padding.div_euclid(2)
+            } else {
+                total_padding - total_padding.div_euclid(2)
+            }
+        })
+        .collect())
+}
+
+/// The activations that the optimizer fuses into a preceding convolution
+pub(crate) const CONV_ACTIVATIONS: [&str; 6] =
+    ["Relu", "LeakyRelu", "Mish", "Sigmoid", "Clip", "HardSwish"];
+
+/// Splits a (fused) convolution op type (`Conv`, `ConvAdd`, `Conv<Activation>` or `ConvAdd<Activation>`) into whether the
+/// op adds a residual input (its last input) to the convolution, and the activation it applies ("" if none). Returns
+/// `None` for any other op type.
+pub(crate) fn fused_conv_type(op_type: &str) -> Option<(bool, &str)> {
+    let fused = op_type.strip_prefix("Conv")?;
+    let (residual, activation) = match fused.strip_prefix("Add") {
+        Some(activation) => (true, activation),
+        None => (false, fused),
+    };
+    if activation.is_empty() || CONV_ACTIVATIONS.contains(&activation) {
+        Some((residual, activation))
+    } else {
+        None
+    }
+}
+
+/// Returns the inputs of a node by their position in the operator definition. Optional inputs that are left out (these
+/// are removed from the node by the optimizer, which leaves an empty input name in their place) are returned as `None`.
+pub(crate) fn optional_inputs<'a, T>(
+    node: &crate::onnx::NodeProto,
+    inputs: &'a [T],
+) -> Vec<Option<&'a T>> {
+    let mut present = inputs.iter();
+    node.get_input()
+        .iter()
+        .map(|name| {
+            if name.is_empty() {
+                None
+            } else {
+                present.next()
+            }
+        })
+        .collect()
+}
+
+/// An activation function used by a recurrent op (LSTM, GRU or RNN)
+#[derive(Clone, Debug, serde::Serialize)]
+pub(crate) struct RecurrentActivation {
+    pub(crate) name: String,
+    pub(crate) alpha: f32,
+    pub(crate) beta: f32,
+}
+
+/// Returns the activation functions of a recurrent op for each direction (e.g. f, g and h for LSTM). The values in the
+/// `activation_alpha` and `activation_beta` attributes are consumed in order by the functions that take a parameter.
+pub(crate) fn recurrent_activations(
+    node: &crate::onnx::NodeProto,
+    num_directions: usize,
+    opset_version: i64,
+) -> Result<Vec<RecurrentActivation>, CompileError> {
+    let op = node.get_op_type();
+    let defaults: &[&str] = match op {
+        "LSTM" => &["Sigmoid", "Tanh", "Tanh"],
+        "GRU" => &["Sigmoid", "Tanh"],
+        _ => &["Tanh"],
+    };
+    let names: Vec<String> = node.get_attribute_value(
+        "activations",
+        Some(
+            defaults
+                .iter()
+                .cycle()
+                .take(defaults.len() * num_directions)
+                .map(|name| name.to_string())
+                .collect(),
+        ),
+    )?;
+    if names.len() != defaults.len() * num_directions {
+        return Err(CompileError::InvalidAttributeValue {
+            attribute: "activations".to_string(),
+            value: format!("{:?}", names),
+            opset_version,
+        });
+    }
+
+    let mut alphas = node
+        .get_attribute_value::<Vec<f32>>("activation_alpha", Some(vec![]))?
+        .into_iter();
+    let mut betas = node
+        .get_attribute_value::<Vec<f32>>("activation_beta", Some(vec![]))?
+        .into_iter();
+    names
+        .into_iter()
+        .map(|name| {
+            let (alpha, beta) = match name.as_str() {
+                "Sigmoid" | "Tanh" | "Relu" | "Softsign" | "Softplus" => (None, None),
+                "LeakyRelu" => (Some(0.01), None),
+                "ThresholdedRelu" | "Elu" => (Some(1.0), None),
+                "ScaledTanh" => (Some(1.0), Some(1.0)),
+                "HardSigmoid" => (Some(0.2), Some(0.5)),
+                "Affine" => (Some(1.0), Some(0.0)),
+                _ => {
+                    return Err(CompileError::UnimplementedVariant {
+                        variant: format!("activation function {}", name),
+                        op: op.to_string(),
+                    })
+                }
+            };
+            Ok(RecurrentActivation {
+                alpha: alpha.map_or(0.0, |default| alphas.next().unwrap_or(default)),
+                beta: beta.map_or(0.0, |default| betas.next().unwrap_or(default)),
+                name,
+            })
+        })
+        .collect()
+}
+
+/// The attributes of a RoiAlign op (the output size follows from the output shape)
+#[derive(Clone, Debug, serde::Serialize)]
+pub(crate) struct RoiAlignAttributes {
+    /// Whether the maximum (rather than the average) of the samples in a bin is taken
+    pub(crate) max: bool,
+
+    /// Whether RoI coordinates are shifted by half a pixel (coordinate_transformation_mode 'half_pixel', the default
+    /// since opset 16) rather than taken as-is ('output_half_pixel')
+    pub(crate) half_pixel: bool,
+    pub(crate) sampling_ratio: i64,
+    pub(crate) spatial_scale: f32,
+}
+
+/// Reads the attributes of a RoiAlign node, applying the defaults for the specified opset version
+pub(crate) fn roi_align_attributes(
+    node: &crate::onnx::NodeProto,
+    opset_version: i64,
+) -> Result<RoiAlignAttributes, CompileError> {
+    let mode = node.get_attribute_value("mode", Some("avg".to_string()))?;
+    let coordinate_transformation_mode = node.get_attribute_value(
+        "coordinate_transformation_mode",
+        Some(
+            if opset_version >= 16 {
+                "half_pixel"
+            } else {
+                "output_half_pixel"
+            }
+            .to_string(),
+        ),
+    )?;
+    let sampling_ratio = node.get_attribute_value("sampling_ratio", Some(0))?;
+    if sampling_ratio < 0 {
+        return Err(CompileError::InvalidAttributeValue {
+            attribute: "sampling_ratio".to_string(),
+            value: sampling_ratio.to_string(),
+            opset_version,
+        });
+    }
+
+    Ok(RoiAlignAttributes {
+        max: match mode.as_str() {
+            "avg" => false,
+            "max" => true,
+            _ => {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "mode".to_string(),
+                    value: mode,
+                    opset_version,
+                })
+            }
+        },
+        half_pixel: match coordinate_transformation_mode.as_str() {
+            "half_pixel" => true,
+            "output_half_pixel" => false,
+            _ => {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "coordinate_transformation_mode".to_string(),
+                    value: coordinate_transformation_mode,
+                    opset_version,
+                })
+            }
+        },
+        sampling_ratio,
+        spatial_scale: node.get_attribute_value("spatial_scale", Some(1.0))?,
+    })
+}
+
+/// The attributes of a Scan op, with axes normalized and directions given as whether the values are scanned in reverse
+pub(crate) struct ScanAttributes {
+    /// The number of state variables (the inputs that precede the scan inputs)
+    pub(crate) states: usize,
+    pub(crate) input_axes: Vec<usize>,
+    pub(crate) input_reverse: Vec<bool>,
+    pub(crate) output_axes: Vec<usize>,
+    pub(crate) output_reverse: Vec<bool>,
+}
+
+/// Reads the attributes of a Scan node (opset 9 and later) given the shapes of its inputs (not including values that
+/// its body takes from enclosing graphs) and outputs
+pub(crate) fn scan_attributes(
+    node: &crate::onnx::NodeProto,
+    input_shapes: &[&Shape],
+    output_shapes: &[&Shape],
+    opset_version: i64,
+) -> Result<ScanAttributes, CompileError> {
+    if opset_version < 9 {
+        return Err(CompileError::UnsupportedOpsetVersion(opset_version));
+    }
+    let num_scan_inputs = node.get_attribute_value::<i64>("num_scan_inputs", None)?;
+    if num_scan_inputs < 1 || num_scan_inputs as usize > input_shapes.len() {
+        return Err(CompileError::InvalidAttributeValue {
+            attribute: "num_scan_inputs".to_string(),
+            value: num_scan_inputs.to_string(),
+            opset_version,
+        });
+    }
+    let states = input_shapes.len() - num_scan_inputs as usize;
+    let scan_outputs = output_shapes.len().saturating_sub(states);
+
+    // Reads a list of axes or directions that has a value for each of the specified shapes
+    let values = |attribute: &str, shapes: &[&Shape], axes: bool| {
+        let values = node.get_attribute_value(attribute, Some(vec![0; shapes.len()]))?;
+        if values.len() != shapes.len() {
+            return Err(CompileError::InvalidAttributeValue {
+                attribute: attribute.to_string(),
+                value: format!("{values:?}"),
+                opset_version,
+            });
+        }
+        values
+            .iter()
+            .zip(shapes)
+            .map(|(value, shape)| {
+                let rank = shape.rank() as i64;
+                let normalized = if axes && *value < 0 {
+                    value + rank
+                } else {
+                    *value
+                };
+                if (axes && (normalized < 0 || normalized >= rank))
+                    || (!axes && !matches!(normalized, 0 | 1))
+                {
+                    return Err(CompileError::InvalidAttributeValue {
+                        attribute: attribute.to_string(),
+                        value: format!("{values:?}"),
+                        opset_version,
+                    });
+                }
+                Ok(normalized as usize)
+            })
+            .collect::<Result<Vec<usize>, CompileError>>()
+    };
+
+    let scan_input_shapes = &input_shapes[states..];
+    let scan_output_shapes = &output_shapes[(output_shapes.len() - scan_outputs)..];
+    Ok(ScanAttributes {
+        states,
+        input_axes: values("scan_input_axes", scan_input_shapes, true)?,
+        input_reverse: values("scan_input_directions", scan_input_shapes, false)?
+            .into_iter()
+            .map(|direction| direction == 1)
+            .collect(),
+        output_axes: values("scan_output_axes", scan_output_shapes, true)?,
+        output_reverse: values("scan_output_directions", scan_output_shapes, false)?
+            .into_iter()
+            .map(|direction| direction == 1)
+            .collect(),
+    })
+}
+
+/// Inserts the shapes of the inputs of an op that broadcasts its inputs to the output shape into the context (as
+/// `input_padded_shapes` and `input_padded_chunks`), after left-padding them to the rank of the output.
+fn insert_broadcast_inputs(
+    context: &mut Context,
+    input_shapes: &[&Shape],
+    output_shape: &Shape,
+) -> Result<(), CompileError> {
+    let padded_shapes: Vec<Shape> = input_shapes
+        .iter()
+        .map(|shape| shape.left_padded_to(1, output_shape.rank()))
+        .collect();
+    let can_broadcast = padded_shapes.iter().all(|shape| {
+        shape.rank() == output_shape.rank()
+            && shape
+                .dims
+                .iter()
+                .zip(&output_shape.dims)
+                .all(|(dim, output_dim)| dim == output_dim || *dim == 1)
+    });
+    if !can_broadcast {
+        return Err(CompileError::InvalidBroadcast {
+            input_shapes: input_shapes.iter().map(|x| (*x).clone()).collect(),
+            output_shape: output_shape.clone(),
+        });
+    }
+
+    log::debug!(
+        "padded shapes for broadcast: {:?} => {:?}",
+        padded_shapes,
+        output_shape.dims
+    );
+    let padded_dims: Vec<&Vec<u64>> = padded_shapes.iter().map(|shape| &shape.dims).collect();
+    let padded_chunks: Vec<Vec<u64>> = padded_shapes.iter().map(|shape| shape.chunks()).collect();
+    context.insert("input_padded_shapes", &padded_dims);
+    context.insert("input_padded_chunks", &padded_chunks);
+    Ok(())
+}
+
+/// Inserts the quantization parameters (scales and zero points) of a quantized op into the context. The optimizer moves
+/// these from inputs to attributes named after the inputs; zero points default to zero.
+fn insert_quantization_parameters(
+    context: &mut Context,
+    node: &crate::onnx::NodeProto,
+    names: &[&str],
+) -> Result<(), CompileError> {
+    for name in names {
+        if name.ends_with("_scale") {
+            let scale: Vec<f32> = node.get_attribute_value(name, None)?;
+            context.insert(*name, &scale);
+        } else {
+            let zero_point: Vec<i64> = node.get_attribute_value(name, Some(vec![0]))?;
+            context.insert(*name, &zero_point);
+        }
+    }
+    Ok(())
 }
 
 pub fn compile(
@@ -284,6 +746,13 @@ pub fn compile(
             return Err(CompileError::InvalidOperation(op.to_string()));
         }
 
+        "Einsum" => {
+            // Einsum is lowered to other ops by the optimizer, so only equations that cannot be lowered end up here
+            let equation: String = node.get_attribute_value("equation", None)?;
+            EinsumEquation::parse(&equation, input_shapes)?.lower(output_shapes[0].data_type)?;
+            return Err(EinsumError::NotLowered(equation).into());
+        }
+
         // Map simple function
         "Abs" | "Acos" | "Asin" | "Atan" | "Ceil" | "Cos" | "Cosh" | "Exp" | "Floor" | "Log"
         | "Round" | "Sign" | "Sin" | "Sinh" | "Sqrt" | "Tan" | "Tanh" | "Reciprocal" | "Acosh"
@@ -359,6 +828,84 @@ pub fn compile(
             }
         }
 
+        op @ ("ArgMax" | "ArgMin" | "TopK") => {
+            let rank = input_shapes[0].rank() as i64;
+            let default_axis = if op == "TopK" { -1 } else { 0 };
+            let axis = node.get_attribute_value("axis", Some(default_axis))?;
+            let normalized_axis = if axis < 0 { axis + rank } else { axis };
+            if normalized_axis < 0 || normalized_axis >= rank {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "axis".to_string(),
+                    value: axis.to_string(),
+                    opset_version,
+                });
+            }
+            let axis_size = input_shapes[0].dim(normalized_axis as usize);
+
+            // ArgMax and ArgMin select a single element (ties are resolved to the first or last index), TopK selects k
+            // elements (ties are resolved to the lowest index). K is moved from an input to an attribute by the optimizer.
+            let (k, largest, last_index_first) = if op == "TopK" {
+                if output_shapes.len() != 2 {
+                    return Err(CompileError::UnimplementedVariant {
+                        variant: "without indices output".to_string(),
+                        op: op.to_string(),
+                    });
+                }
+                let k = match node.get_attribute_value::<Vec<i64>>("K", None) {
+                    Ok(k) => k.first().copied().unwrap_or(-1),
+                    Err(_) => node.get_attribute_value("k", None)?,
+                };
+                if k < 0 || k as u64 > axis_size {
+                    return Err(CompileError::InvalidAttributeValue {
+                        attribute: "K".to_string(),
+                        value: k.to_string(),
+                        opset_version,
+                    });
+                }
+                if node.get_attribute_value("sorted", Some(1))? == 0 {
+                    log::info!("TopK with sorted=0 will produce sorted output");
+                }
+                (
+                    k as u64,
+                    node.get_attribute_value("largest", Some(1))? != 0,
+                    false,
+                )
+            } else {
+                (
+                    1,
+                    op == "ArgMax",
+                    node.get_attribute_value("select_last_index", Some(0))? != 0,
+                )
+            };
+            context.insert("axis_size", &axis_size);
+            context.insert("axis_chunk", &input_chunks[0][normalized_axis as usize]);
+            context.insert("k", &k);
+            context.insert("largest", &largest);
+            context.insert("last_index_first", &last_index_first);
+
+            // Each thread selects elements along the axis for one position in the other dimensions
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                input_lengths[0] / axis_size.max(1),
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                // The values output (TopK only) has the type of the input; indices are always I64
+                scalar_type: agreed_type(
+                    &input_shapes[0..1],
+                    if op == "TopK" {
+                        &output_shapes[0..1]
+                    } else {
+                        &[]
+                    },
+                )?,
+                template: "pool/topk.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
         "OneHot" => {
             // Currently only OneHot on the last axis is supported
             let axis = node.get_attribute_value("axis", Some(-1))?;
@@ -429,44 +976,674 @@ pub fn compile(
             let (y_threads, workgroup_size_y) = workgroup_size(
                 ceil(elements_per_index, chunk_size as u64),
                 MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
-                MAX_WORKGROUP_SIZE_Y,
+                MAX_WORKGROUP_SIZE_Y,
+            )?;
+
+            context.insert("chunk_type", &chunk_type.wgsl_type_name());
+            context.insert("chunk_size", &chunk_size);
+            context.insert("workgroup_size_x", &workgroup_size_x);
+            context.insert("workgroup_size_y", &workgroup_size_y);
+
+            NodeTemplate {
+                scalar_type,
+                template: "endomorphism/gather.wgsl",
+                threads: (x_threads, y_threads, 1),
+            }
+        }
+
+        "GatherElements" => {
+            // Input 0 is data, input 1 is indices (which has the same rank as data and the shape of the output)
+            let rank = input_shapes[0].rank();
+            let axis = node.get_attribute_value("axis", Some(0))?;
+            let axis = if axis < 0 { axis + rank as i64 } else { axis };
+            if axis < 0 || axis >= rank as i64 || input_shapes[1].rank() != rank {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "axis".to_string(),
+                    value: format!("{}", axis),
+                    opset_version,
+                });
+            }
+            context.insert("axis", &axis);
+            context.insert("axis_size", &input_shapes[0].dim(axis as usize));
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                output_lengths[0],
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type: agreed_type(&input_shapes[0..1], output_shapes)?,
+                template: "endomorphism/gather_elements.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        "GatherND" => {
+            // Input 0 is data, input 1 is indices. The last dimension of indices holds index tuples that each select a
+            // slice of data (within the batch, if batch_dims > 0).
+            let batch_dims = node.get_attribute_value("batch_dims", Some(0))?;
+            let (data_shape, indices_shape) = (input_shapes[0], input_shapes[1]);
+            let tuple_length = indices_shape.dims.last().copied().unwrap_or(0) as usize;
+            if batch_dims < 0
+                || batch_dims as usize >= indices_shape.rank()
+                || tuple_length == 0
+                || batch_dims as usize + tuple_length > data_shape.rank()
+            {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "batch_dims".to_string(),
+                    value: format!("{}", batch_dims),
+                    opset_version,
+                });
+            }
+            let batch_dims = batch_dims as usize;
+            let indexed_dims = batch_dims..(batch_dims + tuple_length);
+
+            context.insert("index_strides", &input_chunks[0][indexed_dims.clone()]);
+            context.insert("index_dims", &data_shape.dims[indexed_dims]);
+            context.insert(
+                "slice_size",
+                &data_shape.dims[(batch_dims + tuple_length)..]
+                    .iter()
+                    .product::<u64>(),
+            );
+            context.insert(
+                "tuples_per_batch",
+                &indices_shape.dims[batch_dims..(indices_shape.rank() - 1)]
+                    .iter()
+                    .product::<u64>()
+                    .max(1),
+            );
+            context.insert(
+                "batch_stride",
+                &data_shape.dims[batch_dims..].iter().product::<u64>(),
+            );
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                output_lengths[0],
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type: agreed_type(&input_shapes[0..1], output_shapes)?,
+                template: "endomorphism/gather_nd.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        op @ ("ScatterElements" | "ScatterND") => {
+            // Input 0 is data, input 1 is indices and input 2 is updates. Each output element applies the updates
+            // targeting it (in order) using the reduction.
+            let reduction = node.get_attribute_value("reduction", Some("none".to_string()))?;
+            if !["none", "add", "mul", "max", "min"].contains(&reduction.as_str()) {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "reduction".to_string(),
+                    value: reduction,
+                    opset_version,
+                });
+            }
+            context.insert("reduction", &reduction);
+
+            let (data_shape, indices_shape) = (input_shapes[0], input_shapes[1]);
+            let template = if op == "ScatterElements" {
+                let rank = data_shape.rank();
+                let axis = node.get_attribute_value("axis", Some(0))?;
+                let axis = if axis < 0 { axis + rank as i64 } else { axis };
+                if axis < 0 || axis >= rank as i64 || indices_shape.rank() != rank {
+                    return Err(CompileError::InvalidAttributeValue {
+                        attribute: "axis".to_string(),
+                        value: format!("{}", axis),
+                        opset_version,
+                    });
+                }
+                context.insert("axis", &axis);
+                context.insert("axis_size", &data_shape.dim(axis as usize));
+                context.insert("updates_along_axis", &indices_shape.dim(axis as usize));
+                "endomorphism/scatter_elements.wgsl"
+            } else {
+                let tuple_length = indices_shape.dims.last().copied().unwrap_or(0) as usize;
+                if tuple_length == 0 || tuple_length > data_shape.rank() {
+                    return Err(CompileError::InvalidInputShape {
+                        input_index: 1,
+                        input_shape: indices_shape.clone(),
+                    });
+                }
+                context.insert("index_strides", &input_chunks[0][0..tuple_length]);
+                context.insert("index_dims", &data_shape.dims[0..tuple_length]);
+                context.insert(
+                    "slice_size",
+                    &data_shape.dims[tuple_length..].iter().product::<u64>(),
+                );
+                context.insert(
+                    "tuples",
+                    &indices_shape.dims[0..(indices_shape.rank() - 1)]
+                        .iter()
+                        .product::<u64>(),
+                );
+                "endomorphism/scatter_nd.wgsl"
+            };
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                output_lengths[0],
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type: agreed_type(&[input_shapes[0], input_shapes[2]], output_shapes)?,
+                template,
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        "Cast" => {
+            // This is the type from the 'to' attribute, except when f16 tensors are stored as f32 on the GPU. Types that
+            // are not supported by WGSL are stored as i32 or f32 (booleans as zero or one).
+            let cast_to_type = output_shapes[0].data_type.gpu_type();
+            context.insert(
+                "cast_to_bool",
+                &(output_shapes[0].data_type == ScalarType::Bool),
+            );
+
+            if !cast_to_type.wgsl_supported() {
+                return Err(CompileError::UnimplementedVariant {
+                    variant: format!("with data type {} (WGSL limitation)", cast_to_type),
+                    op: "Cast".to_string(),
+                });
+            }
+
+            context.insert("cast_to_type", cast_to_type.wgsl_type_name());
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                ceil(output_lengths[0], 4),
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+            NodeTemplate {
+                scalar_type: agreed_type(input_shapes, &[])?,
+                template: "endomorphism/cast.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        op @ ("QuantizeLinear" | "DequantizeLinear") => {
+            if node.get_attribute_value("block_size", Some(0))? != 0 {
+                return Err(CompileError::UnimplementedVariant {
+                    variant: "blocked quantization".to_string(),
+                    op: op.to_string(),
+                });
+            }
+
+            // The scale and zero point are scalars, or apply to slices along the specified axis
+            let (float_shape, names) = if op == "QuantizeLinear" {
+                let (q_min, q_max) = quantized_range(output_shapes[0].data_type, op)?;
+                context.insert("q_min", &q_min);
+                context.insert("q_max", &q_max);
+                (input_shapes[0], ["y_scale", "y_zero_point"])
+            } else {
+                (output_shapes[0], ["x_scale", "x_zero_point"])
+            };
+            insert_quantization_parameters(&mut context, node, &names)?;
+
+            let rank = input_shapes[0].rank() as i64;
+            let axis = node.get_attribute_value("axis", Some(1))?;
+            let axis = if axis < 0 { axis + rank } else { axis };
+            if rank > 0 && (axis < 0 || axis >= rank) {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "axis".to_string(),
+                    value: axis.to_string(),
+                    opset_version,
+                });
+            }
+            let axis = axis.clamp(0, (rank - 1).max(0)) as usize;
+            context.insert("axis_chunk", &input_chunks[0].get(axis).unwrap_or(&1));
+            context.insert("axis_dim", &input_shapes[0].dims.get(axis).unwrap_or(&1));
+            context.insert("float_type", float_shape.data_type.wgsl_type_name());
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                output_lengths[0],
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+            NodeTemplate {
+                scalar_type: float_shape.data_type,
+                template: "endomorphism/quantize.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        op @ ("LayerNormalization" | "SkipLayerNormalization") => {
+            if output_shapes.len() > 1 {
+                return Err(CompileError::UnimplementedVariant {
+                    variant: "with mean, inverse standard deviation or sum outputs".to_string(),
+                    op: op.to_string(),
+                });
+            }
+
+            // Values are normalized over the dimensions starting at axis (SkipLayerNormalization uses the last axis)
+            let rank = input_shapes[0].rank() as i64;
+            let (axis, default_epsilon) = if op == "LayerNormalization" {
+                (node.get_attribute_value("axis", Some(-1))?, 1e-5)
+            } else {
+                (-1, 1e-12)
+            };
+            let normalized_axis = if axis < 0 { axis + rank } else { axis };
+            if normalized_axis < 0 || normalized_axis >= rank {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "axis".to_string(),
+                    value: axis.to_string(),
+                    opset_version,
+                });
+            }
+            let normalized_size: u64 = input_shapes[0].dims[normalized_axis as usize..]
+                .iter()
+                .product();
+            let epsilon: f32 = node.get_attribute_value("epsilon", Some(default_epsilon))?;
+            context.insert("normalized_size", &normalized_size);
+            context.insert("epsilon", &epsilon);
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                input_lengths[0] / normalized_size.max(1),
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type: agreed_type(input_shapes, output_shapes)?,
+                template: "endomorphism/layernormalization.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        op @ ("InstanceNormalization" | "GroupNormalization") => {
+            if input_shapes.len() != 3 {
+                return Err(CompileError::InvalidInputCount {
+                    expected: 3,
+                    actual: input_shapes.len(),
+                });
+            }
+            if input_shapes[0].rank() < 2 {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 0,
+                    input_shape: input_shapes[0].clone(),
+                });
+            }
+
+            // Input is [N, C, D1, D2, ...]; values are normalized per batch and group of channels
+            let channels = input_shapes[0].dim(1);
+            let num_groups = if op == "InstanceNormalization" {
+                channels
+            } else {
+                let num_groups: i64 = node.get_attribute_value("num_groups", None)?;
+                if num_groups <= 0 || channels % num_groups as u64 != 0 {
+                    return Err(CompileError::InvalidAttributeValue {
+                        attribute: "num_groups".to_string(),
+                        value: num_groups.to_string(),
+                        opset_version,
+                    });
+                }
+                num_groups as u64
+            };
+
+            // Scale and bias are per channel (or per group for GroupNormalization before opset 21)
+            let parameter_count = input_lengths[1];
+            if (parameter_count != channels && parameter_count != num_groups)
+                || input_lengths[2] != parameter_count
+            {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 1,
+                    input_shape: input_shapes[1].clone(),
+                });
+            }
+
+            let channels_per_group = channels / num_groups;
+            let spatial_size = input_shapes[0].dims[2..].iter().product::<u64>();
+            let epsilon: f32 = node.get_attribute_value("epsilon", Some(1e-5))?;
+            let rows = input_shapes[0].dim(0) * num_groups;
+            context.insert("rows", &rows);
+            context.insert("num_groups", &num_groups);
+            context.insert("channels_per_group", &channels_per_group);
+            context.insert("channels_per_parameter", &(channels / parameter_count));
+            context.insert("spatial_size", &spatial_size);
+            context.insert("group_size", &(channels_per_group * spatial_size));
+            context.insert("epsilon", &epsilon);
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                rows,
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type: agreed_type(input_shapes, output_shapes)?,
+                template: "endomorphism/groupnormalization.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        "LpNormalization" => {
+            let rank = input_shapes[0].rank() as i64;
+            let axis = node.get_attribute_value("axis", Some(-1))?;
+            let normalized_axis = if axis < 0 { axis + rank } else { axis };
+            if normalized_axis < 0 || normalized_axis >= rank {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "axis".to_string(),
+                    value: axis.to_string(),
+                    opset_version,
+                });
+            }
+            let p: i64 = node.get_attribute_value("p", Some(2))?;
+            if p != 1 && p != 2 {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "p".to_string(),
+                    value: p.to_string(),
+                    opset_version,
+                });
+            }
+
+            let axis_size = input_shapes[0].dim(normalized_axis as usize);
+            let inner_size = input_chunks[0][normalized_axis as usize];
+            let lines = input_lengths[0] / axis_size.max(1);
+            context.insert("p", &p);
+            context.insert("axis_size", &axis_size);
+            context.insert("inner_size", &inner_size);
+            context.insert("outer_stride", &(axis_size * inner_size));
+            context.insert("lines", &lines);
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                lines,
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type: agreed_type(input_shapes, output_shapes)?,
+                template: "endomorphism/lpnormalization.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        op @ ("Gelu" | "BiasGelu") => {
+            let approximate = node.get_attribute_value("approximate", Some("none".to_string()))?;
+            match approximate.as_str() {
+                "none" | "tanh" => context.insert("approximate", &approximate),
+                _ => {
+                    return Err(CompileError::InvalidAttributeValue {
+                        attribute: "approximate".to_string(),
+                        value: approximate,
+                        opset_version,
+                    })
+                }
+            }
+            if op == "BiasGelu" && input_shapes.len() != 2 {
+                return Err(CompileError::InvalidInputCount {
+                    expected: 2,
+                    actual: input_shapes.len(),
+                });
+            }
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                output_lengths[0],
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type: agreed_type(input_shapes, output_shapes)?,
+                template: "endomorphism/gelu.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        "Attention" => {
+            // Only self-attention with a packed QKV projection is supported (no past state or attention bias)
+            if !(3..=4).contains(&input_shapes.len()) || output_shapes.len() != 1 {
+                return Err(CompileError::UnimplementedVariant {
+                    variant: "with past state or attention bias".to_string(),
+                    op: "Attention".to_string(),
+                });
+            }
+            let (input_shape, weights_shape) = (input_shapes[0], input_shapes[1]);
+            if input_shape.rank() != 3 {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 0,
+                    input_shape: input_shape.clone(),
+                });
+            }
+            if weights_shape.rank() != 2
+                || weights_shape.dim(0) != input_shape.dim(2)
+                || weights_shape.dim(1) % 3 != 0
+            {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 1,
+                    input_shape: weights_shape.clone(),
+                });
+            }
+            let qkv_hidden_sizes: Vec<i64> =
+                node.get_attribute_value("qkv_hidden_sizes", Some(vec![]))?;
+            if qkv_hidden_sizes
+                .iter()
+                .any(|size| *size != qkv_hidden_sizes[0])
+            {
+                return Err(CompileError::UnimplementedVariant {
+                    variant: "with different hidden sizes for Q, K and V".to_string(),
+                    op: "Attention".to_string(),
+                });
+            }
+
+            // The mask (if any) is a 0/1 mask of shape (batch_size, sequence_length)
+            let (batch_size, sequence_length) = (input_shape.dim(0), input_shape.dim(1));
+            if let Some(mask_shape) = input_shapes.get(3) {
+                if mask_shape.dims != [batch_size, sequence_length] {
+                    return Err(CompileError::UnimplementedVariant {
+                        variant: format!("with mask of shape {}", mask_shape),
+                        op: "Attention".to_string(),
+                    });
+                }
+            }
+
+            let hidden_size = weights_shape.dim(1) / 3;
+            let num_heads = node.get_attribute_value::<i64>("num_heads", None)? as u64;
+            if num_heads == 0 || hidden_size % num_heads != 0 {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "num_heads".to_string(),
+                    value: num_heads.to_string(),
+                    opset_version,
+                });
+            }
+            let head_size = hidden_size / num_heads;
+            let scale = node.get_attribute_value("scale", Some(1.0 / (head_size as f32).sqrt()))?;
+            context.insert("input_size", &input_shape.dim(2));
+            context.insert("hidden_size", &hidden_size);
+            context.insert("num_heads", &num_heads);
+            context.insert("head_size", &head_size);
+            context.insert("sequence_length", &sequence_length);
+            context.insert("scale", &scale);
+            context.insert(
+                "unidirectional",
+                &(node.get_attribute_value("unidirectional", Some(0))? != 0),
+            );
+
+            // Each thread calculates the output for one head of one element of the sequence
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                batch_size * num_heads * sequence_length,
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
             )?;
-
-            context.insert("chunk_type", &chunk_type.wgsl_type_name());
-            context.insert("chunk_size", &chunk_size);
             context.insert("workgroup_size_x", &workgroup_size_x);
-            context.insert("workgroup_size_y", &workgroup_size_y);
 
             NodeTemplate {
-                scalar_type,
-                template: "endomorphism/gather.wgsl",
-                threads: (x_threads, y_threads, 1),
+                scalar_type: agreed_type(&input_shapes[0..3], output_shapes)?,
+                template: "matrix/attention.wgsl",
+                threads: (x_threads, 1, 1),
             }
         }
 
-        "Cast" => {
-            let cast_to_type =
-                ScalarType::from_i32(node.get_attribute_value::<i64>("to", None)? as i32)?;
+        op @ ("LSTM" | "GRU" | "RNN") => {
+            let inputs = optional_inputs(node, input_shapes);
+            let (input_shape, weights_shape, recurrence_weights_shape) = match inputs[..] {
+                [Some(x), Some(w), Some(r), ..] => (*x, *w, *r),
+                _ => {
+                    return Err(CompileError::InvalidInputCount {
+                        expected: 3,
+                        actual: input_shapes.len(),
+                    })
+                }
+            };
+            let optional_input = |index: usize| inputs.get(index).copied().flatten().copied();
 
-            if !cast_to_type.wgsl_supported() {
+            let direction = node.get_attribute_value("direction", Some("forward".to_string()))?;
+            let num_directions = match direction.as_str() {
+                "forward" | "reverse" => 1,
+                "bidirectional" => 2,
+                _ => {
+                    return Err(CompileError::InvalidAttributeValue {
+                        attribute: "direction".to_string(),
+                        value: direction,
+                        opset_version,
+                    })
+                }
+            };
+            if op == "LSTM" && node.get_attribute_value("input_forget", Some(0))? != 0 {
                 return Err(CompileError::UnimplementedVariant {
-                    variant: format!("with data type {} (WGSL limitation)", cast_to_type),
-                    op: "Cast".to_string(),
+                    variant: "input_forget".to_string(),
+                    op: op.to_string(),
                 });
             }
 
-            context.insert("cast_to_type", cast_to_type.wgsl_type_name());
+            let layout = node.get_attribute_value("layout", Some(0))?;
+            if input_shape.rank() != 3 {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 0,
+                    input_shape: input_shape.clone(),
+                });
+            }
+            let (sequence_length, batch_size, input_size) = if layout == 0 {
+                (input_shape.dim(0), input_shape.dim(1), input_shape.dim(2))
+            } else {
+                (input_shape.dim(1), input_shape.dim(0), input_shape.dim(2))
+            };
 
-            let (x_threads, workgroup_size_x) = workgroup_size(
-                ceil(output_lengths[0], 4),
-                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
-                MAX_WORKGROUP_SIZE_X,
-            )?;
+            // The weights of all gates are stacked (iofc for LSTM, zrh for GRU)
+            let gates: u64 = match op {
+                "LSTM" => 4,
+                "GRU" => 3,
+                _ => 1,
+            };
+            let hidden_size = recurrence_weights_shape.dim(2);
+            let state_dims = if layout == 0 {
+                vec![num_directions, batch_size, hidden_size]
+            } else {
+                vec![batch_size, num_directions, hidden_size]
+            };
+            let expected_dims: [(usize, Vec<u64>); 7] = [
+                (1, vec![num_directions, gates * hidden_size, input_size]),
+                (2, vec![num_directions, gates * hidden_size, hidden_size]),
+                (3, vec![num_directions, 2 * gates * hidden_size]),
+                (4, vec![batch_size]),
+                (5, state_dims.clone()),
+                (6, state_dims),
+                (7, vec![num_directions, 3 * hidden_size]),
+            ];
+            for (index, dims) in expected_dims {
+                if let Some(shape) = optional_input(index) {
+                    if shape.dims != dims {
+                        return Err(CompileError::InvalidInputShape {
+                            input_index: index,
+                            input_shape: shape.clone(),
+                        });
+                    }
+                }
+            }
+            if weights_shape.dims[0] != num_directions {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 1,
+                    input_shape: weights_shape.clone(),
+                });
+            }
+
+            // The hidden state is shared between the threads of a workgroup
+            if hidden_size > MAX_RECURRENT_HIDDEN_SIZE {
+                return Err(CompileError::ComputeLimitExceeded(
+                    "recurrent hidden size".to_string(),
+                    hidden_size as u32,
+                    MAX_RECURRENT_HIDDEN_SIZE as u32,
+                ));
+            }
+            let workgroups = num_directions * batch_size;
+            if workgroups > MAX_COMPUTE_WORKGROUPS_PER_DIMENSION as u64 {
+                return Err(CompileError::ComputeLimitExceeded(
+                    "workgroups".to_string(),
+                    workgroups as u32,
+                    MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                ));
+            }
+            let workgroup_size_x = hidden_size.min(MAX_WORKGROUP_SIZE_X as u64);
+
+            let input_roles: Vec<&str> = [
+                "X",
+                "W",
+                "R",
+                "B",
+                "sequence_lens",
+                "initial_h",
+                "initial_c",
+                "P",
+            ]
+            .iter()
+            .zip(&inputs)
+            .filter(|(_, input)| input.is_some())
+            .map(|(role, _)| *role)
+            .collect();
+            let output_present = |index: usize| {
+                node.get_output()
+                    .get(index)
+                    .is_some_and(|name| !name.is_empty())
+            };
+            let clip = node.get_attribute_value::<f32>("clip", None).ok();
+
+            context.insert("input_roles", &input_roles);
+            context.insert("has_y", &output_present(0));
+            context.insert("has_y_h", &output_present(1));
+            context.insert("has_y_c", &output_present(2));
+            let activations = recurrent_activations(node, num_directions as usize, opset_version)?;
+            context.insert(
+                "activation_slots",
+                &(activations.len() / num_directions as usize),
+            );
+            context.insert("activations", &activations);
+            context.insert("gates", &gates);
+            context.insert("layout", &layout);
+            context.insert("reverse", &(direction == "reverse"));
+            context.insert("num_directions", &num_directions);
+            context.insert("sequence_length", &sequence_length);
+            context.insert("batch_size", &batch_size);
+            context.insert("input_size", &input_size);
+            context.insert("hidden_size", &hidden_size);
+            context.insert("has_clip", &clip.is_some());
+            context.insert("clip", &clip.unwrap_or(0.0));
+            context.insert(
+                "linear_before_reset",
+                &(node.get_attribute_value("linear_before_reset", Some(0))? != 0),
+            );
             context.insert("workgroup_size_x", &workgroup_size_x);
+            context.insert("units_per_thread", &ceil(hidden_size, workgroup_size_x));
+
+            // Each workgroup processes the whole sequence for one direction and batch element
             NodeTemplate {
-                scalar_type: agreed_type(input_shapes, &[])?,
-                template: "endomorphism/cast.wgsl",
-                threads: (x_threads, 1, 1),
+                scalar_type: agreed_type(&input_shapes[0..3], &[])?,
+                template: "matrix/recurrent.wgsl",
+                threads: (workgroups as u32, 1, 1),
             }
         }
 
@@ -557,6 +1734,22 @@ pub fn compile(
                 });
             }
 
+            // Comparisons produce booleans, which are stored as i32 on the GPU
+            let comparison = matches!(
+                op,
+                "Equal" | "Greater" | "GreaterOrEqual" | "Less" | "LessOrEqual"
+            );
+            let scalar_type = if comparison {
+                agreed_type(input_shapes, &[])?
+            } else {
+                agreed_type(input_shapes, output_shapes)?
+            };
+            context.insert("comparison", &comparison);
+            context.insert(
+                "output_type",
+                output_shapes[0].data_type.gpu_type().wgsl_type_name(),
+            );
+
             // Determine the operator to use in the shader for this op
             context.insert(
                 "op_type",
@@ -584,42 +1777,11 @@ pub fn compile(
             );
 
             if input_shapes.len() == 2
-                && (input_shapes[0] != output_shapes[0] || input_shapes[1] != output_shapes[0])
+                && (input_shapes[0].dims != output_shapes[0].dims
+                    || input_shapes[1].dims != output_shapes[0].dims)
             {
-                // We are likely broadcasting; check if the broadcast is valid. Compute the possible broadcast output shape
-                let out_shape =
-                    Shape::multi_broadcast(&[input_shapes[0].clone(), input_shapes[1].clone()])
-                        .ok_or(CompileError::InvalidBroadcast {
-                            input_shapes: input_shapes
-                                .iter()
-                                .map(|x| (*x).clone())
-                                .collect::<Vec<Shape>>(),
-                            output_shape: output_shapes[0].clone(),
-                        })?;
-
-                if &out_shape != output_shapes[0] {
-                    return Err(CompileError::InvalidBroadcast {
-                        input_shapes: input_shapes
-                            .iter()
-                            .map(|x| (*x).clone())
-                            .collect::<Vec<Shape>>(),
-                        output_shape: output_shapes[0].clone(),
-                    });
-                }
-
-                let lhs_padded_shape = input_shapes[0].left_padded_to(1, out_shape.rank());
-                let rhs_padded_shape = input_shapes[1].left_padded_to(1, out_shape.rank());
-                context.insert("lhs_padded_shape", &lhs_padded_shape.dims);
-                context.insert("rhs_padded_shape", &rhs_padded_shape.dims);
-                context.insert("lhs_padded_chunks", &lhs_padded_shape.chunks());
-                context.insert("rhs_padded_chunks", &rhs_padded_shape.chunks());
-
-                log::debug!(
-                    "padded shapes for broadcast: {:?}, {:?} => {:?}",
-                    lhs_padded_shape,
-                    rhs_padded_shape,
-                    out_shape.dims
-                );
+                // We are likely broadcasting; check if the broadcast is valid
+                insert_broadcast_inputs(&mut context, input_shapes, output_shapes[0])?;
 
                 let (x_threads, workgroup_size_x) = workgroup_size(
                     output_lengths[0],
@@ -629,11 +1791,11 @@ pub fn compile(
                 context.insert("workgroup_size_x", &workgroup_size_x);
 
                 NodeTemplate {
-                    scalar_type: agreed_type(input_shapes, output_shapes)?,
+                    scalar_type,
                     template: "endomorphism/broadcast.wgsl",
                     threads: (x_threads, 1, 1),
                 }
-            } else if input_shapes[0] != output_shapes[0] {
+            } else if input_shapes[0].dims != output_shapes[0].dims {
                 // If we are not broadcasting, the input shape needs to be equal to the output shape
                 return Err(CompileError::InvalidInputShape {
                     input_index: 0,
@@ -652,7 +1814,7 @@ pub fn compile(
                 context.insert("workgroup_size_x", &workgroup_size_x);
 
                 NodeTemplate {
-                    scalar_type: agreed_type(input_shapes, output_shapes)?,
+                    scalar_type,
                     template: "endomorphism/arithmetic.wgsl",
                     threads: (x_threads, 1, 1),
                 }
@@ -813,8 +1975,11 @@ pub fn compile(
                 threads: (per_dim as u32, per_dim as u32, 1),
             }
         }
-        op @ ("MaxPool" | "AveragePool" | "Conv" | "ConvRelu" | "ConvLeakyRelu" | "ConvMish"
-        | "GlobalAveragePool") => {
+        op if matches!(
+            op,
+            "MaxPool" | "AveragePool" | "GlobalAveragePool" | "QLinearConv"
+        ) || fused_conv_type(op).is_some() =>
+        {
             // TODO: Conv only support NxCxHxW for the moment.
             if input_shapes[0].rank() != 4 {
                 return Err(CompileError::InvalidInputShape {
@@ -890,6 +2055,16 @@ pub fn compile(
                 });
             }
 
+            // Fused convolutions may take a residual input after the bias, which is added to the output
+            let residual = matches!(fused_conv_type(op), Some((true, _)));
+            if residual && input_shapes[input_shapes.len() - 1] != *output_shape {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: input_shapes.len() - 1,
+                    input_shape: input_shapes[input_shapes.len() - 1].clone(),
+                });
+            }
+            let input_shapes = &input_shapes[0..input_shapes.len() - residual as usize];
+
             if input_shapes.len() >= 2 && output_shape.dim(1) != input_shapes[1].dim(0) {
                 // Output feature map count != Filter count.
                 return Err(CompileError::InvalidInputShape {
@@ -922,6 +2097,8 @@ pub fn compile(
             context.insert("pad", &pads);
             context.insert("count_include_pad", &count_include_pad);
             context.insert("dilation", &dilations);
+            context.insert("bias", &(input_shapes.len() == 3));
+            context.insert("residual", &residual);
 
             // GLSL shader for convolution computation
             match op {
@@ -940,10 +2117,26 @@ pub fn compile(
                         }
                     }
                 }
-                "Conv" | "ConvRelu" | "ConvLeakyRelu" | "ConvMish" => {
-                    // Alpha is the Leaky Relu attribute
-                    let alpha = node.get_attribute_value("alpha", Some(0.01))?;
-                    context.insert("alpha", &alpha);
+                op if fused_conv_type(op).is_some() => {
+                    // Parameters of the fused activation (alpha and beta for LeakyRelu and HardSwish, min and max for Clip)
+                    match fused_conv_type(op) {
+                        Some((_, "HardSwish")) => {
+                            let alpha = node.get_attribute_value("alpha", Some(1.0 / 6.0))?;
+                            let beta = node.get_attribute_value("beta", Some(0.5))?;
+                            context.insert("alpha", &alpha);
+                            context.insert("beta", &beta);
+                        }
+                        Some((_, "Clip")) => {
+                            let min: f32 = node.get_attribute_value("min", None)?;
+                            let max: f32 = node.get_attribute_value("max", None)?;
+                            context.insert("min", &format!("{:?}", min));
+                            context.insert("max", &format!("{:?}", max));
+                        }
+                        _ => {
+                            let alpha = node.get_attribute_value("alpha", Some(0.01))?;
+                            context.insert("alpha", &alpha);
+                        }
+                    }
 
                     let scalar_type = agreed_type(input_shapes, output_shapes)?;
 
@@ -983,9 +2176,100 @@ pub fn compile(
                         }
                     }
                 }
+                "QLinearConv" => {
+                    // The quantization parameters were moved to attributes, so the inputs are x, w and (optionally) B
+                    let (q_min, q_max) = quantized_range(output_shapes[0].data_type, op)?;
+                    context.insert("q_min", &q_min);
+                    context.insert("q_max", &q_max);
+                    insert_quantization_parameters(
+                        &mut context,
+                        node,
+                        &[
+                            "x_scale",
+                            "x_zero_point",
+                            "w_scale",
+                            "w_zero_point",
+                            "y_scale",
+                            "y_zero_point",
+                        ],
+                    )?;
+                    NodeTemplate {
+                        scalar_type: ScalarType::I32,
+                        template: "pool/qlinearconv.wgsl",
+                        threads: (ceil(output_lengths[0], 256) as _, 1, 1),
+                    }
+                }
                 _ => return Err(CompileError::InvalidOperation(op.to_string())),
             }
         }
+        "ConvTranspose" => {
+            // Weights have shape (C x M/group x kH x kW). Only NxCxHxW inputs are supported (as for Conv).
+            let (input_shape, weights_shape, output_shape) =
+                (input_shapes[0], input_shapes[1], output_shapes[0]);
+            if input_shape.rank() != 4 {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 0,
+                    input_shape: input_shape.clone(),
+                });
+            }
+            let group = node.get_attribute_value("group", Some(1))? as u64;
+            let channels_per_group = input_shape.dim(1) / group.max(1);
+            let output_channels_per_group = weights_shape.dim(1);
+            if group == 0
+                || channels_per_group * group != input_shape.dim(1)
+                || weights_shape.rank() != 4
+                || weights_shape.dim(0) != input_shape.dim(1)
+                || output_channels_per_group * group != output_shape.dim(1)
+            {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 1,
+                    input_shape: weights_shape.clone(),
+                });
+            }
+            if input_shapes.len() >= 3 && input_shapes[2].dims != [output_shape.dim(1)] {
+                // Bias count != output channel count.
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 2,
+                    input_shape: input_shapes[2].clone(),
+                });
+            }
+
+            let kernel_shape = node.get_attribute_value(
+                "kernel_shape",
+                Some(vec![
+                    weights_shape.dim(2) as i64,
+                    weights_shape.dim(3) as i64,
+                ]),
+            )?;
+            let strides = node.get_attribute_value("strides", Some(vec![1, 1]))?;
+            let dilations = node.get_attribute_value("dilations", Some(vec![1, 1]))?;
+            let pads = conv_transpose_pads(
+                node,
+                input_shape,
+                output_shape,
+                &kernel_shape,
+                &strides,
+                &dilations,
+            )?;
+
+            context.insert("original_width", &input_shape.dim(3));
+            context.insert("original_height", &input_shape.dim(2));
+            context.insert("groups", &group);
+            context.insert("channels_per_group", &channels_per_group);
+            context.insert("output_channels_per_group", &output_channels_per_group);
+            context.insert("stride", &strides);
+            context.insert("kernel_shape", &kernel_shape);
+            context.insert("kernel_length", &(kernel_shape[0] * kernel_shape[1]));
+            context.insert("pad", &pads);
+            context.insert("dilation", &dilations);
+            context.insert("bias", &(input_shapes.len() == 3));
+
+            NodeTemplate {
+                scalar_type: agreed_type(input_shapes, output_shapes)?,
+                template: "pool/conv.wgsl",
+                threads: (ceil(output_lengths[0], 256) as _, 1, 1),
+            }
+        }
         op @ ("Gemm" | "MatMul") => {
             // Generic matrix multiplication; outputs an M*N matrix from inputs A (size M*K) and B (size K*N)
 
@@ -1134,13 +2418,19 @@ pub fn compile(
             // See https://github.com/gfx-rs/naga/issues/1896
             let scalar_type = agreed_type(input_shapes, output_shapes)?;
             match scalar_type {
-                ScalarType::I32 | ScalarType::I64 | ScalarType::U8 => {
+                ScalarType::I32
+                | ScalarType::I64
+                | ScalarType::U8
+                | ScalarType::I8
+                | ScalarType::U16
+                | ScalarType::I16
+                | ScalarType::Bool => {
                     return Err(CompileError::UnimplementedVariant {
                         variant: "with integers".to_string(),
                         op: op.to_string(),
                     })
                 }
-                ScalarType::F32 => (),
+                ScalarType::F32 | ScalarType::F16 | ScalarType::F64 => (),
             }
 
             // Obtain alpha and beta coefficients
@@ -1203,6 +2493,138 @@ pub fn compile(
                 }
             }
         }
+
+        op @ ("MatMulInteger" | "QLinearMatMul") => {
+            // Quantized matrix multiplication; each thread calculates one element of the output from the (zero point
+            // adjusted) integer inputs A (M*K) and B (K*N), which may both be stacks of matrices
+            if op == "QLinearMatMul" {
+                let (q_min, q_max) = quantized_range(output_shapes[0].data_type, op)?;
+                context.insert("q_min", &q_min);
+                context.insert("q_max", &q_max);
+                insert_quantization_parameters(
+                    &mut context,
+                    node,
+                    &[
+                        "a_scale",
+                        "a_zero_point",
+                        "b_scale",
+                        "b_zero_point",
+                        "y_scale",
+                        "y_zero_point",
+                    ],
+                )?;
+            } else {
+                insert_quantization_parameters(
+                    &mut context,
+                    node,
+                    &["a_zero_point", "b_zero_point"],
+                )?;
+            }
+
+            if input_shapes.len() != 2 {
+                return Err(CompileError::InvalidInputCount {
+                    expected: 2,
+                    actual: input_shapes.len(),
+                });
+            }
+
+            // 1-D inputs are promoted to matrices (this does not change the layout of the output)
+            let (a_shape, b_shape) = (input_shapes[0], input_shapes[1]);
+            let (dim_m, dim_k) = match a_shape.rank() {
+                0 => {
+                    return Err(CompileError::InvalidInputShape {
+                        input_index: 0,
+                        input_shape: a_shape.clone(),
+                    })
+                }
+                1 => (1, a_shape.dim(0)),
+                rank => (a_shape.dim(rank - 2), a_shape.dim(rank - 1)),
+            };
+            let dim_n = match b_shape.rank() {
+                1 => 1,
+                rank if rank > 1 && b_shape.dim(rank - 2) == dim_k => b_shape.dim(rank - 1),
+                _ => {
+                    return Err(CompileError::InvalidInputShape {
+                        input_index: 1,
+                        input_shape: b_shape.clone(),
+                    })
+                }
+            };
+
+            // Stacks of matrices can only be broadcast when one of the inputs is a single matrix
+            let stack_count = output_lengths[0] / (dim_m * dim_n).max(1);
+            let batch_stride = |shape: &Shape, matrix_size: u64| {
+                let count = shape.element_count() / matrix_size.max(1);
+                if count == stack_count {
+                    Ok(if count > 1 { matrix_size } else { 0 })
+                } else if count == 1 {
+                    Ok(0)
+                } else {
+                    Err(CompileError::UnimplementedVariant {
+                        variant: "broadcasting of stacked matrices".to_string(),
+                        op: op.to_string(),
+                    })
+                }
+            };
+            context.insert("a_batch_stride", &batch_stride(a_shape, dim_m * dim_k)?);
+            context.insert("b_batch_stride", &batch_stride(b_shape, dim_k * dim_n)?);
+            context.insert("dim_m", &dim_m);
+            context.insert("dim_k", &dim_k);
+            context.insert("dim_n", &dim_n);
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                output_lengths[0],
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+            NodeTemplate {
+                scalar_type: ScalarType::I32,
+                template: "matrix/qmatmul.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+        "RoiAlign" => {
+            // Input 0 is X [N, C, H, W], input 1 the RoIs [num_rois, 4] and input 2 their batch indices [num_rois]
+            if input_shapes.len() != 3 {
+                return Err(CompileError::InvalidInputCount {
+                    expected: 3,
+                    actual: input_shapes.len(),
+                });
+            }
+            if input_shapes[0].rank() != 4 {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 0,
+                    input_shape: input_shapes[0].clone(),
+                });
+            }
+            if input_shapes[1].dims != [output_shapes[0].dim(0), 4] {
+                return Err(CompileError::InvalidInputShape {
+                    input_index: 1,
+                    input_shape: input_shapes[1].clone(),
+                });
+            }
+
+            let attributes = roi_align_attributes(node, opset_version)?;
+            context.insert("offset", &if attributes.half_pixel { 0.5 } else { 0.0 });
+            context.insert("roi_align", &attributes);
+            context.insert("height", &input_shapes[0].dim(2));
+            context.insert("width", &input_shapes[0].dim(3));
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                output_lengths[0],
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type: agreed_type(&input_shapes[0..2], output_shapes)?,
+                template: "pool/roialign.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
         "Resize" => {
             let coordinate_transformation_mode = node.get_attribute_value(
                 "coordinate_transformation_mode",
@@ -1297,7 +2719,6 @@ pub fn compile(
                 threads: (ceil(output_lengths[0], 256) as u32, 1, 1),
             }
         }
-        "Sum" => return Err(CompileError::UnimplementedOp(String::from("Sum"))),
         "Split" => {
             let mut axis = node.get_attribute_value("axis", Some(0))?;
             if axis < 0 {
@@ -1365,6 +2786,127 @@ pub fn compile(
                 threads: (ceil(output_lengths[0], 256) as u32, 1, 1),
             }
         }
+        op @ ("Expand" | "Tile" | "Where") => {
+            let scalar_type = if op == "Where" {
+                // The condition is a boolean tensor
+                agreed_type(&input_shapes[1..], output_shapes)?
+            } else {
+                agreed_type(&input_shapes[0..1], output_shapes)?
+            };
+
+            if op == "Tile" {
+                // Repeats were moved to an attribute by the optimizer; the output shape already reflects them
+                if input_shapes[0].rank() != output_shapes[0].rank()
+                    || input_shapes[0]
+                        .dims
+                        .iter()
+                        .zip(&output_shapes[0].dims)
+                        .any(|(dim, output_dim)| *dim == 0 || output_dim % dim != 0)
+                {
+                    return Err(CompileError::InvalidInputShape {
+                        input_index: 0,
+                        input_shape: input_shapes[0].clone(),
+                    });
+                }
+                context.insert("input_padded_shapes", &i_dims);
+                context.insert("input_padded_chunks", &input_chunks);
+            } else {
+                insert_broadcast_inputs(&mut context, input_shapes, output_shapes[0])?;
+            }
+            context.insert(
+                "output_type",
+                output_shapes[0].data_type.gpu_type().wgsl_type_name(),
+            );
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                output_lengths[0],
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type,
+                template: "endomorphism/broadcast.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        // Variadic element-wise operation (with multidirectional broadcasting of all inputs)
+        "Sum" | "Mean" | "Max" | "Min" => {
+            insert_broadcast_inputs(&mut context, input_shapes, output_shapes[0])?;
+            context.insert(
+                "output_type",
+                output_shapes[0].data_type.gpu_type().wgsl_type_name(),
+            );
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                output_lengths[0],
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type: agreed_type(input_shapes, output_shapes)?,
+                template: "endomorphism/broadcast.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
+        "Slice" => {
+            // Starts, ends, axes and steps are attributes before opset 10, and moved to attributes by the optimizer after
+            let rank = input_shapes[0].rank();
+            let starts: Vec<i64> = node.get_attribute_value("starts", None)?;
+            let axes: Vec<i64> =
+                node.get_attribute_value("axes", Some((0..starts.len() as i64).collect()))?;
+            let steps: Vec<i64> = node.get_attribute_value("steps", Some(vec![1; starts.len()]))?;
+            if axes.len() != starts.len() || steps.len() != starts.len() {
+                return Err(CompileError::InvalidAttributeValue {
+                    attribute: "axes".to_string(),
+                    value: format!("{:?}", axes),
+                    opset_version,
+                });
+            }
+
+            // The first element and step along each axis of the input (unsliced axes are copied entirely)
+            let mut slice_starts = vec![0; rank];
+            let mut slice_steps = vec![1; rank];
+            for ((axis, start), step) in axes.iter().zip(&starts).zip(&steps) {
+                let axis = if *axis < 0 { axis + rank as i64 } else { *axis };
+                if axis < 0 || axis >= rank as i64 || *step == 0 {
+                    return Err(CompileError::InvalidAttributeValue {
+                        attribute: "axes".to_string(),
+                        value: format!("{:?}", axes),
+                        opset_version,
+                    });
+                }
+                let dim = input_shapes[0].dim(axis as usize) as i64;
+                let start = if *start < 0 { start + dim } else { *start };
+                slice_starts[axis as usize] = if *step > 0 {
+                    start.clamp(0, dim)
+                } else {
+                    start.clamp(0, dim - 1)
+                };
+                slice_steps[axis as usize] = *step;
+            }
+            context.insert("slice_starts", &slice_starts);
+            context.insert("slice_steps", &slice_steps);
+
+            let (x_threads, workgroup_size_x) = workgroup_size(
+                output_lengths[0],
+                MAX_COMPUTE_WORKGROUPS_PER_DIMENSION,
+                MAX_WORKGROUP_SIZE_X,
+            )?;
+            context.insert("workgroup_size_x", &workgroup_size_x);
+
+            NodeTemplate {
+                scalar_type: agreed_type(&input_shapes[0..1], output_shapes)?,
+                template: "matrix/slice.wgsl",
+                threads: (x_threads, 1, 1),
+            }
+        }
+
         "Transpose" => {
             let n_dims: i64 = input_shapes[0].rank() as i64;
             let default = (0..n_dims).rev().collect::<Vec<i64>>();
@@ -1440,10 +2982,19 @@ pub fn compile(
     context.insert("mat3x3_stride", &(48));
 
     // Render template
-    let shader = get_templates()
+    let mut shader = get_templates()
         .render(node_template.template, &context)
         .expect("failed to render shader");
 
+    // Half-precision floats are an extension to WGSL that needs to be enabled explicitly
+    if input_shapes
+        .iter()
+        .chain(output_shapes)
+        .any(|shape| shape.data_type == ScalarType::F16)
+    {
+        shader.insert_str(0, "enable f16;\n");
+    }
+
     Ok(CompiledNode {
         shader,
         threads: node_template.threads,
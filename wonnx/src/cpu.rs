@@ -0,0 +1,3724 @@
+//! Reference implementation of the supported ONNX ops that executes the IR graph on the CPU
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use bytemuck::pod_collect_to_vec;
+use half::f16;
+use thiserror::Error;
+
+use crate::{
+    compiler::{
+        conv_transpose_pads, fused_conv_type, optional_inputs, quantized_range,
+        recurrent_activations, roi_align_attributes, scan_attributes, CompileError,
+        RecurrentActivation,
+    },
+    einsum::EinsumEquation,
+    ir::{op_forwards_input, Node, NodeDefinition, NodeIdentifier, RowCounts},
+    onnx::{AttributeProto, NodeProto, TensorProto},
+    utils::{DataTypeError, InputTensor, NodeAttributes, OutputTensor, ScalarType, Shape},
+};
+
+/// A model that performs inference on the CPU. It executes the same (optimized) IR graph that [`crate::gpu::GpuModel`]
+/// consumes, and is intended for use on machines without a GPU and as a reference to check GPU results against.
+pub struct CpuModel {
+    onnx_opset_version: i64,
+    steps: Vec<CpuStep>,
+    value_count: usize,
+    inference_outputs: HashMap<String, usize>,
+}
+
+/// An operation that is performed on the CPU as part of inference. Each step writes one or more values, which are
+/// identified by their index in the list of values that is built up during inference.
+enum CpuStep {
+    /// A tensor with static data
+    Initializer { output: usize, tensor: CpuTensor },
+
+    /// A tensor whose data is obtained from inference input
+    Input {
+        output: usize,
+        name: String,
+        shape: Shape,
+    },
+
+    /// An op that reads values produced by earlier steps and produces new values
+    Operator {
+        proto: Box<NodeProto>,
+        inputs: Vec<usize>,
+        outputs: Vec<usize>,
+        output_shapes: Vec<Shape>,
+    },
+
+    /// An op with subgraphs (If, Loop or Scan), which runs the models of its subgraphs as often as needed. The inputs
+    /// are those of the op, followed by the values that the subgraphs take from enclosing graphs.
+    ControlFlow {
+        proto: Box<NodeProto>,
+        inputs: Vec<usize>,
+        outputs: Vec<usize>,
+        output_shapes: Vec<Shape>,
+        subgraphs: Vec<CpuSubgraph>,
+    },
+}
+
+/// A subgraph of a control flow op
+struct CpuSubgraph {
+    /// The name of the attribute holding the graph
+    attribute: String,
+    model: CpuModel,
+
+    /// The names of the declared inputs and outputs of the graph
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+
+    /// The values of enclosing graphs that the graph uses, by name and index in the inputs of the op
+    captures: Vec<(String, usize)>,
+}
+
+/// A tensor that resides in main memory. Values are stored as f64 regardless of the data type of the tensor (which is
+/// exact for all supported integer types as long as values stay below 2^53), but are rounded to the precision of the data
+/// type whenever a tensor is created.
+#[derive(Clone, Debug)]
+struct CpuTensor {
+    shape: Shape,
+    data: Vec<f64>,
+}
+
+#[derive(Error, Debug)]
+pub enum CpuError {
+    #[error("executing node '{node}' failed: {error}")]
+    OperatorError { node: String, error: CompileError },
+
+    #[error(
+        "executing node '{node}' failed: index {index} is out of bounds for an axis of size {size}"
+    )]
+    IndexOutOfBounds { node: String, index: i64, size: u64 },
+
+    #[error("inference input not found: '{0}'")]
+    InferenceInputMissing(String),
+
+    #[error("inference input '{name}' has {actual} elements, expected {expected}")]
+    InferenceInputInvalidLength {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("node output not found: index {0}")]
+    OutputMissing(usize),
+
+    #[error("executing node '{node}' failed: the output has {actual} elements, which exceeds the size of its declared shape ({expected} elements)")]
+    OutputTooLarge {
+        node: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("scalar type error: {0}")]
+    ScalarType(#[from] DataTypeError),
+
+    #[error("node '{node}' performed {actual} iterations, but its scan outputs have room for {expected}")]
+    IterationCountMismatch {
+        node: String,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl CpuModel {
+    /// Create a version of the specified model for which inference can be performed on the CPU
+    pub fn from(root: Arc<Node>, onnx_opset_version: i64) -> Result<CpuModel, CpuError> {
+        let mut nodes = vec![];
+        root.topological_sort(&mut HashSet::new(), &mut nodes);
+
+        let mut cpu_model = CpuModel {
+            onnx_opset_version,
+            steps: vec![],
+            value_count: 0,
+            inference_outputs: HashMap::new(),
+        };
+
+        // Assign value indices to the outputs of each node, in order of execution
+        let mut node_outputs = HashMap::<NodeIdentifier, Vec<usize>>::new();
+        for node in &nodes {
+            let inputs = node
+                .inputs
+                .iter()
+                .map(|input| {
+                    node_outputs[&input.source_node.identifier()]
+                        .get(input.output_index)
+                        .copied()
+                        .ok_or(CpuError::OutputMissing(input.output_index))
+                })
+                .collect::<Result<Vec<usize>, CpuError>>()?;
+
+            let outputs: Vec<usize> = match &node.definition {
+                NodeDefinition::Operator(op_def) if !op_def.subgraphs.is_empty() => {
+                    let outputs = cpu_model.new_values(op_def.output_shapes.len());
+                    let present_count = op_def
+                        .proto
+                        .get_input()
+                        .iter()
+                        .filter(|name| !name.is_empty())
+                        .count();
+                    let captures = op_def.captures();
+                    let subgraphs = op_def
+                        .subgraphs
+                        .iter()
+                        .map(|subgraph| {
+                            let NodeDefinition::Outputs { names } = &subgraph.root.definition
+                            else {
+                                unreachable!("subgraph root must be an outputs node");
+                            };
+                            Ok(CpuSubgraph {
+                                attribute: subgraph.attribute.clone(),
+                                model: CpuModel::from(subgraph.root.clone(), onnx_opset_version)?,
+                                inputs: subgraph
+                                    .inputs
+                                    .iter()
+                                    .map(|input| input.get_name().to_string())
+                                    .collect(),
+                                outputs: names.clone(),
+                                captures: subgraph
+                                    .captures
+                                    .iter()
+                                    .map(|name| {
+                                        let index =
+                                            captures.iter().position(|c| c == name).unwrap();
+                                        (name.clone(), present_count + index)
+                                    })
+                                    .collect(),
+                            })
+                        })
+                        .collect::<Result<Vec<CpuSubgraph>, CpuError>>()?;
+                    cpu_model.steps.push(CpuStep::ControlFlow {
+                        proto: Box::new(op_def.proto.clone().into_owned()),
+                        inputs,
+                        outputs: outputs.clone(),
+                        output_shapes: op_def.output_shapes.clone(),
+                        subgraphs,
+                    });
+                    outputs
+                }
+                NodeDefinition::Operator(op_def) => {
+                    let outputs = cpu_model.new_values(op_def.output_shapes.len());
+                    cpu_model.steps.push(CpuStep::Operator {
+                        proto: Box::new(op_def.proto.clone().into_owned()),
+                        inputs,
+                        outputs: outputs.clone(),
+                        output_shapes: op_def.output_shapes.clone(),
+                    });
+                    outputs
+                }
+                NodeDefinition::Tensor(tensor_def) => {
+                    let outputs = cpu_model.new_values(1);
+                    cpu_model.steps.push(CpuStep::Initializer {
+                        output: outputs[0],
+                        tensor: CpuTensor::from_tensor_proto(tensor_def)?,
+                    });
+                    outputs
+                }
+                NodeDefinition::Input(input_def) => {
+                    let outputs = cpu_model.new_values(1);
+                    cpu_model.steps.push(CpuStep::Input {
+                        output: outputs[0],
+                        name: input_def.get_name().to_string(),
+                        shape: input_def.get_shape()?,
+                    });
+                    outputs
+                }
+                NodeDefinition::Outputs { names } => {
+                    for (output_name, value) in names.iter().zip(inputs) {
+                        cpu_model
+                            .inference_outputs
+                            .insert(output_name.to_string(), value);
+                    }
+                    vec![]
+                }
+                NodeDefinition::Missing => vec![],
+            };
+            node_outputs.insert(node.identifier(), outputs);
+        }
+
+        Ok(cpu_model)
+    }
+
+    /// Reserves indices for the specified number of new values
+    fn new_values(&mut self, count: usize) -> Vec<usize> {
+        let indices = (self.value_count..(self.value_count + count)).collect();
+        self.value_count += count;
+        indices
+    }
+
+    /// Perform inference using this model and the specified inference inputs. Also returns the actual number of rows of
+    /// the outputs of ops with a data-dependent output shape.
+    pub fn infer(
+        &self,
+        inference_inputs: &HashMap<String, InputTensor>,
+    ) -> Result<(HashMap<String, OutputTensor>, RowCounts), CpuError> {
+        let (outputs, row_counts) = self.run(&|name, shape| {
+            let input = inference_inputs
+                .get(name)
+                .ok_or_else(|| CpuError::InferenceInputMissing(name.to_string()))?;
+            CpuTensor::from_input(name, shape, input)
+        })?;
+        Ok((
+            outputs
+                .into_iter()
+                .map(|(output_name, tensor)| (output_name, tensor.to_output()))
+                .collect(),
+            row_counts,
+        ))
+    }
+
+    /// Performs the steps of this model, obtaining the value of each input (by name and declared shape) from `input`.
+    /// Also returns the actual number of rows of the outputs of ops with a data-dependent output shape.
+    fn run(
+        &self,
+        input: &dyn Fn(&str, &Shape) -> Result<CpuTensor, CpuError>,
+    ) -> Result<(HashMap<String, CpuTensor>, RowCounts), CpuError> {
+        let mut values: Vec<Option<Cow<CpuTensor>>> = (0..self.value_count).map(|_| None).collect();
+        let mut row_counts = RowCounts::new();
+
+        for step in &self.steps {
+            match step {
+                CpuStep::Initializer { output, tensor } => {
+                    values[*output] = Some(Cow::Borrowed(tensor));
+                }
+                CpuStep::Input {
+                    output,
+                    name,
+                    shape,
+                } => {
+                    values[*output] = Some(Cow::Owned(input(name, shape)?));
+                }
+                CpuStep::Operator {
+                    proto,
+                    inputs,
+                    outputs,
+                    output_shapes,
+                } => {
+                    log::debug!("execute {} ({})", proto.get_name(), proto.get_op_type());
+                    let input_tensors: Vec<&CpuTensor> = inputs
+                        .iter()
+                        .map(|input| {
+                            values[*input]
+                                .as_deref()
+                                .expect("input value should be produced by an earlier step")
+                        })
+                        .collect();
+                    let (output_tensors, rows) = execute_counting_rows(
+                        proto,
+                        &input_tensors,
+                        output_shapes,
+                        self.onnx_opset_version,
+                    )?;
+                    if let Some(rows) = rows {
+                        row_counts.insert(proto.get_output()[0].clone(), rows);
+                    }
+
+                    for (output, tensor) in outputs.iter().zip(output_tensors) {
+                        values[*output] = Some(Cow::Owned(tensor));
+                    }
+                }
+                CpuStep::ControlFlow {
+                    proto,
+                    inputs,
+                    outputs,
+                    output_shapes,
+                    subgraphs,
+                } => {
+                    log::debug!("execute {} ({})", proto.get_name(), proto.get_op_type());
+                    let input_tensors: Vec<&CpuTensor> = inputs
+                        .iter()
+                        .map(|input| {
+                            values[*input]
+                                .as_deref()
+                                .expect("input value should be produced by an earlier step")
+                        })
+                        .collect();
+                    let output_tensors = control_flow(
+                        proto,
+                        &input_tensors,
+                        output_shapes,
+                        subgraphs,
+                        self.onnx_opset_version,
+                    )?;
+
+                    for (output, tensor) in outputs.iter().zip(output_tensors) {
+                        values[*output] = Some(Cow::Owned(tensor));
+                    }
+                }
+            }
+        }
+
+        let outputs = self
+            .inference_outputs
+            .iter()
+            .map(|(output_name, value)| {
+                let tensor = values[*value]
+                    .as_deref()
+                    .expect("output value should be produced by an earlier step");
+                (output_name.to_string(), tensor.clone())
+            })
+            .collect();
+        Ok((outputs, row_counts))
+    }
+}
+
+impl CpuTensor {
+    /// Creates a tensor, rounding each value to what can be represented in the data type of the tensor
+    fn new(shape: Shape, mut data: Vec<f64>) -> CpuTensor {
+        let round: fn(f64) -> f64 = match shape.data_type {
+            ScalarType::F32 => |x| x as f32 as f64,
+            ScalarType::F16 => |x| f16::from_f64(x).to_f64(),
+            ScalarType::I32 => |x| x as i32 as f64,
+            ScalarType::I64 => |x| x as i64 as f64,
+            ScalarType::F64 => |x| x,
+            ScalarType::U8 => |x| x as u8 as f64,
+            ScalarType::I8 => |x| x as i8 as f64,
+            ScalarType::U16 => |x| x as u16 as f64,
+            ScalarType::I16 => |x| x as i16 as f64,
+            ScalarType::Bool => |x| (x != 0.0) as u8 as f64,
+        };
+        data.iter_mut().for_each(|x| *x = round(*x));
+        CpuTensor { shape, data }
+    }
+
+    fn from_tensor_proto(tensor: &TensorProto) -> Result<CpuTensor, DataTypeError> {
+        let scalar_type = ScalarType::from_i32(tensor.get_data_type())?;
+        let raw_data = tensor.get_raw_data();
+        let data: Vec<f64> = match scalar_type {
+            ScalarType::F32 if tensor.get_float_data().is_empty() => {
+                pod_collect_to_vec::<u8, f32>(raw_data)
+                    .into_iter()
+                    .map(f64::from)
+                    .collect()
+            }
+            ScalarType::F32 => tensor.get_float_data().iter().map(|x| *x as f64).collect(),
+            ScalarType::F16 => tensor.get_f16_data().into_iter().map(f64::from).collect(),
+            ScalarType::I32 if tensor.get_int32_data().is_empty() => {
+                pod_collect_to_vec::<u8, i32>(raw_data)
+                    .into_iter()
+                    .map(f64::from)
+                    .collect()
+            }
+            ScalarType::I32 => tensor.get_int32_data().iter().map(|x| *x as f64).collect(),
+            ScalarType::I64 if tensor.get_int64_data().is_empty() => {
+                pod_collect_to_vec::<u8, i64>(raw_data)
+                    .into_iter()
+                    .map(|x| x as f64)
+                    .collect()
+            }
+            ScalarType::I64 => tensor.get_int64_data().iter().map(|x| *x as f64).collect(),
+            // Non-raw uint8 data is stored in the int32_data field
+            ScalarType::U8 if raw_data.is_empty() => {
+                tensor.get_int32_data().iter().map(|x| *x as f64).collect()
+            }
+            ScalarType::U8 => raw_data.iter().map(|x| *x as f64).collect(),
+            ScalarType::F64 => tensor.get_f64_data(),
+            ScalarType::I8 => small_ints_to_f64(tensor.get_small_int_data(|x| x as i8)),
+            ScalarType::U16 => small_ints_to_f64(tensor.get_small_int_data(|x| x as u16)),
+            ScalarType::I16 => small_ints_to_f64(tensor.get_small_int_data(|x| x as i16)),
+            ScalarType::Bool => small_ints_to_f64(tensor.get_bool_data()),
+        };
+
+        Ok(CpuTensor {
+            shape: Shape::from(scalar_type, tensor.get_dims()),
+            data,
+        })
+    }
+
+    fn from_input(name: &str, shape: &Shape, input: &InputTensor) -> Result<CpuTensor, CpuError> {
+        let data: Vec<f64> = match input {
+            InputTensor::F32(floats) => floats.iter().map(|x| *x as f64).collect(),
+            InputTensor::F16(floats) => floats.iter().map(|x| x.to_f64()).collect(),
+            InputTensor::I32(ints) => ints.iter().map(|x| *x as f64).collect(),
+            InputTensor::I64(ints) => ints.iter().map(|x| *x as f64).collect(),
+            InputTensor::U8(ints) => ints.iter().map(|x| *x as f64).collect(),
+            InputTensor::F64(floats) => floats.to_vec(),
+            InputTensor::I8(ints) => ints.iter().map(|x| *x as f64).collect(),
+            InputTensor::U16(ints) => ints.iter().map(|x| *x as f64).collect(),
+            InputTensor::I16(ints) => ints.iter().map(|x| *x as f64).collect(),
+            InputTensor::Bool(bools) => bools.iter().map(|x| *x as u8 as f64).collect(),
+        };
+
+        let expected = shape.element_count() as usize;
+        if data.len() != expected {
+            return Err(CpuError::InferenceInputInvalidLength {
+                name: name.to_string(),
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        Ok(CpuTensor::new(shape.clone(), data))
+    }
+
+    fn to_output(&self) -> OutputTensor {
+        match self.shape.data_type {
+            ScalarType::F32 => OutputTensor::F32(self.data.iter().map(|x| *x as f32).collect()),
+            ScalarType::F16 => {
+                OutputTensor::F16(self.data.iter().map(|x| f16::from_f64(*x)).collect())
+            }
+            ScalarType::I32 => OutputTensor::I32(self.data.iter().map(|x| *x as i32).collect()),
+            ScalarType::I64 => OutputTensor::I64(self.data.iter().map(|x| *x as i64).collect()),
+            ScalarType::U8 => OutputTensor::U8(self.data.iter().map(|x| *x as u8).collect()),
+            ScalarType::F64 => OutputTensor::F64(self.data.clone()),
+            ScalarType::I8 => OutputTensor::I8(self.data.iter().map(|x| *x as i8).collect()),
+            ScalarType::U16 => OutputTensor::U16(self.data.iter().map(|x| *x as u16).collect()),
+            ScalarType::I16 => OutputTensor::I16(self.data.iter().map(|x| *x as i16).collect()),
+            ScalarType::Bool => OutputTensor::Bool(self.data.iter().map(|x| *x != 0.0).collect()),
+        }
+    }
+}
+
+fn small_ints_to_f64<T: Into<f64>>(values: Vec<T>) -> Vec<f64> {
+    values.into_iter().map(Into::into).collect()
+}
+
+fn node_name(node: &NodeProto) -> String {
+    if node.has_name() {
+        node.get_name().to_string()
+    } else {
+        node.get_op_type().to_string()
+    }
+}
+
+fn operator_error(node: &NodeProto, error: CompileError) -> CpuError {
+    CpuError::OperatorError {
+        node: node_name(node),
+        error,
+    }
+}
+
+fn attribute<T: From<AttributeProto>>(
+    node: &NodeProto,
+    name: &str,
+    default: Option<T>,
+) -> Result<T, CpuError> {
+    node.get_attribute_value(name, default)
+        .map_err(|e| operator_error(node, e.into()))
+}
+
+/// Reads a scalar attribute that is either a single float, or a list of values that was moved to the attribute from an
+/// input by the optimizer.
+fn scalar_attribute(node: &NodeProto, name: &str, default: f64) -> f64 {
+    match node.get_attribute().iter().find(|a| a.get_name() == name) {
+        None => default,
+        Some(a) if !a.get_floats().is_empty() => a.get_floats()[0] as f64,
+        Some(a) if !a.get_ints().is_empty() => a.get_ints()[0] as f64,
+        Some(a) => a.get_f() as f64,
+    }
+}
+
+/// Normalizes an axis attribute value that may count from the back
+fn normalize_axis(node: &NodeProto, axis: i64, rank: usize) -> Result<usize, CpuError> {
+    let rank = rank as i64;
+    let normalized = if axis < 0 { axis + rank } else { axis };
+    if normalized < 0 || normalized >= rank {
+        return Err(operator_error(
+            node,
+            CompileError::InvalidAttributeValue {
+                attribute: "axis".to_string(),
+                value: axis.to_string(),
+                opset_version: 0,
+            },
+        ));
+    }
+    Ok(normalized as usize)
+}
+
+fn invalid_input_shape(node: &NodeProto, input_index: usize, shape: &Shape) -> CpuError {
+    operator_error(
+        node,
+        CompileError::InvalidInputShape {
+            input_index,
+            input_shape: shape.clone(),
+        },
+    )
+}
+
+/// Resolves an index (which may count from the back) along an axis of the specified size
+fn resolve_index(node: &NodeProto, index: f64, size: u64) -> Result<usize, CpuError> {
+    let index = index as i64;
+    let resolved = if index < 0 {
+        index + size as i64
+    } else {
+        index
+    };
+    if resolved < 0 || resolved >= size as i64 {
+        return Err(CpuError::IndexOutOfBounds {
+            node: node_name(node),
+            index,
+            size,
+        });
+    }
+    Ok(resolved as usize)
+}
+
+/// Returns the number of elements between two consecutive indices along each axis
+fn strides(dims: &[u64]) -> Vec<usize> {
+    let mut strides = vec![1; dims.len()];
+    for axis in (0..dims.len().saturating_sub(1)).rev() {
+        strides[axis] = strides[axis + 1] * dims[axis + 1] as usize;
+    }
+    strides
+}
+
+fn product(dims: &[u64]) -> usize {
+    dims.iter().product::<u64>() as usize
+}
+
+/// For each element of a tensor with the output dimensions, returns the index of the element in a tensor with the input
+/// dimensions it is (multidirectionally) broadcast from, or None when the input cannot be broadcast to the output.
+fn broadcast_indices(input_dims: &[u64], output_dims: &[u64]) -> Option<Vec<usize>> {
+    let rank = output_dims.len();
+    if input_dims.len() > rank {
+        return None;
+    }
+    let mut padded_dims = vec![1; rank - input_dims.len()];
+    padded_dims.extend_from_slice(input_dims);
+    if padded_dims
+        .iter()
+        .zip(output_dims)
+        .any(|(i, o)| *i != 1 && i != o)
+    {
+        return None;
+    }
+
+    let input_strides = strides(&padded_dims);
+    let output_strides = strides(output_dims);
+    Some(
+        (0..product(output_dims))
+            .map(|index| {
+                (0..rank)
+                    .filter(|axis| padded_dims[*axis] != 1)
+                    .map(|axis| {
+                        let coordinate =
+                            (index / output_strides[axis]) % output_dims[axis] as usize;
+                        coordinate * input_strides[axis]
+                    })
+                    .sum()
+            })
+            .collect(),
+    )
+}
+
+fn broadcast_error(node: &NodeProto, inputs: &[&CpuTensor], output_shape: &Shape) -> CpuError {
+    operator_error(
+        node,
+        CompileError::InvalidBroadcast {
+            input_shapes: inputs.iter().map(|x| x.shape.clone()).collect(),
+            output_shape: output_shape.clone(),
+        },
+    )
+}
+
+/// Calculate the outputs of an op whose inputs are all initializers. The optimizer uses this to fold constant nodes.
+pub(crate) fn fold_constant(
+    node: &NodeProto,
+    inputs: &[&TensorProto],
+    output_shapes: &[Shape],
+    opset_version: i64,
+) -> Result<Vec<OutputTensor>, CpuError> {
+    let input_tensors = inputs
+        .iter()
+        .map(|tensor| CpuTensor::from_tensor_proto(tensor))
+        .collect::<Result<Vec<CpuTensor>, DataTypeError>>()?;
+    let input_tensors: Vec<&CpuTensor> = input_tensors.iter().collect();
+    Ok(execute(node, &input_tensors, output_shapes, opset_version)?
+        .iter()
+        .map(CpuTensor::to_output)
+        .collect())
+}
+
+/// Execute a single op on the CPU given its input data. The GPU backend uses this for ops that it executes on the host.
+/// For ops whose output has a data-dependent shape, the actual number of rows of the (padded) output is returned as well.
+pub(crate) fn execute_on_host(
+    node: &NodeProto,
+    inputs: &[(Shape, OutputTensor)],
+    output_shapes: &[Shape],
+    opset_version: i64,
+) -> Result<(Vec<OutputTensor>, Option<usize>), CpuError> {
+    let input_tensors = inputs
+        .iter()
+        .map(|(shape, data)| CpuTensor::from_input(&node_name(node), shape, &data.into()))
+        .collect::<Result<Vec<CpuTensor>, CpuError>>()?;
+    let input_tensors: Vec<&CpuTensor> = input_tensors.iter().collect();
+    let (outputs, rows) =
+        execute_counting_rows(node, &input_tensors, output_shapes, opset_version)?;
+    Ok((outputs.iter().map(CpuTensor::to_output).collect(), rows))
+}
+
+impl CpuSubgraph {
+    /// Runs the model of the subgraph given the values of its declared inputs. The values that the subgraph takes from
+    /// enclosing graphs are taken from the inputs of the op.
+    fn run(
+        &self,
+        inputs: Vec<CpuTensor>,
+        op_inputs: &[&CpuTensor],
+    ) -> Result<Vec<CpuTensor>, CpuError> {
+        let mut values: HashMap<&str, &CpuTensor> = self
+            .inputs
+            .iter()
+            .map(String::as_str)
+            .zip(&inputs)
+            .collect();
+        for (name, index) in &self.captures {
+            values.insert(name, op_inputs[*index]);
+        }
+
+        let (outputs, _) = self.model.run(&|name, shape| {
+            let value = values
+                .get(name)
+                .ok_or_else(|| CpuError::InferenceInputMissing(name.to_string()))?;
+            let expected = shape.element_count() as usize;
+            if value.data.len() != expected {
+                return Err(CpuError::InferenceInputInvalidLength {
+                    name: name.to_string(),
+                    expected,
+                    actual: value.data.len(),
+                });
+            }
+            Ok(CpuTensor::new(shape.clone(), value.data.clone()))
+        })?;
+        Ok(self
+            .outputs
+            .iter()
+            .map(|name| outputs[name].clone())
+            .collect())
+    }
+}
+
+/// Execute an op with subgraphs (If, Loop or Scan). The inputs of the op are followed by the values that its subgraphs
+/// take from enclosing graphs.
+fn control_flow(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shapes: &[Shape],
+    subgraphs: &[CpuSubgraph],
+    opset_version: i64,
+) -> Result<Vec<CpuTensor>, CpuError> {
+    let op_inputs = optional_inputs(node, inputs);
+    let first_value = |tensor: &CpuTensor| {
+        tensor
+            .data
+            .first()
+            .copied()
+            .ok_or_else(|| CpuError::IndexOutOfBounds {
+                node: node_name(node),
+                index: 0,
+                size: 0,
+            })
+    };
+    let iteration_count_mismatch = |expected: u64, actual: u64| CpuError::IterationCountMismatch {
+        node: node_name(node),
+        expected,
+        actual,
+    };
+
+    let outputs = match node.get_op_type() {
+        "If" => {
+            let cond = first_value(op_inputs[0].unwrap())? != 0.0;
+            let branch_name = if cond { "then_branch" } else { "else_branch" };
+            let branch = subgraphs
+                .iter()
+                .find(|subgraph| subgraph.attribute == branch_name)
+                .unwrap();
+            branch.run(vec![], inputs)?
+        }
+        "Loop" => {
+            let max_trip_count = op_inputs[0].map(|count| first_value(count)).transpose()?;
+            let mut cond = match op_inputs[1] {
+                Some(cond) => first_value(cond)? != 0.0,
+                None => true,
+            };
+            let mut carried: Vec<CpuTensor> = op_inputs[2..]
+                .iter()
+                .map(|input| (*input.unwrap()).clone())
+                .collect();
+            let carried_count = carried.len();
+            let scan_shapes = &output_shapes[carried_count..];
+            let scan_length = scan_shapes.first().map(|shape| shape.dim(0));
+            let mut scans: Vec<Vec<f64>> = vec![vec![]; scan_shapes.len()];
+
+            let mut iteration = 0;
+            while cond && max_trip_count.map_or(true, |count| (iteration as f64) < count) {
+                if let Some(length) = scan_length.filter(|length| iteration >= *length) {
+                    return Err(iteration_count_mismatch(length, iteration + 1));
+                }
+                let mut body_inputs = vec![
+                    CpuTensor::new(Shape::from(ScalarType::I64, &[]), vec![iteration as f64]),
+                    CpuTensor::new(Shape::from(ScalarType::Bool, &[]), vec![cond as u8 as f64]),
+                ];
+                body_inputs.extend(carried);
+                let mut body_outputs = subgraphs[0].run(body_inputs, inputs)?;
+
+                for (scan, slice) in scans
+                    .iter_mut()
+                    .zip(body_outputs.split_off(1 + carried_count))
+                {
+                    scan.extend(slice.data);
+                }
+                carried = body_outputs.split_off(1);
+                cond = first_value(&body_outputs[0])? != 0.0;
+                iteration += 1;
+            }
+
+            if let Some(length) = scan_length.filter(|length| iteration != *length) {
+                return Err(iteration_count_mismatch(length, iteration));
+            }
+            carried
+                .into_iter()
+                .chain(
+                    scans
+                        .into_iter()
+                        .zip(scan_shapes)
+                        .map(|(data, shape)| CpuTensor::new(shape.clone(), data)),
+                )
+                .collect()
+        }
+        "Scan" => {
+            let input_count = node.get_input().len();
+            let input_shapes: Vec<&Shape> = inputs[..input_count]
+                .iter()
+                .map(|input| &input.shape)
+                .collect();
+            let attributes = scan_attributes(
+                node,
+                &input_shapes,
+                &output_shapes.iter().collect::<Vec<_>>(),
+                opset_version,
+            )
+            .map_err(|error| operator_error(node, error))?;
+            let states = attributes.states;
+            let scan_inputs = &inputs[states..input_count];
+            let length = scan_inputs[0].shape.dim(attributes.input_axes[0]);
+            for (index, (input, axis)) in scan_inputs.iter().zip(&attributes.input_axes).enumerate()
+            {
+                if input.shape.dim(*axis) != length {
+                    return Err(invalid_input_shape(node, states + index, &input.shape));
+                }
+            }
+            for (shape, axis) in output_shapes[states..].iter().zip(&attributes.output_axes) {
+                if shape.dim(*axis) != length {
+                    return Err(iteration_count_mismatch(shape.dim(*axis), length));
+                }
+            }
+
+            let mut state: Vec<CpuTensor> = inputs[..states]
+                .iter()
+                .map(|input| (*input).clone())
+                .collect();
+            let mut scans: Vec<Vec<CpuTensor>> = vec![vec![]; output_shapes.len() - states];
+            for iteration in 0..length {
+                let position = |reverse: bool| {
+                    if reverse {
+                        length - 1 - iteration
+                    } else {
+                        iteration
+                    }
+                };
+                let mut body_inputs = state;
+                for ((input, axis), reverse) in scan_inputs
+                    .iter()
+                    .zip(&attributes.input_axes)
+                    .zip(&attributes.input_reverse)
+                {
+                    body_inputs.push(slice_along(input, *axis, position(*reverse) as usize));
+                }
+                let mut body_outputs = subgraphs[0].run(body_inputs, inputs)?;
+                for (scan, slice) in scans.iter_mut().zip(body_outputs.split_off(states)) {
+                    scan.push(slice);
+                }
+                state = body_outputs;
+            }
+
+            state
+                .into_iter()
+                .chain(
+                    scans
+                        .into_iter()
+                        .zip(&output_shapes[states..])
+                        .zip(
+                            attributes
+                                .output_axes
+                                .iter()
+                                .zip(&attributes.output_reverse),
+                        )
+                        .map(|((mut slices, shape), (axis, reverse))| {
+                            if *reverse {
+                                slices.reverse();
+                            }
+                            stack_along(&slices, *axis, shape)
+                        }),
+                )
+                .collect()
+        }
+        op => {
+            return Err(operator_error(
+                node,
+                CompileError::UnimplementedOp(op.to_string()),
+            ))
+        }
+    };
+
+    // The outputs take the declared shapes of the op
+    Ok(outputs
+        .into_iter()
+        .zip(output_shapes)
+        .map(|(output, shape)| CpuTensor::new(shape.clone(), output.data))
+        .collect())
+}
+
+/// Takes the values at the specified index along an axis, removing the axis
+fn slice_along(tensor: &CpuTensor, axis: usize, index: usize) -> CpuTensor {
+    let dims = &tensor.shape.dims;
+    let inner = product(&dims[(axis + 1)..]);
+    let size = dims[axis] as usize;
+    let data = tensor
+        .data
+        .chunks(inner * size)
+        .flat_map(|chunk| &chunk[(index * inner)..((index + 1) * inner)])
+        .copied()
+        .collect();
+    let mut slice_dims = dims.clone();
+    slice_dims.remove(axis);
+    CpuTensor::new(
+        Shape {
+            dims: slice_dims,
+            data_type: tensor.shape.data_type,
+        },
+        data,
+    )
+}
+
+/// Stacks slices along a new axis (the inverse of [`slice_along`])
+fn stack_along(slices: &[CpuTensor], axis: usize, output_shape: &Shape) -> CpuTensor {
+    let inner = product(&output_shape.dims[(axis + 1)..]);
+    let outer = product(&output_shape.dims[..axis]);
+    let mut data = Vec::with_capacity(output_shape.element_count() as usize);
+    for outer_index in 0..outer {
+        for slice in slices {
+            data.extend(&slice.data[(outer_index * inner)..((outer_index + 1) * inner)]);
+        }
+    }
+    CpuTensor::new(output_shape.clone(), data)
+}
+
+/// Execute a single op given its input values and the expected output shapes. For ops whose output has a data-dependent
+/// shape, the actual number of rows of the (padded) output is returned as well.
+fn execute_counting_rows(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shapes: &[Shape],
+    opset_version: i64,
+) -> Result<(Vec<CpuTensor>, Option<usize>), CpuError> {
+    if node.get_op_type() == "NonMaxSuppression" {
+        let (output, rows) = non_max_suppression(node, inputs, &output_shapes[0])?;
+        return Ok((vec![output], Some(rows)));
+    }
+    Ok((execute(node, inputs, output_shapes, opset_version)?, None))
+}
+
+/// Execute a single op given its input values and the expected output shapes
+fn execute(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shapes: &[Shape],
+    opset_version: i64,
+) -> Result<Vec<CpuTensor>, CpuError> {
+    let op = node.get_op_type();
+    if inputs.is_empty() {
+        return Err(operator_error(
+            node,
+            CompileError::InvalidInputCount {
+                expected: 1,
+                actual: 0,
+            },
+        ));
+    }
+
+    if op_forwards_input(op) {
+        // These ops only change metadata
+        let mut outputs = vec![CpuTensor {
+            shape: output_shapes[0].clone(),
+            data: inputs[0].data.clone(),
+        }];
+
+        // Dropout is a no-op during inference, so the (optional) mask output is all true
+        if let Some(mask_shape) = output_shapes.get(1) {
+            outputs.push(CpuTensor::new(
+                mask_shape.clone(),
+                vec![1.0; mask_shape.element_count() as usize],
+            ));
+        }
+        return Ok(outputs);
+    }
+
+    let output_shape = &output_shapes[0];
+    let output = match op {
+        "Abs" | "Acos" | "Asin" | "Atan" | "Ceil" | "Cos" | "Cosh" | "Exp" | "Floor" | "Log"
+        | "Round" | "Sign" | "Sin" | "Sinh" | "Sqrt" | "Tan" | "Tanh" | "Reciprocal" | "Acosh"
+        | "Asinh" | "Atanh" | "Neg" => {
+            let f: fn(f64) -> f64 = match op {
+                "Abs" => f64::abs,
+                "Acos" => f64::acos,
+                "Asin" => f64::asin,
+                "Atan" => f64::atan,
+                "Ceil" => f64::ceil,
+                "Cos" => f64::cos,
+                "Cosh" => f64::cosh,
+                "Exp" => f64::exp,
+                "Floor" => f64::floor,
+                "Log" => f64::ln,
+                "Round" => round_half_to_even,
+                "Sign" => |x| {
+                    if x > 0.0 {
+                        1.0
+                    } else if x < 0.0 {
+                        -1.0
+                    } else {
+                        0.0
+                    }
+                },
+                "Sin" => f64::sin,
+                "Sinh" => f64::sinh,
+                "Sqrt" => f64::sqrt,
+                "Tan" => f64::tan,
+                "Tanh" => f64::tanh,
+                "Reciprocal" => |x| 1.0 / x,
+                "Acosh" => f64::acosh,
+                "Asinh" => f64::asinh,
+                "Atanh" => f64::atanh,
+                "Neg" => |x| -x,
+                _ => unreachable!(),
+            };
+            CpuTensor::new(
+                output_shape.clone(),
+                inputs[0].data.iter().map(|x| f(*x)).collect(),
+            )
+        }
+
+        "ReduceMean" | "ReduceSum" | "ReduceMax" | "ReduceMin" | "ReduceProd" | "ReduceL1"
+        | "ReduceL2" | "ReduceLogSum" | "ReduceLogSumExp" | "ReduceSumSquare" => {
+            reduce(node, inputs[0], output_shape)?
+        }
+
+        "ArgMax" | "ArgMin" | "TopK" => return top_k(node, inputs[0], output_shapes),
+        "LSTM" | "GRU" | "RNN" => return recurrent(node, inputs, output_shapes, opset_version),
+
+        "OneHot" => one_hot(node, inputs, output_shape)?,
+
+        "Gather" => gather(node, inputs, output_shape)?,
+        "GatherElements" => gather_elements(node, inputs, output_shape)?,
+        "GatherND" => gather_nd(node, inputs, output_shape)?,
+        "ScatterElements" => scatter_elements(node, inputs, output_shape)?,
+        "ScatterND" => scatter_nd(node, inputs, output_shape)?,
+
+        "Cast" => CpuTensor::new(output_shape.clone(), inputs[0].data.clone()),
+
+        "QuantizeLinear" | "DequantizeLinear" => quantize_linear(node, inputs[0], output_shape)?,
+
+        "MatMulInteger" | "QLinearMatMul" => quantized_matmul(node, inputs, output_shape)?,
+
+        "QLinearConv" => qlinear_conv(node, inputs, output_shape)?,
+
+        "Softmax" => softmax(node, inputs[0], output_shape, opset_version)?,
+
+        "LayerNormalization" | "SkipLayerNormalization" => {
+            layer_normalization(node, inputs, output_shape)?
+        }
+
+        "Gelu" | "BiasGelu" => gelu(node, inputs, output_shape)?,
+
+        "Einsum" => einsum(node, inputs, output_shape)?,
+
+        "Attention" => attention(node, inputs, output_shape)?,
+
+        "Add" | "And" | "Div" | "Equal" | "Greater" | "GreaterOrEqual" | "Less" | "LessOrEqual"
+        | "Mod" | "Mul" | "Or" | "Sub" | "Pow" | "PRelu" => arithmetic(node, inputs, output_shape)?,
+        "Sum" | "Mean" | "Max" | "Min" => variadic(node, inputs, output_shape)?,
+
+        "BatchNormalization" => batch_normalization(node, inputs, output_shape)?,
+        "InstanceNormalization" | "GroupNormalization" => {
+            group_normalization(node, inputs, output_shape)?
+        }
+        "LpNormalization" => lp_normalization(node, inputs[0], output_shape)?,
+
+        "Relu" | "Sigmoid" | "Softsign" | "Softplus" | "Clip" | "Celu" | "Elu" | "LeakyRelu"
+        | "HardSigmoid" => {
+            let x = inputs[0];
+            let y = match op {
+                "Relu" => x.data.iter().map(|x| x.max(0.0)).collect(),
+                "Sigmoid" => x.data.iter().map(|x| sigmoid(*x)).collect(),
+                "Softsign" => x.data.iter().map(|x| x / (1.0 + x.abs())).collect(),
+                "Softplus" => x.data.iter().map(|x| softplus(*x)).collect(),
+                "Clip" => {
+                    let min = scalar_attribute(node, "min", f64::NEG_INFINITY);
+                    let max = scalar_attribute(node, "max", f64::INFINITY);
+                    x.data.iter().map(|x| x.max(min).min(max)).collect()
+                }
+                "Celu" => {
+                    let alpha = attribute(node, "alpha", Some(1.0))? as f64;
+                    x.data
+                        .iter()
+                        .map(|x| x.max(0.0) + (alpha * ((x / alpha).exp() - 1.0)).min(0.0))
+                        .collect()
+                }
+                "Elu" => {
+                    let alpha = attribute(node, "alpha", Some(1.0))? as f64;
+                    x.data
+                        .iter()
+                        .map(|x| {
+                            if *x < 0.0 {
+                                alpha * (x.exp() - 1.0)
+                            } else {
+                                *x
+                            }
+                        })
+                        .collect()
+                }
+                "LeakyRelu" => {
+                    let alpha = attribute(node, "alpha", Some(0.01))? as f64;
+                    x.data
+                        .iter()
+                        .map(|x| if *x < 0.0 { alpha * x } else { *x })
+                        .collect()
+                }
+                "HardSigmoid" => {
+                    let alpha = attribute(node, "alpha", Some(0.2))? as f64;
+                    let beta = attribute(node, "beta", Some(0.5))? as f64;
+                    x.data
+                        .iter()
+                        .map(|x| (alpha * x + beta).clamp(0.0, 1.0))
+                        .collect()
+                }
+                _ => unreachable!(),
+            };
+            CpuTensor::new(output_shape.clone(), y)
+        }
+
+        "Concat" => concat(node, inputs, output_shape)?,
+
+        op if matches!(op, "MaxPool" | "AveragePool" | "GlobalAveragePool")
+            || fused_conv_type(op).is_some() =>
+        {
+            return pool_or_conv(node, inputs, output_shapes)
+        }
+
+        "ConvTranspose" => conv_transpose(node, inputs, output_shape)?,
+
+        "Gemm" => gemm(node, inputs, output_shape)?,
+
+        "MatMul" => matmul(node, inputs, output_shape)?,
+
+        "Resize" => resize(node, inputs[0], output_shape)?,
+
+        "RoiAlign" => roi_align(node, inputs, output_shape, opset_version)?,
+
+        "Split" => return split(node, inputs[0], output_shapes),
+
+        "Pad" => pad(node, inputs[0], output_shape)?,
+
+        "Transpose" => transpose(node, inputs[0], output_shape)?,
+
+        "Slice" => slice(node, inputs[0], output_shape)?,
+
+        "Expand" => {
+            let indices = broadcast_indices(&inputs[0].shape.dims, &output_shape.dims)
+                .ok_or_else(|| broadcast_error(node, inputs, output_shape))?;
+            CpuTensor::new(
+                output_shape.clone(),
+                indices.iter().map(|i| inputs[0].data[*i]).collect(),
+            )
+        }
+
+        "Tile" => tile(node, inputs[0], output_shape)?,
+
+        "Where" => {
+            let indices = inputs
+                .iter()
+                .map(|input| broadcast_indices(&input.shape.dims, &output_shape.dims))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| broadcast_error(node, inputs, output_shape))?;
+            let output = (0..output_shape.element_count() as usize)
+                .map(|index| {
+                    if inputs[0].data[indices[0][index]] != 0.0 {
+                        inputs[1].data[indices[1][index]]
+                    } else {
+                        inputs[2].data[indices[2][index]]
+                    }
+                })
+                .collect();
+            CpuTensor::new(output_shape.clone(), output)
+        }
+
+        op => {
+            return Err(operator_error(
+                node,
+                CompileError::UnimplementedOp(op.to_string()),
+            ))
+        }
+    };
+
+    Ok(vec![output])
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Rounds to the nearest integer, rounding halfway cases to the even one (as `f64::round_ties_even`, which is not
+/// available in the minimum supported Rust version)
+fn round_half_to_even(x: f64) -> f64 {
+    if (x - x.trunc()).abs() == 0.5 {
+        2.0 * (x / 2.0).round()
+    } else {
+        x.round()
+    }
+}
+
+fn softplus(x: f64) -> f64 {
+    // Equivalent to ln(1 + e^x), but does not overflow for large x
+    x.max(0.0) + (-x.abs()).exp().ln_1p()
+}
+
+fn reduce(
+    node: &NodeProto,
+    input: &CpuTensor,
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let dims = &input.shape.dims;
+    let rank = dims.len();
+    let axes: Vec<i64> = attribute(node, "axes", Some(vec![]))?;
+    let noop_with_empty_axes = attribute(node, "noop_with_empty_axes", Some(0))?;
+    if axes.is_empty() && noop_with_empty_axes != 0 {
+        return Ok(CpuTensor::new(output_shape.clone(), input.data.clone()));
+    }
+
+    let axes: Vec<usize> = if axes.is_empty() {
+        (0..rank).collect()
+    } else {
+        axes.into_iter()
+            .map(|axis| normalize_axis(node, axis, rank))
+            .collect::<Result<_, _>>()?
+    };
+
+    // Each element of the input contributes to the output element at the same position with the reduced axes removed
+    let reduced_dims: Vec<u64> = dims
+        .iter()
+        .enumerate()
+        .map(|(axis, dim)| if axes.contains(&axis) { 1 } else { *dim })
+        .collect();
+    let input_strides = strides(dims);
+    let reduced_strides = strides(&reduced_dims);
+    let mut groups: Vec<Vec<f64>> = vec![vec![]; product(&reduced_dims)];
+    for (index, value) in input.data.iter().enumerate() {
+        let group: usize = (0..rank)
+            .filter(|axis| !axes.contains(axis))
+            .map(|axis| {
+                ((index / input_strides[axis]) % dims[axis] as usize) * reduced_strides[axis]
+            })
+            .sum();
+        groups[group].push(*value);
+    }
+
+    let max = |g: &Vec<f64>| g.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let output = groups
+        .iter()
+        .map(|g| match node.get_op_type() {
+            "ReduceSum" => g.iter().sum(),
+            "ReduceMean" => g.iter().sum::<f64>() / g.len() as f64,
+            "ReduceMax" => max(g),
+            "ReduceMin" => g.iter().cloned().fold(f64::INFINITY, f64::min),
+            "ReduceProd" => g.iter().product(),
+            "ReduceL1" => g.iter().map(|x| x.abs()).sum(),
+            "ReduceL2" => g.iter().map(|x| x * x).sum::<f64>().sqrt(),
+            "ReduceLogSum" => g.iter().sum::<f64>().ln(),
+            "ReduceLogSumExp" => {
+                let max = max(g);
+                if max.is_finite() {
+                    max + g.iter().map(|x| (x - max).exp()).sum::<f64>().ln()
+                } else {
+                    max
+                }
+            }
+            "ReduceSumSquare" => g.iter().map(|x| x * x).sum(),
+            _ => unreachable!(),
+        })
+        .collect();
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+/// Selects the k largest or smallest elements along an axis (ArgMax and ArgMin select one, and only output its index)
+fn top_k(
+    node: &NodeProto,
+    input: &CpuTensor,
+    output_shapes: &[Shape],
+) -> Result<Vec<CpuTensor>, CpuError> {
+    let op = node.get_op_type();
+    let dims = &input.shape.dims;
+    let default_axis = if op == "TopK" { -1 } else { 0 };
+    let axis = normalize_axis(
+        node,
+        attribute(node, "axis", Some(default_axis))?,
+        dims.len(),
+    )?;
+    let (outer, size, inner) = (
+        product(&dims[..axis]),
+        dims[axis] as usize,
+        product(&dims[(axis + 1)..]),
+    );
+    let (k, largest, last_index_first) = if op == "TopK" {
+        let k = match attribute::<Vec<i64>>(node, "K", None) {
+            Ok(k) => k.first().copied().unwrap_or(-1),
+            Err(_) => attribute(node, "k", None)?,
+        };
+        if k < 0 || k as usize > size {
+            return Err(operator_error(
+                node,
+                CompileError::InvalidAttributeValue {
+                    attribute: "K".to_string(),
+                    value: k.to_string(),
+                    opset_version: 11,
+                },
+            ));
+        }
+        (k as usize, attribute(node, "largest", Some(1))? != 0, false)
+    } else {
+        (
+            1,
+            op == "ArgMax",
+            attribute(node, "select_last_index", Some(0))? != 0,
+        )
+    };
+
+    let mut values = vec![0.0; outer * k * inner];
+    let mut indices = vec![0.0; outer * k * inner];
+    for outer_index in 0..outer {
+        for inner_index in 0..inner {
+            let value = |i: usize| input.data[(outer_index * size + i) * inner + inner_index];
+            let mut order: Vec<usize> = (0..size).collect();
+            order.sort_by(|a, b| {
+                let by_value = if largest {
+                    value(*b).partial_cmp(&value(*a))
+                } else {
+                    value(*a).partial_cmp(&value(*b))
+                };
+                let by_index = if last_index_first { b.cmp(a) } else { a.cmp(b) };
+                by_value.unwrap_or(std::cmp::Ordering::Equal).then(by_index)
+            });
+            for (rank, index) in order.into_iter().take(k).enumerate() {
+                let output_index = (outer_index * k + rank) * inner + inner_index;
+                values[output_index] = value(index);
+                indices[output_index] = index as f64;
+            }
+        }
+    }
+
+    if op == "TopK" {
+        if output_shapes.len() != 2 {
+            return Err(operator_error(
+                node,
+                CompileError::UnimplementedVariant {
+                    variant: "without indices output".to_string(),
+                    op: op.to_string(),
+                },
+            ));
+        }
+        Ok(vec![
+            CpuTensor::new(output_shapes[0].clone(), values),
+            CpuTensor::new(output_shapes[1].clone(), indices),
+        ])
+    } else {
+        Ok(vec![CpuTensor::new(output_shapes[0].clone(), indices)])
+    }
+}
+
+fn one_hot(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (indices, depth, values) = (inputs[0], inputs[1], inputs[2]);
+    if depth.data.len() != 1 {
+        return Err(invalid_input_shape(node, 1, &depth.shape));
+    }
+    if values.data.len() != 2 {
+        return Err(invalid_input_shape(node, 2, &values.shape));
+    }
+
+    let depth = depth.data[0] as usize;
+    let (off_value, on_value) = (values.data[0], values.data[1]);
+    let axis = normalize_axis(
+        node,
+        attribute(node, "axis", Some(-1))?,
+        indices.shape.rank() + 1,
+    )?;
+
+    // The output has an extra axis of size depth (at the position of 'axis') compared to the indices
+    let inner = product(&indices.shape.dims[axis..]);
+    let mut output = vec![off_value; indices.data.len() * depth];
+    for (position, index) in indices.data.iter().enumerate() {
+        let mut index = *index as i64;
+        if index < 0 {
+            index += depth as i64;
+        }
+        if index >= 0 && (index as usize) < depth {
+            let (outer_index, inner_index) = (position / inner, position % inner);
+            output[(outer_index * depth + index as usize) * inner + inner_index] = on_value;
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn gather(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (data, indices) = (inputs[0], inputs[1]);
+    let dims = &data.shape.dims;
+    let axis = normalize_axis(node, attribute(node, "axis", Some(0))?, dims.len())?;
+    let axis_size = dims[axis];
+    let outer = product(&dims[..axis]);
+    let inner = product(&dims[(axis + 1)..]);
+
+    let mut output = Vec::with_capacity(outer * indices.data.len() * inner);
+    for outer_index in 0..outer {
+        for index in &indices.data {
+            let resolved = resolve_index(node, *index, axis_size)?;
+            let start = (outer_index * axis_size as usize + resolved) * inner;
+            output.extend_from_slice(&data.data[start..(start + inner)]);
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+/// Returns the coordinates of the element at a position in a tensor with the given dimensions, with the coordinate along
+/// the axis replaced by the index
+fn index_along_axis(position: usize, dims: &[u64], axis: usize, index: usize) -> Vec<usize> {
+    let position_strides = strides(dims);
+    (0..dims.len())
+        .map(|d| {
+            if d == axis {
+                index
+            } else {
+                (position / position_strides[d]) % dims[d] as usize
+            }
+        })
+        .collect()
+}
+
+/// Checks that the indices tensor of GatherElements or ScatterElements has the rank of the data and does not exceed its
+/// dimensions (except along the axis)
+fn check_element_indices(
+    node: &NodeProto,
+    data: &CpuTensor,
+    indices: &CpuTensor,
+    axis: usize,
+) -> Result<(), CpuError> {
+    let dims = &data.shape.dims;
+    if indices.shape.rank() != dims.len()
+        || indices
+            .shape
+            .dims
+            .iter()
+            .zip(dims)
+            .enumerate()
+            .any(|(d, (i, n))| d != axis && i > n)
+    {
+        return Err(invalid_input_shape(node, 1, &indices.shape));
+    }
+    Ok(())
+}
+
+fn gather_elements(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (data, indices) = (inputs[0], inputs[1]);
+    let dims = &data.shape.dims;
+    let axis = normalize_axis(node, attribute(node, "axis", Some(0))?, dims.len())?;
+    check_element_indices(node, data, indices, axis)?;
+
+    let data_strides = strides(dims);
+    let output = indices
+        .data
+        .iter()
+        .enumerate()
+        .map(|(position, index)| {
+            let index = resolve_index(node, *index, dims[axis])?;
+            let coordinates = index_along_axis(position, &indices.shape.dims, axis, index);
+            let data_index: usize = coordinates
+                .iter()
+                .zip(&data_strides)
+                .map(|(c, s)| c * s)
+                .sum();
+            Ok(data.data[data_index])
+        })
+        .collect::<Result<Vec<f64>, CpuError>>()?;
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn gather_nd(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (data, indices) = (inputs[0], inputs[1]);
+    let dims = &data.shape.dims;
+    let batch_dims = attribute(node, "batch_dims", Some(0))?.max(0) as usize;
+    let tuple_length = indices.shape.dims.last().copied().unwrap_or(0) as usize;
+    if batch_dims >= indices.shape.rank()
+        || tuple_length == 0
+        || batch_dims + tuple_length > dims.len()
+    {
+        return Err(invalid_input_shape(node, 1, &indices.shape));
+    }
+
+    // Each index tuple selects a slice from the data within its batch
+    let data_strides = strides(dims);
+    let slice_size = product(&dims[(batch_dims + tuple_length)..]);
+    let tuples_per_batch = product(&indices.shape.dims[batch_dims..(indices.shape.rank() - 1)]);
+    let batch_stride = product(&dims[batch_dims..]);
+    let mut output = Vec::with_capacity(product(&output_shape.dims));
+    for (tuple_index, tuple) in indices.data.chunks(tuple_length).enumerate() {
+        let mut start = (tuple_index / tuples_per_batch) * batch_stride;
+        for (j, index) in tuple.iter().enumerate() {
+            let axis = batch_dims + j;
+            start += resolve_index(node, *index, dims[axis])? * data_strides[axis];
+        }
+        output.extend_from_slice(&data.data[start..(start + slice_size)]);
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+/// Returns the function that combines an existing value with an update for the 'reduction' attribute of the scatter ops
+fn scatter_reduction(node: &NodeProto) -> Result<fn(f64, f64) -> f64, CpuError> {
+    Ok(
+        match attribute(node, "reduction", Some("none".to_string()))?.as_str() {
+            "none" => |_, update| update,
+            "add" => |value, update| value + update,
+            "mul" => |value, update| value * update,
+            "max" => f64::max,
+            "min" => f64::min,
+            reduction => {
+                return Err(operator_error(
+                    node,
+                    CompileError::InvalidAttributeValue {
+                        attribute: "reduction".to_string(),
+                        value: reduction.to_string(),
+                        opset_version: 0,
+                    },
+                ))
+            }
+        },
+    )
+}
+
+fn scatter_elements(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (data, indices, updates) = (inputs[0], inputs[1], inputs[2]);
+    let dims = &data.shape.dims;
+    let axis = normalize_axis(node, attribute(node, "axis", Some(0))?, dims.len())?;
+    check_element_indices(node, data, indices, axis)?;
+    if updates.shape.dims != indices.shape.dims {
+        return Err(invalid_input_shape(node, 2, &updates.shape));
+    }
+
+    let reduce = scatter_reduction(node)?;
+    let data_strides = strides(dims);
+    let mut output = data.data.clone();
+    for (position, (index, update)) in indices.data.iter().zip(&updates.data).enumerate() {
+        let index = resolve_index(node, *index, dims[axis])?;
+        let coordinates = index_along_axis(position, &indices.shape.dims, axis, index);
+        let target: usize = coordinates
+            .iter()
+            .zip(&data_strides)
+            .map(|(c, s)| c * s)
+            .sum();
+        output[target] = reduce(output[target], *update);
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn scatter_nd(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (data, indices, updates) = (inputs[0], inputs[1], inputs[2]);
+    let dims = &data.shape.dims;
+    let tuple_length = indices.shape.dims.last().copied().unwrap_or(0) as usize;
+    if tuple_length == 0 || tuple_length > dims.len() {
+        return Err(invalid_input_shape(node, 1, &indices.shape));
+    }
+
+    // Each index tuple selects a slice of the data that is updated with the corresponding slice of the updates
+    let data_strides = strides(dims);
+    let slice_size = product(&dims[tuple_length..]);
+    if updates.data.len() != (indices.data.len() / tuple_length) * slice_size {
+        return Err(invalid_input_shape(node, 2, &updates.shape));
+    }
+
+    let reduce = scatter_reduction(node)?;
+    let mut output = data.data.clone();
+    for (tuple, slice) in indices
+        .data
+        .chunks(tuple_length)
+        .zip(updates.data.chunks(slice_size.max(1)))
+    {
+        let mut start = 0;
+        for (axis, index) in tuple.iter().enumerate() {
+            start += resolve_index(node, *index, dims[axis])? * data_strides[axis];
+        }
+        for (offset, update) in slice.iter().enumerate() {
+            output[start + offset] = reduce(output[start + offset], *update);
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn softmax(
+    node: &NodeProto,
+    input: &CpuTensor,
+    output_shape: &Shape,
+    opset_version: i64,
+) -> Result<CpuTensor, CpuError> {
+    let dims = &input.shape.dims;
+    let default_axis = if opset_version < 13 { 1 } else { -1 };
+    let axis = normalize_axis(
+        node,
+        attribute(node, "axis", Some(default_axis))?,
+        dims.len(),
+    )?;
+
+    // Before opset 13, the input is coerced to 2D and softmax is calculated over all values right of the axis
+    let (outer, size, inner) = if opset_version < 13 {
+        (product(&dims[..axis]), product(&dims[axis..]), 1)
+    } else {
+        (
+            product(&dims[..axis]),
+            dims[axis] as usize,
+            product(&dims[(axis + 1)..]),
+        )
+    };
+
+    let mut output = vec![0.0; input.data.len()];
+    for outer_index in 0..outer {
+        for inner_index in 0..inner {
+            let index = |i: usize| (outer_index * size + i) * inner + inner_index;
+            let max = (0..size)
+                .map(|i| input.data[index(i)])
+                .fold(f64::NEG_INFINITY, f64::max);
+            let sum: f64 = (0..size).map(|i| (input.data[index(i)] - max).exp()).sum();
+            for i in 0..size {
+                output[index(i)] = (input.data[index(i)] - max).exp() / sum;
+            }
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn layer_normalization(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let x = inputs[0];
+    let skip = node.get_op_type() == "SkipLayerNormalization";
+    let (axis, default_epsilon) = if skip {
+        (-1, 1e-12)
+    } else {
+        (attribute(node, "axis", Some(-1))?, 1e-5)
+    };
+    let axis = normalize_axis(node, axis, x.shape.rank())?;
+    let epsilon = attribute(node, "epsilon", Some(default_epsilon))? as f64;
+    let size = product(&x.shape.dims[axis..]);
+
+    // SkipLayerNormalization adds the skip input and (optional) bias before normalizing
+    let (scale, bias) = if skip {
+        (inputs.get(2), inputs.get(3))
+    } else {
+        (inputs.get(1), inputs.get(2))
+    };
+    let values: Vec<f64> = if skip {
+        let (skip, skip_bias) = (inputs[1], inputs.get(4));
+        (0..x.data.len())
+            .map(|i| {
+                x.data[i]
+                    + skip.data[i % skip.data.len()]
+                    + skip_bias.map_or(0.0, |b| b.data[i % b.data.len()])
+            })
+            .collect()
+    } else {
+        x.data.clone()
+    };
+
+    let mut output = Vec::with_capacity(values.len());
+    for row in values.chunks(size.max(1)) {
+        let mean = row.iter().sum::<f64>() / size as f64;
+        let variance = row.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / size as f64;
+        let inverse_std_dev = 1.0 / (variance + epsilon).sqrt();
+        for (i, v) in row.iter().enumerate() {
+            let normalized = (v - mean) * inverse_std_dev;
+            output.push(
+                normalized * scale.map_or(1.0, |s| s.data[i % s.data.len()])
+                    + bias.map_or(0.0, |b| b.data[i % b.data.len()]),
+            );
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+/// Approximation of the error function (Abramowitz and Stegun 7.1.26), identical to the one used by the GPU backend
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = ((((1.061405429 * t - 1.453152027) * t + 1.421413741) * t - 0.284496736) * t
+        + 0.254829592)
+        * t;
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+}
+
+fn gelu(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let approximate = attribute(node, "approximate", Some("none".to_string()))?;
+    let f: fn(f64) -> f64 = match approximate.as_str() {
+        "none" => |x| 0.5 * x * (1.0 + erf(x / std::f64::consts::SQRT_2)),
+        "tanh" => |x| {
+            0.5 * x
+                * (1.0 + ((2.0 / std::f64::consts::PI).sqrt() * (x + 0.044715 * x.powi(3))).tanh())
+        },
+        _ => {
+            return Err(operator_error(
+                node,
+                CompileError::InvalidAttributeValue {
+                    attribute: "approximate".to_string(),
+                    value: approximate,
+                    opset_version: 20,
+                },
+            ))
+        }
+    };
+
+    // BiasGelu adds a bias along the last dimension first
+    let x = inputs[0];
+    let output = (0..x.data.len())
+        .map(|i| f(x.data[i] + inputs.get(1).map_or(0.0, |b| b.data[i % b.data.len()])))
+        .collect();
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+/// Evaluates an activation function of a recurrent op
+fn recurrent_activation(activation: &RecurrentActivation, x: f64) -> f64 {
+    let (alpha, beta) = (activation.alpha as f64, activation.beta as f64);
+    match activation.name.as_str() {
+        "Sigmoid" => 1.0 / (1.0 + (-x).exp()),
+        "Tanh" => x.tanh(),
+        "Relu" => x.max(0.0),
+        "LeakyRelu" if x < 0.0 => alpha * x,
+        "ThresholdedRelu" if x <= alpha => 0.0,
+        "ScaledTanh" => alpha * (beta * x).tanh(),
+        "HardSigmoid" => (alpha * x + beta).clamp(0.0, 1.0),
+        "Elu" if x < 0.0 => alpha * (x.exp() - 1.0),
+        "Softsign" => x / (1.0 + x.abs()),
+        "Softplus" => (1.0 + x.exp()).ln(),
+        "Affine" => alpha * x + beta,
+        _ => x,
+    }
+}
+
+/// Calculates the outputs (Y, Y_h and Y_c) of an LSTM, GRU or RNN node
+fn recurrent(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shapes: &[Shape],
+    opset_version: i64,
+) -> Result<Vec<CpuTensor>, CpuError> {
+    let op = node.get_op_type();
+    let inputs = optional_inputs(node, inputs);
+    let (x, weights, recurrence_weights) = match inputs[..] {
+        [Some(x), Some(w), Some(r), ..] => (*x, *w, *r),
+        _ => {
+            return Err(operator_error(
+                node,
+                CompileError::InvalidInputCount {
+                    expected: 3,
+                    actual: inputs.iter().flatten().count(),
+                },
+            ))
+        }
+    };
+    let optional_input = |index: usize| inputs.get(index).copied().flatten().copied();
+    if x.shape.rank() != 3 {
+        return Err(invalid_input_shape(node, 0, &x.shape));
+    }
+    if recurrence_weights.shape.rank() != 3 {
+        return Err(invalid_input_shape(node, 2, &recurrence_weights.shape));
+    }
+
+    let layout = attribute(node, "layout", Some(0))?;
+    let (sequence_length, batch_size) = if layout == 0 {
+        (x.shape.dim(0) as usize, x.shape.dim(1) as usize)
+    } else {
+        (x.shape.dim(1) as usize, x.shape.dim(0) as usize)
+    };
+    let input_size = x.shape.dim(2) as usize;
+    let num_directions = recurrence_weights.shape.dim(0) as usize;
+    let hidden_size = recurrence_weights.shape.dim(2) as usize;
+    let gate_rows = recurrence_weights.shape.dim(1) as usize;
+    let reverse = attribute(node, "direction", Some("forward".to_string()))? == "reverse";
+    let linear_before_reset = attribute(node, "linear_before_reset", Some(0))? != 0;
+    let clip = attribute::<f32>(node, "clip", None).ok().map(f64::from);
+    let clipped = |v: f64| clip.map_or(v, |clip| v.clamp(-clip, clip));
+    let activations = recurrent_activations(node, num_directions, opset_version)
+        .map_err(|error| operator_error(node, error))?;
+    let slots = activations.len() / num_directions;
+
+    let state_index = |direction: usize, batch: usize| {
+        if layout == 0 {
+            (direction * batch_size + batch) * hidden_size
+        } else {
+            (batch * num_directions + direction) * hidden_size
+        }
+    };
+    let mut y = vec![0.0; sequence_length * num_directions * batch_size * hidden_size];
+    let mut y_h = vec![0.0; num_directions * batch_size * hidden_size];
+    let mut y_c = vec![0.0; num_directions * batch_size * hidden_size];
+    for direction in 0..num_directions {
+        let activate =
+            |slot: usize, v: f64| recurrent_activation(&activations[direction * slots + slot], v);
+        let peephole = |row: usize| {
+            optional_input(7).map_or(0.0, |p| p.data[direction * 3 * hidden_size + row])
+        };
+        for batch in 0..batch_size {
+            let length = optional_input(4).map_or(sequence_length, |lengths| {
+                (lengths.data[batch].max(0.0) as usize).min(sequence_length)
+            });
+            let initial_state = |index: usize| {
+                let base = state_index(direction, batch);
+                optional_input(index).map_or(vec![0.0; hidden_size], |state| {
+                    state.data[base..(base + hidden_size)].to_vec()
+                })
+            };
+            let mut hidden = initial_state(5);
+            let mut cell = initial_state(6);
+
+            for step in 0..length {
+                let t = if reverse || direction == 1 {
+                    length - 1 - step
+                } else {
+                    step
+                };
+                let x_base = if layout == 0 {
+                    (t * batch_size + batch) * input_size
+                } else {
+                    (batch * sequence_length + t) * input_size
+                };
+                let x_t = &x.data[x_base..(x_base + input_size)];
+
+                // The input and recurrence parts (including bias) of the pre-activation of a row of the stacked gates
+                let input_part = |row: usize| {
+                    let w_base = (direction * gate_rows + row) * input_size;
+                    optional_input(3).map_or(0.0, |b| b.data[direction * 2 * gate_rows + row])
+                        + (0..input_size)
+                            .map(|i| x_t[i] * weights.data[w_base + i])
+                            .sum::<f64>()
+                };
+                let recurrence_part = |row: usize, state: &[f64]| {
+                    let r_base = (direction * gate_rows + row) * hidden_size;
+                    optional_input(3)
+                        .map_or(0.0, |b| b.data[direction * 2 * gate_rows + gate_rows + row])
+                        + (0..hidden_size)
+                            .map(|k| state[k] * recurrence_weights.data[r_base + k])
+                            .sum::<f64>()
+                };
+                let gate = |row: usize| input_part(row) + recurrence_part(row, &hidden);
+
+                let mut next_hidden = vec![0.0; hidden_size];
+                match op {
+                    "LSTM" => {
+                        // Gates are stacked in the order i, o, f, c (peepholes in the order i, o, f)
+                        for j in 0..hidden_size {
+                            let i = activate(0, clipped(gate(j) + peephole(j) * cell[j]));
+                            let f = activate(
+                                0,
+                                clipped(
+                                    gate(2 * hidden_size + j)
+                                        + peephole(2 * hidden_size + j) * cell[j],
+                                ),
+                            );
+                            let c = activate(1, clipped(gate(3 * hidden_size + j)));
+                            cell[j] = f * cell[j] + i * c;
+                            let o = activate(
+                                0,
+                                clipped(
+                                    gate(hidden_size + j) + peephole(hidden_size + j) * cell[j],
+                                ),
+                            );
+                            next_hidden[j] = o * activate(2, cell[j]);
+                        }
+                    }
+                    "GRU" => {
+                        // Gates are stacked in the order z, r, h
+                        let reset: Vec<f64> = (0..hidden_size)
+                            .map(|j| activate(0, clipped(gate(hidden_size + j))))
+                            .collect();
+                        let reset_hidden: Vec<f64> =
+                            reset.iter().zip(&hidden).map(|(r, h)| r * h).collect();
+                        for j in 0..hidden_size {
+                            let z = activate(0, clipped(gate(j)));
+                            let row = 2 * hidden_size + j;
+                            let h = if linear_before_reset {
+                                activate(
+                                    1,
+                                    clipped(
+                                        input_part(row) + reset[j] * recurrence_part(row, &hidden),
+                                    ),
+                                )
+                            } else {
+                                activate(
+                                    1,
+                                    clipped(input_part(row) + recurrence_part(row, &reset_hidden)),
+                                )
+                            };
+                            next_hidden[j] = (1.0 - z) * h + z * hidden[j];
+                        }
+                    }
+                    _ => {
+                        for (j, next) in next_hidden.iter_mut().enumerate() {
+                            *next = activate(0, clipped(gate(j)));
+                        }
+                    }
+                }
+
+                let y_base = if layout == 0 {
+                    ((t * num_directions + direction) * batch_size + batch) * hidden_size
+                } else {
+                    ((batch * sequence_length + t) * num_directions + direction) * hidden_size
+                };
+                y[y_base..(y_base + hidden_size)].copy_from_slice(&next_hidden);
+                hidden = next_hidden;
+            }
+
+            let base = state_index(direction, batch);
+            y_h[base..(base + hidden_size)].copy_from_slice(&hidden);
+            y_c[base..(base + hidden_size)].copy_from_slice(&cell);
+        }
+    }
+
+    // Optional outputs that are left out have an empty placeholder shape
+    Ok(output_shapes
+        .iter()
+        .zip([y, y_h, y_c])
+        .map(|(shape, data)| {
+            let data = if shape.element_count() == 0 {
+                vec![]
+            } else {
+                data
+            };
+            CpuTensor::new(shape.clone(), data)
+        })
+        .collect())
+}
+
+fn attention(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (x, weights, bias) = (inputs[0], inputs[1], inputs[2]);
+    if x.shape.rank() != 3 {
+        return Err(invalid_input_shape(node, 0, &x.shape));
+    }
+    let (batch_size, sequence_length, input_size) = (
+        x.shape.dim(0) as usize,
+        x.shape.dim(1) as usize,
+        x.shape.dim(2) as usize,
+    );
+    if weights.shape.rank() != 2 || weights.shape.dim(0) as usize != input_size {
+        return Err(invalid_input_shape(node, 1, &weights.shape));
+    }
+    let hidden_size = weights.shape.dim(1) as usize / 3;
+    let num_heads = attribute::<i64>(node, "num_heads", None)? as usize;
+    let head_size = hidden_size / num_heads.max(1);
+    if num_heads == 0 || head_size * num_heads != hidden_size {
+        return Err(invalid_input_shape(node, 1, &weights.shape));
+    }
+    let scale = attribute(node, "scale", Some(1.0 / (head_size as f32).sqrt()))? as f64;
+    let unidirectional = attribute(node, "unidirectional", Some(0))? != 0;
+    let mask = inputs.get(3);
+
+    // Project the input to queries, keys and values (each of shape [batch_size * sequence_length, hidden_size])
+    let projections: Vec<Vec<f64>> = (0..3)
+        .map(|p| {
+            let mut projection = Vec::with_capacity(batch_size * sequence_length * hidden_size);
+            for row in x.data.chunks(input_size) {
+                for j in 0..hidden_size {
+                    let column = p * hidden_size + j;
+                    projection.push(
+                        bias.data[column]
+                            + (0..input_size)
+                                .map(|i| row[i] * weights.data[i * 3 * hidden_size + column])
+                                .sum::<f64>(),
+                    );
+                }
+            }
+            projection
+        })
+        .collect();
+    let (query, key, value) = (&projections[0], &projections[1], &projections[2]);
+
+    let mut output = vec![0.0; batch_size * sequence_length * hidden_size];
+    for b in 0..batch_size {
+        for head in 0..num_heads {
+            let offset = |s: usize| (b * sequence_length + s) * hidden_size + head * head_size;
+            for s in 0..sequence_length {
+                let attended: Vec<usize> = (0..sequence_length)
+                    .filter(|t| !unidirectional || *t <= s)
+                    .filter(|t| !mask.is_some_and(|m| m.data[b * sequence_length + t] == 0.0))
+                    .collect();
+                let scores: Vec<f64> = attended
+                    .iter()
+                    .map(|t| {
+                        (0..head_size)
+                            .map(|j| query[offset(s) + j] * key[offset(*t) + j])
+                            .sum::<f64>()
+                            * scale
+                    })
+                    .collect();
+                let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                let sum: f64 = scores.iter().map(|score| (score - max).exp()).sum();
+                for (t, score) in attended.iter().zip(scores.iter()) {
+                    let p = (score - max).exp() / sum;
+                    for j in 0..head_size {
+                        output[offset(s) + j] += p * value[offset(*t) + j];
+                    }
+                }
+                // When all elements are masked, the output is just the value bias
+                if attended.is_empty() {
+                    for j in 0..head_size {
+                        output[offset(s) + j] = bias.data[2 * hidden_size + head * head_size + j];
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn arithmetic(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let lhs = inputs[0];
+    let lhs_indices = broadcast_indices(&lhs.shape.dims, &output_shape.dims)
+        .ok_or_else(|| broadcast_error(node, inputs, output_shape))?;
+
+    // With a single input, the right hand side is taken from the 'coefficient' attribute
+    let coefficient = [attribute(node, "coefficient", Some(1.0))? as f64];
+    let (rhs, rhs_indices): (&[f64], Vec<usize>) = match inputs.get(1) {
+        Some(rhs) => (
+            &rhs.data,
+            broadcast_indices(&rhs.shape.dims, &output_shape.dims)
+                .ok_or_else(|| broadcast_error(node, inputs, output_shape))?,
+        ),
+        None => (&coefficient, vec![0; lhs_indices.len()]),
+    };
+
+    let fmod = attribute(node, "fmod", Some(0))? != 0 || lhs.shape.data_type.is_float();
+    let bool = |b: bool| if b { 1.0 } else { 0.0 };
+    let f: fn(f64, f64) -> f64 = match node.get_op_type() {
+        "Add" => |a, b| a + b,
+        "Sub" => |a, b| a - b,
+        "Mul" => |a, b| a * b,
+        "Div" => |a, b| a / b,
+        "Pow" => f64::powf,
+        "PRelu" => |a, b| if a < 0.0 { a * b } else { a },
+        // With fmod=0 (only allowed for integers) the sign of the result follows the divisor
+        "Mod" if fmod => |a, b| a % b,
+        "Mod" => |a, b| {
+            let r = a % b;
+            if r != 0.0 && (r < 0.0) != (b < 0.0) {
+                r + b
+            } else {
+                r
+            }
+        },
+        _ => |_, _| unreachable!(),
+    };
+
+    let output: Vec<f64> = lhs_indices
+        .iter()
+        .zip(rhs_indices.iter())
+        .map(|(l, r)| {
+            let (a, b) = (lhs.data[*l], rhs[*r]);
+            match node.get_op_type() {
+                "And" => bool(a != 0.0 && b != 0.0),
+                "Or" => bool(a != 0.0 || b != 0.0),
+                "Equal" => bool(a == b),
+                "Greater" => bool(a > b),
+                "GreaterOrEqual" => bool(a >= b),
+                "Less" => bool(a < b),
+                "LessOrEqual" => bool(a <= b),
+                _ => f(a, b),
+            }
+        })
+        .collect();
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+/// Calculates the element-wise Sum, Mean, Max or Min of any number of (multidirectionally broadcast) inputs
+fn variadic(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let input_indices = inputs
+        .iter()
+        .map(|input| {
+            broadcast_indices(&input.shape.dims, &output_shape.dims)
+                .ok_or_else(|| broadcast_error(node, inputs, output_shape))
+        })
+        .collect::<Result<Vec<Vec<usize>>, CpuError>>()?;
+
+    let op = node.get_op_type();
+    let f: fn(f64, f64) -> f64 = match op {
+        "Max" => f64::max,
+        "Min" => f64::min,
+        _ => |a, b| a + b,
+    };
+    let output: Vec<f64> = (0..product(&output_shape.dims))
+        .map(|index| {
+            let value = inputs
+                .iter()
+                .zip(&input_indices)
+                .map(|(input, indices)| input.data[indices[index]])
+                .reduce(f)
+                .unwrap_or_default();
+            if op == "Mean" {
+                value / inputs.len() as f64
+            } else {
+                value
+            }
+        })
+        .collect();
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn batch_normalization(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    if inputs.len() < 5 {
+        return Err(operator_error(
+            node,
+            CompileError::InvalidInputCount {
+                expected: 5,
+                actual: inputs.len(),
+            },
+        ));
+    }
+    let (x, scale, bias, mean, var) = (inputs[0], inputs[1], inputs[2], inputs[3], inputs[4]);
+    if x.shape.rank() < 2 {
+        return Err(invalid_input_shape(node, 0, &x.shape));
+    }
+
+    // Input is [N, C, D1, D2, ...]; statistics are per channel C
+    let epsilon = attribute(node, "epsilon", Some(1e-05))? as f64;
+    let channels = x.shape.dim(1) as usize;
+    let inner = product(&x.shape.dims[2..]);
+    for (index, stat) in [scale, bias, mean, var].iter().enumerate() {
+        if stat.data.len() != channels {
+            return Err(invalid_input_shape(node, index + 1, &stat.shape));
+        }
+    }
+
+    let output = x
+        .data
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let c = (index / inner) % channels;
+            scale.data[c] * (value - mean.data[c]) / (var.data[c] + epsilon).sqrt() + bias.data[c]
+        })
+        .collect();
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn group_normalization(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    if inputs.len() != 3 {
+        return Err(operator_error(
+            node,
+            CompileError::InvalidInputCount {
+                expected: 3,
+                actual: inputs.len(),
+            },
+        ));
+    }
+    let (x, scale, bias) = (inputs[0], inputs[1], inputs[2]);
+    if x.shape.rank() < 2 {
+        return Err(invalid_input_shape(node, 0, &x.shape));
+    }
+
+    // Input is [N, C, D1, D2, ...]; statistics are per batch and group of channels (InstanceNormalization has a group
+    // for each channel). Scale and bias are per channel or per group.
+    let channels = x.shape.dim(1) as usize;
+    let num_groups = if node.get_op_type() == "InstanceNormalization" {
+        channels
+    } else {
+        attribute::<i64>(node, "num_groups", None)?.max(1) as usize
+    };
+    if channels % num_groups != 0 {
+        return Err(invalid_input_shape(node, 0, &x.shape));
+    }
+    if (scale.data.len() != channels && scale.data.len() != num_groups)
+        || bias.data.len() != scale.data.len()
+    {
+        return Err(invalid_input_shape(node, 1, &scale.shape));
+    }
+
+    let epsilon = attribute(node, "epsilon", Some(1e-05))? as f64;
+    let spatial_size = product(&x.shape.dims[2..]);
+    let group_size = (channels / num_groups) * spatial_size;
+    let channels_per_parameter = channels / scale.data.len();
+    let mut output = Vec::with_capacity(x.data.len());
+    for (row, values) in x.data.chunks(group_size.max(1)).enumerate() {
+        let mean = values.iter().sum::<f64>() / group_size as f64;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / group_size as f64;
+        let inverse_std_dev = 1.0 / (variance + epsilon).sqrt();
+        let first_channel = (row % num_groups) * (channels / num_groups);
+        for (i, v) in values.iter().enumerate() {
+            let parameter = (first_channel + i / spatial_size) / channels_per_parameter;
+            output
+                .push((v - mean) * inverse_std_dev * scale.data[parameter] + bias.data[parameter]);
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn lp_normalization(
+    node: &NodeProto,
+    input: &CpuTensor,
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let dims = &input.shape.dims;
+    let axis = normalize_axis(node, attribute(node, "axis", Some(-1))?, dims.len())?;
+    let p: i64 = attribute(node, "p", Some(2))?;
+    if p != 1 && p != 2 {
+        return Err(operator_error(
+            node,
+            CompileError::InvalidAttributeValue {
+                attribute: "p".to_string(),
+                value: p.to_string(),
+                opset_version: 0,
+            },
+        ));
+    }
+
+    let axis_size = dims[axis] as usize;
+    let inner = product(&dims[(axis + 1)..]);
+    let mut output = input.data.clone();
+    for outer in 0..product(&dims[..axis]) {
+        for i in 0..inner {
+            let start = outer * axis_size * inner + i;
+            let indices = (0..axis_size).map(|j| start + j * inner);
+            let norm = if p == 1 {
+                indices
+                    .clone()
+                    .map(|index| input.data[index].abs())
+                    .sum::<f64>()
+            } else {
+                indices
+                    .clone()
+                    .map(|index| input.data[index].powi(2))
+                    .sum::<f64>()
+                    .sqrt()
+            };
+            // Values that are all zero are left unchanged
+            if norm != 0.0 {
+                for index in indices {
+                    output[index] /= norm;
+                }
+            }
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+/// Calculates an Einsum by summing the products of the input elements for every combination of label values. The
+/// optimizer lowers Einsum to other ops where possible, so this is only used for other equations (e.g. diagonals).
+fn einsum(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let equation: String = attribute(node, "equation", None)?;
+    let input_shapes: Vec<&Shape> = inputs.iter().map(|input| &input.shape).collect();
+    let equation = EinsumEquation::parse(&equation, &input_shapes)
+        .map_err(|error| operator_error(node, error.into()))?;
+
+    let mut labels: Vec<char> = equation.output().to_vec();
+    for label in equation.inputs().iter().flatten() {
+        if !labels.contains(label) {
+            labels.push(*label);
+        }
+    }
+
+    // The stride of each label in a tensor (a label that repeats within a term selects a diagonal)
+    let label_strides = |term: &[char]| -> Vec<usize> {
+        let term_strides = strides(&equation.dims(term));
+        labels
+            .iter()
+            .map(|label| {
+                term.iter()
+                    .zip(&term_strides)
+                    .filter(|(l, _)| *l == label)
+                    .map(|(_, stride)| stride)
+                    .sum()
+            })
+            .collect()
+    };
+    let input_strides: Vec<Vec<usize>> = equation
+        .inputs()
+        .iter()
+        .map(|term| label_strides(term))
+        .collect();
+    let output_strides = label_strides(equation.output());
+
+    let dims = equation.dims(&labels);
+    let combination_strides = strides(&dims);
+    let mut output = vec![0.0; product(&output_shape.dims)];
+    for combination in 0..product(&dims) {
+        let coordinates: Vec<usize> = combination_strides
+            .iter()
+            .zip(&dims)
+            .map(|(stride, dim)| (combination / stride) % *dim as usize)
+            .collect();
+        let offset = |strides: &[usize]| -> usize {
+            coordinates.iter().zip(strides).map(|(c, s)| c * s).sum()
+        };
+        let value: f64 = inputs
+            .iter()
+            .zip(&input_strides)
+            .map(|(input, strides)| input.data[offset(strides)])
+            .product();
+        output[offset(&output_strides)] += value;
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn concat(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let axis = normalize_axis(node, attribute(node, "axis", None)?, output_shape.rank())?;
+    let outer = product(&output_shape.dims[..axis]);
+    let mut output = Vec::with_capacity(output_shape.element_count() as usize);
+    for outer_index in 0..outer {
+        for input in inputs {
+            if input.shape.rank() != output_shape.rank() {
+                return Err(broadcast_error(node, inputs, output_shape));
+            }
+            let chunk = product(&input.shape.dims[axis..]);
+            output
+                .extend_from_slice(&input.data[(outer_index * chunk)..((outer_index + 1) * chunk)]);
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+/// Calculates the padding at the start of each spatial axis, for convolution and pooling ops
+fn conv_pads(
+    node: &NodeProto,
+    input_shape: &Shape,
+    output_shape: &Shape,
+    kernel_shape: &[i64],
+    strides: &[i64],
+    dilations: &[i64],
+) -> Result<Vec<i64>, CpuError> {
+    let auto_pad = attribute(node, "auto_pad", Some("NOTSET".to_string()))?;
+    Ok(match auto_pad.as_str() {
+        "NOTSET" => attribute(node, "pads", Some(vec![0, 0, 0, 0]))?[0..2].to_vec(),
+        "VALID" => vec![0, 0],
+        "SAME_UPPER" | "SAME_LOWER" => (0..2)
+            .map(|axis| {
+                let total = ((output_shape.dim(axis + 2) as i64 - 1) * strides[axis]
+                    + (kernel_shape[axis] - 1) * dilations[axis]
+                    + 1
+                    - input_shape.dim(axis + 2) as i64)
+                    .max(0);
+                if auto_pad == "SAME_UPPER" {
+                    total / 2
+                } else {
+                    total - total / 2
+                }
+            })
+            .collect(),
+        _ => {
+            return Err(operator_error(
+                node,
+                CompileError::UnimplementedVariant {
+                    op: node.get_op_type().to_string(),
+                    variant: format!("auto_pad={}", auto_pad),
+                },
+            ))
+        }
+    })
+}
+
+fn pool_or_conv(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shapes: &[Shape],
+) -> Result<Vec<CpuTensor>, CpuError> {
+    let op = node.get_op_type();
+    let x = inputs[0];
+    let output_shape = &output_shapes[0];
+
+    // Only NxCxHxW inputs are supported (as in the GPU implementation)
+    if x.shape.rank() != 4 {
+        return Err(invalid_input_shape(node, 0, &x.shape));
+    }
+    let (batches, channels, height, width) = (
+        x.shape.dim(0) as usize,
+        x.shape.dim(1) as usize,
+        x.shape.dim(2) as usize,
+        x.shape.dim(3) as usize,
+    );
+    let (output_channels, output_height, output_width) = (
+        output_shape.dim(1) as usize,
+        output_shape.dim(2) as usize,
+        output_shape.dim(3) as usize,
+    );
+
+    let fused = fused_conv_type(op);
+    let is_conv = fused.is_some();
+    let (kernel_shape, strides, dilations, pads) = if op == "GlobalAveragePool" {
+        (
+            vec![height as i64, width as i64],
+            vec![1, 1],
+            vec![1, 1],
+            vec![0, 0],
+        )
+    } else {
+        let kernel_shape: Vec<i64> = if is_conv {
+            attribute(
+                node,
+                "kernel_shape",
+                Some(
+                    inputs[1].shape.dims[2..]
+                        .iter()
+                        .map(|x| *x as i64)
+                        .collect(),
+                ),
+            )?
+        } else {
+            attribute(node, "kernel_shape", None)?
+        };
+        let strides = attribute(node, "strides", Some(vec![1, 1]))?;
+        let dilations = attribute(node, "dilations", Some(vec![1, 1]))?;
+        let pads = conv_pads(
+            node,
+            &x.shape,
+            output_shape,
+            &kernel_shape,
+            &strides,
+            &dilations,
+        )?;
+        (kernel_shape, strides, dilations, pads)
+    };
+    if kernel_shape.len() != 2 || strides.len() != 2 || dilations.len() != 2 {
+        return Err(operator_error(
+            node,
+            CompileError::UnimplementedVariant {
+                op: op.to_string(),
+                variant: format!("with kernel shape {:?}", kernel_shape),
+            },
+        ));
+    }
+    let (kernel_height, kernel_width) = (kernel_shape[0] as usize, kernel_shape[1] as usize);
+
+    // Returns the index in the input for the specified output position and kernel offset, if it is not in the padding
+    let input_position = |y: usize, x: usize, ky: usize, kx: usize| -> Option<(usize, usize)> {
+        let iy = (y as i64) * strides[0] - pads[0] + (ky as i64) * dilations[0];
+        let ix = (x as i64) * strides[1] - pads[1] + (kx as i64) * dilations[1];
+        if iy >= 0 && ix >= 0 && (iy as usize) < height && (ix as usize) < width {
+            Some((iy as usize, ix as usize))
+        } else {
+            None
+        }
+    };
+
+    let mut output = Vec::with_capacity(output_shape.element_count() as usize);
+    if is_conv {
+        let weights = inputs[1];
+        let group = attribute(node, "group", Some(1))? as usize;
+        if group == 0 || channels % group != 0 || output_channels % group != 0 {
+            return Err(invalid_input_shape(node, 0, &x.shape));
+        }
+        if weights.shape.dim(0) as usize != output_channels {
+            return Err(invalid_input_shape(node, 1, &weights.shape));
+        }
+        let channels_per_group = channels / group;
+        let output_channels_per_group = output_channels / group;
+
+        // The optimizer may have padded the weights of 3x3 kernels to the stride of a mat3x3 in WGSL (12 values per 9)
+        let kernel_size = kernel_height * kernel_width;
+        let weight_count = output_channels * channels_per_group * kernel_size;
+        let weights: Cow<[f64]> = if weights.data.len() != weight_count
+            && kernel_size == 9
+            && weights.data.len() == weight_count / 9 * 12
+        {
+            Cow::Owned(
+                weights
+                    .data
+                    .chunks(4)
+                    .flat_map(|row| row[0..3].iter().cloned())
+                    .collect(),
+            )
+        } else if weights.data.len() == weight_count {
+            Cow::Borrowed(&weights.data)
+        } else {
+            return Err(invalid_input_shape(node, 1, &weights.shape));
+        };
+
+        // A fused residual input (the last input) is added after the bias, before the activation
+        let (residual, activation) = fused.unwrap();
+        let residual = if residual {
+            let residual = inputs[inputs.len() - 1];
+            if residual.shape != *output_shape {
+                return Err(invalid_input_shape(node, inputs.len() - 1, &residual.shape));
+            }
+            Some(&residual.data)
+        } else {
+            None
+        };
+        let bias = if inputs.len() - residual.is_some() as usize > 2 {
+            Some(&inputs[2].data)
+        } else {
+            None
+        };
+        let (alpha, beta) = match activation {
+            "HardSwish" => (
+                scalar_attribute(node, "alpha", 1.0 / 6.0),
+                scalar_attribute(node, "beta", 0.5),
+            ),
+            _ => (scalar_attribute(node, "alpha", 0.01), 0.0),
+        };
+        let min = scalar_attribute(node, "min", f64::NEG_INFINITY);
+        let max = scalar_attribute(node, "max", f64::INFINITY);
+
+        for n in 0..batches {
+            for m in 0..output_channels {
+                let g = m / output_channels_per_group;
+                for y in 0..output_height {
+                    for x_out in 0..output_width {
+                        let mut sum = bias.map(|b| b[m]).unwrap_or(0.0);
+                        for c in 0..channels_per_group {
+                            let channel = g * channels_per_group + c;
+                            for ky in 0..kernel_height {
+                                for kx in 0..kernel_width {
+                                    if let Some((iy, ix)) = input_position(y, x_out, ky, kx) {
+                                        sum += x.data
+                                            [((n * channels + channel) * height + iy) * width + ix]
+                                            * weights[((m * channels_per_group + c)
+                                                * kernel_height
+                                                + ky)
+                                                * kernel_width
+                                                + kx];
+                                    }
+                                }
+                            }
+                        }
+
+                        if let Some(residual) = residual {
+                            sum += residual[output.len()];
+                        }
+                        output.push(match activation {
+                            "Relu" => sum.max(0.0),
+                            "LeakyRelu" if sum < 0.0 => alpha * sum,
+                            "Mish" => sum * softplus(sum).tanh(),
+                            "Sigmoid" => sigmoid(sum),
+                            "Clip" => sum.max(min).min(max),
+                            "HardSwish" => sum * (alpha * sum + beta).clamp(0.0, 1.0),
+                            _ => sum,
+                        });
+                    }
+                }
+            }
+        }
+
+        return Ok(vec![CpuTensor::new(output_shape.clone(), output)]);
+    }
+
+    // Pooling
+    let count_include_pad = attribute(node, "count_include_pad", Some(0))? != 0;
+    let storage_order = attribute(node, "storage_order", Some(0))?;
+    let mut indices = vec![];
+    for n in 0..batches {
+        for c in 0..channels {
+            let plane = (n * channels + c) * height * width;
+            for y in 0..output_height {
+                for x_out in 0..output_width {
+                    let window: Vec<(usize, usize)> = (0..kernel_height)
+                        .flat_map(|ky| (0..kernel_width).map(move |kx| (ky, kx)))
+                        .filter_map(|(ky, kx)| input_position(y, x_out, ky, kx))
+                        .collect();
+
+                    if op == "MaxPool" {
+                        let (mut max, mut max_index) = (f64::NEG_INFINITY, 0);
+                        for (iy, ix) in window {
+                            let value = x.data[plane + iy * width + ix];
+                            if value > max {
+                                max = value;
+                                max_index = if storage_order == 0 {
+                                    plane + iy * width + ix
+                                } else {
+                                    plane + ix * height + iy
+                                };
+                            }
+                        }
+                        output.push(max);
+                        indices.push(max_index as f64);
+                    } else {
+                        let sum: f64 = window
+                            .iter()
+                            .map(|(iy, ix)| x.data[plane + iy * width + ix])
+                            .sum();
+                        let count = if count_include_pad {
+                            kernel_height * kernel_width
+                        } else {
+                            window.len()
+                        };
+                        output.push(sum / count as f64);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut outputs = vec![CpuTensor::new(output_shape.clone(), output)];
+    if let Some(indices_shape) = output_shapes.get(1) {
+        outputs.push(CpuTensor::new(indices_shape.clone(), indices));
+    }
+    Ok(outputs)
+}
+
+fn conv_transpose(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (x, weights) = (inputs[0], inputs[1]);
+    if x.shape.rank() != 4 {
+        return Err(invalid_input_shape(node, 0, &x.shape));
+    }
+    let (batches, channels, height, width) = (
+        x.shape.dim(0) as usize,
+        x.shape.dim(1) as usize,
+        x.shape.dim(2) as usize,
+        x.shape.dim(3) as usize,
+    );
+    let (output_channels, output_height, output_width) = (
+        output_shape.dim(1) as usize,
+        output_shape.dim(2) as usize,
+        output_shape.dim(3) as usize,
+    );
+    let group = attribute(node, "group", Some(1))? as usize;
+    if group == 0 || channels % group != 0 {
+        return Err(invalid_input_shape(node, 0, &x.shape));
+    }
+    let (channels_per_group, output_channels_per_group) =
+        (channels / group, output_channels / group);
+    if weights.shape.rank() != 4
+        || weights.shape.dim(0) as usize != channels
+        || weights.shape.dim(1) as usize * group != output_channels
+    {
+        return Err(invalid_input_shape(node, 1, &weights.shape));
+    }
+
+    let kernel_shape: Vec<i64> = attribute(
+        node,
+        "kernel_shape",
+        Some(weights.shape.dims[2..].iter().map(|x| *x as i64).collect()),
+    )?;
+    let strides = attribute(node, "strides", Some(vec![1, 1]))?;
+    let dilations = attribute(node, "dilations", Some(vec![1, 1]))?;
+    let pads = conv_transpose_pads(
+        node,
+        &x.shape,
+        output_shape,
+        &kernel_shape,
+        &strides,
+        &dilations,
+    )
+    .map_err(|e| operator_error(node, e))?;
+    let (kernel_height, kernel_width) = (kernel_shape[0] as usize, kernel_shape[1] as usize);
+
+    // Each input element is multiplied with the kernel and added to the output (starting at its strided position)
+    let mut output = vec![0.0; output_shape.element_count() as usize];
+    for n in 0..batches {
+        for c in 0..channels {
+            let g = c / channels_per_group;
+            for iy in 0..height {
+                for ix in 0..width {
+                    let value = x.data[((n * channels + c) * height + iy) * width + ix];
+                    for mo in 0..output_channels_per_group {
+                        let m = g * output_channels_per_group + mo;
+                        for ky in 0..kernel_height {
+                            let oy =
+                                (iy as i64) * strides[0] - pads[0] + (ky as i64) * dilations[0];
+                            if oy < 0 || oy as usize >= output_height {
+                                continue;
+                            }
+                            for kx in 0..kernel_width {
+                                let ox =
+                                    (ix as i64) * strides[1] - pads[1] + (kx as i64) * dilations[1];
+                                if ox < 0 || ox as usize >= output_width {
+                                    continue;
+                                }
+                                let weight = weights.data[((c * output_channels_per_group + mo)
+                                    * kernel_height
+                                    + ky)
+                                    * kernel_width
+                                    + kx];
+                                output[((n * output_channels + m) * output_height
+                                    + oy as usize)
+                                    * output_width
+                                    + ox as usize] += value * weight;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(bias) = inputs.get(2) {
+        let spatial_size = output_height * output_width;
+        for (index, value) in output.iter_mut().enumerate() {
+            *value += bias.data[(index / spatial_size) % output_channels];
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn gemm(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (a, b) = (inputs[0], inputs[1]);
+    if a.shape.rank() != 2 {
+        return Err(invalid_input_shape(node, 0, &a.shape));
+    }
+    if b.shape.rank() != 2 {
+        return Err(invalid_input_shape(node, 1, &b.shape));
+    }
+
+    let transpose_a = attribute(node, "transA", Some(0))? != 0;
+    let transpose_b = attribute(node, "transB", Some(0))? != 0;
+    let alpha = attribute(node, "alpha", Some(1.0))? as f64;
+    let beta = attribute(node, "beta", Some(1.0))? as f64;
+
+    let (a_rows, a_cols) = (a.shape.dim(0) as usize, a.shape.dim(1) as usize);
+    let (b_rows, b_cols) = (b.shape.dim(0) as usize, b.shape.dim(1) as usize);
+    let (m, k) = if transpose_a {
+        (a_cols, a_rows)
+    } else {
+        (a_rows, a_cols)
+    };
+    let (k_b, n) = if transpose_b {
+        (b_cols, b_rows)
+    } else {
+        (b_rows, b_cols)
+    };
+    if k != k_b {
+        return Err(invalid_input_shape(node, 1, &b.shape));
+    }
+
+    let a_at = |i: usize, l: usize| {
+        if transpose_a {
+            a.data[l * a_cols + i]
+        } else {
+            a.data[i * a_cols + l]
+        }
+    };
+    let b_at = |l: usize, j: usize| {
+        if transpose_b {
+            b.data[j * b_cols + l]
+        } else {
+            b.data[l * b_cols + j]
+        }
+    };
+
+    // The bias C is unidirectionally broadcast to MxN
+    let bias = match inputs.get(2) {
+        Some(c) => Some((
+            c,
+            broadcast_indices(&c.shape.dims, &[m as u64, n as u64])
+                .ok_or_else(|| invalid_input_shape(node, 2, &c.shape))?,
+        )),
+        None => None,
+    };
+
+    let mut output = Vec::with_capacity(m * n);
+    for i in 0..m {
+        for j in 0..n {
+            let product: f64 = (0..k).map(|l| a_at(i, l) * b_at(l, j)).sum();
+            let bias = bias
+                .as_ref()
+                .map(|(c, indices)| beta * c.data[indices[i * n + j]])
+                .unwrap_or(0.0);
+            output.push(alpha * product + bias);
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn matmul(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (a, b) = (inputs[0], inputs[1]);
+
+    // 1-D arguments are promoted to matrices; the added dimension is not part of the output shape, but because it has
+    // size 1, the layout of the output data is the same
+    let mut a_dims = a.shape.dims.clone();
+    if a_dims.len() == 1 {
+        a_dims.insert(0, 1);
+    }
+    let mut b_dims = b.shape.dims.clone();
+    if b_dims.len() == 1 {
+        b_dims.push(1);
+    }
+    if a_dims.len() < 2 {
+        return Err(invalid_input_shape(node, 0, &a.shape));
+    }
+    if b_dims.len() < 2 {
+        return Err(invalid_input_shape(node, 1, &b.shape));
+    }
+
+    let (a_stack, a_matrix) = a_dims.split_at(a_dims.len() - 2);
+    let (b_stack, b_matrix) = b_dims.split_at(b_dims.len() - 2);
+    let (m, k, n) = (
+        a_matrix[0] as usize,
+        a_matrix[1] as usize,
+        b_matrix[1] as usize,
+    );
+    if b_matrix[0] as usize != k {
+        return Err(invalid_input_shape(node, 1, &b.shape));
+    }
+
+    // Stacks of matrices are broadcast against each other
+    let stack_dims = Shape::multi_broadcast(&[
+        Shape {
+            dims: a_stack.to_vec(),
+            data_type: a.shape.data_type,
+        },
+        Shape {
+            dims: b_stack.to_vec(),
+            data_type: a.shape.data_type,
+        },
+    ])
+    .ok_or_else(|| broadcast_error(node, inputs, output_shape))?
+    .dims;
+    let a_stack_indices = broadcast_indices(a_stack, &stack_dims)
+        .ok_or_else(|| broadcast_error(node, inputs, output_shape))?;
+    let b_stack_indices = broadcast_indices(b_stack, &stack_dims)
+        .ok_or_else(|| broadcast_error(node, inputs, output_shape))?;
+
+    let mut output = Vec::with_capacity(a_stack_indices.len() * m * n);
+    for (a_stack_index, b_stack_index) in a_stack_indices.iter().zip(b_stack_indices.iter()) {
+        let a_matrix = &a.data[(a_stack_index * m * k)..((a_stack_index + 1) * m * k)];
+        let b_matrix = &b.data[(b_stack_index * k * n)..((b_stack_index + 1) * k * n)];
+        for i in 0..m {
+            for j in 0..n {
+                output.push(
+                    (0..k)
+                        .map(|l| a_matrix[i * k + l] * b_matrix[l * n + j])
+                        .sum(),
+                );
+            }
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+/// Reads a quantization parameter (scale or zero point) that was moved to an attribute by the optimizer
+fn quantization_parameter(node: &NodeProto, name: &str) -> Result<Vec<f64>, CpuError> {
+    Ok(if name.ends_with("_scale") {
+        attribute::<Vec<f32>>(node, name, None)?
+            .into_iter()
+            .map(f64::from)
+            .collect()
+    } else {
+        attribute::<Vec<i64>>(node, name, Some(vec![0]))?
+            .into_iter()
+            .map(|x| x as f64)
+            .collect()
+    })
+}
+
+/// Returns the quantization parameter that applies to the slice with the specified index (parameters with a single value
+/// apply to all slices)
+fn parameter_at(values: &[f64], index: usize) -> f64 {
+    if values.len() > 1 {
+        values[index]
+    } else {
+        values[0]
+    }
+}
+
+fn quantize(
+    node: &NodeProto,
+    values: impl Iterator<Item = f64>,
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (q_min, q_max) = quantized_range(output_shape.data_type, node.get_op_type())
+        .map_err(|e| operator_error(node, e))?;
+    let scale = quantization_parameter(node, "y_scale")?[0];
+    let zero_point = quantization_parameter(node, "y_zero_point")?[0];
+    Ok(CpuTensor::new(
+        output_shape.clone(),
+        values
+            .map(|x| (round_half_to_even(x / scale) + zero_point).clamp(q_min as f64, q_max as f64))
+            .collect(),
+    ))
+}
+
+fn quantize_linear(
+    node: &NodeProto,
+    x: &CpuTensor,
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (scale, zero_point) = if node.get_op_type() == "QuantizeLinear" {
+        ("y_scale", "y_zero_point")
+    } else {
+        ("x_scale", "x_zero_point")
+    };
+    let scale = quantization_parameter(node, scale)?;
+    let zero_point = quantization_parameter(node, zero_point)?;
+
+    // Parameters with more than one value apply to slices along the specified axis
+    let (chunk, dim) = if x.shape.rank() > 0 {
+        let axis = normalize_axis(node, attribute(node, "axis", Some(1))?, x.shape.rank())?;
+        (strides(&x.shape.dims)[axis], x.shape.dim(axis) as usize)
+    } else {
+        (1, 1)
+    };
+
+    let (q_min, q_max) = if node.get_op_type() == "QuantizeLinear" {
+        quantized_range(output_shape.data_type, node.get_op_type())
+            .map_err(|e| operator_error(node, e))?
+    } else {
+        (i64::MIN, i64::MAX)
+    };
+    let output = x.data.iter().enumerate().map(|(index, x)| {
+        let axis_index = (index / chunk) % dim;
+        let (s, z) = (
+            parameter_at(&scale, axis_index),
+            parameter_at(&zero_point, axis_index),
+        );
+        if node.get_op_type() == "QuantizeLinear" {
+            (round_half_to_even(x / s) + z).clamp(q_min as f64, q_max as f64)
+        } else {
+            (x - z) * s
+        }
+    });
+    Ok(CpuTensor::new(output_shape.clone(), output.collect()))
+}
+
+/// Dequantizes a tensor with parameters that apply to each row (A) or column (B) of the (stacked) matrices
+fn dequantize_matrix(x: &CpuTensor, scale: &[f64], zero_point: &[f64], per_row: bool) -> CpuTensor {
+    let dims = &x.shape.dims;
+    let columns = dims.last().copied().unwrap_or(1) as usize;
+    let rows = if dims.len() > 1 {
+        dims[dims.len() - 2] as usize
+    } else {
+        1
+    };
+    let data = x.data.iter().enumerate().map(|(index, x)| {
+        let slice = if per_row {
+            (index / columns) % rows
+        } else {
+            index % columns
+        };
+        (x - parameter_at(zero_point, slice)) * parameter_at(scale, slice)
+    });
+    CpuTensor {
+        shape: Shape {
+            data_type: ScalarType::F64,
+            dims: x.shape.dims.clone(),
+        },
+        data: data.collect(),
+    }
+}
+
+fn quantized_matmul(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    if node.get_op_type() == "MatMulInteger" {
+        let a = dequantize_matrix(
+            inputs[0],
+            &[1.0],
+            &quantization_parameter(node, "a_zero_point")?,
+            true,
+        );
+        let b = dequantize_matrix(
+            inputs[1],
+            &[1.0],
+            &quantization_parameter(node, "b_zero_point")?,
+            false,
+        );
+        return matmul(node, &[&a, &b], output_shape);
+    }
+
+    let a = dequantize_matrix(
+        inputs[0],
+        &quantization_parameter(node, "a_scale")?,
+        &quantization_parameter(node, "a_zero_point")?,
+        true,
+    );
+    let b = dequantize_matrix(
+        inputs[1],
+        &quantization_parameter(node, "b_scale")?,
+        &quantization_parameter(node, "b_zero_point")?,
+        false,
+    );
+    let product = matmul(
+        node,
+        &[&a, &b],
+        &Shape {
+            data_type: ScalarType::F64,
+            dims: output_shape.dims.clone(),
+        },
+    )?;
+    quantize(node, product.data.into_iter(), output_shape)
+}
+
+/// QLinearConv is performed as a regular convolution of the dequantized input and weights
+fn qlinear_conv(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let (x, w) = (inputs[0], inputs[1]);
+    let x_scale = quantization_parameter(node, "x_scale")?[0];
+    let x_zero_point = quantization_parameter(node, "x_zero_point")?[0];
+    let w_scale = quantization_parameter(node, "w_scale")?;
+    let w_zero_point = quantization_parameter(node, "w_zero_point")?;
+
+    let x = CpuTensor {
+        shape: Shape {
+            data_type: ScalarType::F64,
+            dims: x.shape.dims.clone(),
+        },
+        data: x
+            .data
+            .iter()
+            .map(|x| (x - x_zero_point) * x_scale)
+            .collect(),
+    };
+
+    // Weight parameters may be specified per output channel
+    let chunk = product(&w.shape.dims[1..]);
+    let w = CpuTensor {
+        shape: Shape {
+            data_type: ScalarType::F64,
+            dims: w.shape.dims.clone(),
+        },
+        data: w
+            .data
+            .iter()
+            .enumerate()
+            .map(|(index, w)| {
+                let m = index / chunk;
+                (w - parameter_at(&w_zero_point, m)) * parameter_at(&w_scale, m)
+            })
+            .collect(),
+    };
+
+    // The (int32) bias is quantized with scale x_scale * w_scale and a zero point of zero
+    let bias = inputs.get(2).map(|bias| CpuTensor {
+        shape: Shape {
+            data_type: ScalarType::F64,
+            dims: bias.shape.dims.clone(),
+        },
+        data: bias
+            .data
+            .iter()
+            .enumerate()
+            .map(|(m, b)| b * x_scale * parameter_at(&w_scale, m))
+            .collect(),
+    });
+
+    let mut conv = node.clone();
+    conv.set_op_type("Conv".to_string());
+    let mut conv_inputs = vec![&x, &w];
+    conv_inputs.extend(bias.as_ref());
+    let output = pool_or_conv(
+        &conv,
+        &conv_inputs,
+        &[Shape {
+            data_type: ScalarType::F64,
+            dims: output_shape.dims.clone(),
+        }],
+    )?
+    .remove(0);
+    quantize(node, output.data.into_iter(), output_shape)
+}
+
+fn resize(
+    node: &NodeProto,
+    input: &CpuTensor,
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let input_dims = &input.shape.dims;
+    let output_dims = &output_shape.dims;
+    let rank = input_dims.len();
+    if output_dims.len() != rank {
+        return Err(invalid_input_shape(node, 0, &input.shape));
+    }
+
+    let unimplemented = |variant: String| {
+        operator_error(
+            node,
+            CompileError::UnimplementedVariant {
+                op: "Resize".to_string(),
+                variant,
+            },
+        )
+    };
+
+    let coordinate_transformation_mode = attribute(
+        node,
+        "coordinate_transformation_mode",
+        Some("half_pixel".to_string()),
+    )?;
+    let mode = attribute(node, "mode", Some("nearest".to_string()))?;
+    let nearest_mode = attribute(node, "nearest_mode", Some("round_prefer_floor".to_string()))?;
+    let scales: Vec<f32> = attribute(node, "scales", Some(vec![]))?;
+    let roi: Vec<f32> = attribute(node, "roi", Some(vec![]))?;
+    let extrapolation_value = attribute(node, "extrapolation_value", Some(0.0))? as f64;
+
+    if mode != "nearest" && mode != "linear" {
+        return Err(unimplemented(format!("mode={}", mode)));
+    }
+
+    // For each axis and each output coordinate, find the coordinate in the input (None if it needs to be extrapolated)
+    let mut input_coordinates: Vec<Vec<Option<f64>>> = Vec::with_capacity(rank);
+    for axis in 0..rank {
+        let (input_size, output_size) = (input_dims[axis] as f64, output_dims[axis] as f64);
+        let scale = if scales.len() == rank {
+            scales[axis] as f64
+        } else {
+            output_size / input_size
+        };
+
+        let mut coordinates = Vec::with_capacity(output_dims[axis] as usize);
+        for x in 0..output_dims[axis] {
+            let x = x as f64;
+            let coordinate = match coordinate_transformation_mode.as_str() {
+                "half_pixel" => (x + 0.5) / scale - 0.5,
+                "pytorch_half_pixel" if output_size > 1.0 => (x + 0.5) / scale - 0.5,
+                "pytorch_half_pixel" => 0.0,
+                "align_corners" if output_size > 1.0 => {
+                    x * (input_size - 1.0) / (output_size - 1.0)
+                }
+                "align_corners" => 0.0,
+                "asymmetric" => x / scale,
+                "tf_half_pixel_for_nn" => (x + 0.5) / scale,
+                "tf_crop_and_resize" => {
+                    if roi.len() != rank * 2 {
+                        return Err(unimplemented("tf_crop_and_resize without roi".to_string()));
+                    }
+                    let (start, end) = (roi[axis] as f64, roi[rank + axis] as f64);
+                    if output_size > 1.0 {
+                        start * (input_size - 1.0)
+                            + x * (end - start) * (input_size - 1.0) / (output_size - 1.0)
+                    } else {
+                        0.5 * (start + end) * (input_size - 1.0)
+                    }
+                }
+                other => {
+                    return Err(unimplemented(format!(
+                        "coordinate_transformation_mode={}",
+                        other
+                    )))
+                }
+            };
+
+            if coordinate_transformation_mode == "tf_crop_and_resize"
+                && (coordinate < 0.0 || coordinate > input_size - 1.0)
+            {
+                coordinates.push(None);
+            } else {
+                coordinates.push(Some(coordinate.clamp(0.0, input_size - 1.0)));
+            }
+        }
+        input_coordinates.push(coordinates);
+    }
+
+    let nearest: fn(f64) -> f64 = match nearest_mode.as_str() {
+        "round_prefer_floor" => |x| {
+            if x.fract() == 0.5 {
+                x.floor()
+            } else {
+                x.round()
+            }
+        },
+        "round_prefer_ceil" => |x| {
+            if x.fract() == 0.5 {
+                x.ceil()
+            } else {
+                x.round()
+            }
+        },
+        "floor" => f64::floor,
+        "ceil" => f64::ceil,
+        other => return Err(unimplemented(format!("nearest_mode={}", other))),
+    };
+
+    let input_strides = strides(input_dims);
+    let output_strides = strides(output_dims);
+    let mut output = Vec::with_capacity(output_shape.element_count() as usize);
+    'elements: for index in 0..(output_shape.element_count() as usize) {
+        let mut coordinates = Vec::with_capacity(rank);
+        for axis in 0..rank {
+            let x = (index / output_strides[axis]) % output_dims[axis] as usize;
+            match input_coordinates[axis][x] {
+                Some(coordinate) => coordinates.push(coordinate),
+                None => {
+                    output.push(extrapolation_value);
+                    continue 'elements;
+                }
+            }
+        }
+
+        if mode == "nearest" {
+            let input_index: usize = coordinates
+                .iter()
+                .enumerate()
+                .map(|(axis, c)| {
+                    (nearest(*c) as usize).min(input_dims[axis] as usize - 1) * input_strides[axis]
+                })
+                .sum();
+            output.push(input.data[input_index]);
+        } else {
+            // Linear interpolation: take the weighted sum over the 2^rank surrounding input elements
+            let mut value = 0.0;
+            for corner in 0..(1usize << rank) {
+                let mut weight = 1.0;
+                let mut input_index = 0;
+                for (axis, c) in coordinates.iter().enumerate() {
+                    let lower = c.floor();
+                    let fraction = c - lower;
+                    let upper_corner = (corner >> axis) & 1 == 1;
+                    let position = if upper_corner {
+                        weight *= fraction;
+                        (lower as usize + 1).min(input_dims[axis] as usize - 1)
+                    } else {
+                        weight *= 1.0 - fraction;
+                        lower as usize
+                    };
+                    input_index += position * input_strides[axis];
+                }
+                if weight != 0.0 {
+                    value += weight * input.data[input_index];
+                }
+            }
+            output.push(value);
+        }
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+/// Selects boxes by non-maximum suppression, returning the selected boxes along with their number. The number of selected
+/// boxes depends on the data; the output is padded with -1 up to the size of the output shape, which is an upper bound.
+fn non_max_suppression(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+) -> Result<(CpuTensor, usize), CpuError> {
+    let inputs = optional_inputs(node, inputs);
+    let (boxes, scores) = match inputs[..] {
+        [Some(boxes), Some(scores), ..] => (boxes, scores),
+        _ => {
+            return Err(operator_error(
+                node,
+                CompileError::InvalidInputCount {
+                    expected: 2,
+                    actual: inputs.iter().flatten().count(),
+                },
+            ))
+        }
+    };
+    let scalar_input = |index: usize| {
+        inputs
+            .get(index)
+            .copied()
+            .flatten()
+            .and_then(|input| input.data.first().copied())
+    };
+    let max_output_boxes_per_class = scalar_input(2).unwrap_or(0.0).max(0.0) as usize;
+    let iou_threshold = scalar_input(3).unwrap_or(0.0);
+    let score_threshold = scalar_input(4);
+    let center_point_box: i64 = attribute(node, "center_point_box", Some(0))?;
+
+    // Boxes are [batch, box, 4] and scores are [batch, class, box]
+    if boxes.shape.rank() != 3 || boxes.shape.dim(2) != 4 {
+        return Err(invalid_input_shape(node, 0, &boxes.shape));
+    }
+    if scores.shape.rank() != 3
+        || scores.shape.dim(0) != boxes.shape.dim(0)
+        || scores.shape.dim(2) != boxes.shape.dim(1)
+    {
+        return Err(invalid_input_shape(node, 1, &scores.shape));
+    }
+    let (batches, classes, box_count) = (
+        scores.shape.dim(0) as usize,
+        scores.shape.dim(1) as usize,
+        scores.shape.dim(2) as usize,
+    );
+
+    // Returns the corners of a box as [y1, x1, y2, x2] with y1 <= y2 and x1 <= x2
+    let corners = |batch: usize, index: usize| -> [f64; 4] {
+        let b = &boxes.data[(batch * box_count + index) * 4..][..4];
+        if center_point_box == 1 {
+            let (x, y, half_width, half_height) = (b[0], b[1], b[2] / 2.0, b[3] / 2.0);
+            [
+                y - half_height,
+                x - half_width,
+                y + half_height,
+                x + half_width,
+            ]
+        } else {
+            [
+                b[0].min(b[2]),
+                b[1].min(b[3]),
+                b[0].max(b[2]),
+                b[1].max(b[3]),
+            ]
+        }
+    };
+    let area = |c: &[f64; 4]| (c[2] - c[0]) * (c[3] - c[1]);
+    let iou = |a: &[f64; 4], b: &[f64; 4]| {
+        let intersection =
+            (a[2].min(b[2]) - a[0].max(b[0])).max(0.0) * (a[3].min(b[3]) - a[1].max(b[1])).max(0.0);
+        let union = area(a) + area(b) - intersection;
+        if intersection <= 0.0 || area(a) <= 0.0 || area(b) <= 0.0 || union <= 0.0 {
+            0.0
+        } else {
+            intersection / union
+        }
+    };
+
+    let mut selected: Vec<f64> = vec![];
+    for batch in 0..batches {
+        for class in 0..classes {
+            let class_scores = &scores.data[(batch * classes + class) * box_count..][..box_count];
+
+            // Candidates in order of descending score (ties are broken by the lowest index)
+            let mut candidates: Vec<usize> = (0..box_count)
+                .filter(|index| score_threshold.map_or(true, |t| class_scores[*index] > t))
+                .collect();
+            candidates.sort_by(|a, b| class_scores[*b].total_cmp(&class_scores[*a]));
+
+            let mut selected_boxes: Vec<[f64; 4]> = vec![];
+            for index in candidates {
+                if selected_boxes.len() >= max_output_boxes_per_class {
+                    break;
+                }
+                let candidate = corners(batch, index);
+                if selected_boxes
+                    .iter()
+                    .all(|selected_box| iou(selected_box, &candidate) <= iou_threshold)
+                {
+                    selected_boxes.push(candidate);
+                    selected.extend([batch as f64, class as f64, index as f64]);
+                }
+            }
+        }
+    }
+
+    let expected = output_shape.element_count() as usize;
+    if selected.len() > expected {
+        return Err(CpuError::OutputTooLarge {
+            node: node_name(node),
+            expected,
+            actual: selected.len(),
+        });
+    }
+    let rows = selected.len() / 3;
+    selected.resize(expected, -1.0);
+    Ok((CpuTensor::new(output_shape.clone(), selected), rows))
+}
+
+/// Returns the four terms of the bilinear interpolation of a plane at the specified position (the samples of RoiAlign).
+/// Positions that lie more than one pixel outside of the plane yield zero.
+fn roi_align_sample(plane: &[f64], height: usize, width: usize, y: f64, x: f64) -> [f64; 4] {
+    if y < -1.0 || y > height as f64 || x < -1.0 || x > width as f64 {
+        return [0.0; 4];
+    }
+
+    // Returns the lower and upper position and the distance to the lower position along an axis
+    let neighbours = |position: f64, size: usize| {
+        let low = position.max(0.0) as usize;
+        if low >= size - 1 {
+            (size - 1, size - 1, 0.0)
+        } else {
+            (low, low + 1, position.max(0.0) - low as f64)
+        }
+    };
+    let (y_low, y_high, ly) = neighbours(y, height);
+    let (x_low, x_high, lx) = neighbours(x, width);
+    let (hy, hx) = (1.0 - ly, 1.0 - lx);
+    [
+        hy * hx * plane[y_low * width + x_low],
+        hy * lx * plane[y_low * width + x_high],
+        ly * hx * plane[y_high * width + x_low],
+        ly * lx * plane[y_high * width + x_high],
+    ]
+}
+
+fn roi_align(
+    node: &NodeProto,
+    inputs: &[&CpuTensor],
+    output_shape: &Shape,
+    opset_version: i64,
+) -> Result<CpuTensor, CpuError> {
+    let attributes =
+        roi_align_attributes(node, opset_version).map_err(|error| operator_error(node, error))?;
+    let (x, rois, batch_indices) = match inputs {
+        [x, rois, batch_indices] => (x, rois, batch_indices),
+        _ => {
+            return Err(operator_error(
+                node,
+                CompileError::InvalidInputCount {
+                    expected: 3,
+                    actual: inputs.len(),
+                },
+            ))
+        }
+    };
+    if x.shape.rank() != 4 {
+        return Err(invalid_input_shape(node, 0, &x.shape));
+    }
+    let (channels, height, width) = (
+        x.shape.dim(1) as usize,
+        x.shape.dim(2) as usize,
+        x.shape.dim(3) as usize,
+    );
+    let (roi_count, output_height, output_width) = (
+        output_shape.dim(0) as usize,
+        output_shape.dim(2) as usize,
+        output_shape.dim(3) as usize,
+    );
+    if rois.shape.dims != [roi_count as u64, 4] {
+        return Err(invalid_input_shape(node, 1, &rois.shape));
+    }
+
+    let offset = if attributes.half_pixel { 0.5 } else { 0.0 };
+    let spatial_scale = attributes.spatial_scale as f64;
+    let mut output = Vec::with_capacity(output_shape.element_count() as usize);
+    for roi_index in 0..roi_count {
+        let batch = resolve_index(node, batch_indices.data[roi_index], x.shape.dim(0))?;
+        let roi = &rois.data[roi_index * 4..][..4];
+        let (start_x, start_y) = (
+            roi[0] * spatial_scale - offset,
+            roi[1] * spatial_scale - offset,
+        );
+        let (mut roi_width, mut roi_height) = (
+            roi[2] * spatial_scale - offset - start_x,
+            roi[3] * spatial_scale - offset - start_y,
+        );
+        if !attributes.half_pixel {
+            roi_width = roi_width.max(1.0);
+            roi_height = roi_height.max(1.0);
+        }
+        let bin_height = roi_height / output_height as f64;
+        let bin_width = roi_width / output_width as f64;
+        let (grid_height, grid_width) = if attributes.sampling_ratio > 0 {
+            let ratio = attributes.sampling_ratio as usize;
+            (ratio, ratio)
+        } else {
+            (bin_height.ceil() as usize, bin_width.ceil() as usize)
+        };
+        let sample_count = (grid_height * grid_width).max(1) as f64;
+
+        for channel in 0..channels {
+            let plane = &x.data[(batch * channels + channel) * height * width..][..height * width];
+            for output_y in 0..output_height {
+                for output_x in 0..output_width {
+                    let mut value: Option<f64> = None;
+                    for grid_y in 0..grid_height {
+                        let y = start_y
+                            + output_y as f64 * bin_height
+                            + (grid_y as f64 + 0.5) * bin_height / grid_height as f64;
+                        for grid_x in 0..grid_width {
+                            let x = start_x
+                                + output_x as f64 * bin_width
+                                + (grid_x as f64 + 0.5) * bin_width / grid_width as f64;
+                            let terms = roi_align_sample(plane, height, width, y, x);
+                            value = Some(if attributes.max {
+                                let sample = terms.iter().copied().fold(f64::MIN, f64::max);
+                                value.map_or(sample, |value| value.max(sample))
+                            } else {
+                                value.unwrap_or(0.0) + terms.iter().sum::<f64>()
+                            });
+                        }
+                    }
+                    let value = value.unwrap_or(0.0);
+                    output.push(if attributes.max {
+                        value
+                    } else {
+                        value / sample_count
+                    });
+                }
+            }
+        }
+    }
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn split(
+    node: &NodeProto,
+    input: &CpuTensor,
+    output_shapes: &[Shape],
+) -> Result<Vec<CpuTensor>, CpuError> {
+    let dims = &input.shape.dims;
+    let axis = normalize_axis(node, attribute(node, "axis", Some(0))?, dims.len())?;
+    let outer = product(&dims[..axis]);
+    let inner = product(&dims[(axis + 1)..]);
+    let axis_size = dims[axis] as usize;
+
+    // The size of each part along the axis follows from the output shapes
+    let mut offset = 0;
+    let mut outputs = Vec::with_capacity(output_shapes.len());
+    for output_shape in output_shapes {
+        let size = output_shape.dims.get(axis).copied().unwrap_or(0) as usize;
+        if offset + size > axis_size {
+            return Err(invalid_input_shape(node, 0, &input.shape));
+        }
+        let mut output = Vec::with_capacity(outer * size * inner);
+        for outer_index in 0..outer {
+            let start = (outer_index * axis_size + offset) * inner;
+            output.extend_from_slice(&input.data[start..(start + size * inner)]);
+        }
+        outputs.push(CpuTensor::new(output_shape.clone(), output));
+        offset += size;
+    }
+
+    Ok(outputs)
+}
+
+fn pad(node: &NodeProto, input: &CpuTensor, output_shape: &Shape) -> Result<CpuTensor, CpuError> {
+    let input_dims = &input.shape.dims;
+    let rank = input_dims.len();
+    let mode = attribute(node, "mode", Some("constant".to_string()))?;
+    let pads: Vec<i64> = attribute(node, "pads", None)?;
+    if pads.len() != rank * 2 {
+        return Err(operator_error(
+            node,
+            CompileError::InvalidAttributeValue {
+                attribute: "pads".into(),
+                value: format!("{:?}", pads),
+                opset_version: 0,
+            },
+        ));
+    }
+    let constant_value = scalar_attribute(node, "constant_value", 0.0);
+
+    let input_strides = strides(input_dims);
+    let output_strides = strides(&output_shape.dims);
+    let mut output = Vec::with_capacity(output_shape.element_count() as usize);
+    'elements: for index in 0..(output_shape.element_count() as usize) {
+        let mut input_index = 0;
+        for axis in 0..rank {
+            let size = input_dims[axis] as i64;
+            let coordinate = ((index / output_strides[axis]) % output_shape.dims[axis] as usize)
+                as i64
+                - pads[axis];
+            let coordinate = if (0..size).contains(&coordinate) {
+                coordinate
+            } else {
+                match mode.as_str() {
+                    "constant" => {
+                        output.push(constant_value);
+                        continue 'elements;
+                    }
+                    "edge" => coordinate.clamp(0, size - 1),
+                    "reflect" if size > 1 => {
+                        let period = 2 * (size - 1);
+                        let c = coordinate.rem_euclid(period);
+                        if c < size {
+                            c
+                        } else {
+                            period - c
+                        }
+                    }
+                    "reflect" => 0,
+                    "wrap" => coordinate.rem_euclid(size),
+                    _ => {
+                        return Err(operator_error(
+                            node,
+                            CompileError::UnimplementedVariant {
+                                op: String::from("Pad"),
+                                variant: format!("mode={}", mode),
+                            },
+                        ))
+                    }
+                }
+            };
+            input_index += coordinate as usize * input_strides[axis];
+        }
+        output.push(input.data[input_index]);
+    }
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn slice(node: &NodeProto, input: &CpuTensor, output_shape: &Shape) -> Result<CpuTensor, CpuError> {
+    let rank = input.shape.rank();
+    let starts: Vec<i64> = attribute(node, "starts", None)?;
+    let axes: Vec<i64> = attribute(node, "axes", Some((0..starts.len() as i64).collect()))?;
+    let steps: Vec<i64> = attribute(node, "steps", Some(vec![1; starts.len()]))?;
+
+    // The first element and step along each axis of the input (unsliced axes are copied entirely)
+    let mut slice_starts = vec![0; rank];
+    let mut slice_steps = vec![1; rank];
+    for ((axis, start), step) in axes.iter().zip(&starts).zip(&steps) {
+        let axis = normalize_axis(node, *axis, rank)?;
+        let dim = input.shape.dim(axis) as i64;
+        let start = if *start < 0 { start + dim } else { *start };
+        slice_starts[axis] = if *step > 0 {
+            start.clamp(0, dim)
+        } else {
+            start.clamp(0, dim - 1)
+        };
+        slice_steps[axis] = *step;
+    }
+
+    let input_strides = strides(&input.shape.dims);
+    let output_strides = strides(&output_shape.dims);
+    let output = (0..output_shape.element_count() as usize)
+        .map(|index| {
+            let input_index: i64 = (0..rank)
+                .map(|axis| {
+                    let coordinate =
+                        ((index / output_strides[axis]) % output_shape.dim(axis) as usize) as i64;
+                    (slice_starts[axis] + coordinate * slice_steps[axis])
+                        * input_strides[axis] as i64
+                })
+                .sum();
+            input.data[input_index as usize]
+        })
+        .collect();
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn tile(node: &NodeProto, input: &CpuTensor, output_shape: &Shape) -> Result<CpuTensor, CpuError> {
+    let rank = input.shape.rank();
+    if output_shape.rank() != rank {
+        return Err(invalid_input_shape(node, 0, &input.shape));
+    }
+
+    // The output consists of copies of the input, so coordinates wrap around along each axis
+    let input_strides = strides(&input.shape.dims);
+    let output_strides = strides(&output_shape.dims);
+    let output = (0..output_shape.element_count() as usize)
+        .map(|index| {
+            let input_index: usize = (0..rank)
+                .map(|axis| {
+                    let coordinate =
+                        (index / output_strides[axis]) % output_shape.dim(axis) as usize;
+                    (coordinate % input.shape.dim(axis) as usize) * input_strides[axis]
+                })
+                .sum();
+            input.data[input_index]
+        })
+        .collect();
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
+
+fn transpose(
+    node: &NodeProto,
+    input: &CpuTensor,
+    output_shape: &Shape,
+) -> Result<CpuTensor, CpuError> {
+    let rank = input.shape.rank();
+    let default = (0..(rank as i64)).rev().collect::<Vec<i64>>();
+    let perms: Vec<i64> = attribute(node, "perm", Some(default))?;
+    if perms.len() != rank || perms.iter().any(|p| *p < 0 || *p as usize >= rank) {
+        return Err(operator_error(
+            node,
+            CompileError::InvalidAttributeValue {
+                attribute: "perm".to_string(),
+                value: format!("{:?}", perms),
+                opset_version: 0,
+            },
+        ));
+    }
+
+    // Axis i of the output is axis perms[i] of the input
+    let input_strides = strides(&input.shape.dims);
+    let output_dims: Vec<u64> = perms.iter().map(|p| input.shape.dim(*p as usize)).collect();
+    let output_strides = strides(&output_dims);
+    let output = (0..input.data.len())
+        .map(|index| {
+            let input_index: usize = (0..rank)
+                .map(|axis| {
+                    ((index / output_strides[axis]) % output_dims[axis] as usize)
+                        * input_strides[perms[axis] as usize]
+                })
+                .sum();
+            input.data[input_index]
+        })
+        .collect();
+
+    Ok(CpuTensor::new(output_shape.clone(), output))
+}
@@ -0,0 +1,487 @@
+//! Parsing of Einsum equations, and lowering of Einsum onto operators that have templates (Transpose, ReduceSum, Mul and
+//! MatMul, connected by Reshape)
+use crate::onnx::AttributeProto;
+use crate::utils::{attribute, ScalarType, Shape};
+use num::integer::gcd;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Labels for the dimensions covered by an ellipsis ('...') are taken from the Unicode private use area, so that they
+/// never clash with the letters used in an equation.
+const ELLIPSIS_LABEL_START: u32 = 0xE000;
+
+#[derive(Error, Debug)]
+pub enum EinsumError {
+    #[error("invalid Einsum equation '{equation}': {reason}")]
+    InvalidEquation { equation: String, reason: String },
+
+    #[error("Einsum equation '{equation}' cannot be lowered: {reason}")]
+    Unsupported { equation: String, reason: String },
+
+    #[error(
+        "Einsum equation '{0}' must be lowered to other ops by the optimizer before compiling"
+    )]
+    NotLowered(String),
+}
+
+/// An Einsum equation, with the labels of the dimensions of each input and of the output
+#[derive(Debug, Clone)]
+pub struct EinsumEquation {
+    equation: String,
+    inputs: Vec<Vec<char>>,
+    output: Vec<char>,
+    dims: HashMap<char, u64>,
+}
+
+impl EinsumEquation {
+    /// Parses an equation for inputs of the given shapes. When the equation has no output term, the output consists of
+    /// the ellipsis dimensions followed by the labels that appear only once (in alphabetical order).
+    pub fn parse(equation: &str, input_shapes: &[&Shape]) -> Result<EinsumEquation, EinsumError> {
+        let invalid = |reason: String| EinsumError::InvalidEquation {
+            equation: equation.to_string(),
+            reason,
+        };
+        let compact: String = equation.chars().filter(|c| !c.is_whitespace()).collect();
+        let (lhs, rhs) = match compact.split_once("->") {
+            Some((lhs, rhs)) => (lhs, Some(rhs)),
+            None => (compact.as_str(), None),
+        };
+        let terms: Vec<&str> = lhs.split(',').collect();
+        if terms.len() != input_shapes.len() {
+            return Err(invalid(format!(
+                "{} terms for {} inputs",
+                terms.len(),
+                input_shapes.len()
+            )));
+        }
+
+        // The number of dimensions the ellipsis covers in each term (ellipsis dimensions are aligned to the right)
+        let mut ellipsis_ranks = Vec::with_capacity(terms.len());
+        for (term, shape) in terms.iter().zip(input_shapes) {
+            let letters = term.replace("...", "").chars().count();
+            let ellipsis_rank = if term.contains("...") {
+                shape.rank().saturating_sub(letters)
+            } else {
+                0
+            };
+            ellipsis_ranks.push(ellipsis_rank);
+        }
+        let ellipsis_rank = ellipsis_ranks.iter().copied().max().unwrap_or(0);
+
+        let mut inputs = Vec::with_capacity(terms.len());
+        let mut dims = HashMap::new();
+        for ((term, shape), term_ellipsis_rank) in
+            terms.iter().zip(input_shapes).zip(ellipsis_ranks)
+        {
+            let labels = term_labels(term, term_ellipsis_rank, ellipsis_rank).map_err(invalid)?;
+            if labels.len() != shape.rank() {
+                return Err(invalid(format!(
+                    "term '{}' does not match input shape {}",
+                    term, shape
+                )));
+            }
+            for (label, dim) in labels.iter().zip(&shape.dims) {
+                if let Some(other) = dims.insert(*label, *dim) {
+                    if other != *dim {
+                        return Err(invalid(format!(
+                            "dimensions {} and {} of the same label differ (broadcasting is not supported)",
+                            other, dim
+                        )));
+                    }
+                }
+            }
+            inputs.push(labels);
+        }
+
+        let output = match rhs {
+            Some(rhs) => term_labels(rhs, ellipsis_rank, ellipsis_rank).map_err(invalid)?,
+            None => {
+                let mut once: Vec<char> = dims
+                    .keys()
+                    .copied()
+                    .filter(|label| {
+                        (*label as u32) < ELLIPSIS_LABEL_START
+                            && inputs.iter().flatten().filter(|l| *l == label).count() == 1
+                    })
+                    .collect();
+                once.sort_unstable();
+                ellipsis_labels(0, ellipsis_rank).chain(once).collect()
+            }
+        };
+        for (index, label) in output.iter().enumerate() {
+            if !dims.contains_key(label) || output[..index].contains(label) {
+                return Err(invalid(format!(
+                    "output label '{}' does not appear in the inputs or appears twice",
+                    label
+                )));
+            }
+        }
+
+        Ok(EinsumEquation {
+            equation: equation.to_string(),
+            inputs,
+            output,
+            dims,
+        })
+    }
+
+    /// The labels of the dimensions of each input
+    pub fn inputs(&self) -> &[Vec<char>] {
+        &self.inputs
+    }
+
+    /// The labels of the dimensions of the output
+    pub fn output(&self) -> &[char] {
+        &self.output
+    }
+
+    /// The dimension for each of the labels
+    pub fn dims(&self, labels: &[char]) -> Vec<u64> {
+        labels.iter().map(|label| self.dims[label]).collect()
+    }
+
+    /// Lowers the equation to a sequence of operations (each on the inputs or the outputs of earlier operations), of
+    /// which the last produces the output. Inputs are first summed over the labels that appear only in them, and then
+    /// contracted pairwise (using MatMul, or Mul followed by ReduceSum when MatMul cannot be used).
+    pub(crate) fn lower(&self, data_type: ScalarType) -> Result<Vec<LoweredOp>, EinsumError> {
+        let mut lowering = Lowering {
+            equation: self,
+            data_type,
+            ops: vec![],
+        };
+
+        let mut operands = Vec::with_capacity(self.inputs.len());
+        for (index, labels) in self.inputs.iter().enumerate() {
+            if labels
+                .iter()
+                .enumerate()
+                .any(|(i, label)| labels[..i].contains(label))
+            {
+                return Err(EinsumError::Unsupported {
+                    equation: self.equation.clone(),
+                    reason: "labels that repeat within a term (diagonals) are not supported"
+                        .to_string(),
+                });
+            }
+            let operand = Operand {
+                value: Value::Input(index),
+                labels: labels.clone(),
+            };
+            let needed = self.needed_labels(&operands, &self.inputs[(index + 1)..]);
+            operands.push(lowering.summed(operand, &needed));
+        }
+
+        while operands.len() > 1 {
+            let a = operands.remove(0);
+            let b = operands.remove(0);
+            let needed = self.needed_labels(&operands, &[]);
+            let contracted = lowering.contracted(a, b, &needed);
+            operands.insert(0, contracted);
+        }
+
+        let result = operands.pop().ok_or_else(|| EinsumError::InvalidEquation {
+            equation: self.equation.clone(),
+            reason: "no inputs".to_string(),
+        })?;
+        let result = lowering.summed(result, &self.output);
+        let result = lowering.transposed(result, &self.output);
+        if lowering.ops.is_empty() || result.value != Value::Op(lowering.ops.len() - 1) {
+            lowering.push("Identity", vec![result.value], vec![], &self.output);
+        }
+        Ok(lowering.ops)
+    }
+
+    /// Labels that are used by the given operands, the given (not yet processed) input terms, or the output
+    fn needed_labels(&self, operands: &[Operand], terms: &[Vec<char>]) -> Vec<char> {
+        operands
+            .iter()
+            .map(|operand| &operand.labels)
+            .chain(terms)
+            .flatten()
+            .chain(&self.output)
+            .copied()
+            .collect()
+    }
+}
+
+/// Returns the labels of a term, replacing an ellipsis with labels for the last dimensions out of all ellipsis dimensions
+fn term_labels(
+    term: &str,
+    ellipsis_rank: usize,
+    total_ellipsis_rank: usize,
+) -> Result<Vec<char>, String> {
+    let letters = |part: &str| -> Result<Vec<char>, String> {
+        part.chars()
+            .map(|c| {
+                if c.is_ascii_alphabetic() {
+                    Ok(c)
+                } else {
+                    Err(format!("invalid label '{}'", c))
+                }
+            })
+            .collect()
+    };
+    match term.split_once("...") {
+        Some((before, after)) => {
+            let mut labels = letters(before)?;
+            labels.extend(ellipsis_labels(
+                total_ellipsis_rank - ellipsis_rank,
+                total_ellipsis_rank,
+            ));
+            labels.extend(letters(after)?);
+            Ok(labels)
+        }
+        None => letters(term),
+    }
+}
+
+fn ellipsis_labels(start: usize, end: usize) -> impl Iterator<Item = char> {
+    (start..end).map(|i| char::from_u32(ELLIPSIS_LABEL_START + i as u32).unwrap())
+}
+
+/// A value in a lowered Einsum: one of its inputs, or the output of an earlier operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Value {
+    Input(usize),
+    Op(usize),
+}
+
+/// An operation that (part of) an Einsum is lowered to
+#[derive(Debug)]
+pub(crate) struct LoweredOp {
+    pub op_type: &'static str,
+    pub inputs: Vec<Value>,
+    pub attributes: Vec<AttributeProto>,
+    pub output_shape: Shape,
+}
+
+/// A value with a label for each of its dimensions
+struct Operand {
+    value: Value,
+    labels: Vec<char>,
+}
+
+struct Lowering<'a> {
+    equation: &'a EinsumEquation,
+    data_type: ScalarType,
+    ops: Vec<LoweredOp>,
+}
+
+impl<'a> Lowering<'a> {
+    fn push(
+        &mut self,
+        op_type: &'static str,
+        inputs: Vec<Value>,
+        attributes: Vec<AttributeProto>,
+        labels: &[char],
+    ) -> Value {
+        let dims = self.equation.dims(labels);
+        self.push_with_dims(op_type, inputs, attributes, &dims)
+    }
+
+    fn push_with_dims(
+        &mut self,
+        op_type: &'static str,
+        inputs: Vec<Value>,
+        attributes: Vec<AttributeProto>,
+        dims: &[u64],
+    ) -> Value {
+        let dims: Vec<i64> = dims.iter().map(|d| *d as i64).collect();
+        self.ops.push(LoweredOp {
+            op_type,
+            inputs,
+            attributes,
+            output_shape: Shape::from(self.data_type, &dims),
+        });
+        Value::Op(self.ops.len() - 1)
+    }
+
+    fn reshaped(&mut self, value: Value, from: &[u64], to: &[u64]) -> Value {
+        if from == to {
+            value
+        } else {
+            self.push_with_dims("Reshape", vec![value], vec![], to)
+        }
+    }
+
+    /// Sums the operand over the labels that are not needed
+    fn summed(&mut self, operand: Operand, needed: &[char]) -> Operand {
+        let axes: Vec<i64> = (0..operand.labels.len())
+            .filter(|axis| !needed.contains(&operand.labels[*axis]))
+            .map(|axis| axis as i64)
+            .collect();
+        if axes.is_empty() {
+            return operand;
+        }
+        let labels: Vec<char> = operand
+            .labels
+            .iter()
+            .copied()
+            .filter(|label| needed.contains(label))
+            .collect();
+        let value = self.push(
+            "ReduceSum",
+            vec![operand.value],
+            vec![attribute("axes", axes), attribute("keepdims", 0)],
+            &labels,
+        );
+        Operand { value, labels }
+    }
+
+    /// Transposes the operand so that its dimensions are in the order of the given labels
+    fn transposed(&mut self, operand: Operand, labels: &[char]) -> Operand {
+        if operand.labels == labels {
+            return operand;
+        }
+        let perm: Vec<i64> = labels
+            .iter()
+            .map(|label| operand.labels.iter().position(|l| l == label).unwrap() as i64)
+            .collect();
+        let value = self.push(
+            "Transpose",
+            vec![operand.value],
+            vec![attribute("perm", perm)],
+            labels,
+        );
+        Operand {
+            value,
+            labels: labels.to_vec(),
+        }
+    }
+
+    /// Multiplies two operands as a stack of matrices, with the needed labels they share as batch dimensions and the
+    /// labels that are only in one of them as rows resp. columns. Shared labels that are not needed are summed over.
+    fn contracted(&mut self, a: Operand, b: Operand, needed: &[char]) -> Operand {
+        let batch: Vec<char> = a
+            .labels
+            .iter()
+            .copied()
+            .filter(|l| b.labels.contains(l) && needed.contains(l))
+            .collect();
+        let rows: Vec<char> = a
+            .labels
+            .iter()
+            .copied()
+            .filter(|l| !b.labels.contains(l))
+            .collect();
+        let columns: Vec<char> = b
+            .labels
+            .iter()
+            .copied()
+            .filter(|l| !a.labels.contains(l))
+            .collect();
+        let summed: Vec<char> = a
+            .labels
+            .iter()
+            .copied()
+            .filter(|l| b.labels.contains(l) && !needed.contains(l))
+            .collect();
+
+        let a = self.transposed(a, &[&batch[..], &rows, &summed].concat());
+        let b = self.transposed(b, &[&batch[..], &summed, &columns].concat());
+        let size = |labels: &[char]| self.equation.dims(labels).iter().product::<u64>();
+        let (stack, m, k, n) = (size(&batch), size(&rows), size(&summed), size(&columns));
+        let a_dims = self.equation.dims(&a.labels);
+        let b_dims = self.equation.dims(&b.labels);
+
+        let value = if summed.is_empty() {
+            // Outer product
+            let a = self.reshaped(a.value, &a_dims, &[stack, m, 1]);
+            let b = self.reshaped(b.value, &b_dims, &[stack, 1, n]);
+            self.push_with_dims("Mul", vec![a, b], vec![], &[stack, m, n])
+        } else if self.data_type.is_float() && matmul_supported(m, k, n) {
+            let a = self.reshaped(a.value, &a_dims, &[stack, m, k]);
+            let b = self.reshaped(b.value, &b_dims, &[stack, k, n]);
+            self.push_with_dims("MatMul", vec![a, b], vec![], &[stack, m, n])
+        } else {
+            let a = self.reshaped(a.value, &a_dims, &[stack, m, k, 1]);
+            let b = self.reshaped(b.value, &b_dims, &[stack, 1, k, n]);
+            let product = self.push_with_dims("Mul", vec![a, b], vec![], &[stack, m, k, n]);
+            self.push_with_dims(
+                "ReduceSum",
+                vec![product],
+                vec![attribute("axes", vec![2]), attribute("keepdims", 0)],
+                &[stack, m, n],
+            )
+        };
+
+        let labels = [&batch[..], &rows, &columns].concat();
+        let dims = self.equation.dims(&labels);
+        let value = self.reshaped(value, &[stack, m, n], &dims);
+        Operand { value, labels }
+    }
+}
+
+/// Whether the MatMul templates can multiply stacks of M*K and K*N matrices (matrix/gemm_1.wgsl requires K to be a
+/// multiple of 4 for M=1, matrix/gemm.wgsl requires all dimensions to be multiples of the 2x2, 3x3 or 4x4 kernel).
+fn matmul_supported(m: u64, k: u64, n: u64) -> bool {
+    if m == 1 {
+        return k % 4 == 0;
+    }
+    let common = gcd(m, gcd(k, n));
+    let kernel_size = common.clamp(1, 4);
+    kernel_size > 1 && common % kernel_size == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EinsumEquation;
+    use crate::utils::{ScalarType, Shape};
+
+    fn parse(equation: &str, shapes: &[&[i64]]) -> EinsumEquation {
+        let shapes: Vec<Shape> = shapes
+            .iter()
+            .map(|dims| Shape::from(ScalarType::F32, dims))
+            .collect();
+        EinsumEquation::parse(equation, &shapes.iter().collect::<Vec<&Shape>>()).unwrap()
+    }
+
+    fn lowered_ops(equation: &EinsumEquation) -> Vec<&'static str> {
+        equation
+            .lower(ScalarType::F32)
+            .unwrap()
+            .iter()
+            .map(|op| op.op_type)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let equation = parse("bhqd, bhkd -> bhqk", &[&[1, 2, 4, 8], &[1, 2, 6, 8]]);
+        assert_eq!(equation.output(), ['b', 'h', 'q', 'k']);
+        assert_eq!(equation.dims(equation.output()), [1, 2, 4, 6]);
+
+        // Implicit output (labels that appear once, in alphabetical order)
+        let equation = parse("ij,jk", &[&[2, 3], &[3, 4]]);
+        assert_eq!(equation.output(), ['i', 'k']);
+
+        // Ellipsis dimensions are aligned to the right
+        let equation = parse("...ij,...jk->...ik", &[&[5, 2, 3], &[3, 4]]);
+        assert_eq!(equation.dims(equation.output()), [5, 2, 4]);
+
+        let shape = Shape::from(ScalarType::F32, &[2, 3]);
+        assert!(EinsumEquation::parse("ij,jk->ik", &[&shape]).is_err());
+        assert!(EinsumEquation::parse("ijk->ik", &[&shape]).is_err());
+        assert!(EinsumEquation::parse("ij->ix", &[&shape]).is_err());
+    }
+
+    #[test]
+    fn test_lower() {
+        assert_eq!(lowered_ops(&parse("ij->ji", &[&[2, 3]])), ["Transpose"]);
+        assert_eq!(lowered_ops(&parse("ij->i", &[&[2, 3]])), ["ReduceSum"]);
+        assert_eq!(lowered_ops(&parse("ij->ij", &[&[2, 3]])), ["Identity"]);
+        assert_eq!(
+            lowered_ops(&parse("bij,bjk->bik", &[&[2, 4, 4], &[2, 4, 8]])),
+            ["MatMul"]
+        );
+        assert_eq!(
+            lowered_ops(&parse("bhqd,bhkd->bhqk", &[&[1, 2, 4, 8], &[1, 2, 6, 8]])),
+            ["Transpose", "Reshape", "Reshape", "MatMul", "Reshape"]
+        );
+        assert_eq!(
+            lowered_ops(&parse("i,j->ij", &[&[3], &[5]])),
+            ["Reshape", "Reshape", "Mul", "Reshape"]
+        );
+        assert!(parse("ii->i", &[&[3, 3]]).lower(ScalarType::F32).is_err());
+    }
+}
@@ -9,19 +9,25 @@ use std::{
     sync::Arc,
 };
 
+use async_recursion::async_recursion;
 use bytemuck::NoUninit;
+use half::f16;
 use num::FromPrimitive;
 use thiserror::Error;
 use wgpu::{Buffer, BufferAsyncError, BufferUsages, CommandEncoder, Device};
 
 use crate::{
-    compiler::{compile, CompileError, CompiledNode},
-    ir::{Node, NodeDefinition, NodeIdentifier, OperatorDefinition},
-    onnx::TensorProto,
+    compiled,
+    compiler::{compile, optional_inputs, scan_attributes, CompileError, CompiledNode},
+    cpu::{execute_on_host, CpuError},
+    ir::{
+        op_forwards_input, op_has_data_dependent_output, Node, NodeDefinition, NodeIdentifier,
+        OperatorDefinition, RowCounts,
+    },
+    onnx::{NodeProto, TensorProto},
     resource::{self, resize},
     utils::{
-        ceil, DataTypeError, InputTensor, OutputTensor, ScalarType, Shape,
-        MINIMUM_BUFFER_SIZE_BYTES,
+        DataTypeError, InputTensor, OutputTensor, ScalarType, Shape, MINIMUM_BUFFER_SIZE_BYTES,
     },
 };
 
@@ -29,9 +35,21 @@ use crate::{
 const MAX_BINDINGS_PER_GROUP: usize = 4;
 
 pub struct GpuModel {
-    device: wgpu::Device,
-    queue: wgpu::Queue,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
     onnx_opset_version: i64,
+    lanes: Vec<GpuLane>,
+
+    /// Whether the device supports half-precision floats in shaders (see [`device_shape`])
+    shader_f16: bool,
+
+    /// Whether the model is a compiled model, whose nodes carry the shaders to use (see [`compile_op`])
+    compiled: bool,
+}
+
+/// The steps needed to perform a single inference, using a set of buffers that is not used by any other lane (except for
+/// initializer buffers, which are shared). A model has one or more lanes so multiple inferences can be in flight at once.
+struct GpuLane {
     steps: Vec<GpuStep>,
     inference_outputs: HashMap<String, InferenceOutput>,
 }
@@ -42,13 +60,35 @@ enum GpuStep {
     Initializer(Arc<Buffer>),
 
     /// A buffer containing tensor data that is obtained from inference input
-    Input(String, Arc<Buffer>),
+    Input(String, GpuTensor),
 
     /// A GPU program (shader) that reads from buffers created by other steps and writes to output buffers
     Operator {
         pipeline: wgpu::ComputePipeline,
         bind_groups: Vec<wgpu::BindGroup>,
         threads: (u32, u32, u32),
+        /// The tensors bound as inputs, for binding inference inputs in their place (see [`GpuModel::infer_gpu`])
+        input_tensors: Vec<GpuTensor>,
+        output_tensors: Vec<GpuTensor>,
+    },
+
+    /// An op that is executed on the host: its inputs are read back after the preceding steps have been performed, and
+    /// its outputs are written to buffers used by subsequent steps. This is done for ops whose output has a
+    /// data-dependent shape (see [`op_has_data_dependent_output`]).
+    Host {
+        proto: Box<NodeProto>,
+        input_tensors: Vec<GpuTensor>,
+        output_tensors: Vec<GpuTensor>,
+    },
+
+    /// An op with subgraphs (If, Loop or Scan) that is driven by the host: after the preceding steps have been performed,
+    /// the steps of its subgraphs are submitted (as often as needed), and values are copied between the buffers of the
+    /// op and those of the subgraphs.
+    ControlFlow {
+        proto: Box<NodeProto>,
+        subgraphs: Vec<GpuSubgraph>,
+        /// The inputs of the op in the order of its definition (`None` for missing optional inputs)
+        inputs: Vec<Option<GpuTensor>>,
         output_tensors: Vec<GpuTensor>,
     },
 
@@ -59,13 +99,68 @@ enum GpuStep {
     None,
 }
 
-/// A tensor that resides in GPU memory
+/// A subgraph of a control flow op, sequenced into steps that use a set of buffers of its own
+struct GpuSubgraph {
+    /// The name of the attribute holding the graph
+    attribute: String,
+    lane: GpuLane,
+
+    /// The buffers for the declared inputs of the graph (`None` for inputs that the graph does not use)
+    inputs: Vec<Option<GpuTensor>>,
+
+    /// The tensors holding the outputs of the graph after its steps have been performed
+    outputs: Vec<GpuTensor>,
+
+    /// Values of enclosing graphs that the graph uses (as taken by the op), along with the buffers they are copied to
+    captures: Vec<(GpuTensor, GpuTensor)>,
+
+    /// The buffers of all inputs of the graph by name; these are passed as inference inputs when running the lane
+    input_tensors: HashMap<String, GpuTensor>,
+}
+
+/// A copy of (part of) the data in one buffer to another
+struct TensorCopy<'a> {
+    from: &'a GpuTensor,
+    from_offset: u64,
+    to: &'a GpuTensor,
+    to_offset: u64,
+    size: u64,
+}
+
+/// A tensor that resides in GPU memory. Note that integer tensors are stored as 32-bit integers on the GPU (i.e. an I64 or
+/// U8 tensor takes four bytes per element).
 #[derive(Clone)]
-struct GpuTensor {
+pub struct GpuTensor {
     buffer: Arc<Buffer>,
     shape: Shape,
 }
 
+/// The source of the data for inference inputs
+enum InferenceInputs<'a, 'input> {
+    /// Input data resides in main memory and is written to the input buffers
+    Host(&'a HashMap<String, InputTensor<'input>>),
+
+    /// Input data resides in GPU memory and is copied to the input buffers, except for the buffers in `bound` (pairs of an
+    /// input buffer and the buffer of the inference input), which are bound to the shaders in place of the input buffers
+    Gpu {
+        inputs: &'a HashMap<String, GpuTensor>,
+        bound: Vec<(Arc<Buffer>, Arc<Buffer>)>,
+    },
+}
+
+impl<'a, 'input> InferenceInputs<'a, 'input> {
+    /// The buffer to bind to shaders in place of the specified buffer
+    fn bound_buffer<'b>(&'b self, buffer: &'b Arc<Buffer>) -> &'b Arc<Buffer> {
+        match self {
+            InferenceInputs::Gpu { bound, .. } => bound
+                .iter()
+                .find(|(input_buffer, _)| Arc::ptr_eq(input_buffer, buffer))
+                .map_or(buffer, |(_, bound_buffer)| bound_buffer),
+            InferenceInputs::Host(_) => buffer,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum GpuError {
     #[error("compiling node '{node}' failed: {error}")]
@@ -74,6 +169,13 @@ pub enum GpuError {
     #[error("inference input not found: '{0}'")]
     InferenceInputMissing(String),
 
+    #[error("inference input '{name}' has shape {actual}, expected {expected}")]
+    InferenceInputInvalidShape {
+        name: String,
+        expected: Shape,
+        actual: Shape,
+    },
+
     #[error("node output not found: index {0}")]
     OutputMissing(usize),
 
@@ -85,11 +187,27 @@ pub enum GpuError {
 
     #[error("async buffer error: {0}")]
     BufferAsyncError(#[from] BufferAsyncError),
+
+    #[error("executing an op on the host failed: {0}")]
+    HostError(#[from] CpuError),
+
+    #[error("node '{0}' is executed on the host, which is not supported for inference on inputs in GPU memory")]
+    HostStepUnsupported(String),
+
+    #[error("node '{node}' performed {actual} iterations, but its scan outputs have room for {expected}")]
+    IterationCountMismatch {
+        node: String,
+        expected: u64,
+        actual: u64,
+    },
 }
 
 enum InferenceOutput {
     InferenceInput(String),
-    Tensor(GpuTensor),
+
+    /// A tensor, along with the data type of the output as declared by the model (which may differ from the type of the
+    /// tensor, see [`device_shape`])
+    Tensor(GpuTensor, ScalarType),
 }
 
 /// The buffer manager tracks the use of buffers and manages recycling (sharing) of buffers for intermediate values that
@@ -299,29 +417,77 @@ impl GpuModel {
     /// Create a version of the specified model for which inference can be performed using the powers of the GPU
     pub fn from(
         root: Arc<Node>,
-        device: wgpu::Device,
-        queue: wgpu::Queue,
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        onnx_opset_version: i64,
+    ) -> Result<GpuModel, GpuError> {
+        Self::with_lanes(root, device, queue, onnx_opset_version, 1, false)
+    }
+
+    /// Create a version of the specified model with the specified number of lanes, each of which has its own set of
+    /// buffers for inputs, outputs and intermediate values (see [`GpuModel::infer_batch`]). The shaders stored in the
+    /// nodes are only used when `compiled` is true, i.e. the model was verified to be a compiled model.
+    pub fn with_lanes(
+        root: Arc<Node>,
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
         onnx_opset_version: i64,
+        lanes: usize,
+        compiled: bool,
     ) -> Result<GpuModel, GpuError> {
+        let shader_f16 = device.features().contains(wgpu::Features::SHADER_F16);
         let mut gpu_model = GpuModel {
             device,
             queue,
             onnx_opset_version,
+            lanes: vec![],
+            shader_f16,
+            compiled,
+        };
+
+        let mut nodes = vec![];
+        let mut nodes_seen = HashSet::new();
+        root.topological_sort(&mut nodes_seen, &mut nodes);
+        drop(nodes_seen);
+
+        let mut initializer_outputs = HashMap::<NodeIdentifier, Vec<GpuTensor>>::new();
+        for _ in 0..lanes.max(1) {
+            let lane = gpu_model.lane(&root, &nodes, &mut initializer_outputs)?;
+            gpu_model.lanes.push(lane);
+        }
+
+        // Upload the data (for initializers etc.) by submitting an empty command queue
+        log::debug!("submit initializer buffers");
+        let encoder = gpu_model
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        gpu_model.queue.submit(Some(encoder.finish()));
+
+        Ok(gpu_model)
+    }
+
+    /// Walk the IR DAG and encode into GPU execution steps that use a new set of buffers. Initializer buffers are taken
+    /// from `initializer_outputs` when available (and added to it otherwise).
+    fn lane<'model>(
+        &self,
+        root: &Arc<Node<'model>>,
+        nodes: &[Arc<Node<'model>>],
+        initializer_outputs: &mut HashMap<NodeIdentifier<'model>, Vec<GpuTensor>>,
+    ) -> Result<GpuLane, GpuError> {
+        let mut lane = GpuLane {
             steps: vec![],
             inference_outputs: HashMap::new(),
         };
 
-        // Walk the IR DAG and encode into GPU execution steps
         let mut readable_nodes: HashSet<NodeIdentifier> = HashSet::new();
-        let mut node_outputs = HashMap::<NodeIdentifier, Vec<GpuTensor>>::new();
-
+        let mut node_outputs = initializer_outputs.clone();
         let mut buffer_manager = BufferManager::new();
-
-        let mut nodes = vec![];
-        let mut nodes_seen = HashSet::new();
-        GpuModel::topological_sort(root.clone(), &mut nodes_seen, &mut nodes);
-        drop(nodes_seen);
-        GpuModel::pre_sequence(&nodes, &mut readable_nodes, &mut buffer_manager)?;
+        GpuModel::pre_sequence(
+            nodes,
+            &mut readable_nodes,
+            &mut buffer_manager,
+            self.shader_f16,
+        )?;
 
         #[cfg(debug_assertions)]
         {
@@ -335,7 +501,8 @@ impl GpuModel {
         }
 
         let mut nodes_seen = HashSet::new();
-        gpu_model.sequence(
+        self.sequence(
+            &mut lane,
             root.clone(),
             &readable_nodes,
             &mut node_outputs,
@@ -343,18 +510,39 @@ impl GpuModel {
             &mut buffer_manager,
         )?;
 
+        for node in nodes {
+            if let NodeDefinition::Tensor(_) = node.definition {
+                if let Some(outputs) = node_outputs.get(&node.identifier()) {
+                    initializer_outputs
+                        .entry(node.identifier())
+                        .or_insert_with(|| outputs.clone());
+                }
+            }
+        }
+
         // Find out which outputs we should return as inference outputs
         if let NodeDefinition::Outputs { names } = &root.definition {
             for (usize, output_name) in names.iter().enumerate() {
                 let input = &root.inputs[usize];
-                gpu_model.inference_outputs.insert(
+                lane.inference_outputs.insert(
                     output_name.to_string(),
                     match &input.source_node.definition {
-                        NodeDefinition::Operator(_) | NodeDefinition::Tensor(_) => {
+                        NodeDefinition::Operator(op_def) => {
                             let source_identifier = input.source_node.identifier();
                             let outputs = &node_outputs[&source_identifier];
                             let tensor = outputs[input.output_index].clone();
-                            InferenceOutput::Tensor(tensor)
+                            InferenceOutput::Tensor(
+                                tensor,
+                                op_def.output_shapes[input.output_index].data_type,
+                            )
+                        }
+                        NodeDefinition::Tensor(tensor_def) => {
+                            let source_identifier = input.source_node.identifier();
+                            let tensor = node_outputs[&source_identifier][0].clone();
+                            InferenceOutput::Tensor(
+                                tensor,
+                                ScalarType::from_i32(tensor_def.get_data_type())?,
+                            )
                         }
                         NodeDefinition::Input(proto) => {
                             InferenceOutput::InferenceInput(proto.get_name().to_string())
@@ -372,34 +560,7 @@ impl GpuModel {
             unimplemented!("reading from non-outputs IR node")
         }
 
-        // Upload the data (for initializers etc.) by submitting an empty command queue
-        log::debug!("submit initializer buffers");
-        let encoder = gpu_model
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-        gpu_model.queue.submit(Some(encoder.finish()));
-
-        Ok(gpu_model)
-    }
-
-    /// Traverse the graph and sort nodes in the order of execution (topological sort)
-    fn topological_sort<'model>(
-        node: Arc<Node<'model>>,
-        nodes_seen: &mut HashSet<NodeIdentifier<'model>>,
-        sorted_nodes: &mut Vec<Arc<Node<'model>>>,
-    ) {
-        let identifier = node.identifier();
-        if !nodes_seen.contains(&identifier) {
-            nodes_seen.insert(identifier);
-            for node_input in &node.inputs {
-                GpuModel::topological_sort(
-                    node_input.source_node.clone(),
-                    nodes_seen,
-                    sorted_nodes,
-                );
-            }
-            sorted_nodes.push(node);
-        }
+        Ok(lane)
     }
 
     /// Run a first pass over the IR graph to determine the outputs of which nodes are supposed to be readable as outputs
@@ -412,6 +573,7 @@ impl GpuModel {
         nodes: &[Arc<Node<'model>>],
         nodes_readable: &mut HashSet<NodeIdentifier<'model>>,
         buffer_manager: &mut BufferManager<'model>,
+        shader_f16: bool,
     ) -> Result<(), GpuError> {
         for node in nodes.iter().rev() {
             let node_identifier = node.identifier();
@@ -439,7 +601,7 @@ impl GpuModel {
                     buffer_manager.lease(
                         ultimate_input.source_node.identifier(),
                         ultimate_input.output_index,
-                        output_shape.buffer_bytes_aligned(),
+                        device_shape(output_shape, shader_f16).buffer_bytes_aligned(),
                     );
                 }
 
@@ -450,6 +612,15 @@ impl GpuModel {
                     outputs_readable = true;
                 }
 
+                // Ops executed on the host read back their inputs, and control flow ops copy them to their subgraphs
+                if let NodeDefinition::Operator(op_def) = &node.definition {
+                    if op_has_data_dependent_output(op_def.proto.get_op_type())
+                        || !op_def.subgraphs.is_empty()
+                    {
+                        nodes_readable.insert(source_node_identifier.clone());
+                    }
+                }
+
                 if outputs_readable {
                     if let NodeDefinition::Operator(op_def) = &node.definition {
                         // For these ops we just forward the buffer (so we should also forward readability)
@@ -467,7 +638,7 @@ impl GpuModel {
                         buffer_manager.release(
                             node_identifier.clone(),
                             output_index,
-                            output_shape.buffer_bytes_aligned(),
+                            device_shape(output_shape, shader_f16).buffer_bytes_aligned(),
                         );
                     }
                 }
@@ -480,7 +651,8 @@ impl GpuModel {
     /// Write out the GPU commands and create the necessary resources to be able to perform inference (e.g. allocates buffers
     /// for intermediate results, compiles shader code, determines which outputs to return, etc.).
     fn sequence<'model>(
-        &mut self,
+        &self,
+        lane: &mut GpuLane,
         node: Arc<Node<'model>>,
         nodes_readable: &HashSet<NodeIdentifier<'model>>,
         node_outputs: &mut HashMap<NodeIdentifier<'model>, Vec<GpuTensor>>,
@@ -501,6 +673,7 @@ impl GpuModel {
 
                 // Sequence the source node
                 self.sequence(
+                    lane,
                     node_input.source_node.clone(),
                     nodes_readable,
                     node_outputs,
@@ -548,13 +721,11 @@ impl GpuModel {
                             })
                             .collect();
 
-                    let gpu_op = op_def.gpu_op(
-                        &self.device,
-                        outputs_readable,
-                        self.onnx_opset_version,
-                        &input_tensors,
-                        &shared_buffers,
-                    )?;
+                    let gpu_op = if op_def.subgraphs.is_empty() {
+                        op_def.gpu_op(self, outputs_readable, &input_tensors, &shared_buffers)?
+                    } else {
+                        self.control_flow_step(op_def, &input_tensors)?
+                    };
 
                     match &gpu_op {
                         GpuStep::Operator {
@@ -563,6 +734,16 @@ impl GpuModel {
                         } => {
                             output_tensors.extend(op_output_tensors.iter().cloned());
                         }
+                        GpuStep::Host {
+                            output_tensors: host_output_tensors,
+                            ..
+                        }
+                        | GpuStep::ControlFlow {
+                            output_tensors: host_output_tensors,
+                            ..
+                        } => {
+                            output_tensors.extend(host_output_tensors.iter().cloned());
+                        }
                         GpuStep::Forward(output_tensor) => {
                             output_tensors.push(output_tensor.clone());
                         }
@@ -573,12 +754,18 @@ impl GpuModel {
                 }
                 // For tensor (initializer) nodes, we just create a buffer and fill it with the initializer data
                 NodeDefinition::Tensor(tensor_def) => {
-                    let tensor_buffer =
-                        Arc::new(tensor_def.buffer(&self.device, outputs_readable)?);
+                    let tensor_buffer = Arc::new(tensor_def.buffer(
+                        &self.device,
+                        outputs_readable,
+                        self.shader_f16,
+                    )?);
                     output_tensors.push(GpuTensor {
-                        shape: Shape::from(
-                            ScalarType::from_i32(tensor_def.get_data_type())?,
-                            tensor_def.get_dims(),
+                        shape: device_shape(
+                            &Shape::from(
+                                ScalarType::from_i32(tensor_def.get_data_type())?,
+                                tensor_def.get_dims(),
+                            ),
+                            self.shader_f16,
                         ),
                         buffer: tensor_buffer.clone(),
                     });
@@ -587,13 +774,13 @@ impl GpuModel {
                 // For inputs we create an empty buffer that can be used at inference time to supply input data
                 NodeDefinition::Input(input_def) => {
                     if outputs_readable {
-                        log::warn!(
-                            "it looks like you will be reading back inference input '{}' as output",
+                        log::debug!(
+                            "inference input '{}' will be read back (as output, or by an op executed on the host)",
                             input_def.get_name()
                         );
                     }
 
-                    let input_shape = input_def.get_shape()?;
+                    let input_shape = device_shape(&input_def.get_shape()?, self.shader_f16);
                     let buffer_size_aligned = input_shape.buffer_bytes_aligned();
                     log::debug!(
                         "creating input buffer for {} shape {} size {}",
@@ -601,21 +788,26 @@ impl GpuModel {
                         input_shape,
                         buffer_size_aligned
                     );
+                    // Inputs that are returned as outputs are not read back from the GPU (the inference input is returned
+                    // instead), but the buffer is readable when an op executed on the host uses the input.
                     let input_buffer = Arc::new(resource::buffer(
                         &self.device,
                         input_shape.buffer_bytes_aligned(),
                         input_def.get_name(),
-                        // Usage is not COPY_SRC/MAP_READ even when outputs_readable is true; we'll deal with the special
-                        // case of reading back inputs as outputs separately.
-                        BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                        if outputs_readable {
+                            BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC
+                        } else {
+                            BufferUsages::STORAGE | BufferUsages::COPY_DST
+                        },
                     ));
 
-                    output_tensors.push(GpuTensor {
+                    let input_tensor = GpuTensor {
                         shape: input_shape,
-                        buffer: input_buffer.clone(),
-                    });
+                        buffer: input_buffer,
+                    };
+                    output_tensors.push(input_tensor.clone());
 
-                    GpuStep::Input(input_def.get_name().to_string(), input_buffer)
+                    GpuStep::Input(input_def.get_name().to_string(), input_tensor)
                 }
                 NodeDefinition::Missing | NodeDefinition::Outputs { .. } => {
                     // Nothing to sequence
@@ -624,7 +816,7 @@ impl GpuModel {
             };
 
             e.insert(output_tensors);
-            self.steps.push(gpu_op);
+            lane.steps.push(gpu_op);
             Ok(())
         } else {
             // This node is already sequenced
@@ -633,40 +825,374 @@ impl GpuModel {
         }
     }
 
-    /// Perform inference using this model and the specified inference inputs.
+    /// Create the step for an op with subgraphs (see [`GpuStep::ControlFlow`]). Each subgraph is sequenced into a lane of
+    /// its own.
+    fn control_flow_step(
+        &self,
+        op_def: &OperatorDefinition,
+        input_tensors: &[GpuTensor],
+    ) -> Result<GpuStep, GpuError> {
+        let proto = &op_def.proto;
+        let unimplemented = |variant: String| GpuError::CompileError {
+            node: proto.get_name().to_string(),
+            error: CompileError::UnimplementedVariant {
+                variant,
+                op: proto.get_op_type().to_string(),
+            },
+        };
+
+        // Values captured by the subgraphs follow the (present) inputs of the op
+        let inputs: Vec<Option<GpuTensor>> = optional_inputs(proto, input_tensors)
+            .into_iter()
+            .map(|input| input.cloned())
+            .collect();
+        let present_count = inputs.iter().flatten().count();
+        let captured: HashMap<&str, &GpuTensor> = op_def
+            .captures()
+            .into_iter()
+            .zip(&input_tensors[present_count..])
+            .collect();
+
+        let mut subgraphs = Vec::with_capacity(op_def.subgraphs.len());
+        for subgraph in &op_def.subgraphs {
+            let mut nodes = vec![];
+            subgraph
+                .root
+                .topological_sort(&mut HashSet::new(), &mut nodes);
+            let lane = self.lane(&subgraph.root, &nodes, &mut HashMap::new())?;
+            let input_tensors: HashMap<String, GpuTensor> = lane
+                .steps
+                .iter()
+                .filter_map(|step| match step {
+                    GpuStep::Input(name, tensor) => Some((name.clone(), tensor.clone())),
+                    _ => None,
+                })
+                .collect();
+
+            let NodeDefinition::Outputs { names } = &subgraph.root.definition else {
+                unreachable!("subgraph root must be an outputs node");
+            };
+            let outputs = names
+                .iter()
+                .map(|name| match &lane.inference_outputs[name] {
+                    InferenceOutput::Tensor(tensor, _) => tensor.clone(),
+                    InferenceOutput::InferenceInput(input_name) => {
+                        input_tensors[input_name].clone()
+                    }
+                })
+                .collect();
+
+            subgraphs.push(GpuSubgraph {
+                attribute: subgraph.attribute.clone(),
+                inputs: subgraph
+                    .inputs
+                    .iter()
+                    .map(|input| input_tensors.get(input.get_name()).cloned())
+                    .collect(),
+                outputs,
+                captures: subgraph
+                    .captures
+                    .iter()
+                    .filter_map(|name| {
+                        Some((
+                            (*captured.get(name.as_str())?).clone(),
+                            input_tensors.get(name)?.clone(),
+                        ))
+                    })
+                    .collect(),
+                input_tensors,
+                lane,
+            });
+        }
+
+        // The outputs of the op are written by copying from the outputs of the subgraphs
+        let output_tensors: Vec<GpuTensor> = op_def
+            .output_shapes
+            .iter()
+            .zip(proto.get_output())
+            .map(|(shape, output_name)| {
+                let shape = device_shape(shape, self.shader_f16);
+                GpuTensor {
+                    buffer: Arc::new(resource::buffer(
+                        &self.device,
+                        shape.buffer_bytes_aligned(),
+                        output_name,
+                        BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+                    )),
+                    shape,
+                }
+            })
+            .collect();
+
+        // Values are passed in slices along the first axis, which must be aligned for copying
+        let slice_shapes: Vec<&Shape> = match proto.get_op_type() {
+            "If" => vec![],
+            "Loop" => {
+                let carried = inputs.len().saturating_sub(2);
+                subgraphs[0].outputs[(1 + carried)..]
+                    .iter()
+                    .map(|tensor| &tensor.shape)
+                    .collect()
+            }
+            "Scan" => {
+                let input_shapes: Vec<&Shape> = input_tensors[..present_count]
+                    .iter()
+                    .map(|tensor| &tensor.shape)
+                    .collect();
+                let output_shapes: Vec<&Shape> =
+                    output_tensors.iter().map(|tensor| &tensor.shape).collect();
+                let attributes = scan_attributes(
+                    proto,
+                    &input_shapes,
+                    &output_shapes,
+                    self.onnx_opset_version,
+                )
+                .map_err(|error| GpuError::CompileError {
+                    node: proto.get_name().to_string(),
+                    error,
+                })?;
+                if attributes
+                    .input_axes
+                    .iter()
+                    .chain(&attributes.output_axes)
+                    .any(|axis| *axis != 0)
+                {
+                    return Err(unimplemented("scan axes other than 0".to_string()));
+                }
+                let body = &subgraphs[0];
+                body.inputs[attributes.states..]
+                    .iter()
+                    .flatten()
+                    .chain(&body.outputs[attributes.states..])
+                    .map(|tensor| &tensor.shape)
+                    .collect()
+            }
+            op => return Err(unimplemented(format!("control flow op {op}"))),
+        };
+        if slice_shapes
+            .iter()
+            .any(|shape| slice_bytes(shape) % wgpu::COPY_BUFFER_ALIGNMENT != 0)
+        {
+            return Err(unimplemented(
+                "slices that are not a multiple of four bytes in size".to_string(),
+            ));
+        }
+
+        Ok(GpuStep::ControlFlow {
+            proto: Box::new(proto.clone().into_owned()),
+            subgraphs,
+            inputs,
+            output_tensors,
+        })
+    }
+
+    /// Perform inference using this model and the specified inference inputs. Also returns the actual number of rows of
+    /// the outputs of ops with a data-dependent output shape.
     pub async fn infer<'a>(
         &self,
         inference_inputs: &HashMap<String, InputTensor<'a>>,
-    ) -> Result<HashMap<String, OutputTensor>, GpuError> {
+    ) -> Result<(HashMap<String, OutputTensor>, RowCounts), GpuError> {
+        let lane = &self.lanes[0];
+        let row_counts = self
+            .submit(lane, &InferenceInputs::Host(inference_inputs))
+            .await?;
+        Ok((self.read_outputs(lane, inference_inputs).await?, row_counts))
+    }
+
+    /// Perform inference for a batch of independent sets of inference inputs. A set of inputs is submitted to each lane
+    /// before any outputs are read back, so that uploads, computation and readback of multiple inferences can overlap.
+    pub async fn infer_batch<'a>(
+        &self,
+        batch: &[HashMap<String, InputTensor<'a>>],
+    ) -> Result<Vec<(HashMap<String, OutputTensor>, RowCounts)>, GpuError> {
+        let mut outputs = Vec::with_capacity(batch.len());
+        for chunk in batch.chunks(self.lanes.len()) {
+            let mut chunk_row_counts = Vec::with_capacity(chunk.len());
+            for (lane, inference_inputs) in self.lanes.iter().zip(chunk) {
+                chunk_row_counts.push(
+                    self.submit(lane, &InferenceInputs::Host(inference_inputs))
+                        .await?,
+                );
+            }
+            for ((lane, inference_inputs), row_counts) in
+                self.lanes.iter().zip(chunk).zip(chunk_row_counts)
+            {
+                outputs.push((self.read_outputs(lane, inference_inputs).await?, row_counts));
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Perform inference using this model and inference inputs that reside in GPU memory. The outputs are not read back
+    /// to main memory. Output buffers are owned by the model and are overwritten by the next inference run. Inference
+    /// inputs in buffers of the same size as the input buffers of the model that have the `STORAGE` usage are bound to the
+    /// shaders directly; others are copied to the input buffers.
+    pub fn infer_gpu(
+        &self,
+        inference_inputs: &HashMap<String, GpuTensor>,
+    ) -> Result<HashMap<String, GpuTensor>, GpuError> {
+        let lane = &self.lanes[0];
+        if let Some(GpuStep::Host { proto, .. } | GpuStep::ControlFlow { proto, .. }) =
+            lane.steps.iter().find(|step| step.is_host_driven())
+        {
+            return Err(GpuError::HostStepUnsupported(proto.get_name().to_string()));
+        }
+        let bound = lane
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                GpuStep::Input(input_name, input_tensor) => {
+                    let source = inference_inputs.get(input_name)?;
+                    (source.shape == input_tensor.shape
+                        && source.buffer.size() == input_tensor.buffer.size()
+                        && source.buffer.usage().contains(BufferUsages::STORAGE)
+                        && !Arc::ptr_eq(&source.buffer, &input_tensor.buffer))
+                    .then(|| (input_tensor.buffer.clone(), source.buffer.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        let inputs = InferenceInputs::Gpu {
+            inputs: inference_inputs,
+            bound,
+        };
+        self.submit_steps(&lane.steps, &inputs)?;
+        Ok(lane
+            .inference_outputs
+            .iter()
+            .map(|(output_name, output_source)| {
+                let tensor = match output_source {
+                    InferenceOutput::InferenceInput(input_name) => {
+                        inference_inputs[input_name].clone()
+                    }
+                    // Outputs forwarded from an input that was bound directly reside in the buffer of the inference input
+                    InferenceOutput::Tensor(tensor, _) => GpuTensor {
+                        buffer: inputs.bound_buffer(&tensor.buffer).clone(),
+                        shape: tensor.shape.clone(),
+                    },
+                };
+                (output_name.to_string(), tensor)
+            })
+            .collect())
+    }
+
+    /// Performs the inference steps of a lane. Steps are submitted to the GPU in runs that are separated by the steps
+    /// that are executed or driven by the host, which wait for the preceding steps to complete. Returns the actual number
+    /// of rows of the outputs of the ops executed on the host that have a data-dependent output shape.
+    #[async_recursion]
+    async fn submit(
+        &self,
+        lane: &GpuLane,
+        inference_inputs: &InferenceInputs<'_, '_>,
+    ) -> Result<RowCounts, GpuError> {
+        let mut row_counts = RowCounts::new();
+        let mut remaining_steps = &lane.steps[..];
+        while let Some(host_index) = remaining_steps
+            .iter()
+            .position(|step| step.is_host_driven())
+        {
+            self.submit_steps(&remaining_steps[..host_index], inference_inputs)?;
+            match &remaining_steps[host_index] {
+                step @ GpuStep::Host { proto, .. } => {
+                    if let Some(rows) = step.execute_on_host(self).await? {
+                        row_counts.insert(proto.get_output()[0].clone(), rows);
+                    }
+                }
+                step => step.execute_control_flow(self).await?,
+            }
+            remaining_steps = &remaining_steps[(host_index + 1)..];
+        }
+        self.submit_steps(remaining_steps, inference_inputs)?;
+        Ok(row_counts)
+    }
+
+    /// Encodes copies between buffers and submits them to the GPU
+    fn copy(&self, copies: &[TensorCopy]) {
+        if copies.is_empty() {
+            return;
+        }
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for copy in copies {
+            encoder.copy_buffer_to_buffer(
+                &copy.from.buffer,
+                copy.from_offset,
+                &copy.to.buffer,
+                copy.to_offset,
+                copy.size,
+            );
+        }
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Encodes the specified inference steps (which must not include steps executed on the host) and submits them to
+    /// the GPU
+    fn submit_steps(
+        &self,
+        steps: &[GpuStep],
+        inference_inputs: &InferenceInputs,
+    ) -> Result<(), GpuError> {
         log::info!("encode inference steps");
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        for step in &self.steps {
-            step.encode(&self.queue, &mut encoder, inference_inputs)?;
+        for step in steps {
+            step.encode(&self.device, &self.queue, &mut encoder, inference_inputs)?;
         }
         log::debug!("submit inference steps");
         self.queue.submit(Some(encoder.finish()));
         log::info!("inference completed");
-        self.read_outputs(inference_inputs).await
+        Ok(())
+    }
+
+    /// Read the first value of an integer or boolean tensor, such as the condition of If or the trip count of Loop
+    async fn read_scalar(&self, tensor: &GpuTensor) -> Result<i64, GpuError> {
+        let value = match tensor.read_to_vec(&self.device, &self.queue).await? {
+            OutputTensor::I64(values) => values.first().copied(),
+            OutputTensor::I32(values) => values.first().map(|x| *x as i64),
+            OutputTensor::Bool(values) => values.first().map(|x| *x as i64),
+            _ => return Err(GpuError::ScalarType(DataTypeError::Undefined)),
+        };
+        value.ok_or(GpuError::OutOfBoundsError)
+    }
+
+    /// Read a tensor that resides in GPU memory (e.g. an output of [`GpuModel::infer_gpu`]) to main memory
+    pub async fn read_tensor(&self, tensor: &GpuTensor) -> Result<OutputTensor, GpuError> {
+        tensor.read_to_vec(&self.device, &self.queue).await
+    }
+
+    /// The device and queue used by this model
+    pub fn device(&self) -> (&Arc<wgpu::Device>, &Arc<wgpu::Queue>) {
+        (&self.device, &self.queue)
     }
 
     /// Reads the relevant buffers for the requested inference outputs
     async fn read_outputs<'a>(
         &self,
+        lane: &GpuLane,
         inference_inputs: &HashMap<String, InputTensor<'a>>,
     ) -> Result<HashMap<String, OutputTensor>, GpuError> {
         let mut output_data: HashMap<String, OutputTensor> = HashMap::new();
 
-        for (output_name, output_source) in &self.inference_outputs {
+        for (output_name, output_source) in &lane.inference_outputs {
             output_data.insert(
                 output_name.to_string(),
                 match output_source {
                     InferenceOutput::InferenceInput(input_name) => {
                         (&inference_inputs[input_name]).into()
                     }
-                    InferenceOutput::Tensor(tensor) => {
-                        tensor.read_to_vec(&self.device, &self.queue).await?
+                    InferenceOutput::Tensor(tensor, data_type) => {
+                        match (
+                            tensor.read_to_vec(&self.device, &self.queue).await?,
+                            data_type,
+                        ) {
+                            // Convert f16 outputs that were calculated as f32
+                            (OutputTensor::F32(floats), ScalarType::F16) => {
+                                OutputTensor::F16(floats.into_iter().map(f16::from_f32).collect())
+                            }
+                            (output, _) => output,
+                        }
                     }
                 },
             );
@@ -677,12 +1203,23 @@ impl GpuModel {
 }
 
 trait TensorProtoExtra {
-    fn buffer(&self, device: &wgpu::Device, readable: bool) -> Result<Buffer, GpuError>;
+    fn buffer(
+        &self,
+        device: &wgpu::Device,
+        readable: bool,
+        shader_f16: bool,
+    ) -> Result<Buffer, GpuError>;
 }
 
 impl TensorProtoExtra for TensorProto {
-    /// Create a GPU buffer containing the data of this initializer
-    fn buffer(&self, device: &wgpu::Device, readable: bool) -> Result<Buffer, GpuError> {
+    /// Create a GPU buffer containing the data of this initializer. When `shader_f16` is false, f16 data is converted to
+    /// f32 (see [`device_shape`]).
+    fn buffer(
+        &self,
+        device: &wgpu::Device,
+        readable: bool,
+        shader_f16: bool,
+    ) -> Result<Buffer, GpuError> {
         let scalar_type = ScalarType::from_i32(self.get_data_type())?;
         let input_shape = Shape::from(scalar_type, self.get_dims());
         log::debug!(
@@ -705,6 +1242,25 @@ impl TensorProtoExtra for TensorProto {
                     },
                 )
             }
+            ScalarType::F16 => {
+                let data = self.get_f16_data();
+                if shader_f16 {
+                    buffer_with_bytes(
+                        device,
+                        readable,
+                        self.get_name(),
+                        bytemuck::cast_slice(&data),
+                    )
+                } else {
+                    let floats: Vec<f32> = data.iter().map(|x| x.to_f32()).collect();
+                    buffer_with_bytes(
+                        device,
+                        readable,
+                        self.get_name(),
+                        bytemuck::cast_slice(&floats),
+                    )
+                }
+            }
             ScalarType::U8 => {
                 // WGSL doesn't support 8 bit unsigned integers, so we load them as 32 bit ints
                 log::warn!("initializers with uint8 data type are not supported, converting into int32 initializer");
@@ -729,6 +1285,44 @@ impl TensorProtoExtra for TensorProto {
                 let raw_data = bytemuck::cast_slice(&ints);
                 buffer_with_bytes(device, readable, self.get_name(), raw_data)
             }
+            ScalarType::F64 => {
+                // WGSL doesn't support 64 bit floats, so we load them as 32 bit floats
+                log::warn!("initializers with double data type are not supported, converting into float initializer");
+                let floats: Vec<f32> = self.get_f64_data().iter().map(|x| *x as f32).collect();
+                buffer_with_bytes(
+                    device,
+                    readable,
+                    self.get_name(),
+                    bytemuck::cast_slice(&floats),
+                )
+            }
+            ScalarType::I8 | ScalarType::U16 | ScalarType::I16 | ScalarType::Bool => {
+                // Integer types smaller than 32 bits (and booleans) are loaded as 32 bit ints
+                let ints: Vec<i32> = match scalar_type {
+                    ScalarType::I8 => self
+                        .get_small_int_data(|x| x as i8)
+                        .into_iter()
+                        .map(i32::from)
+                        .collect(),
+                    ScalarType::U16 => self
+                        .get_small_int_data(|x| x as u16)
+                        .into_iter()
+                        .map(i32::from)
+                        .collect(),
+                    ScalarType::I16 => self
+                        .get_small_int_data(|x| x as i16)
+                        .into_iter()
+                        .map(i32::from)
+                        .collect(),
+                    _ => self.get_bool_data().into_iter().map(i32::from).collect(),
+                };
+                buffer_with_bytes(
+                    device,
+                    readable,
+                    self.get_name(),
+                    bytemuck::cast_slice(&ints),
+                )
+            }
             ScalarType::I32 => {
                 let data = self.get_int32_data();
                 buffer_with_bytes(
@@ -767,30 +1361,67 @@ fn buffer_with_bytes(
     })
 }
 
-/// Returns whether the op of the specified type will forward inputs unchanged. If this is the case, the inputs of such
-/// an op should be marked as 'outputs readable' if the output of the op itself is to be readable.
-fn op_forwards_input(op_type: &str) -> bool {
-    matches!(
-        op_type,
-        "Reshape" | "Identity" | "Flatten" | "Squeeze" | "Unsqueeze" | "Dropout"
-    )
+/// The shape of a tensor as it is stored on the GPU. When the device does not support half-precision floats, f16 tensors
+/// are stored (and calculated with) as f32.
+fn device_shape(shape: &Shape, shader_f16: bool) -> Shape {
+    match shape.data_type {
+        ScalarType::F16 if !shader_f16 => Shape {
+            dims: shape.dims.clone(),
+            data_type: ScalarType::F32,
+        },
+        _ => shape.clone(),
+    }
+}
+
+/// Generate the shader for an op node, or take it from the node when it is part of a compiled model (`compiled` is true).
+/// Shaders stored in nodes of other models are ignored. Stored shaders that use f16 cannot be used when the device does
+/// not support f16 (`shader_f16` is false).
+pub(crate) fn compile_op(
+    proto: &NodeProto,
+    input_shapes: &[&Shape],
+    output_shapes: &[&Shape],
+    opset_version: i64,
+    shader_f16: bool,
+    compiled: bool,
+) -> Result<CompiledNode, GpuError> {
+    if let Some(compiled_node) = compiled.then(|| compiled::stored_shader(proto)).flatten() {
+        if shader_f16 || !compiled_node.shader.starts_with("enable f16;") {
+            return Ok(compiled_node);
+        }
+    }
+
+    compile(proto, input_shapes, output_shapes, opset_version).map_err(|ce| {
+        GpuError::CompileError {
+            node: if proto.has_name() {
+                proto.get_name().to_string()
+            } else {
+                proto.get_op_type().to_string()
+            },
+            error: ce,
+        }
+    })
 }
 
 impl<'model> OperatorDefinition<'model> {
     fn gpu_op(
         &self,
-        device: &wgpu::Device,
+        model: &GpuModel,
         outputs_readable: bool,
-        opset_version: i64,
         input_tensors: &[GpuTensor],
         shared_buffers: &[Option<Rc<RefCell<LeaseableBuffer>>>],
     ) -> Result<GpuStep, GpuError> {
         let proto = &self.proto;
+        let device = model.device.as_ref();
+        let output_shapes: Vec<Shape> = self
+            .output_shapes
+            .iter()
+            .map(|shape| device_shape(shape, model.shader_f16))
+            .collect();
 
         // Some nodes have specific GPU implementations, match these here
         if op_forwards_input(proto.get_op_type()) {
             // Some ops do nothing but forward their input
-            let value_shape = &self.output_shapes[0];
+            let value_shape = &output_shapes[0];
             let output_tensor = GpuTensor {
                 buffer: input_tensors[0].buffer.clone(),
                 shape: value_shape.clone(),
@@ -798,6 +1429,36 @@ impl<'model> OperatorDefinition<'model> {
             return Ok(GpuStep::Forward(output_tensor));
         }
 
+        if op_has_data_dependent_output(proto.get_op_type()) {
+            // The outputs of ops executed on the host are written by the host, and are therefore not shared
+            let output_tensors = proto
+                .get_output()
+                .iter()
+                .zip(output_shapes)
+                .map(|(output_name, shape)| {
+                    let buffer_usage = if outputs_readable {
+                        BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC
+                    } else {
+                        BufferUsages::STORAGE | BufferUsages::COPY_DST
+                    };
+                    GpuTensor {
+                        buffer: Arc::new(resource::buffer(
+                            device,
+                            shape.buffer_bytes_aligned(),
+                            output_name.as_str(),
+                            buffer_usage,
+                        )),
+                        shape,
+                    }
+                })
+                .collect();
+            return Ok(GpuStep::Host {
+                proto: Box::new(proto.clone().into_owned()),
+                input_tensors: input_tensors.to_vec(),
+                output_tensors,
+            });
+        }
+
         let label = Some(proto.get_name());
 
         // Create output buffers for this op node
@@ -806,7 +1467,7 @@ impl<'model> OperatorDefinition<'model> {
             .iter()
             .enumerate()
             .map(|(output_index, output_name)| {
-                let value_shape = &self.output_shapes[output_index];
+                let value_shape = &output_shapes[output_index];
 
                 let buffer = match shared_buffers.get(output_index) {
                     Some(Some(shared_buffer)) if !outputs_readable => {
@@ -845,52 +1506,20 @@ impl<'model> OperatorDefinition<'model> {
             .collect();
 
         let input_shapes: Vec<&Shape> = input_tensors.iter().map(|input| &input.shape).collect();
-        let output_shapes: Vec<&Shape> = self.output_shapes.iter().collect();
+        let output_shapes: Vec<&Shape> = output_shapes.iter().collect();
 
         // Compile shader for node
-        let CompiledNode { shader, threads } =
-            compile(proto, &input_shapes, &output_shapes, opset_version).map_err(|ce| {
-                GpuError::CompileError {
-                    node: if proto.has_name() {
-                        proto.get_name().to_string()
-                    } else {
-                        proto.get_op_type().to_string()
-                    },
-                    error: ce,
-                }
-            })?;
+        let CompiledNode { shader, threads } = compile_op(
+            proto,
+            &input_shapes,
+            &output_shapes,
+            model.onnx_opset_version,
+            model.shader_f16,
+            model.compiled,
+        )?;
         log::trace!("shader: {}", shader);
 
-        // Bind input and output buffers to the shader
-        let mut binding_counter: usize = 0;
-        let mut entries = vec![];
-
-        // Bind input buffers
-        for input in input_tensors {
-            // Bindings are numbered 0...3 (MAX_BINDINGS_PER_GROUP-1) in binding groups (starting at group 0)
-            let binding_index = (binding_counter % MAX_BINDINGS_PER_GROUP) as u32;
-
-            entries.push(wgpu::BindGroupEntry {
-                binding: binding_index,
-                resource: input.buffer.as_entire_binding(),
-            });
-            binding_counter += 1;
-        }
-
-        // Bind output buffers
-        for output_tensor in &output_tensors {
-            // Bindings are numbered 0...3 (MAX_BINDINGS_PER_GROUP-1) in binding groups (starting at group 0)
-            let binding_index = (binding_counter % MAX_BINDINGS_PER_GROUP) as u32;
-
-            entries.push(wgpu::BindGroupEntry {
-                binding: binding_index,
-                resource: output_tensor.buffer.as_entire_binding(),
-            });
-            binding_counter += 1;
-        }
-
         // Set up a pipeline (basically the shader source code with some metadata that determines how it will be executed)
-        let mut bind_groups = vec![];
         let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
             label,
             layout: None,
@@ -901,22 +1530,16 @@ impl<'model> OperatorDefinition<'model> {
             entry_point: "main",
         });
 
-        // Create 'bind groups' (groups of bound buffers)
-        let number_of_groups = ceil(binding_counter as u64, MAX_BINDINGS_PER_GROUP as u64) as usize;
-        for group_index in 0..number_of_groups {
-            let group_range = group_index * MAX_BINDINGS_PER_GROUP
-                ..usize::min(
-                    binding_counter as _,
-                    (group_index + 1) * MAX_BINDINGS_PER_GROUP,
-                );
-            bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label,
-                layout: &pipeline.get_bind_group_layout(group_index as u32),
-                entries: &entries[group_range],
-            }));
-        }
+        // Bind input and output buffers to the shader
+        let buffers: Vec<&Buffer> = input_tensors
+            .iter()
+            .chain(&output_tensors)
+            .map(|tensor| tensor.buffer.as_ref())
+            .collect();
+        let bind_groups = create_bind_groups(device, &pipeline, label, &buffers);
 
         Ok(GpuStep::Operator {
+            input_tensors: input_tensors.to_vec(),
             output_tensors,
             pipeline,
             bind_groups,
@@ -930,87 +1553,530 @@ impl GpuStep {
     /// writing the inference input data to the appropriate (already created) buffers.
     fn encode(
         &self,
+        device: &wgpu::Device,
         queue: &wgpu::Queue,
         encoder: &mut CommandEncoder,
-        inputs: &HashMap<String, InputTensor>,
+        inputs: &InferenceInputs,
     ) -> Result<(), GpuError> {
         match self {
             GpuStep::None | GpuStep::Forward(_) | GpuStep::Initializer(_) => {
                 // Buffer already filled, no need to encode anything at this point.
                 Ok(())
             }
-            GpuStep::Input(input_name, input_buffer) => {
+            GpuStep::Input(input_name, input_tensor) => {
+                let input_buffer = &input_tensor.buffer;
+                let inputs = match inputs {
+                    InferenceInputs::Host(inputs) => inputs,
+                    InferenceInputs::Gpu { inputs, bound } => {
+                        // Encode a command to copy the data from the provided buffer to the input buffer on the GPU
+                        let source = inputs.get(input_name).ok_or_else(|| {
+                            GpuError::InferenceInputMissing(input_name.to_string())
+                        })?;
+                        if Arc::ptr_eq(&source.buffer, input_buffer)
+                            || bound
+                                .iter()
+                                .any(|(buffer, _)| Arc::ptr_eq(buffer, input_buffer))
+                        {
+                            return Ok(());
+                        }
+                        if source.shape != input_tensor.shape {
+                            return Err(GpuError::InferenceInputInvalidShape {
+                                name: input_name.to_string(),
+                                expected: input_tensor.shape.clone(),
+                                actual: source.shape.clone(),
+                            });
+                        }
+                        let size = source.buffer.size().min(input_buffer.size())
+                            / wgpu::COPY_BUFFER_ALIGNMENT
+                            * wgpu::COPY_BUFFER_ALIGNMENT;
+                        log::debug!("copy input buffer for {}", input_name);
+                        encoder.copy_buffer_to_buffer(&source.buffer, 0, input_buffer, 0, size);
+                        return Ok(());
+                    }
+                };
+
                 // Encode a command to write the input data to the corresponding input buffer (which was created empty
                 // by `GpuModel::from`
                 let input_data = inputs
                     .get(input_name)
                     .ok_or_else(|| GpuError::InferenceInputMissing(input_name.to_string()))?;
                 log::debug!("write input data for {}", input_name);
-
                 match input_data {
-                    InputTensor::F32(float_input) => {
-                        queue.write_buffer(
-                            input_buffer,
-                            0,
-                            bytemuck::cast_slice(&resize(float_input.to_vec())),
-                        );
-                    }
-                    InputTensor::I32(int_input) => {
-                        queue.write_buffer(
-                            input_buffer,
-                            0,
-                            bytemuck::cast_slice(&resize(int_input.to_vec())),
-                        );
-                    }
-                    InputTensor::I64(int_input) => {
-                        log::warn!("reading int64 input '{input_name}' as int32 (int64 is not supported for calculation but can be used as input as long as values fit in int32)");
-                        let int32_input = int_input
-                            .iter()
-                            .map(|i| i32::from_i64(*i).ok_or(GpuError::OutOfBoundsError))
-                            .collect::<Result<_, _>>()?;
-                        queue.write_buffer(
-                            input_buffer,
-                            0,
-                            bytemuck::cast_slice(&resize(int32_input)),
-                        );
-                    }
-                    InputTensor::U8(int_input) => {
-                        log::warn!("reading uint8 input as int32 (uint8 is not supported for calculation but can be used as input)");
-                        let int32_input = int_input
-                            .iter()
-                            .map(|i| i32::from_u8(*i).ok_or(GpuError::OutOfBoundsError))
-                            .collect::<Result<_, _>>()?;
-                        queue.write_buffer(
-                            input_buffer,
-                            0,
-                            bytemuck::cast_slice(&resize(int32_input)),
-                        );
-                    }
+                    InputTensor::I64(_) => log::warn!("reading int64 input '{input_name}' as int32 (int64 is not supported for calculation but can be used as input as long as values fit in int32)"),
+                    InputTensor::U8(_) => log::warn!("reading uint8 input '{input_name}' as int32 (uint8 is not supported for calculation but can be used as input)"),
+                    InputTensor::F64(_) => log::warn!("reading double input '{input_name}' as float (double is not supported for calculation)"),
+                    _ => {}
                 }
-
+                input_tensor.write(queue, input_data)?;
                 Ok(())
             }
             GpuStep::Operator {
                 pipeline,
                 bind_groups,
                 threads,
-                ..
+                input_tensors,
+                output_tensors,
             } => {
+                // Inference inputs that are bound in place of input buffers need bind groups of their own
+                let rebound_groups = input_tensors
+                    .iter()
+                    .any(|tensor| !Arc::ptr_eq(inputs.bound_buffer(&tensor.buffer), &tensor.buffer))
+                    .then(|| {
+                        let buffers: Vec<&Buffer> = input_tensors
+                            .iter()
+                            .chain(output_tensors)
+                            .map(|tensor| inputs.bound_buffer(&tensor.buffer).as_ref())
+                            .collect();
+                        create_bind_groups(device, pipeline, None, &buffers)
+                    });
+
                 // Encode a command for invocation of a shader.
                 let mut compute_pass = encoder.begin_compute_pass(&Default::default());
                 compute_pass.set_pipeline(pipeline);
-                for (index, bind_group) in bind_groups.iter().enumerate() {
+                for (index, bind_group) in rebound_groups
+                    .as_ref()
+                    .unwrap_or(bind_groups)
+                    .iter()
+                    .enumerate()
+                {
                     compute_pass.set_bind_group(index as u32, bind_group, &[]);
                 }
                 let (x, y, z) = *threads;
                 compute_pass.dispatch_workgroups(x, y, z);
                 Ok(())
             }
+            GpuStep::Host { .. } | GpuStep::ControlFlow { .. } => {
+                unreachable!("steps executed on the host cannot be encoded")
+            }
+        }
+    }
+
+    /// Executes a step on the host: reads back its inputs (the steps producing them must have been submitted), performs
+    /// the op using the CPU implementation and writes the outputs. Returns the actual number of rows of the (padded)
+    /// output when the output of the op has a data-dependent shape.
+    async fn execute_on_host(&self, model: &GpuModel) -> Result<Option<usize>, GpuError> {
+        let GpuStep::Host {
+            proto,
+            input_tensors,
+            output_tensors,
+        } = self
+        else {
+            unreachable!("step is not executed on the host");
+        };
+        log::debug!("execute {} on the host", proto.get_name());
+
+        let mut inputs = Vec::with_capacity(input_tensors.len());
+        for input_tensor in input_tensors {
+            let data = input_tensor
+                .read_to_vec(&model.device, &model.queue)
+                .await?;
+            inputs.push((input_tensor.shape.clone(), data));
+        }
+        let output_shapes: Vec<Shape> = output_tensors
+            .iter()
+            .map(|tensor| tensor.shape.clone())
+            .collect();
+        let (outputs, rows) =
+            execute_on_host(proto, &inputs, &output_shapes, model.onnx_opset_version)?;
+
+        for (output_tensor, output) in output_tensors.iter().zip(&outputs) {
+            output_tensor.write(&model.queue, &InputTensor::from(output))?;
+        }
+        Ok(rows)
+    }
+
+    /// Whether the step is performed by the host (which requires the preceding steps to be completed first)
+    fn is_host_driven(&self) -> bool {
+        matches!(self, GpuStep::Host { .. } | GpuStep::ControlFlow { .. })
+    }
+
+    /// Executes a control flow op (the steps producing its inputs must have been submitted) by running the lanes of its
+    /// subgraphs.
+    async fn execute_control_flow(&self, model: &GpuModel) -> Result<(), GpuError> {
+        let GpuStep::ControlFlow {
+            proto,
+            subgraphs,
+            inputs,
+            output_tensors,
+        } = self
+        else {
+            unreachable!("step is not a control flow op");
+        };
+        log::debug!("execute {} ({})", proto.get_name(), proto.get_op_type());
+
+        match proto.get_op_type() {
+            "If" => {
+                let cond = model.read_scalar(inputs[0].as_ref().unwrap()).await? != 0;
+                let branch_name = if cond { "then_branch" } else { "else_branch" };
+                let branch = subgraphs
+                    .iter()
+                    .find(|subgraph| subgraph.attribute == branch_name)
+                    .unwrap();
+                branch.run(model, branch.capture_copies()).await?;
+                model.copy(
+                    &branch
+                        .outputs
+                        .iter()
+                        .zip(output_tensors)
+                        .map(|(from, to)| TensorCopy::whole(from, to))
+                        .collect::<Vec<_>>(),
+                );
+            }
+            "Loop" => {
+                let max_trip_count = match &inputs[0] {
+                    Some(tensor) => Some(model.read_scalar(tensor).await?),
+                    None => None,
+                };
+                let mut cond = match &inputs[1] {
+                    Some(tensor) => model.read_scalar(tensor).await? != 0,
+                    None => true,
+                };
+                let body = &subgraphs[0];
+                let carried = inputs.len() - 2;
+                let (carried_outputs, scan_outputs) = output_tensors.split_at(carried);
+                let scan_length = scan_outputs.first().map(|tensor| tensor.shape.dim(0));
+
+                // A body that passes on its condition unchanged keeps looping until the trip count is reached
+                let cond_changes = !matches!(
+                    (&body.inputs[1], &body.outputs[0]),
+                    (Some(cond_in), cond_out) if Arc::ptr_eq(&cond_in.buffer, &cond_out.buffer)
+                );
+
+                model.copy(
+                    &inputs[2..]
+                        .iter()
+                        .zip(carried_outputs)
+                        .filter_map(|(from, to)| Some(TensorCopy::whole(from.as_ref()?, to)))
+                        .collect::<Vec<_>>(),
+                );
+                let mut copies = body.capture_copies();
+                let mut iteration = 0;
+                while cond && max_trip_count.map_or(true, |count| iteration < count) {
+                    if let Some(length) = scan_length.filter(|length| iteration as u64 >= *length) {
+                        return Err(GpuError::IterationCountMismatch {
+                            node: proto.get_name().to_string(),
+                            expected: length,
+                            actual: iteration as u64 + 1,
+                        });
+                    }
+                    if let Some(iteration_input) = &body.inputs[0] {
+                        iteration_input
+                            .write(&model.queue, &InputTensor::I64(Cow::Owned(vec![iteration])))?;
+                    }
+                    if let Some(cond_input) = &body.inputs[1] {
+                        cond_input
+                            .write(&model.queue, &InputTensor::Bool(Cow::Owned(vec![cond])))?;
+                    }
+                    copies.extend(
+                        carried_outputs
+                            .iter()
+                            .zip(&body.inputs[2..])
+                            .filter_map(|(from, to)| Some(TensorCopy::whole(from, to.as_ref()?))),
+                    );
+                    body.run(model, copies).await?;
+
+                    copies = body.outputs[1..]
+                        .iter()
+                        .zip(carried_outputs)
+                        .map(|(from, to)| TensorCopy::whole(from, to))
+                        .collect();
+                    for (from, to) in body.outputs[(1 + carried)..].iter().zip(scan_outputs) {
+                        let size = slice_bytes(&from.shape);
+                        copies.push(TensorCopy {
+                            from,
+                            from_offset: 0,
+                            to,
+                            to_offset: iteration as u64 * size,
+                            size,
+                        });
+                    }
+                    model.copy(&copies);
+                    copies = vec![];
+
+                    if cond_changes {
+                        cond = model.read_scalar(&body.outputs[0]).await? != 0;
+                    }
+                    iteration += 1;
+                }
+
+                if let Some(length) = scan_length {
+                    if length != iteration as u64 {
+                        return Err(GpuError::IterationCountMismatch {
+                            node: proto.get_name().to_string(),
+                            expected: length,
+                            actual: iteration as u64,
+                        });
+                    }
+                }
+            }
+            "Scan" => {
+                // Only axis 0 is supported (see `GpuModel::control_flow_step`)
+                let input_tensors: Vec<&GpuTensor> = inputs.iter().flatten().collect();
+                let output_shapes: Vec<&Shape> =
+                    output_tensors.iter().map(|tensor| &tensor.shape).collect();
+                let attributes = scan_attributes(
+                    proto,
+                    &input_tensors
+                        .iter()
+                        .map(|tensor| &tensor.shape)
+                        .collect::<Vec<_>>(),
+                    &output_shapes,
+                    model.onnx_opset_version,
+                )
+                .map_err(|error| GpuError::CompileError {
+                    node: proto.get_name().to_string(),
+                    error,
+                })?;
+                let body = &subgraphs[0];
+                let states = attributes.states;
+                let (state_outputs, scan_outputs) = output_tensors.split_at(states);
+                let length = input_tensors[states].shape.dim(0);
+                for (index, input) in input_tensors[states..].iter().enumerate() {
+                    if input.shape.dim(0) != length {
+                        return Err(GpuError::CompileError {
+                            node: proto.get_name().to_string(),
+                            error: CompileError::InvalidInputShape {
+                                input_index: states + index,
+                                input_shape: input.shape.clone(),
+                            },
+                        });
+                    }
+                }
+                if let Some(output) = scan_outputs.iter().find(|o| o.shape.dim(0) != length) {
+                    return Err(GpuError::IterationCountMismatch {
+                        node: proto.get_name().to_string(),
+                        expected: output.shape.dim(0),
+                        actual: length,
+                    });
+                }
+
+                model.copy(
+                    &input_tensors[..states]
+                        .iter()
+                        .zip(state_outputs)
+                        .map(|(from, to)| TensorCopy::whole(from, to))
+                        .collect::<Vec<_>>(),
+                );
+                let mut copies = body.capture_copies();
+                for iteration in 0..length {
+                    let position = |reverse: bool| {
+                        if reverse {
+                            length - 1 - iteration
+                        } else {
+                            iteration
+                        }
+                    };
+                    copies.extend(
+                        state_outputs
+                            .iter()
+                            .zip(&body.inputs[..states])
+                            .filter_map(|(from, to)| Some(TensorCopy::whole(from, to.as_ref()?))),
+                    );
+                    for ((from, to), reverse) in input_tensors[states..]
+                        .iter()
+                        .zip(&body.inputs[states..])
+                        .zip(&attributes.input_reverse)
+                    {
+                        if let Some(to) = to {
+                            let size = slice_bytes(&to.shape);
+                            copies.push(TensorCopy {
+                                from,
+                                from_offset: position(*reverse) * size,
+                                to,
+                                to_offset: 0,
+                                size,
+                            });
+                        }
+                    }
+                    body.run(model, copies).await?;
+
+                    copies = body.outputs[..states]
+                        .iter()
+                        .zip(state_outputs)
+                        .map(|(from, to)| TensorCopy::whole(from, to))
+                        .collect();
+                    for ((from, to), reverse) in body.outputs[states..]
+                        .iter()
+                        .zip(scan_outputs)
+                        .zip(&attributes.output_reverse)
+                    {
+                        let size = slice_bytes(&from.shape);
+                        copies.push(TensorCopy {
+                            from,
+                            from_offset: 0,
+                            to,
+                            to_offset: position(*reverse) * size,
+                            size,
+                        });
+                    }
+                    model.copy(&copies);
+                    copies = vec![];
+                }
+            }
+            _ => unreachable!("op is not a control flow op"),
         }
+        Ok(())
     }
 }
 
+impl GpuSubgraph {
+    /// Copies of the values of enclosing graphs to the buffers of this subgraph
+    fn capture_copies(&self) -> Vec<TensorCopy<'_>> {
+        self.captures
+            .iter()
+            .map(|(from, to)| TensorCopy::whole(from, to))
+            .collect()
+    }
+
+    /// Performs the specified copies (to set the inputs of the subgraph) and then the steps of the subgraph
+    async fn run(&self, model: &GpuModel, copies: Vec<TensorCopy<'_>>) -> Result<(), GpuError> {
+        model.copy(&copies);
+        // The input steps of the lane find their own buffers as inputs, and leave them as they are
+        model
+            .submit(
+                &self.lane,
+                &InferenceInputs::Gpu {
+                    inputs: &self.input_tensors,
+                    bound: vec![],
+                },
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+impl<'a> TensorCopy<'a> {
+    /// A copy of all data of a tensor to another tensor of the same shape
+    fn whole(from: &'a GpuTensor, to: &'a GpuTensor) -> TensorCopy<'a> {
+        TensorCopy {
+            from,
+            from_offset: 0,
+            to,
+            to_offset: 0,
+            size: from.buffer.size().min(to.buffer.size()) / wgpu::COPY_BUFFER_ALIGNMENT
+                * wgpu::COPY_BUFFER_ALIGNMENT,
+        }
+    }
+}
+
+/// Create the 'bind groups' (groups of bound buffers) that bind the specified buffers to the bindings of a pipeline, in order
+fn create_bind_groups(
+    device: &wgpu::Device,
+    pipeline: &wgpu::ComputePipeline,
+    label: Option<&str>,
+    buffers: &[&Buffer],
+) -> Vec<wgpu::BindGroup> {
+    // Bindings are numbered 0...3 (MAX_BINDINGS_PER_GROUP-1) in binding groups (starting at group 0)
+    let entries: Vec<wgpu::BindGroupEntry> = buffers
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| wgpu::BindGroupEntry {
+            binding: (index % MAX_BINDINGS_PER_GROUP) as u32,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect();
+    entries
+        .chunks(MAX_BINDINGS_PER_GROUP)
+        .enumerate()
+        .map(|(group_index, group_entries)| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label,
+                layout: &pipeline.get_bind_group_layout(group_index as u32),
+                entries: group_entries,
+            })
+        })
+        .collect()
+}
+
+/// The number of bytes a tensor of the specified shape takes up in GPU memory (without padding)
+fn slice_bytes(shape: &Shape) -> u64 {
+    shape.element_count() * shape.data_type.gpu_type().stride() as u64
+}
+
 impl GpuTensor {
+    /// Create a tensor for data in the specified buffer. For use as inference input, the buffer must have the
+    /// `COPY_SRC` usage, unless it has the `STORAGE` usage and the same size as the input buffer of the model (in which
+    /// case it is bound directly, see [`crate::Session::run_gpu`]).
+    pub fn new(buffer: Arc<Buffer>, shape: Shape) -> GpuTensor {
+        GpuTensor { buffer, shape }
+    }
+
+    /// The buffer that holds the data of this tensor
+    pub fn buffer(&self) -> &Arc<Buffer> {
+        &self.buffer
+    }
+
+    /// The shape of this tensor
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    /// Write the specified data to the buffer of this tensor, converting it to the type the data is stored as on the GPU
+    fn write(&self, queue: &wgpu::Queue, data: &InputTensor) -> Result<(), GpuError> {
+        match data {
+            InputTensor::F32(float_input) => {
+                queue.write_buffer(
+                    &self.buffer,
+                    0,
+                    bytemuck::cast_slice(&resize(float_input.to_vec())),
+                );
+            }
+            InputTensor::F16(float_input) => {
+                if self.shape.data_type == ScalarType::F16 {
+                    // Buffer writes must be a multiple of four bytes in size
+                    let mut float_input = resize(float_input.to_vec());
+                    if float_input.len() % 2 != 0 {
+                        float_input.push(f16::ZERO);
+                    }
+                    queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&float_input));
+                } else {
+                    let float_input: Vec<f32> = float_input.iter().map(|x| x.to_f32()).collect();
+                    queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&resize(float_input)));
+                }
+            }
+            InputTensor::I32(int_input) => {
+                queue.write_buffer(
+                    &self.buffer,
+                    0,
+                    bytemuck::cast_slice(&resize(int_input.to_vec())),
+                );
+            }
+            InputTensor::I64(int_input) => {
+                let int32_input = int_input
+                    .iter()
+                    .map(|i| i32::from_i64(*i).ok_or(GpuError::OutOfBoundsError))
+                    .collect::<Result<_, _>>()?;
+                queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&resize(int32_input)));
+            }
+            InputTensor::U8(int_input) => {
+                let int32_input = int_input
+                    .iter()
+                    .map(|i| i32::from_u8(*i).ok_or(GpuError::OutOfBoundsError))
+                    .collect::<Result<_, _>>()?;
+                queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&resize(int32_input)));
+            }
+            InputTensor::F64(float_input) => {
+                let float_input = float_input.iter().map(|x| *x as f32).collect();
+                queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&resize(float_input)));
+            }
+            InputTensor::I8(_)
+            | InputTensor::U16(_)
+            | InputTensor::I16(_)
+            | InputTensor::Bool(_) => {
+                // Integer types smaller than 32 bits (and booleans) are stored as int32
+                let int32_input = match data {
+                    InputTensor::I8(ints) => ints.iter().map(|x| *x as i32).collect(),
+                    InputTensor::U16(ints) => ints.iter().map(|x| *x as i32).collect(),
+                    InputTensor::I16(ints) => ints.iter().map(|x| *x as i32).collect(),
+                    InputTensor::Bool(bools) => bools.iter().map(|x| *x as i32).collect(),
+                    _ => unreachable!(),
+                };
+                queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&resize(int32_input)));
+            }
+        }
+        Ok(())
+    }
+
     /// Read the tensor from GPU memory to main memory (as Vec<f32>)
     async fn read_to_vec(
         &self,
@@ -1072,18 +2138,38 @@ impl GpuTensor {
             ScalarType::F32 => {
                 OutputTensor::F32(bytemuck::cast_slice(output_data)[..output_buffer_size].to_vec())
             }
+            ScalarType::F16 => {
+                OutputTensor::F16(bytemuck::cast_slice(output_data)[..output_buffer_size].to_vec())
+            }
             ScalarType::I32 => {
                 OutputTensor::I32(bytemuck::cast_slice(output_data)[..output_buffer_size].to_vec())
             }
-            ScalarType::U8 => {
-                OutputTensor::U8(bytemuck::cast_slice(output_data)[..output_buffer_size].to_vec())
-            }
             ScalarType::I64 => {
                 log::warn!("reading int64 output as int32 because internally int64 scalars are not supported");
                 let result_ints: Vec<i32> =
                     bytemuck::cast_slice(output_data)[..output_buffer_size].to_vec();
                 OutputTensor::I64(result_ints.iter().map(|i| *i as i64).collect())
             }
+            ScalarType::F64 => {
+                let result_floats: &[f32] =
+                    &bytemuck::cast_slice(output_data)[..output_buffer_size];
+                OutputTensor::F64(result_floats.iter().map(|x| *x as f64).collect())
+            }
+            ScalarType::U8
+            | ScalarType::I8
+            | ScalarType::U16
+            | ScalarType::I16
+            | ScalarType::Bool => {
+                // These types are stored as int32 on the GPU
+                let ints: &[i32] = &bytemuck::cast_slice(output_data)[..output_buffer_size];
+                match shape.data_type {
+                    ScalarType::U8 => OutputTensor::U8(ints.iter().map(|i| *i as u8).collect()),
+                    ScalarType::I8 => OutputTensor::I8(ints.iter().map(|i| *i as i8).collect()),
+                    ScalarType::U16 => OutputTensor::U16(ints.iter().map(|i| *i as u16).collect()),
+                    ScalarType::I16 => OutputTensor::I16(ints.iter().map(|i| *i as i16).collect()),
+                    _ => OutputTensor::Bool(ints.iter().map(|i| *i != 0).collect()),
+                }
+            }
         }
     }
 }
@@ -1,17 +1,40 @@
 //! DAG representation of ONNX ops allowing for transformations and optimizations before compilation
-use crate::onnx::{ModelProto, NodeProto, TensorProto, ValueInfoProto};
-use crate::utils::{DataTypeError, Shape};
+use crate::onnx::{GraphProto, ModelProto, NodeProto, TensorProto, ValueInfoProto};
+use crate::utils::{DataTypeError, NodeAttributes, ScalarType, Shape};
 use std::borrow::Cow;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::ptr;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 use thiserror::Error;
 
 #[derive(Clone)]
 pub struct OperatorDefinition<'model> {
     pub(crate) proto: Cow<'model, NodeProto>,
     pub(crate) output_shapes: Vec<Shape>,
+
+    /// The graphs in attributes of the op (the branches of If, and the body of Loop and Scan)
+    pub(crate) subgraphs: Vec<Subgraph<'model>>,
+}
+
+/// A graph that is an attribute of an op
+#[derive(Clone)]
+pub struct Subgraph<'model> {
+    /// The name of the attribute holding the graph
+    pub attribute: String,
+
+    /// The outputs node of the graph
+    pub root: Arc<Node<'model>>,
+
+    /// The declared inputs of the graph (e.g. the iteration number, condition and loop-carried values of a Loop body)
+    pub inputs: Vec<&'model ValueInfoProto>,
+
+    /// The names of the values of enclosing graphs that the graph uses. In the graph these are inputs; the op node takes
+    /// them as inputs following its own inputs (see [`OperatorDefinition::captures`]).
+    pub captures: Vec<String>,
 }
 
 impl<'model> OperatorDefinition<'model> {
@@ -21,6 +44,12 @@ impl<'model> OperatorDefinition<'model> {
     ) -> Result<OperatorDefinition<'model>, IrError> {
         let mut output_shapes: Vec<Shape> = Vec::with_capacity(node.get_output().len());
         for output_name in node.get_output() {
+            // Optional outputs that are left out are given an empty placeholder shape
+            if output_name.is_empty() {
+                output_shapes.push(Shape::from(ScalarType::F32, &[0]));
+                continue;
+            }
+
             if !value_shapes.contains_key(output_name.as_str()) {
                 return Err(IrError::OutputNodeNotFound(output_name.to_string()));
             }
@@ -30,8 +59,26 @@ impl<'model> OperatorDefinition<'model> {
         Ok(OperatorDefinition {
             proto: node,
             output_shapes,
+            subgraphs: vec![],
         })
     }
+
+    /// The names of the values of enclosing graphs used by the subgraphs of this op, in the order in which they follow
+    /// the inputs of the op in the IR node
+    pub(crate) fn captures(&self) -> Vec<&str> {
+        let mut captures: Vec<&str> = vec![];
+        for capture in self.subgraphs.iter().flat_map(|s| &s.captures) {
+            if !captures.contains(&capture.as_str()) {
+                captures.push(capture);
+            }
+        }
+        captures
+    }
+
+    /// Returns the subgraph held by the attribute with the specified name
+    pub(crate) fn subgraph(&self, attribute: &str) -> Option<&Subgraph<'model>> {
+        self.subgraphs.iter().find(|s| s.attribute == attribute)
+    }
 }
 
 #[derive(Clone)]
@@ -51,6 +98,21 @@ pub struct Input<'model> {
     pub output_index: usize,
 }
 
+impl<'model> Input<'model> {
+    /// The name of the value (the name of the output of an op, initializer or graph input)
+    pub fn name(&self) -> &str {
+        match &self.source_node.definition {
+            NodeDefinition::Operator(op_def) => {
+                op_def.proto.get_output()[self.output_index].as_str()
+            }
+            NodeDefinition::Tensor(tensor) => tensor.get_name(),
+            NodeDefinition::Input(info) => info.get_name(),
+            NodeDefinition::Outputs { names } => names[self.output_index].as_str(),
+            NodeDefinition::Missing => "",
+        }
+    }
+}
+
 pub struct Node<'model> {
     pub definition: NodeDefinition<'model>,
     pub inputs: Vec<Input<'model>>,
@@ -69,6 +131,83 @@ pub enum IrError {
 
     #[error("issue with data types: {0}")]
     Type(#[from] DataTypeError),
+
+    #[error("subgraph of node '{node}' is invalid: {reason}")]
+    InvalidSubgraph { node: String, reason: String },
+
+    #[error("the output of node '{source_node}' has a data-dependent number of rows, which node '{target_node}' does not keep")]
+    DataDependentShapeUnsupported {
+        source_node: String,
+        target_node: String,
+    },
+}
+
+/// Returns whether the output of ops of the specified type has a data-dependent shape. The first dimension (the number of
+/// rows) of such an output in the graph is an upper bound: the op fills the rows beyond the actual number with -1, and
+/// reports the actual number of rows when it is executed (see [`RowCounts`]).
+pub(crate) fn op_has_data_dependent_output(op_type: &str) -> bool {
+    op_type == "NonMaxSuppression"
+}
+
+/// Returns whether the op of the specified type will forward its (first) input unchanged
+pub(crate) fn op_forwards_input(op_type: &str) -> bool {
+    matches!(
+        op_type,
+        "Reshape" | "Identity" | "Flatten" | "Squeeze" | "Unsqueeze" | "Dropout"
+    )
+}
+
+/// The actual number of rows of the outputs of ops with a data-dependent output shape in an inference run, by the name
+/// of the output
+pub(crate) type RowCounts = HashMap<String, usize>;
+
+/// An output of the graph whose rows are those of a value with a data-dependent shape. Only the actual rows are returned
+/// after inference.
+#[derive(Clone, Debug)]
+pub(crate) struct DataDependentOutput {
+    /// The name of the output of the op with a data-dependent output shape that determines the number of rows
+    pub source: String,
+
+    /// The number of rows of the output in the graph (an upper bound for the actual number of rows)
+    pub max_rows: usize,
+}
+
+/// Returns whether the (first) output of an op keeps the rows of the input at the specified index, which has a
+/// data-dependent number of rows. Such an op can be performed on the padded input, as its output has the same number of
+/// rows, each of which only depends on the corresponding row of the input.
+fn op_keeps_rows(op_def: &OperatorDefinition, input_index: usize, input_shape: &Shape) -> bool {
+    let output_shape = &op_def.output_shapes[0];
+    if input_shape.rank() == 0
+        || output_shape.rank() == 0
+        || output_shape.dim(0) != input_shape.dim(0)
+    {
+        return false;
+    }
+
+    let proto = &op_def.proto;
+    let op_type = proto.get_op_type();
+    match op_type {
+        _ if op_forwards_input(op_type) => input_index == 0,
+        "Abs" | "Ceil" | "Floor" | "Neg" | "Round" | "Sign" | "Sqrt" | "Exp" | "Log" | "Relu"
+        | "Sigmoid" | "Not" | "Cast" => true,
+        "Add" | "Sub" | "Mul" | "Div" | "Min" | "Max" | "Equal" | "Greater" | "GreaterOrEqual"
+        | "Less" | "LessOrEqual" | "And" | "Or" => output_shape.rank() == input_shape.rank(),
+        // Rows of the indices select slices along the first axis, or rows of the data are indexed along another axis
+        "Gather" => {
+            let axis = proto.get_attribute_value("axis", Some(0)).unwrap_or(0);
+            (input_index == 1 && axis == 0) || (input_index == 0 && axis != 0)
+        }
+        // The axes must be set as attribute (by the optimizer) and may not include the first axis
+        "Slice" => {
+            let rank = input_shape.rank() as i64;
+            input_index == 0
+                && proto
+                    .get_attribute_value::<Vec<i64>>("axes", None)
+                    .map(|axes| axes.iter().all(|axis| axis.rem_euclid(rank) != 0))
+                    .unwrap_or(false)
+        }
+        _ => false,
+    }
 }
 
 impl<'m> NodeDefinition<'m> {
@@ -76,7 +215,7 @@ impl<'m> NodeDefinition<'m> {
         match self {
             // Nodes are identified by their first output's name, because node names are optional (and "only to be used
             // for diagnostic purposes" according to the ONNX IR specification) whereas output names are required and should be unique.
-            NodeDefinition::Operator(op_def) => Cow::from(&op_def.proto.get_output()[0]),
+            NodeDefinition::Operator(op_def) => Cow::from(op_def.proto.unique_name()),
             NodeDefinition::Tensor(t) => Cow::from(t.get_name()),
             NodeDefinition::Input(i) => Cow::from(i.get_name()),
             NodeDefinition::Outputs { .. } => Cow::from(" "),
@@ -88,8 +227,14 @@ impl<'m> NodeDefinition<'m> {
 impl NodeProto {
     // Nodes are identified by their first output's name, because node names are optional (and "only to be used
     // for diagnostic purposes" according to the ONNX IR specification) whereas output names are required and should be unique.
+    // Leading optional outputs may be left out (e.g. the Y output of an LSTM), in which case the first output that is
+    // present is used.
     fn unique_name(&self) -> String {
-        self.get_output()[0].clone()
+        self.get_output()
+            .iter()
+            .find(|output| !output.is_empty())
+            .unwrap_or(&self.get_output()[0])
+            .clone()
     }
 }
 
@@ -108,10 +253,18 @@ impl<'model> Node<'model> {
         )
     }
 
+    /// Returns whether the outputs of this node can be calculated in advance. Ops whose output has a data-dependent shape
+    /// are not considered constant, as that would lose the actual size of the output. Control flow ops are always
+    /// executed when the model runs.
     pub fn is_constant(&self) -> bool {
-        !self.is_dynamic()
-            || (matches!(self.definition, NodeDefinition::Operator(..))
-                && self.inputs.iter().all(|i| i.source_node.is_constant()))
+        match &self.definition {
+            NodeDefinition::Operator(op_def) => {
+                !op_has_data_dependent_output(op_def.proto.get_op_type())
+                    && op_def.subgraphs.is_empty()
+                    && self.inputs.iter().all(|i| i.source_node.is_constant())
+            }
+            _ => !self.is_dynamic(),
+        }
     }
 
     pub fn definition(&self) -> &NodeDefinition<'model> {
@@ -122,6 +275,7 @@ impl<'model> Node<'model> {
     pub fn from_node<'a>(
         node: Cow<'model, NodeProto>,
         value_shapes: &HashMap<&'model str, Shape>,
+        value_infos: &HashMap<&'model str, &'model ValueInfoProto>,
         node_definitions_by_output: &'a HashMap<String, NodeDefinition<'model>>,
         nodes_by_unique_name: &mut HashMap<String, Arc<Node<'model>>>,
     ) -> Result<Arc<Node<'model>>, IrError> {
@@ -132,84 +286,283 @@ impl<'model> Node<'model> {
             return Ok(n.clone());
         }
 
+        // Translate graphs in attributes of the node (e.g. the body of a Loop)
+        let mut op_def = OperatorDefinition::from(node.clone(), value_shapes)?;
+        if let Cow::Borrowed(proto) = node {
+            op_def.subgraphs = Node::subgraphs(proto, value_infos, node_definitions_by_output)?;
+            if !op_def.subgraphs.is_empty() {
+                Node::validate_subgraphs(&op_def)?;
+            }
+        }
+
+        // Values of this graph used by the subgraphs are inputs of the node as well
         let inputs: Result<Vec<Input<'model>>, IrError> = node
             .get_input()
             .iter()
-            .map(|input_name: &String| {
-                let my_input_name = input_name.clone();
-                let source_node_definition = node_definitions_by_output
-                    .get(&my_input_name)
-                    .unwrap_or(&MISSING_OPTIONAL_INPUT);
-
-                Ok(match source_node_definition {
-                    // The source is another op - continue translating that node
-                    NodeDefinition::Operator(source_node_proto) => Input {
-                        source_node: Node::from_node(
-                            source_node_proto.proto.clone(),
-                            value_shapes,
-                            node_definitions_by_output,
-                            nodes_by_unique_name,
-                        )?,
-                        output_index: source_node_proto
-                            .proto
-                            .get_output()
-                            .iter()
-                            .position(|s| s == input_name)
-                            .ok_or_else(|| IrError::OutputNodeNotFound(input_name.to_string()))?,
-                    },
-                    _ => {
-                        // The source is an initializer or model onput
-                        let source_name = source_node_definition.get_name().to_string();
-
-                        Input {
-                            output_index: 0,
-                            // Did we already translate this node?
-                            source_node: match nodes_by_unique_name.get(&source_name) {
-                                Some(node) => node.clone(),
-                                None => {
-                                    let node = Arc::new(Node::new(source_node_definition.clone()));
-                                    nodes_by_unique_name.insert(source_name, node.clone());
-                                    node
-                                }
-                            },
-                        }
-                    }
-                })
+            .map(|input_name| input_name.as_str())
+            .chain(op_def.captures())
+            .map(|input_name| {
+                Node::input_from(
+                    input_name,
+                    value_shapes,
+                    value_infos,
+                    node_definitions_by_output,
+                    nodes_by_unique_name,
+                )
             })
             .collect();
 
         let translated = Arc::new(Node {
-            definition: NodeDefinition::Operator(Box::new(OperatorDefinition::from(
-                node.clone(),
-                value_shapes,
-            )?)),
             inputs: inputs?,
+            definition: NodeDefinition::Operator(Box::new(op_def)),
         });
         nodes_by_unique_name.insert(node.unique_name(), translated.clone());
         Ok(translated)
     }
 
+    /// Returns the input for the value with the specified name, translating the node producing it if necessary.
+    fn input_from(
+        input_name: &str,
+        value_shapes: &HashMap<&'model str, Shape>,
+        value_infos: &HashMap<&'model str, &'model ValueInfoProto>,
+        node_definitions_by_output: &HashMap<String, NodeDefinition<'model>>,
+        nodes_by_unique_name: &mut HashMap<String, Arc<Node<'model>>>,
+    ) -> Result<Input<'model>, IrError> {
+        let source_node_definition = node_definitions_by_output
+            .get(input_name)
+            .unwrap_or(&MISSING_OPTIONAL_INPUT);
+
+        Ok(match source_node_definition {
+            // The source is another op - continue translating that node
+            NodeDefinition::Operator(source_node_proto) => Input {
+                source_node: Node::from_node(
+                    source_node_proto.proto.clone(),
+                    value_shapes,
+                    value_infos,
+                    node_definitions_by_output,
+                    nodes_by_unique_name,
+                )?,
+                output_index: source_node_proto
+                    .proto
+                    .get_output()
+                    .iter()
+                    .position(|s| s == input_name)
+                    .ok_or_else(|| IrError::OutputNodeNotFound(input_name.to_string()))?,
+            },
+            _ => {
+                // The source is an initializer or model onput
+                let source_name = source_node_definition.get_name().to_string();
+
+                Input {
+                    output_index: 0,
+                    // Did we already translate this node?
+                    source_node: match nodes_by_unique_name.get(&source_name) {
+                        Some(node) => node.clone(),
+                        None => {
+                            let node = Arc::new(Node::new(source_node_definition.clone()));
+                            nodes_by_unique_name.insert(source_name, node.clone());
+                            node
+                        }
+                    },
+                }
+            }
+        })
+    }
+
+    /// Translate the graphs in the attributes of a node. Initializers of enclosing graphs are initializers in a subgraph
+    /// as well; other values of enclosing graphs are inputs of the subgraph (see [`Subgraph::captures`]).
+    fn subgraphs(
+        node: &'model NodeProto,
+        value_infos: &HashMap<&'model str, &'model ValueInfoProto>,
+        node_definitions_by_output: &HashMap<String, NodeDefinition<'model>>,
+    ) -> Result<Vec<Subgraph<'model>>, IrError> {
+        if !node.get_attribute().iter().any(|a| a.has_g()) {
+            return Ok(vec![]);
+        }
+
+        let outer_scope: HashMap<String, NodeDefinition<'model>> = node_definitions_by_output
+            .iter()
+            .filter_map(|(name, definition)| match definition {
+                NodeDefinition::Operator(_) => value_infos
+                    .get(name.as_str())
+                    .map(|info| (name.clone(), NodeDefinition::Input(info))),
+                _ => Some((name.clone(), definition.clone())),
+            })
+            .collect();
+
+        node.get_attribute()
+            .iter()
+            .filter(|a| a.has_g())
+            .map(|attribute| {
+                let graph = attribute.get_g();
+                let root = Node::from_graph(graph, None, &outer_scope)?;
+                let mut nodes = vec![];
+                root.topological_sort(&mut HashSet::new(), &mut nodes);
+                let captures = nodes
+                    .iter()
+                    .filter_map(|node| match node.definition {
+                        NodeDefinition::Input(info)
+                            if !graph
+                                .get_input()
+                                .iter()
+                                .any(|input| input.get_name() == info.get_name()) =>
+                        {
+                            Some(info.get_name().to_string())
+                        }
+                        _ => None,
+                    })
+                    .collect();
+
+                Ok(Subgraph {
+                    attribute: attribute.get_name().to_string(),
+                    root,
+                    inputs: graph.get_input().iter().collect(),
+                    captures,
+                })
+            })
+            .collect()
+    }
+
+    /// Check that the subgraphs of a control flow op have the inputs and outputs the op requires. Values that are passed
+    /// between the op and its subgraphs must have the same shape in both.
+    fn validate_subgraphs(op_def: &OperatorDefinition<'model>) -> Result<(), IrError> {
+        let proto = &op_def.proto;
+        let invalid = |reason: String| IrError::InvalidSubgraph {
+            node: proto.get_name().to_string(),
+            reason,
+        };
+        let input_count = proto.get_input().len();
+        let output_count = op_def.output_shapes.len();
+
+        // The required subgraphs with their number of inputs and outputs, and the number of values at the start of the
+        // inputs and outputs of the subgraph that correspond to the first outputs of the op (in order)
+        let (subgraphs, input_offset, output_offset, shared) = match proto.get_op_type() {
+            "If" => (
+                vec![
+                    ("then_branch", 0, output_count),
+                    ("else_branch", 0, output_count),
+                ],
+                0,
+                0,
+                output_count,
+            ),
+            "Loop" => {
+                // The trip count and condition inputs are optional, but have to be present (with empty names)
+                if input_count < 2 {
+                    return Err(invalid(format!(
+                        "expected at least 2 inputs, got {input_count}"
+                    )));
+                }
+                let carried = input_count - 2;
+                (vec![("body", input_count, output_count + 1)], 2, 1, carried)
+            }
+            "Scan" => {
+                let scan_inputs = proto
+                    .get_attribute()
+                    .iter()
+                    .find(|a| a.get_name() == "num_scan_inputs")
+                    .map(|a| a.get_i().max(0) as usize)
+                    .unwrap_or(0);
+                let states = input_count.saturating_sub(scan_inputs);
+                (vec![("body", input_count, output_count)], 0, 0, states)
+            }
+            _ => return Ok(()),
+        };
+        if shared > output_count {
+            return Err(invalid(format!(
+                "the op has {output_count} outputs, expected at least {shared}"
+            )));
+        }
+
+        for (attribute, inputs, outputs) in subgraphs {
+            let subgraph = op_def
+                .subgraph(attribute)
+                .ok_or_else(|| invalid(format!("attribute '{attribute}' is missing")))?;
+            let NodeDefinition::Outputs { names } = &subgraph.root.definition else {
+                unreachable!("subgraph root must be an outputs node");
+            };
+            if subgraph.inputs.len() != inputs || names.len() != outputs {
+                return Err(invalid(format!(
+                    "'{attribute}' has {} inputs and {} outputs, expected {inputs} and {outputs}",
+                    subgraph.inputs.len(),
+                    names.len(),
+                )));
+            }
+
+            for (index, shape) in op_def.output_shapes.iter().enumerate().take(shared) {
+                // The branches of If do not have inputs
+                let input_shape = subgraph
+                    .inputs
+                    .get(input_offset + index)
+                    .and_then(|input| input.get_shape().ok());
+                let output_shape = value_shape(&subgraph.root.inputs[output_offset + index])
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "output {} of '{attribute}' has no shape",
+                            names[output_offset + index]
+                        ))
+                    })?;
+                for subgraph_shape in input_shape.iter().chain([&output_shape]) {
+                    if subgraph_shape != shape {
+                        return Err(invalid(format!(
+                            "value {index} has shape {subgraph_shape} in '{attribute}', but shape {shape} in the op"
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Construct an intermediate representation graph for calculating the output with the specified name.
     pub fn from_model(
         model: &'model ModelProto,
         outputs: Option<&[String]>,
+    ) -> Result<Arc<Node<'model>>, IrError> {
+        Node::from_graph(model.get_graph(), outputs, &HashMap::new())
+    }
+
+    /// Construct an intermediate representation graph for calculating the specified outputs of a graph (or all of its
+    /// outputs). Values that are not defined in the graph itself are taken from `outer_scope` (for subgraphs).
+    fn from_graph(
+        graph: &'model GraphProto,
+        outputs: Option<&[String]>,
+        outer_scope: &HashMap<String, NodeDefinition<'model>>,
     ) -> Result<Arc<Node<'model>>, IrError> {
         // Collect value shapes
         let mut value_shapes: HashMap<&'model str, Shape> = HashMap::new();
-        for vi in model.get_graph().get_value_info() {
+        for vi in graph.get_value_info() {
             value_shapes.insert(vi.get_name(), vi.get_shape()?);
         }
 
-        for vi in model.get_graph().get_output() {
+        // The declared shape of an output may have parametrized dimensions (e.g. when its shape depends on the data),
+        // in which case the shape from value info (e.g. as determined by shape inference) is used
+        for vi in graph.get_output() {
             let output_name = vi.get_name();
             if !output_name.is_empty() {
-                value_shapes.insert(output_name, vi.get_shape()?);
+                match vi.get_shape() {
+                    Ok(shape) => {
+                        value_shapes.insert(output_name, shape);
+                    }
+                    Err(DataTypeError::ParametrizedDimensionUnsupported(_))
+                        if value_shapes.contains_key(output_name) => {}
+                    Err(error) => return Err(error.into()),
+                }
             }
         }
 
+        // Value info is used for values that subgraphs take from this graph
+        let value_infos: HashMap<&'model str, &'model ValueInfoProto> = graph
+            .get_output()
+            .iter()
+            .chain(graph.get_input())
+            .chain(graph.get_value_info())
+            .map(|vi| (vi.get_name(), vi))
+            .collect();
+
         // Sort nodes by output nodes
         let mut node_definitions_by_output = HashMap::<String, NodeDefinition<'model>>::new();
-        for node in model.get_graph().get_node().iter() {
+        for node in graph.get_node().iter() {
             let node_def = NodeDefinition::Operator(Box::new(OperatorDefinition::from(
                 Cow::Borrowed(node),
                 &value_shapes,
@@ -222,7 +575,7 @@ impl<'model> Node<'model> {
         }
 
         // Collect intializer info
-        for initializer in model.get_graph().get_initializer().iter() {
+        for initializer in graph.get_initializer().iter() {
             node_definitions_by_output.insert(
                 initializer.get_name().to_string(),
                 NodeDefinition::Tensor(Box::new(Cow::Borrowed(initializer))),
@@ -231,8 +584,7 @@ impl<'model> Node<'model> {
 
         let output_names: Vec<String> = match outputs {
             Some(outputs) => outputs.to_vec(),
-            None => model
-                .get_graph()
+            None => graph
                 .get_output()
                 .iter()
                 .map(|x| x.get_name().to_string())
@@ -240,7 +592,7 @@ impl<'model> Node<'model> {
         };
 
         // Collect input name
-        for input in model.get_graph().get_input().iter() {
+        for input in graph.get_input().iter() {
             if !node_definitions_by_output.contains_key(input.get_name()) {
                 node_definitions_by_output
                     .insert(input.get_name().to_string(), NodeDefinition::Input(input));
@@ -252,35 +604,29 @@ impl<'model> Node<'model> {
             }
         }
 
+        // Values of enclosing graphs, unless this graph defines a value with the same name
+        for (name, definition) in outer_scope {
+            node_definitions_by_output
+                .entry(name.clone())
+                .or_insert_with(|| definition.clone());
+        }
+
         let mut nodes_by_name = HashMap::new();
 
         let output_nodes: Result<Vec<Input<'model>>, IrError> = output_names
             .iter()
             .map(|output_name| {
-                let output_node = model
-                    .get_graph()
-                    .get_node()
-                    .iter()
-                    .find(|x| -> bool { x.get_output().contains(output_name) })
-                    .ok_or_else(|| IrError::OutputNodeNotFound(output_name.clone()))?;
+                if !node_definitions_by_output.contains_key(output_name) {
+                    return Err(IrError::OutputNodeNotFound(output_name.clone()));
+                }
 
-                let source_node = Node::<'model>::from_node(
-                    Cow::Borrowed(output_node),
+                Node::input_from(
+                    output_name,
                     &value_shapes,
+                    &value_infos,
                     &node_definitions_by_output,
                     &mut nodes_by_name,
-                )?;
-
-                let output_index = output_node
-                    .get_output()
-                    .iter()
-                    .position(|s| s == output_name)
-                    .ok_or_else(|| IrError::OutputNodeNotFound(output_name.clone()))?;
-
-                Ok(Input {
-                    source_node,
-                    output_index,
-                })
+                )
             })
             .collect();
 
@@ -293,6 +639,19 @@ impl<'model> Node<'model> {
     }
 }
 
+/// The shape of the value that is used as input, if known
+pub(crate) fn value_shape(input: &Input) -> Option<Shape> {
+    match &input.source_node.definition {
+        NodeDefinition::Operator(op_def) => op_def.output_shapes.get(input.output_index).cloned(),
+        NodeDefinition::Tensor(tensor) => Some(Shape::from(
+            ScalarType::from_i32(tensor.get_data_type()).ok()?,
+            tensor.get_dims(),
+        )),
+        NodeDefinition::Input(info) => info.get_shape().ok(),
+        NodeDefinition::Outputs { .. } | NodeDefinition::Missing => None,
+    }
+}
+
 impl<'model> Debug for NodeDefinition<'model> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -343,4 +702,87 @@ impl<'model> Node<'model> {
     pub fn identifier(self: &Arc<Self>) -> NodeIdentifier<'model> {
         NodeIdentifier(self.clone())
     }
+
+    /// Traverse the graph and sort nodes in the order of execution (topological sort)
+    pub(crate) fn topological_sort(
+        self: &Arc<Self>,
+        nodes_seen: &mut HashSet<NodeIdentifier<'model>>,
+        sorted_nodes: &mut Vec<Arc<Node<'model>>>,
+    ) {
+        let identifier = self.identifier();
+        if !nodes_seen.contains(&identifier) {
+            nodes_seen.insert(identifier);
+            for node_input in &self.inputs {
+                node_input
+                    .source_node
+                    .topological_sort(nodes_seen, sorted_nodes);
+            }
+            sorted_nodes.push(self.clone());
+        }
+    }
+
+    /// Returns the outputs of the graph (of which this node is the outputs node) whose rows are those of a value with a
+    /// data-dependent shape (see [`op_has_data_dependent_output`]), by name. Such values can only be used by ops that
+    /// keep their rows.
+    pub(crate) fn data_dependent_outputs(
+        self: &Arc<Self>,
+    ) -> Result<HashMap<String, DataDependentOutput>, IrError> {
+        let mut nodes = vec![];
+        self.topological_sort(&mut HashSet::new(), &mut nodes);
+
+        // The values whose rows are those of a value with a data-dependent shape, with the name of that value
+        let mut row_sources: HashMap<(NodeIdentifier<'model>, usize), &str> = HashMap::new();
+        let mut data_dependent_outputs = HashMap::new();
+        for node in &nodes {
+            let row_source = |input: &Input<'model>| {
+                row_sources
+                    .get(&(input.source_node.identifier(), input.output_index))
+                    .copied()
+            };
+
+            match &node.definition {
+                NodeDefinition::Operator(op_def) => {
+                    let mut source = None;
+                    for (index, input) in node.inputs.iter().enumerate() {
+                        let Some(input_source) = row_source(input) else {
+                            continue;
+                        };
+                        let keeps_rows = source.map_or(true, |source| source == input_source)
+                            && value_shape(input)
+                                .map(|shape| op_keeps_rows(op_def, index, &shape))
+                                .unwrap_or(false);
+                        if !keeps_rows {
+                            return Err(IrError::DataDependentShapeUnsupported {
+                                source_node: input.source_node.definition.get_name().to_string(),
+                                target_node: node.definition.get_name().to_string(),
+                            });
+                        }
+                        source = Some(input_source);
+                    }
+                    if op_has_data_dependent_output(op_def.proto.get_op_type()) {
+                        source = Some(op_def.proto.get_output()[0].as_str());
+                    }
+                    if let Some(source) = source {
+                        row_sources.insert((node.identifier(), 0), source);
+                    }
+                }
+                NodeDefinition::Outputs { names } => {
+                    for (input, name) in node.inputs.iter().zip(names) {
+                        if let (Some(source), Some(shape)) = (row_source(input), value_shape(input))
+                        {
+                            data_dependent_outputs.insert(
+                                name.clone(),
+                                DataDependentOutput {
+                                    source: source.to_string(),
+                                    max_rows: shape.dim(0) as usize,
+                                },
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(data_dependent_outputs)
+    }
 }
@@ -1,22 +1,34 @@
+mod compiled;
 mod compiler;
+mod cpu;
+mod einsum;
 mod gpu;
 mod ir;
 pub mod onnx;
 mod optimizer;
 mod resource;
+mod rewrite;
 pub mod utils;
 
 pub use compiler::CompileError;
-pub use gpu::GpuError;
-use ir::IrError;
+pub use cpu::CpuError;
+pub use einsum::{EinsumEquation, EinsumError};
+pub use gpu::{GpuError, GpuTensor};
+pub use half;
+use ir::{DataDependentOutput, IrError, RowCounts};
 pub use optimizer::constant_of_shape_output;
 use optimizer::{Optimizer, OptimizerError};
 use protobuf::{self, Message, ProtobufError};
+pub use resource::DeviceError;
+pub use rewrite::{Match, OpPattern, Pattern, Replacement, RewriteRule};
 use std::collections::HashMap;
 use std::path::Path;
 use std::result::Result;
+use std::sync::Arc;
 use utils::{get_opset_version, DataTypeError, InputTensor, OpsetError, OutputTensor};
+pub use wgpu;
 
+use crate::cpu::CpuModel;
 use crate::gpu::GpuModel;
 use thiserror::Error;
 
@@ -35,7 +47,8 @@ pub enum WonnxError {
     TypeError(#[from] DataTypeError),
 }
 
-/// An inference [session](Session) represents a model that is loaded and ready to perform inference on the GPU.
+/// An inference [session](Session) represents a model that is loaded and ready to perform inference on the GPU (or on
+/// the CPU, see [`Backend`]).
 ///
 /// # Examples
 ///
@@ -45,7 +58,16 @@ pub enum WonnxError {
 /// let mut session = Session::from_path("path/to/model.onnx").await.unwrap();
 /// ```
 pub struct Session {
-    gpu_model: GpuModel,
+    model: InferenceModel,
+
+    /// The outputs that have a data-dependent number of rows, which are padded to their upper bound size during inference
+    data_dependent_outputs: HashMap<String, DataDependentOutput>,
+}
+
+/// The compiled model a [Session] performs inference with
+enum InferenceModel {
+    Gpu(GpuModel),
+    Cpu(CpuModel),
 }
 
 #[derive(Error, Debug)]
@@ -75,24 +97,69 @@ pub enum SessionError {
     #[error("GPU model error: {0}")]
     GpuError(#[from] GpuError),
 
+    #[error("could not acquire GPU device: {0}")]
+    DeviceError(#[from] DeviceError),
+
+    #[error("this operation is not supported by the {0:?} backend")]
+    UnsupportedBackend(Backend),
+
+    #[error("CPU model error: {0}")]
+    CpuError(#[from] CpuError),
+
     #[error("optimizer error: {0}")]
     OptimizerError(#[from] OptimizerError),
 
     #[error("opset error: {0}")]
     OpsetError(#[from] OpsetError),
+
+    #[error("the model was compiled by an incompatible version of wonnx (format '{format}', version '{version}')")]
+    IncompatibleCompiledModel { format: String, version: String },
+}
+
+/// The device an inference [Session] performs inference on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Inference is performed by running compute shaders on the GPU
+    #[default]
+    Gpu,
+
+    /// Inference is performed by a (slower) reference implementation on the CPU. Note that the optimizer may still use
+    /// the GPU to fold constant parts of the graph that contain ops the CPU backend does not support.
+    Cpu,
 }
 
 /// Provides optional configuration when creating an inference [Session].
+#[derive(Clone)]
 #[non_exhaustive]
 pub struct SessionConfig {
     /// When set, only the specified outputs will be calculated, and nodes that are not inputs to these outputs may not be processed
     pub outputs: Option<Vec<String>>,
+
+    /// The device to perform inference on
+    pub backend: Backend,
+
+    /// When set, the GPU backend uses this device and queue instead of requesting a new device. This allows sharing a
+    /// device (and buffers) with other parts of an application.
+    pub device: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>,
+
+    /// The number of inferences that the GPU backend can have in flight at the same time when using
+    /// [`Session::run_batch`]. Each lane allocates its own buffers for inputs, outputs and intermediate values.
+    pub lanes: usize,
+
+    /// Rules the optimizer applies to the graph, before its built-in rules
+    pub rewrite_rules: Vec<RewriteRule>,
 }
 
 impl SessionConfig {
     /// Creates a new [SessionConfig] struct with the default options set.
     pub fn new() -> Self {
-        Self { outputs: None }
+        Self {
+            outputs: None,
+            backend: Backend::default(),
+            device: None,
+            lanes: 1,
+            rewrite_rules: vec![],
+        }
     }
 
     /// Sets [`SessionConfig::outputs`] to the specified value and returns [Self].
@@ -100,6 +167,30 @@ impl SessionConfig {
         self.outputs = outputs;
         self
     }
+
+    /// Sets [`SessionConfig::backend`] to the specified value and returns [Self].
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets [`SessionConfig::device`] to the specified device and queue and returns [Self].
+    pub fn with_device(mut self, device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
+        self.device = Some((device, queue));
+        self
+    }
+
+    /// Sets [`SessionConfig::lanes`] to the specified value and returns [Self].
+    pub fn with_lanes(mut self, lanes: usize) -> Self {
+        self.lanes = lanes;
+        self
+    }
+
+    /// Adds a rule to [`SessionConfig::rewrite_rules`] and returns [Self].
+    pub fn with_rewrite_rule(mut self, rule: RewriteRule) -> Self {
+        self.rewrite_rules.push(rule);
+        self
+    }
 }
 
 impl Default for SessionConfig {
@@ -144,20 +235,74 @@ impl Session {
         model: onnx::ModelProto,
         config: &SessionConfig,
     ) -> Result<Session, SessionError> {
-        let (device, queue) = resource::request_device_queue().await;
-
         // Optimize and compile the model graph to a set of buffers and 'builders' which can basically run GPU shader code referencing these buffers
         let onnx_opset_version = get_opset_version(&model)
             .map_err(SessionError::OpsetError)?
             .ok_or(SessionError::UnknownOnnxOpsetVersion)?;
 
-        let mut optimizer = Optimizer::new(onnx_opset_version);
+        // Compiled models have already been optimized
+        let ir = ir::Node::from_model(&model, config.outputs.as_deref())?;
+        let is_compiled = compiled::is_compiled(&model)?;
+        let ir = if is_compiled {
+            ir
+        } else {
+            let mut optimizer = Optimizer::new(onnx_opset_version)
+                .with_device(config.device.clone())
+                .with_rewrite_rules(config.rewrite_rules.clone());
+            optimizer.optimize(ir).await?
+        };
+        let data_dependent_outputs = ir.data_dependent_outputs()?;
+        let model = match config.backend {
+            Backend::Gpu => {
+                let (device, queue) = match &config.device {
+                    Some((device, queue)) => (device.clone(), queue.clone()),
+                    None => {
+                        let (device, queue) = resource::request_device_queue().await?;
+                        (Arc::new(device), Arc::new(queue))
+                    }
+                };
+                InferenceModel::Gpu(GpuModel::with_lanes(
+                    ir,
+                    device,
+                    queue,
+                    onnx_opset_version,
+                    config.lanes,
+                    is_compiled,
+                )?)
+            }
+            Backend::Cpu => InferenceModel::Cpu(CpuModel::from(ir, onnx_opset_version)?),
+        };
+
+        Ok(Session {
+            model,
+            data_dependent_outputs,
+        })
+    }
+
+    /// Optimize the model and generate shaders for it, and return the result as a compiled model. A compiled model can be
+    /// serialized (e.g. using [`protobuf::Message::write_to_bytes`]) and later be used to create a [Session] without
+    /// repeating these steps. Compiled models can only be loaded by the same version of wonnx.
+    pub async fn compile_model(
+        model: &onnx::ModelProto,
+        config: &SessionConfig,
+    ) -> Result<onnx::ModelProto, SessionError> {
+        let onnx_opset_version = get_opset_version(model)
+            .map_err(SessionError::OpsetError)?
+            .ok_or(SessionError::UnknownOnnxOpsetVersion)?;
+
+        if compiled::is_compiled(model)? {
+            return Ok(model.clone());
+        }
+
+        let mut optimizer = Optimizer::new(onnx_opset_version)
+            .with_device(config.device.clone())
+            .with_rewrite_rules(config.rewrite_rules.clone());
         let ir = optimizer
-            .optimize(ir::Node::from_model(&model, config.outputs.as_deref())?)
+            .optimize(ir::Node::from_model(model, config.outputs.as_deref())?)
             .await?;
-        let gpu_model = GpuModel::from(ir, device, queue, onnx_opset_version)?;
-
-        Ok(Session { gpu_model })
+        let mut compiled_model = compiled::to_model(&ir, onnx_opset_version)?;
+        compiled_model.set_ir_version(model.get_ir_version());
+        Ok(compiled_model)
     }
 
     /// Create a Session given an ONNX model, using default configuration.
@@ -170,6 +315,79 @@ impl Session {
         &self,
         inputs: &HashMap<String, InputTensor<'a>>,
     ) -> Result<HashMap<String, OutputTensor>, SessionError> {
-        Ok(self.gpu_model.infer(inputs).await?)
+        let (outputs, row_counts) = match &self.model {
+            InferenceModel::Gpu(gpu_model) => gpu_model.infer(inputs).await?,
+            InferenceModel::Cpu(cpu_model) => cpu_model.infer(inputs)?,
+        };
+        Ok(self.without_padding(outputs, &row_counts))
+    }
+
+    /// Perform inference for each of the provided sets of inputs and return the outputs for each set (in the same order).
+    /// With the GPU backend, as many inferences as there are [lanes](SessionConfig::lanes) are in flight at once.
+    pub async fn run_batch<'a>(
+        &self,
+        batch: &[HashMap<String, InputTensor<'a>>],
+    ) -> Result<Vec<HashMap<String, OutputTensor>>, SessionError> {
+        let batch_outputs: Vec<_> = match &self.model {
+            InferenceModel::Gpu(gpu_model) => gpu_model.infer_batch(batch).await?,
+            InferenceModel::Cpu(cpu_model) => batch
+                .iter()
+                .map(|inputs| cpu_model.infer(inputs))
+                .collect::<Result<_, _>>()?,
+        };
+        Ok(batch_outputs
+            .into_iter()
+            .map(|(outputs, row_counts)| self.without_padding(outputs, &row_counts))
+            .collect())
+    }
+
+    /// Perform inference given inputs that reside in GPU memory, and return the outputs as tensors in GPU memory
+    /// (without reading them back to main memory). The output buffers are owned by the session and are overwritten by
+    /// the next inference run. Only supported by the GPU backend, and only for models that do not contain ops that are
+    /// executed on the host (such as NonMaxSuppression). Outputs that have a data-dependent shape are returned padded to
+    /// their upper bound size. Input buffers that have the `STORAGE` usage and the size the model expects are used by the
+    /// model directly (see [`GpuTensor::new`]), so they must not be written to while the inference is running.
+    pub fn run_gpu(
+        &self,
+        inputs: &HashMap<String, GpuTensor>,
+    ) -> Result<HashMap<String, GpuTensor>, SessionError> {
+        Ok(self.gpu_model()?.infer_gpu(inputs)?)
+    }
+
+    /// Read a tensor that resides in GPU memory (i.e. an output returned by [`Session::run_gpu`]) to main memory.
+    pub async fn read_tensor(&self, tensor: &GpuTensor) -> Result<OutputTensor, SessionError> {
+        Ok(self.gpu_model()?.read_tensor(tensor).await?)
+    }
+
+    /// The GPU device and queue used by this session, or None when the session does not use the GPU backend. Buffers
+    /// used as input for [`Session::run_gpu`] must be created on this device.
+    pub fn device(&self) -> Option<(&Arc<wgpu::Device>, &Arc<wgpu::Queue>)> {
+        self.gpu_model().ok().map(|gpu_model| gpu_model.device())
+    }
+
+    /// Removes the padding from outputs that have a data-dependent number of rows, keeping the actual number of rows
+    /// reported by the op that determines it
+    fn without_padding(
+        &self,
+        mut outputs: HashMap<String, OutputTensor>,
+        row_counts: &RowCounts,
+    ) -> HashMap<String, OutputTensor> {
+        for (name, data_dependent_output) in &self.data_dependent_outputs {
+            let rows = row_counts.get(&data_dependent_output.source);
+            if let (Some(output), Some(rows)) = (outputs.remove(name), rows) {
+                outputs.insert(
+                    name.clone(),
+                    output.truncated_rows(*rows, data_dependent_output.max_rows),
+                );
+            }
+        }
+        outputs
+    }
+
+    fn gpu_model(&self) -> Result<&GpuModel, SessionError> {
+        match &self.model {
+            InferenceModel::Gpu(gpu_model) => Ok(gpu_model),
+            InferenceModel::Cpu(_) => Err(SessionError::UnsupportedBackend(Backend::Cpu)),
+        }
     }
 }
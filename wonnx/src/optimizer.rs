@@ -1,12 +1,16 @@
 //! Optimizer that walks the DAG and transforms or coalesces ops for quicker execution
 use crate::{
+    compiler::fused_conv_type,
+    cpu::fold_constant,
+    einsum::{EinsumEquation, Value},
     gpu::GpuModel,
     ir::{Input, Node, NodeDefinition, NodeIdentifier, OperatorDefinition},
-    onnx::{NodeProto, TensorProto},
-    resource::{padding, request_device_queue},
+    onnx::{AttributeProto, NodeProto, TensorProto},
+    resource::{padding, request_device_queue, DeviceError},
+    rewrite::{Match, OpPattern, Pattern, Replacement, RewriteRule},
     utils::{
-        attribute, AttributeNotFoundError, DataTypeError, NodeAttributes, OutputTensor, ScalarType,
-        Shape,
+        attribute, initializer, AttributeNotFoundError, DataTypeError, InputTensor, NodeAttributes,
+        OutputTensor, ScalarType, Shape,
     },
     GpuError,
 };
@@ -15,7 +19,8 @@ use bytemuck::pod_collect_to_vec;
 use protobuf::RepeatedField;
 use std::{
     borrow::Cow,
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
+    convert::{TryFrom, TryInto},
     sync::Arc,
 };
 use thiserror::Error;
@@ -46,12 +51,27 @@ pub enum OptimizerError {
 
     #[error("error during constant folding: {0}")]
     ConstantFoldingError(#[from] GpuError),
+
+    #[error("no device available for constant folding: {0}")]
+    DeviceError(#[from] DeviceError),
+
+    #[error("rewrite rule {rule} produced an invalid replacement: {reason}")]
+    InvalidReplacement { rule: String, reason: String },
 }
 
 pub struct Optimizer<'model> {
     padded_tensors: HashMap<String, Arc<Node<'model>>>,
     optimized: HashMap<NodeIdentifier<'model>, Arc<Node<'model>>>,
     onnx_opset_version: i64,
+    device_queue: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>,
+    rewrite_rules: Vec<RewriteRule>,
+
+    /// The number of inputs of other nodes that refer to each node. Optimized nodes take over the count of the node
+    /// they replace.
+    consumers: HashMap<NodeIdentifier<'model>, usize>,
+
+    /// The nodes whose inputs have been counted in `consumers`
+    counted: HashSet<NodeIdentifier<'model>>,
 }
 
 impl<'model> Optimizer<'model> {
@@ -60,12 +80,32 @@ impl<'model> Optimizer<'model> {
             padded_tensors: HashMap::new(),
             optimized: HashMap::new(),
             onnx_opset_version,
+            device_queue: None,
+            rewrite_rules: builtin_rewrite_rules(),
+            consumers: HashMap::new(),
+            counted: HashSet::new(),
         }
     }
 
+    /// Apply the specified rewrite rules in addition to the built-in rules. The rules are tried (in order) before the
+    /// built-in rules.
+    pub fn with_rewrite_rules(mut self, rules: Vec<RewriteRule>) -> Self {
+        self.rewrite_rules.splice(0..0, rules);
+        self
+    }
+
+    /// Perform constant folding on the specified device instead of a newly requested one
+    pub fn with_device(
+        mut self,
+        device_queue: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>,
+    ) -> Self {
+        self.device_queue = device_queue;
+        self
+    }
+
     // Calculates the output of a constant node, then returns a node that contains the result as initializer
     async fn fold_constant_node(
-        &self,
+        &mut self,
         node: Arc<Node<'model>>,
     ) -> Result<Option<Arc<Node<'model>>>, OptimizerError> {
         assert!(node.is_constant());
@@ -240,35 +280,50 @@ impl<'model> Optimizer<'model> {
         ))
     }
 
-    // Infers the output for a constant node (must be a constant and operator node, or the function panics)
+    // Infers the output for a constant node (must be a constant and operator node, or the function panics). The output is
+    // calculated on the CPU when all inputs are initializers; otherwise (or when the CPU implementation fails) a GPU model
+    // is created for the node and the constant nodes it depends on.
     async fn infer_constant_node_to_tensor(
-        &self,
+        &mut self,
         node: Arc<Node<'model>>,
     ) -> Result<Option<Arc<Node<'model>>>, OptimizerError> {
         assert!(node.is_constant());
 
-        // Create an output node so we can perform inference for this node
         if let NodeDefinition::Operator(op_def) = node.definition() {
             let output_name = op_def.proto.output.get(0).unwrap().to_owned();
 
-            let out_node = Arc::new(Node {
-                definition: NodeDefinition::Outputs {
-                    names: vec!["output".to_string()],
+            let input_tensors: Option<Vec<&TensorProto>> = node
+                .inputs
+                .iter()
+                .map(|input| match &input.source_node.definition {
+                    NodeDefinition::Tensor(tensor) => Some(tensor.as_ref().as_ref()),
+                    _ => None,
+                })
+                .collect();
+            let host_output = match input_tensors {
+                Some(input_tensors) => match fold_constant(
+                    &op_def.proto,
+                    &input_tensors,
+                    &op_def.output_shapes,
+                    self.onnx_opset_version,
+                ) {
+                    Ok(mut outputs) => Some(outputs.remove(0)),
+                    Err(error) => {
+                        log::debug!(
+                            "cannot fold {output_name} on the CPU ({error}), using the GPU"
+                        );
+                        None
+                    }
                 },
-                inputs: vec![Input {
-                    source_node: node.clone(),
-                    output_index: 0,
-                }],
-            });
+                None => None,
+            };
 
-            // Perform inference
-            let (device, queue) = request_device_queue().await;
-            let gm = GpuModel::from(out_node, device, queue, self.onnx_opset_version)
-                .map_err(OptimizerError::ConstantFoldingError)?;
-            let mut outputs = gm.infer(&HashMap::new()).await?;
+            let output_tensor = match host_output {
+                Some(output_tensor) => output_tensor,
+                None => self.infer_constant_node_on_gpu(node.clone()).await?,
+            };
 
             // Take the output tensor and make it into an initializer node
-            let (_, output_tensor) = outputs.drain().take(1).next().unwrap();
             log::info!("folded {output_name} to {output_tensor:?}");
             let mut output_tensor_proto = TensorProto::from(
                 output_tensor,
@@ -291,12 +346,46 @@ impl<'model> Optimizer<'model> {
         }
     }
 
+    // Calculates the (first) output of a constant node using a GPU model. The device is requested once, when it was not
+    // specified.
+    async fn infer_constant_node_on_gpu(
+        &mut self,
+        node: Arc<Node<'model>>,
+    ) -> Result<OutputTensor, OptimizerError> {
+        // Create an output node so we can perform inference for this node
+        let out_node = Arc::new(Node {
+            definition: NodeDefinition::Outputs {
+                names: vec!["output".to_string()],
+            },
+            inputs: vec![Input {
+                source_node: node,
+                output_index: 0,
+            }],
+        });
+
+        // Perform inference
+        let (device, queue) = match &self.device_queue {
+            Some((device, queue)) => (device.clone(), queue.clone()),
+            None => {
+                let (device, queue) = request_device_queue().await?;
+                let device_queue = (Arc::new(device), Arc::new(queue));
+                self.device_queue = Some(device_queue.clone());
+                device_queue
+            }
+        };
+        let gm = GpuModel::from(out_node, device, queue, self.onnx_opset_version)
+            .map_err(OptimizerError::ConstantFoldingError)?;
+        let (mut outputs, _) = gm.infer(&HashMap::new()).await?;
+        Ok(outputs.remove("output").unwrap())
+    }
+
     /// Optimize a branch of a graph (memoized)
     #[async_recursion]
     pub async fn optimize(
         &mut self,
         node: Arc<Node<'model>>,
     ) -> Result<Arc<Node<'model>>, OptimizerError> {
+        self.count_consumers(&node);
         let identifier = node.identifier();
         match self.optimized.get(&identifier) {
             Some(opt_node) => Ok(opt_node.clone()),
@@ -329,6 +418,11 @@ impl<'model> Optimizer<'model> {
                 .collect::<Vec<&Input>>();
 
             if dynamic_inputs.len() != 1 {
+                // Subgraphs that can be replaced by a single node with one dynamic input extend the chain
+                if let Some(fused) = Self::fused_layer_normalization(head) {
+                    chain[0] = fused;
+                    continue;
+                }
                 prior = chain.pop_front().unwrap();
                 break;
             }
@@ -388,33 +482,40 @@ impl<'model> Optimizer<'model> {
                 } else {
                     final_chain[node_index - 1].clone()
                 };
+
+                // Each node is guaranteed to have only one 'dynamic' input (the first output of its source). This is the
+                // one we will replace, along with any other outputs of the same source that the node consumes.
+                let dynamic_source = consumer
+                    .inputs
+                    .iter()
+                    .find(|input| input.source_node.is_dynamic() && input.output_index == 0)
+                    .map(|input| input.source_node.clone());
+                let mut new_inputs = Vec::with_capacity(consumer.inputs.len());
+                for old_input in consumer.inputs.iter() {
+                    let source_node = if dynamic_source
+                        .as_ref()
+                        .is_some_and(|source| Arc::ptr_eq(source, &old_input.source_node))
+                    {
+                        producer.clone()
+                    } else if old_input.source_node.is_dynamic() {
+                        self.optimize(old_input.source_node.clone()).await?
+                    } else {
+                        old_input.source_node.clone()
+                    };
+                    new_inputs.push(Input {
+                        source_node,
+                        output_index: old_input.output_index,
+                    });
+                }
                 final_chain[node_index] = self
-                    .locally_optimized_node_with(
-                        consumer.clone(),
-                        consumer
-                            .inputs
-                            .iter()
-                            .map(|old_input| {
-                                // Each node is guaranteed to have only one 'dynamic' input. This is the one we will replace
-                                let is_dynamic_source = old_input.source_node.is_dynamic()
-                                    && old_input.output_index == 0;
-                                if is_dynamic_source {
-                                    Input {
-                                        source_node: producer.clone(),
-                                        output_index: 0,
-                                    }
-                                } else {
-                                    old_input.clone()
-                                }
-                            })
-                            .collect(),
-                    )
+                    .locally_optimized_node_with(consumer.clone(), new_inputs)
                     .await?;
             }
 
             Ok(final_chain.last().unwrap().clone())
         } else {
-            // Just optimize this nodes' inputs recursively
+            // Just optimize this nodes' inputs recursively (the node may have been replaced while forming the chain)
+            let node = chain.pop_front().unwrap_or(prior);
             let mut new_inputs = Vec::with_capacity(node.inputs.len());
             for input in node.inputs.iter() {
                 new_inputs.push(Input {
@@ -427,8 +528,44 @@ impl<'model> Optimizer<'model> {
         }
     }
 
+    /// Counts the consumers of the nodes in the graph of which `node` is the root, skipping nodes that were counted before
+    fn count_consumers(&mut self, node: &Arc<Node<'model>>) {
+        let mut stack = vec![node.clone()];
+        while let Some(node) = stack.pop() {
+            if self.counted.insert(node.identifier()) {
+                for input in &node.inputs {
+                    *self
+                        .consumers
+                        .entry(input.source_node.identifier())
+                        .or_default() += 1;
+                    stack.push(input.source_node.clone());
+                }
+            }
+        }
+    }
+
     /// Create a new node from an existing definition, applying optimizations local to a single node
+    #[async_recursion]
     async fn locally_optimized_node_with(
+        &mut self,
+        node: Arc<Node<'model>>,
+        new_inputs: Vec<Input<'model>>,
+    ) -> Result<Arc<Node<'model>>, OptimizerError> {
+        let consumers = self.consumers.get(&node.identifier()).copied();
+        let optimized = self
+            .locally_optimized_node_with_actual(node, new_inputs)
+            .await?;
+
+        // The optimized node replaces the original node for its consumers (unless it is an existing node)
+        if self.counted.insert(optimized.identifier()) {
+            if let Some(consumers) = consumers {
+                self.consumers.insert(optimized.identifier(), consumers);
+            }
+        }
+        Ok(optimized)
+    }
+
+    async fn locally_optimized_node_with_actual(
         &mut self,
         node: Arc<Node<'model>>,
         mut new_inputs: Vec<Input<'model>>,
@@ -439,6 +576,68 @@ impl<'model> Optimizer<'model> {
             node.definition()
         );
 
+        // Optimize the graphs in attributes of the op (the branches of If, and the body of Loop and Scan)
+        let node = match &node.definition {
+            NodeDefinition::Operator(op_def) if !op_def.subgraphs.is_empty() => {
+                let mut op_def = op_def.clone();
+                for subgraph in op_def.subgraphs.iter_mut() {
+                    subgraph.root = self.optimize(subgraph.root.clone()).await?;
+                }
+                Arc::new(Node {
+                    inputs: node.inputs.clone(),
+                    definition: NodeDefinition::Operator(op_def),
+                })
+            }
+            _ => node,
+        };
+
+        // Apply the rewrite rules
+        if let NodeDefinition::Operator(_) = &node.definition {
+            let root = Arc::new(Node {
+                definition: node.definition.clone(),
+                inputs: new_inputs.clone(),
+            });
+            if let Some(rewritten) = self.rewritten(&node, root).await? {
+                return Ok(rewritten);
+            }
+        }
+
+        // Move static secondary inputs (such as shapes, and the quantization parameters of quantized ops) to attributes.
+        // This is done before constant folding so that folded nodes are executed in the same way.
+        let node = match &node.definition {
+            NodeDefinition::Operator(op_def)
+                if static_input_names(op_def.proto.get_op_type()).is_some() =>
+            {
+                let new_node = Self::static_inputs_to_attributes(op_def, new_inputs)?;
+                new_inputs = new_node.inputs.clone();
+                new_node
+            }
+            NodeDefinition::Operator(op_def)
+                if quantization_input_names(op_def.proto.get_op_type()).is_some()
+                    && !op_def.proto.get_attribute().iter().any(|a| {
+                        a.get_name().ends_with("_scale") || a.get_name().ends_with("_zero_point")
+                    }) =>
+            {
+                let (quantized_node, data_inputs) =
+                    Self::quantization_parameters_to_attributes(op_def, new_inputs)?;
+                new_inputs = data_inputs;
+                quantized_node
+            }
+            NodeDefinition::Operator(op_def)
+                if matches!(
+                    op_def.proto.get_op_type(),
+                    "LSTM" | "GRU" | "RNN" | "NonMaxSuppression" | "Loop"
+                ) && new_inputs.iter().any(|input| {
+                    matches!(input.source_node.definition, NodeDefinition::Missing)
+                }) =>
+            {
+                let new_node = Self::missing_inputs_removed(op_def, new_inputs);
+                new_inputs = new_node.inputs.clone();
+                new_node
+            }
+            _ => node,
+        };
+
         // Fold Shape/Size nodes (not considered constant but we can still fold it)
         if let NodeDefinition::Operator(op_def) = &node.definition {
             match op_def.proto.get_op_type() {
@@ -462,14 +661,18 @@ impl<'model> Optimizer<'model> {
             }
         }
 
-        // Fold constant nodes
+        // Fold constant nodes. The node is folded with its optimized inputs, which are already folded themselves.
         if node.is_constant() && !matches!(node.definition, NodeDefinition::Missing) {
             log::debug!(
                 "node is constant: {:?} {:?}",
                 node.identifier(),
                 node.definition()
             );
-            if let Some(const_node) = self.fold_constant_node(node.clone()).await? {
+            let folded_node = Arc::new(Node {
+                definition: node.definition.clone(),
+                inputs: new_inputs.clone(),
+            });
+            if let Some(const_node) = self.fold_constant_node(folded_node).await? {
                 return Ok(const_node);
             }
         }
@@ -477,10 +680,11 @@ impl<'model> Optimizer<'model> {
         match &node.definition {
             NodeDefinition::Operator(op_def) => {
                 match op_def.proto.get_op_type() {
-                    "Conv" | "ConvRelu" | "ConvLeakyRelu" => {
+                    op if fused_conv_type(op).is_some() => {
                         // This optimization inserts some padding to convolution between kernels with kernel 3x3, because of
                         // the stride of matrix3x3 is 16 in wgsl. It makes the computation matrixable and increases the performance.
-                        if new_inputs.len() > 2
+                        let residual = matches!(fused_conv_type(op), Some((true, _)));
+                        if new_inputs.len() - residual as usize > 2
                             && op_def
                                 .proto
                                 .get_attribute_value::<Vec<i64>>("kernel_shape", None)?
@@ -553,153 +757,14 @@ impl<'model> Optimizer<'model> {
                         Ok(Arc::new(new_node))
                     }
 
-                    // The Clip, Split, Resize, Reshape and Reduce* operators each take optional inputs that influence the operation.
-                    // These are typically statically initialized tensors containing shapes. For more efficient execution we
-                    // move these static values to attributes.
-                    op @ ("Clip" | "Pad" | "Split" | "Resize" | "Reshape" | "ReduceMean"
-                    | "ReduceSum" | "ReduceMin" | "ReduceMax" | "ReduceSumSquare"
-                    | "ReduceLogSumExp" | "ReduceLogSum" | "ReduceL2" | "ReduceL1"
-                    | "ReduceProd") => {
-                        if new_inputs.is_empty() {
-                            return Err(OptimizerError::NoInputs);
-                        }
-
-                        // Names of the inputs (see ONNX operator spec)
-                        let attr_names = match op {
-                            "Split" => SPLIT_INPUT_NAMES,
-                            "Resize" => RESIZE_INPUT_NAMES,
-                            "Reshape" => RESHAPE_INPUT_NAMES,
-                            "Clip" => CLIP_INPUT_NAMES,
-                            "Pad" => PAD_INPUT_NAMES,
-                            "ReduceSum" => REDUCE_OPS_INPUT_NAMES,
-                            "ReduceL1" => REDUCE_OPS_INPUT_NAMES,
-                            "ReduceL2" => REDUCE_OPS_INPUT_NAMES,
-                            "ReduceLogSum" => REDUCE_OPS_INPUT_NAMES,
-                            "ReduceLogSumExp" => REDUCE_OPS_INPUT_NAMES,
-                            "ReduceMax" => REDUCE_OPS_INPUT_NAMES,
-                            "ReduceMean" => REDUCE_OPS_INPUT_NAMES,
-                            "ReduceMin" => REDUCE_OPS_INPUT_NAMES,
-                            "ReduceProd" => REDUCE_OPS_INPUT_NAMES,
-                            "ReduceSumSquare" => REDUCE_OPS_INPUT_NAMES,
-                            _ => unreachable!(),
-                        };
-
-                        // Make a new copy of the attributes list (we're going to add attributes)
-                        let mut new_proto = op_def.proto.clone().into_owned();
-                        let mut attributes = op_def.proto.get_attribute().to_vec();
-
-                        // Loop over the inputs (skipping the first one - that's going to be the data input)
-                        for input_index in 1..(new_inputs.len().min(attr_names.len())) {
-                            let source_node = &new_inputs[input_index].source_node;
-                            match &source_node.definition {
-                                // If the input is an initializer (Tensor) we can obtain the data from the definition and move it to an attribute
-                                NodeDefinition::Tensor(tensor_proto) => {
-                                    let attr_name = attr_names[input_index];
-                                    let data_type =
-                                        ScalarType::from_i32(tensor_proto.get_data_type())?;
-
-                                    match (op, attr_name) {
-                                        ("Split", "split")
-                                        | ("Resize", "roi")
-                                        | ("Resize", "sizes")
-                                        | ("Reshape", "shape")
-                                        | (
-                                            "ReduceMean" | "ReduceSum" | "ReduceMin" | "ReduceMax"
-                                            | "ReduceSumSquare" | "ReduceLogSumExp"
-                                            | "ReduceLogSum" | "ReduceL2" | "ReduceL1"
-                                            | "ReduceProd",
-                                            "axes",
-                                        )
-                                        | ("Pad", "pads")
-                                        | ("Resize", "scales")
-                                        | ("Clip", "min" | "max") => match data_type {
-                                            ScalarType::F32 => {
-                                                let value: Vec<f32> = if tensor_proto
-                                                    .get_float_data()
-                                                    .is_empty()
-                                                {
-                                                    pod_collect_to_vec(tensor_proto.get_raw_data())
-                                                } else {
-                                                    tensor_proto.get_float_data().to_vec()
-                                                };
-                                                log::info!(
-                                                    "transferring input {} for op {} to f32 attribute (initializer data type: {:?}): {:?}",
-                                                    attr_name,
-                                                    op,
-                                                    data_type,
-                                                    value,
-                                                );
-                                                attributes.push(attribute(
-                                                    attr_names[input_index],
-                                                    value,
-                                                ));
-                                            }
-                                            ScalarType::I64 => {
-                                                let value = if tensor_proto
-                                                    .get_int64_data()
-                                                    .is_empty()
-                                                {
-                                                    pod_collect_to_vec(tensor_proto.get_raw_data())
-                                                } else {
-                                                    tensor_proto.get_int64_data().to_vec()
-                                                };
-                                                log::info!(
-                                                    "transferring input {} for op {} to i64 attribute (initializer data type: {:?}): {:?}",
-                                                    attr_name,
-                                                    op,
-                                                    data_type,
-                                                    value,
-                                                );
-                                                attributes.push(attribute(
-                                                    attr_names[input_index],
-                                                    value,
-                                                ));
-                                            }
-                                            _ => {
-                                                return Err(OptimizerError::InvalidInputDataType {
-                                                    data_type,
-                                                    input: attr_name.to_string(),
-                                                    op: op.to_string(),
-                                                })
-                                            }
-                                        },
-                                        _ => {
-                                            // Some other unspecified input that we do not support yet
-                                            return Err(OptimizerError::Unsupported(format!(
-                                                "data_type {} for input {} to op {}",
-                                                tensor_proto.get_data_type(),
-                                                attr_name,
-                                                op
-                                            )));
-                                        }
-                                    }
-                                }
-                                NodeDefinition::Missing => {
-                                    // Just remove it
-                                }
-                                _ => {
-                                    // One of the inputs (except the first) is something other than a tensor (e.g. 'dynamic')
-                                    return Err(OptimizerError::Unsupported(format!(
-                                        "{} operation with dynamic input for {}",
-                                        op, attr_names[input_index]
-                                    )));
-                                }
-                            }
-                        }
-
-                        // Create new node with extra attributes
-                        new_proto.set_attribute(RepeatedField::from(attributes));
-
-                        let new_node = Node {
-                            inputs: vec![new_inputs[0].clone()],
-                            definition: NodeDefinition::Operator(Box::new(OperatorDefinition {
-                                proto: Cow::Owned(new_proto),
-                                output_shapes: op_def.output_shapes.clone(),
-                            })),
-                        };
-
-                        Ok(Arc::new(new_node))
-                    }
+                    "Einsum" => Ok(Self::lowered_einsum(op_def, &new_inputs).unwrap_or_else(
+                        || {
+                            Arc::new(Node {
+                                inputs: new_inputs,
+                                definition: NodeDefinition::Operator(op_def.clone()),
+                            })
+                        },
+                    )),
 
                     _ => Ok(Arc::new(Node {
                         inputs: new_inputs,
@@ -723,6 +788,443 @@ impl<'model> Optimizer<'model> {
         }
     }
 
+    /// Applies the first rewrite rule that matches `root` (which is `node` with optimized inputs), and returns the
+    /// (optimized) replacement of the node.
+    async fn rewritten(
+        &mut self,
+        node: &Arc<Node<'model>>,
+        root: Arc<Node<'model>>,
+    ) -> Result<Option<Arc<Node<'model>>>, OptimizerError> {
+        let root_def = match &root.definition {
+            NodeDefinition::Operator(op_def) => op_def,
+            _ => return Ok(None),
+        };
+        let found = self.rewrite_rules.iter().find_map(|rule| {
+            let bindings = rule.find(&root)?;
+            if !self.removable(&root, &bindings.removed_ops()) {
+                return None;
+            }
+            let replacement = rule.rewrite(&Match::new(&root_def.proto, &bindings))?;
+            Some((rule.name.clone(), bindings, replacement))
+        });
+        let (rule, bindings, replacement) = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        log::debug!("applying rewrite rule {} to {:?}", rule, node.definition());
+        let invalid = |reason: String| OptimizerError::InvalidReplacement {
+            rule: rule.clone(),
+            reason,
+        };
+
+        let removed = bindings.removed_ops();
+        let mut values = bindings.available_values(&root);
+        let consumers = self.consumers.get(&node.identifier()).copied();
+        let root_outputs = root_def.proto.get_output();
+
+        // New initializers can be used like the values of the match
+        for initializer in replacement.initializers {
+            let name = initializer.get_name().to_string();
+            if values.contains_key(&name) {
+                return Err(invalid(format!("initializer {name} replaces a value")));
+            }
+            let source_node = Arc::new(Node {
+                definition: NodeDefinition::Tensor(Box::new(Cow::Owned(initializer))),
+                inputs: vec![],
+            });
+            values.insert(
+                name,
+                Input {
+                    source_node,
+                    output_index: 0,
+                },
+            );
+        }
+
+        // The root may be replaced by one of the values of the match
+        if let Some(value) = &replacement.value {
+            let input = values
+                .get(value)
+                .ok_or_else(|| invalid(format!("unknown value {value}")))?
+                .clone();
+            if root_outputs.len() != 1 || input.output_index != 0 {
+                return Err(invalid(format!(
+                    "{value} cannot replace the outputs of the matched op"
+                )));
+            }
+            self.remove_consumers(&removed, &root);
+            if let Some(consumers) = consumers {
+                *self
+                    .consumers
+                    .entry(input.source_node.identifier())
+                    .or_default() += consumers;
+            }
+            return Ok(Some(input.source_node));
+        }
+
+        // Check that the inputs of the replacement ops exist, and determine the shapes of their outputs
+        let mut produced: HashMap<&str, usize> = HashMap::new();
+        let mut output_shapes = Vec::with_capacity(replacement.nodes.len());
+        for proto in &replacement.nodes {
+            for input in proto.get_input() {
+                if !input.is_empty() && !values.contains_key(input) {
+                    *produced
+                        .get_mut(input.as_str())
+                        .ok_or_else(|| invalid(format!("unknown value {input}")))? += 1;
+                }
+            }
+            let mut shapes = Vec::with_capacity(proto.get_output().len());
+            for output in proto.get_output() {
+                let shape = match root_outputs.iter().position(|o| o == output) {
+                    Some(index) => root_def.output_shapes[index].clone(),
+                    None => replacement
+                        .shapes
+                        .get(output)
+                        .cloned()
+                        .ok_or_else(|| invalid(format!("the shape of {output} is unknown")))?,
+                };
+                shapes.push(shape);
+                produced.insert(output, 0);
+            }
+            output_shapes.push(shapes);
+        }
+        let root_index = replacement
+            .nodes
+            .iter()
+            .position(|proto| proto.get_output().starts_with(root_outputs))
+            .ok_or_else(|| invalid("no op produces the outputs of the matched op".to_string()))?;
+
+        // Update the consumer counts: the matched ops no longer use their inputs, the replacement ops use theirs
+        self.remove_consumers(&removed, &root);
+        for input in replacement.nodes.iter().flat_map(|proto| proto.get_input()) {
+            if let Some(input) = values.get(input) {
+                *self
+                    .consumers
+                    .entry(input.source_node.identifier())
+                    .or_default() += 1;
+            }
+        }
+        let internal_consumers: Vec<usize> = replacement
+            .nodes
+            .iter()
+            .map(|proto| {
+                proto
+                    .get_output()
+                    .iter()
+                    .map(|output| produced[output.as_str()])
+                    .sum()
+            })
+            .collect();
+
+        // Create and optimize the replacement ops
+        let mut rewritten = None;
+        for (index, (proto, shapes)) in replacement.nodes.into_iter().zip(output_shapes).enumerate()
+        {
+            let inputs: Vec<Input<'model>> = proto
+                .get_input()
+                .iter()
+                .map(|input| match values.get(input) {
+                    Some(input) => input.clone(),
+                    None => Input {
+                        source_node: Arc::new(Node {
+                            definition: NodeDefinition::Missing,
+                            inputs: vec![],
+                        }),
+                        output_index: 0,
+                    },
+                })
+                .collect();
+            let outputs = proto.get_output().to_vec();
+            let new_node = Arc::new(Node {
+                definition: NodeDefinition::Operator(Box::new(OperatorDefinition {
+                    proto: Cow::Owned(proto),
+                    output_shapes: shapes,
+                    subgraphs: vec![],
+                })),
+                inputs: inputs.clone(),
+            });
+            let external_consumers = if index == root_index {
+                consumers
+            } else {
+                Some(0)
+            };
+            self.counted.insert(new_node.identifier());
+            if let Some(external_consumers) = external_consumers {
+                self.consumers.insert(
+                    new_node.identifier(),
+                    external_consumers + internal_consumers[index],
+                );
+            }
+
+            let optimized = self.locally_optimized_node_with(new_node, inputs).await?;
+            for (output_index, output) in outputs.into_iter().enumerate() {
+                values.insert(
+                    output,
+                    Input {
+                        source_node: optimized.clone(),
+                        output_index,
+                    },
+                );
+            }
+            if index == root_index {
+                rewritten = Some(optimized);
+            }
+        }
+        Ok(rewritten)
+    }
+
+    /// Whether the matched ops that are not kept can be removed, i.e. they have no consumers outside of the match
+    fn removable(&self, root: &Arc<Node<'model>>, removed: &[Arc<Node<'model>>]) -> bool {
+        removed.iter().all(|node| {
+            let uses = removed
+                .iter()
+                .chain(std::iter::once(root))
+                .flat_map(|consumer| &consumer.inputs)
+                .filter(|input| Arc::ptr_eq(&input.source_node, node))
+                .count();
+            self.consumers.get(&node.identifier()) == Some(&uses)
+        })
+    }
+
+    /// Updates the consumer counts for the removal of the given nodes
+    fn remove_consumers(&mut self, removed: &[Arc<Node<'model>>], root: &Arc<Node<'model>>) {
+        for input in removed
+            .iter()
+            .chain(std::iter::once(root))
+            .flat_map(|node| &node.inputs)
+        {
+            if let Some(consumers) = self.consumers.get_mut(&input.source_node.identifier()) {
+                *consumers = consumers.saturating_sub(1);
+            }
+        }
+    }
+
+    /// The Clip, Split, Resize, Reshape, Slice, Expand, Tile, TopK and Reduce* operators each take optional inputs that influence
+    /// the operation. These are typically statically initialized tensors containing shapes. For more efficient execution
+    /// we move these static values to attributes. Returns the resulting node, which only takes the first (data) input.
+    fn static_inputs_to_attributes(
+        op_def: &OperatorDefinition<'model>,
+        new_inputs: Vec<Input<'model>>,
+    ) -> Result<Arc<Node<'model>>, OptimizerError> {
+        let op = op_def.proto.get_op_type();
+        if new_inputs.is_empty() {
+            return Err(OptimizerError::NoInputs);
+        }
+
+        // Names of the inputs (see ONNX operator spec)
+        let attr_names = static_input_names(op).unwrap();
+
+        // Make a new copy of the attributes list (we're going to add attributes)
+        let mut new_proto = op_def.proto.clone().into_owned();
+        let mut attributes = op_def.proto.get_attribute().to_vec();
+
+        // Loop over the inputs (skipping the first one - that's going to be the data input)
+        for input_index in 1..(new_inputs.len().min(attr_names.len())) {
+            let source_node = &new_inputs[input_index].source_node;
+            match &source_node.definition {
+                // If the input is an initializer (Tensor) we can obtain the data from the definition and move it to an attribute
+                NodeDefinition::Tensor(tensor_proto) => {
+                    let attr_name = attr_names[input_index];
+                    let data_type = ScalarType::from_i32(tensor_proto.get_data_type())?;
+
+                    match (op, attr_name) {
+                        ("Split", "split")
+                        | ("Resize", "roi")
+                        | ("Resize", "sizes")
+                        | ("Reshape", "shape")
+                        | (
+                            "ReduceMean" | "ReduceSum" | "ReduceMin" | "ReduceMax"
+                            | "ReduceSumSquare" | "ReduceLogSumExp" | "ReduceLogSum" | "ReduceL2"
+                            | "ReduceL1" | "ReduceProd",
+                            "axes",
+                        )
+                        | ("Pad", "pads")
+                        | ("Slice", "starts" | "ends" | "axes" | "steps")
+                        | ("Expand", "shape")
+                        | ("Tile", "repeats")
+                        | ("TopK", "K")
+                        | ("Resize", "scales")
+                        | ("Clip", "min" | "max") => match data_type {
+                            ScalarType::F32 => {
+                                let value: Vec<f32> = if tensor_proto.get_float_data().is_empty() {
+                                    pod_collect_to_vec(tensor_proto.get_raw_data())
+                                } else {
+                                    tensor_proto.get_float_data().to_vec()
+                                };
+                                log::info!(
+                                    "transferring input {} for op {} to f32 attribute (initializer data type: {:?}): {:?}",
+                                    attr_name,
+                                    op,
+                                    data_type,
+                                    value,
+                                );
+                                attributes.push(attribute(attr_names[input_index], value));
+                            }
+                            ScalarType::I64 => {
+                                let value = if tensor_proto.get_int64_data().is_empty() {
+                                    pod_collect_to_vec(tensor_proto.get_raw_data())
+                                } else {
+                                    tensor_proto.get_int64_data().to_vec()
+                                };
+                                log::info!(
+                                    "transferring input {} for op {} to i64 attribute (initializer data type: {:?}): {:?}",
+                                    attr_name,
+                                    op,
+                                    data_type,
+                                    value,
+                                );
+                                attributes.push(attribute(attr_names[input_index], value));
+                            }
+                            // Indices (e.g. for Slice) may also be specified as 32-bit integers
+                            ScalarType::I32 => {
+                                let value: Vec<i32> = if tensor_proto.get_int32_data().is_empty() {
+                                    pod_collect_to_vec(tensor_proto.get_raw_data())
+                                } else {
+                                    tensor_proto.get_int32_data().to_vec()
+                                };
+                                attributes.push(attribute(
+                                    attr_names[input_index],
+                                    value.into_iter().map(i64::from).collect::<Vec<i64>>(),
+                                ));
+                            }
+                            _ => {
+                                return Err(OptimizerError::InvalidInputDataType {
+                                    data_type,
+                                    input: attr_name.to_string(),
+                                    op: op.to_string(),
+                                })
+                            }
+                        },
+                        _ => {
+                            // Some other unspecified input that we do not support yet
+                            return Err(OptimizerError::Unsupported(format!(
+                                "data_type {} for input {} to op {}",
+                                tensor_proto.get_data_type(),
+                                attr_name,
+                                op
+                            )));
+                        }
+                    }
+                }
+                NodeDefinition::Missing => {
+                    // Just remove it
+                }
+                _ => {
+                    // One of the inputs (except the first) is something other than a tensor (e.g. 'dynamic')
+                    return Err(OptimizerError::Unsupported(format!(
+                        "{} operation with dynamic input for {}",
+                        op, attr_names[input_index]
+                    )));
+                }
+            }
+        }
+
+        // Create new node with extra attributes
+        new_proto.set_attribute(RepeatedField::from(attributes));
+
+        let new_node = Node {
+            inputs: vec![new_inputs[0].clone()],
+            definition: NodeDefinition::Operator(Box::new(OperatorDefinition {
+                proto: Cow::Owned(new_proto),
+                output_shapes: op_def.output_shapes.clone(),
+                subgraphs: op_def.subgraphs.clone(),
+            })),
+        };
+
+        Ok(Arc::new(new_node))
+    }
+
+    /// Moves the scale and zero point inputs of a quantized op to attributes (named after the inputs), and returns the
+    /// resulting node along with its remaining inputs. Missing zero points are left out (these default to zero).
+    fn quantization_parameters_to_attributes(
+        op_def: &OperatorDefinition<'model>,
+        inputs: Vec<Input<'model>>,
+    ) -> Result<(Arc<Node<'model>>, Vec<Input<'model>>), OptimizerError> {
+        let op = op_def.proto.get_op_type();
+        let input_names = quantization_input_names(op).unwrap();
+        let mut new_proto = op_def.proto.clone().into_owned();
+        let mut data_inputs = vec![];
+        let mut data_input_names = vec![];
+        for ((input, input_name), name) in inputs
+            .into_iter()
+            .zip(op_def.proto.get_input())
+            .zip(input_names)
+        {
+            let is_parameter = name.ends_with("_scale") || name.ends_with("_zero_point");
+            match &input.source_node.definition {
+                NodeDefinition::Missing => {}
+                NodeDefinition::Tensor(tensor) if is_parameter => {
+                    let values: Vec<f32> = OutputTensor::from(&InputTensor::try_from(&***tensor)?)
+                        .try_into()
+                        .map_err(|_| OptimizerError::InvalidInputDataType {
+                            data_type: ScalarType::from_i32(tensor.get_data_type())
+                                .unwrap_or(ScalarType::F32),
+                            input: name.to_string(),
+                            op: op.to_string(),
+                        })?;
+                    new_proto.mut_attribute().push(if name.ends_with("_scale") {
+                        attribute(name, values)
+                    } else {
+                        attribute(name, values.iter().map(|x| *x as i64).collect::<Vec<i64>>())
+                    });
+                }
+                _ if is_parameter => {
+                    return Err(OptimizerError::Unsupported(format!(
+                        "{} operation with dynamic input for {}",
+                        op, name
+                    )));
+                }
+                _ => {
+                    data_inputs.push(input);
+                    data_input_names.push(input_name.clone());
+                }
+            }
+        }
+        new_proto.set_input(RepeatedField::from(data_input_names));
+
+        let new_node = Arc::new(Node {
+            inputs: data_inputs.clone(),
+            definition: NodeDefinition::Operator(Box::new(OperatorDefinition {
+                proto: Cow::Owned(new_proto),
+                output_shapes: op_def.output_shapes.clone(),
+                subgraphs: op_def.subgraphs.clone(),
+            })),
+        });
+        Ok((new_node, data_inputs))
+    }
+
+    /// Removes missing optional inputs from a node. Their names are left empty in the node definition, so that the
+    /// remaining inputs can still be identified by their position.
+    fn missing_inputs_removed(
+        op_def: &OperatorDefinition<'model>,
+        inputs: Vec<Input<'model>>,
+    ) -> Arc<Node<'model>> {
+        let mut new_proto = op_def.proto.clone().into_owned();
+        let mut present_inputs = vec![];
+        let mut input_names = vec![];
+        let mut inputs = inputs.into_iter();
+        for (input_name, input) in op_def.proto.get_input().iter().zip(inputs.by_ref()) {
+            if matches!(input.source_node.definition, NodeDefinition::Missing) {
+                input_names.push(String::new());
+            } else {
+                present_inputs.push(input);
+                input_names.push(input_name.clone());
+            }
+        }
+        // Values captured by subgraphs follow the inputs of the op
+        present_inputs.extend(inputs);
+        new_proto.set_input(RepeatedField::from(input_names));
+
+        Arc::new(Node {
+            inputs: present_inputs,
+            definition: NodeDefinition::Operator(Box::new(OperatorDefinition {
+                proto: Cow::Owned(new_proto),
+                output_shapes: op_def.output_shapes.clone(),
+                subgraphs: op_def.subgraphs.clone(),
+            })),
+        })
+    }
+
     /// Attempt to fuse several operators in a chain of operators with no other dynamic inputs. The function receives a list
     /// of nodes that are guaranteed to be operators that each have one input (exactly). It is free to remove or add nodes
     /// to this list. The caller will fix up the input/output relationships between the nodes.
@@ -747,66 +1249,653 @@ impl<'model> Optimizer<'model> {
         log::debug!("optimize_chain {:?}", names);
 
         match &names[..] {
-            // Double Neg: just cull
-            ["Neg", "Neg", ..] => {
-                chain.pop_front();
-                chain.pop_front();
+            // LayerNormalization followed by a multiplication with a static scale (and addition of a static bias): absorb
+            // these as the scale and bias inputs of the LayerNormalization node
+            ["LayerNormalization", "Mul", ..] if chain[0].inputs.len() == 1 => {
+                let layer_norm = chain[0].clone();
+                let layer_norm_def = match &layer_norm.definition {
+                    NodeDefinition::Operator(op_def) => op_def,
+                    _ => unreachable!(),
+                };
+                let output_shape = &layer_norm_def.output_shapes[0];
+                let axis = layer_norm_def.proto.get_attribute_value("axis", Some(-1))?;
+                let axis = if axis < 0 {
+                    axis + output_shape.rank() as i64
+                } else {
+                    axis
+                };
+                let normalized_dims =
+                    &output_shape.dims[(axis.max(0) as usize).min(output_shape.rank())..];
+
+                let scale = match Self::static_operand(&chain[1], output_shape, normalized_dims) {
+                    Some(scale) => scale,
+                    None => return Ok(false),
+                };
+                let bias = match names.get(2) {
+                    Some(&"Add") => Self::static_operand(&chain[2], output_shape, normalized_dims),
+                    _ => None,
+                };
+                let fused_count = if bias.is_some() { 3 } else { 2 };
+                let last = chain[fused_count - 1].clone();
+                let last_def = match &last.definition {
+                    NodeDefinition::Operator(op_def) => op_def,
+                    _ => unreachable!(),
+                };
+
+                let mut inputs = vec![layer_norm.inputs[0].clone(), scale];
+                inputs.extend(bias);
+                let mut proto = layer_norm_def.proto.clone().into_owned();
+                proto.set_input(
+                    inputs
+                        .iter()
+                        .enumerate()
+                        .map(|(index, input)| {
+                            if index == 0 {
+                                layer_norm_def.proto.get_input()[0].clone()
+                            } else {
+                                input.source_node.definition.get_name().to_string()
+                            }
+                        })
+                        .collect(),
+                );
+                proto.set_output(last_def.proto.get_output().into());
+                proto.set_name(
+                    chain
+                        .iter()
+                        .take(fused_count)
+                        .map(|node| node.definition.get_name().to_string())
+                        .collect::<Vec<String>>()
+                        .join("+"),
+                );
+                log::debug!(
+                    "can fuse chain of {:?} to LayerNormalization with scale and bias: {}",
+                    &names[0..fused_count],
+                    proto.get_name()
+                );
+
+                let node = Arc::new(Node {
+                    inputs,
+                    definition: NodeDefinition::Operator(Box::new(OperatorDefinition {
+                        proto: Cow::Owned(proto),
+                        output_shapes: last_def.output_shapes.clone(),
+                        subgraphs: vec![],
+                    })),
+                });
+                chain.drain(0..fused_count);
+                chain.push_front(node);
                 Ok(true)
             }
 
-            // Conv+Relu or Conv+LeakyRelu: combine into ConvRelu/ConvLeakyRelu
-            ["Conv", "Relu", ..] | ["Conv", "LeakyRelu", ..] => {
-                let conv = chain[0].clone();
-                let relu = chain[1].clone();
+            _ => Ok(false),
+        }
+    }
 
-                if let (NodeDefinition::Operator(conv_def), NodeDefinition::Operator(relu_def)) =
-                    (&conv.definition, &relu.definition)
-                {
-                    // Use the Conv node as template for the new fused Conv[Leaky]Relu node
-                    let mut convrelu_def = *conv_def.clone();
-                    let mut convrelu_proto = conv_def.proto.clone().into_owned();
-                    let new_op_type = match relu_def.proto.get_op_type() {
-                        "LeakyRelu" => "ConvLeakyRelu",
-                        "Relu" => "ConvRelu",
-                        _ => unreachable!(),
-                    };
-                    convrelu_proto.set_op_type(new_op_type.to_string());
-
-                    // Copy all Relu attributes over to the copy of the Conv node
-                    let mut attributes = conv_def.proto.get_attribute().to_vec();
-                    attributes.extend(relu_def.proto.get_attribute().iter().cloned());
-                    convrelu_proto.set_attribute(RepeatedField::from(attributes));
-                    convrelu_proto.set_name(format!(
-                        "{}+{}",
-                        conv.definition.get_name(),
-                        relu.definition.get_name()
-                    ));
+    /// Returns the static operand of a binary operator that is part of a chain, if the operator does not change the
+    /// shape of the dynamic input and the operand's dimensions (ignoring leading ones) are the given dimensions.
+    fn static_operand(node: &Node<'model>, shape: &Shape, dims: &[u64]) -> Option<Input<'model>> {
+        match &node.definition {
+            NodeDefinition::Operator(op_def) if op_def.output_shapes[0].dims == shape.dims => {}
+            _ => return None,
+        }
+        let dims: Vec<u64> = dims.iter().copied().skip_while(|dim| *dim == 1).collect();
+        node.inputs
+            .iter()
+            .find(|input| match &input.source_node.definition {
+                NodeDefinition::Tensor(tensor) => {
+                    tensor.get_dims().len() <= shape.rank()
+                        && tensor
+                            .get_dims()
+                            .iter()
+                            .map(|dim| *dim as u64)
+                            .skip_while(|dim| *dim == 1)
+                            .eq(dims.iter().copied())
+                }
+                _ => false,
+            })
+            .cloned()
+    }
 
-                    log::debug!(
-                        "can fuse chain of Conv/[Leaky]Relu to Conv[Leaky]Relu: {:?}: {:?} + {:?} = {}",
-                        names,
-                        conv.definition(),
-                        relu.definition(),
-                        convrelu_proto.get_name()
-                    );
+    /// Lowers an Einsum node to a graph of nodes for which templates exist (see [`EinsumEquation::lower`]), returning the
+    /// node that produces the output. Returns None when the equation cannot be lowered (compilation reports why).
+    fn lowered_einsum(
+        op_def: &OperatorDefinition<'model>,
+        inputs: &[Input<'model>],
+    ) -> Option<Arc<Node<'model>>> {
+        let input_shapes: Vec<Shape> = inputs.iter().map(input_shape).collect::<Option<_>>()?;
+        let equation: String = op_def.proto.get_attribute_value("equation", None).ok()?;
+        let output_shape = op_def.output_shapes.first()?;
+        let ops = match EinsumEquation::parse(&equation, &input_shapes.iter().collect::<Vec<_>>())
+            .and_then(|equation| equation.lower(output_shape.data_type))
+        {
+            Ok(ops) if ops.last()?.output_shape.dims == output_shape.dims => ops,
+            Ok(_) => return None,
+            Err(error) => {
+                log::debug!("not lowering Einsum: {}", error);
+                return None;
+            }
+        };
 
-                    convrelu_def.proto = Cow::Owned(convrelu_proto);
+        // The last op produces the output of the Einsum, the others produce intermediate values
+        let output_name = &op_def.proto.get_output()[0];
+        let op_count = ops.len();
+        let mut nodes: Vec<Arc<Node<'model>>> = Vec::with_capacity(op_count);
+        let mut names: Vec<String> = Vec::with_capacity(op_count);
+        for (index, op) in ops.into_iter().enumerate() {
+            let (name, output_shapes) = if index == op_count - 1 {
+                (output_name.clone(), op_def.output_shapes.clone())
+            } else {
+                (
+                    format!("{}_einsum_{}", output_name, index),
+                    vec![op.output_shape],
+                )
+            };
+            let (input_names, node_inputs): (Vec<String>, Vec<Input<'model>>) = op
+                .inputs
+                .iter()
+                .map(|value| match value {
+                    Value::Input(i) => (op_def.proto.get_input()[*i].clone(), inputs[*i].clone()),
+                    Value::Op(i) => (
+                        names[*i].clone(),
+                        Input {
+                            source_node: nodes[*i].clone(),
+                            output_index: 0,
+                        },
+                    ),
+                })
+                .unzip();
+
+            let mut proto = NodeProto::new();
+            proto.set_op_type(op.op_type.to_string());
+            proto.set_name(name.clone());
+            proto.set_input(input_names.into());
+            proto.set_output(vec![name.clone()].into());
+            proto.set_attribute(op.attributes.into());
+            log::debug!(
+                "lowering Einsum '{}' for {}: {:?}",
+                equation,
+                output_name,
+                proto
+            );
 
-                    let node = Arc::new(Node {
-                        inputs: conv.inputs.clone(),
-                        definition: NodeDefinition::Operator(Box::new(convrelu_def)),
-                    });
+            nodes.push(Arc::new(Node {
+                inputs: node_inputs,
+                definition: NodeDefinition::Operator(Box::new(OperatorDefinition {
+                    proto: Cow::Owned(proto),
+                    output_shapes,
+                    subgraphs: vec![],
+                })),
+            }));
+            names.push(name);
+        }
+        nodes.pop()
+    }
 
-                    chain.remove(0);
-                    chain.remove(0);
-                    chain.insert(0, node);
-                    Ok(true)
-                } else {
-                    unreachable!();
+    /// Recognizes layer normalization that is decomposed into elementary operations (as exported by e.g. PyTorch for
+    /// opsets before 17), i.e. Div(Sub(X, ReduceMean(X)), Sqrt(Add(ReduceMean(Pow(Sub(..), 2)), epsilon))) where the
+    /// means are taken over the last axes. Returns a LayerNormalization node with only X as input, replacing the Div.
+    fn fused_layer_normalization(div: &Arc<Node<'model>>) -> Option<Arc<Node<'model>>> {
+        let div_def = match &div.definition {
+            NodeDefinition::Operator(op_def) if op_def.proto.get_op_type() == "Div" => op_def,
+            _ => return None,
+        };
+        let sub = operator_input(div.inputs.first()?, "Sub")?;
+        let sqrt = operator_input(div.inputs.get(1)?, "Sqrt")?;
+        let x = sub.inputs.first()?;
+        let mean = operator_input(sub.inputs.get(1)?, "ReduceMean")?;
+        if !same_source(x, mean.inputs.first()?) {
+            return None;
+        }
+
+        let add = operator_input(sqrt.inputs.first()?, "Add")?;
+        let (variance, epsilon) = match (
+            operator_input(add.inputs.first()?, "ReduceMean"),
+            operator_input(add.inputs.get(1)?, "ReduceMean"),
+        ) {
+            (Some(variance), None) => (variance, tensor_input_values(&add.inputs[1])?),
+            (None, Some(variance)) => (variance, tensor_input_values(&add.inputs[0])?),
+            _ => return None,
+        };
+        let pow = operator_input(variance.inputs.first()?, "Pow")?;
+        if epsilon.len() != 1
+            || !same_source(pow.inputs.first()?, &div.inputs[0])
+            || tensor_input_values(pow.inputs.get(1)?)? != [2.0]
+        {
+            return None;
+        }
+
+        // Both means must be taken over the same trailing axes
+        let output_shape = &div_def.output_shapes[0];
+        let rank = output_shape.rank() as i64;
+        let mut axes: Vec<i64> = reduced_axes(mean)?
+            .into_iter()
+            .map(|axis| if axis < 0 { axis + rank } else { axis })
+            .collect();
+        axes.sort_unstable();
+        let mut variance_axes: Vec<i64> = reduced_axes(variance)?
+            .into_iter()
+            .map(|axis| if axis < 0 { axis + rank } else { axis })
+            .collect();
+        variance_axes.sort_unstable();
+        let axis = *axes.first()?;
+        if axes != variance_axes || axes != (axis..rank).collect::<Vec<i64>>() || axis < 0 {
+            return None;
+        }
+
+        let mut proto = NodeProto::new();
+        proto.set_op_type("LayerNormalization".to_string());
+        proto.set_name(div_def.proto.get_name().to_string());
+        let x_name = match &sub.definition {
+            NodeDefinition::Operator(sub_def) => sub_def.proto.get_input().first()?.clone(),
+            _ => return None,
+        };
+        proto.set_input(vec![x_name].into());
+        proto.set_output(div_def.proto.get_output().into());
+        proto.set_attribute(vec![attribute("axis", axis), attribute("epsilon", epsilon[0])].into());
+        log::debug!(
+            "can fuse decomposed layer normalization to LayerNormalization: {}",
+            proto.get_output()[0]
+        );
+
+        Some(Arc::new(Node {
+            inputs: vec![x.clone()],
+            definition: NodeDefinition::Operator(Box::new(OperatorDefinition {
+                proto: Cow::Owned(proto),
+                output_shapes: div_def.output_shapes.clone(),
+                subgraphs: vec![],
+            })),
+        }))
+    }
+}
+
+/// The rewrite rules that the optimizer applies to every graph
+fn builtin_rewrite_rules() -> Vec<RewriteRule> {
+    vec![
+        // Double Neg: replace with the input of the first Neg (which is kept when it is used elsewhere)
+        RewriteRule::new(
+            "Neg+Neg",
+            Pattern::op("Neg").with_inputs(vec![Pattern::op("Neg")
+                .shared()
+                .with_inputs(vec![Pattern::value("x")])
+                .into()]),
+            |m| Some(Replacement::value(m.value("x")?)),
+        ),
+        // Conv followed by an activation: combine into Conv<Activation> (or ConvAdd<Activation> after a residual Add)
+        RewriteRule::new(
+            "Conv+activation",
+            Pattern::ops(&["Relu", "LeakyRelu", "Sigmoid", "HardSwish"])
+                .with_inputs(vec![conv().into()]),
+            |m| fused_conv(m, m.root().get_op_type(), &[], m.root().get_attribute()),
+        ),
+        // Conv+Clip with static bounds (e.g. ReLU6), which are inputs since opset 11 and attributes before
+        RewriteRule::new(
+            "Conv+Clip",
+            Pattern::op("Clip").with_inputs(vec![
+                conv().into(),
+                Pattern::initializer("min"),
+                Pattern::initializer("max"),
+            ]),
+            fused_conv_clip,
+        ),
+        RewriteRule::new(
+            "Conv+Clip",
+            Pattern::op("Clip").with_inputs(vec![conv().into()]),
+            fused_conv_clip,
+        ),
+        // Conv+Mish, where Mish is expressed as x * tanh(softplus(x))
+        RewriteRule::new(
+            "Conv+Mish",
+            Pattern::op("Mul").commutative().with_inputs(vec![
+                conv().into(),
+                Pattern::op("Tanh")
+                    .with_inputs(vec![Pattern::op("Softplus")
+                        .with_inputs(vec![Pattern::value("conv")])
+                        .into()])
+                    .into(),
+            ]),
+            |m| fused_conv(m, "Mish", &[], &[]),
+        ),
+        // Conv+HardSwish, where HardSwish is expressed as x * HardSigmoid(x)
+        RewriteRule::new(
+            "Conv+HardSwish",
+            Pattern::op("Mul").commutative().with_inputs(vec![
+                conv().into(),
+                Pattern::op("HardSigmoid")
+                    .named("hard_sigmoid")
+                    .with_inputs(vec![Pattern::value("conv")])
+                    .into(),
+            ]),
+            |m| {
+                let hard_sigmoid = m.node("hard_sigmoid")?;
+                let alpha: f32 = hard_sigmoid.get_attribute_value("alpha", Some(0.2)).ok()?;
+                let beta: f32 = hard_sigmoid.get_attribute_value("beta", Some(0.5)).ok()?;
+                fused_conv(
+                    m,
+                    "HardSwish",
+                    &[],
+                    &[attribute("alpha", alpha), attribute("beta", beta)],
+                )
+            },
+        ),
+        // BatchNormalization with static parameters after a Conv or Gemm: fold into the weights and bias of the op
+        RewriteRule::new(
+            "Conv+BatchNormalization",
+            batch_normalization(
+                Pattern::op("Conv")
+                    .with_inputs(vec![Pattern::value("x"), Pattern::initializer("weights")]),
+            ),
+            folded_batch_normalization,
+        ),
+        RewriteRule::new(
+            "Conv+BatchNormalization",
+            batch_normalization(Pattern::op("Conv").with_inputs(vec![
+                Pattern::value("x"),
+                Pattern::initializer("weights"),
+                Pattern::initializer("bias"),
+            ])),
+            folded_batch_normalization,
+        ),
+        RewriteRule::new(
+            "Gemm+BatchNormalization",
+            batch_normalization(
+                Pattern::op("Gemm")
+                    .with_inputs(vec![Pattern::value("x"), Pattern::initializer("weights")]),
+            ),
+            folded_batch_normalization,
+        ),
+        RewriteRule::new(
+            "Gemm+BatchNormalization",
+            batch_normalization(Pattern::op("Gemm").with_inputs(vec![
+                Pattern::value("x"),
+                Pattern::initializer("weights"),
+                Pattern::initializer("bias"),
+            ])),
+            folded_batch_normalization,
+        ),
+        // Residual connection: add the other operand to the output of the Conv (before any activation that follows)
+        RewriteRule::new(
+            "Conv+Add",
+            Pattern::op("Add").commutative().with_inputs(vec![
+                Pattern::op("Conv").named("conv").into(),
+                Pattern::value("residual"),
+            ]),
+            |m| {
+                let residual = m.value("residual")?;
+                if residual == m.value("conv")? || m.shape("residual")? != m.shape("conv")? {
+                    return None;
                 }
+                fused_conv(m, "Add", &[residual], &[])
+            },
+        ),
+    ]
+}
+
+/// Pattern for a convolution (that may already add a residual input) whose output can take a fused activation
+fn conv() -> OpPattern {
+    Pattern::ops(&["Conv", "ConvAdd"]).named("conv")
+}
+
+/// Replaces the root of the match with a fused op based on the convolution captured as "conv". The op type of the fused op
+/// is the type of the convolution followed by `suffix`. It takes `inputs` after the inputs of the convolution, and adds
+/// `attributes` to its attributes.
+fn fused_conv(
+    m: &Match,
+    suffix: &str,
+    inputs: &[&str],
+    attributes: &[AttributeProto],
+) -> Option<Replacement> {
+    let conv = m.node("conv")?;
+    let mut proto = conv.clone();
+    proto.set_op_type(format!("{}{}", conv.get_op_type(), suffix));
+    proto.set_input(
+        m.inputs("conv")?
+            .into_iter()
+            .chain(inputs.iter().copied())
+            .map(String::from)
+            .collect(),
+    );
+    proto.set_output(m.root().get_output().into());
+    let mut fused_attributes = conv.get_attribute().to_vec();
+    fused_attributes.extend(attributes.iter().cloned());
+    proto.set_attribute(RepeatedField::from(fused_attributes));
+    proto.set_name(format!("{}+{}", conv.get_name(), m.root().get_name()));
+    Some(Replacement::nodes(vec![proto]))
+}
+
+/// Fuses a Clip into the preceding convolution when both of its bounds are static and finite
+fn fused_conv_clip(m: &Match) -> Option<Replacement> {
+    let bound = |name: &str| -> Option<f32> {
+        let value = match m.initializer(name) {
+            Some(tensor) => match initializer_data(tensor)?[..] {
+                [value] => value,
+                _ => return None,
+            },
+            None => m.root().get_attribute_value(name, None).ok()?,
+        };
+        Some(value).filter(|value| value.is_finite())
+    };
+    let (min, max) = (bound("min")?, bound("max")?);
+    fused_conv(
+        m,
+        "Clip",
+        &[],
+        &[attribute("min", min), attribute("max", max)],
+    )
+}
+
+/// Pattern for a BatchNormalization with static parameters that follows the given op (captured as "op")
+fn batch_normalization(op: OpPattern) -> OpPattern {
+    Pattern::op("BatchNormalization").with_inputs(vec![
+        op.named("op").into(),
+        Pattern::initializer("scale"),
+        Pattern::initializer("offset"),
+        Pattern::initializer("mean"),
+        Pattern::initializer("variance"),
+    ])
+}
+
+/// Folds a BatchNormalization into the weights and bias of the preceding Conv or Gemm. The normalization multiplies each
+/// output channel by scale / sqrt(variance + epsilon), and then adds offset - mean * (scale / sqrt(variance + epsilon)).
+fn folded_batch_normalization(m: &Match) -> Option<Replacement> {
+    let normalization = m.root();
+    if normalization.get_output().len() != 1
+        || normalization
+            .get_attribute_value("training_mode", Some(0))
+            .ok()?
+            != 0
+        || normalization.get_attribute_value("spatial", Some(1)).ok()? != 1
+    {
+        return None;
+    }
+    let epsilon: f32 = normalization
+        .get_attribute_value("epsilon", Some(1e-5))
+        .ok()?;
+    let scale = initializer_data(m.initializer("scale")?)?;
+    let offset = initializer_data(m.initializer("offset")?)?;
+    let mean = initializer_data(m.initializer("mean")?)?;
+    let variance = initializer_data(m.initializer("variance")?)?;
+    let channels = scale.len();
+    if [&offset, &mean, &variance]
+        .iter()
+        .any(|data| data.len() != channels)
+    {
+        return None;
+    }
+    let factors: Vec<f32> = scale
+        .iter()
+        .zip(&variance)
+        .map(|(scale, variance)| scale / (variance + epsilon).sqrt())
+        .collect();
+
+    let op = m.node("op")?;
+    let weights_tensor = m.initializer("weights")?;
+    let dims = weights_tensor.get_dims();
+    let mut weights = initializer_data(weights_tensor)?;
+    let bias_tensor = m.initializer("bias");
+    let mut bias = match bias_tensor {
+        Some(tensor) => initializer_data(tensor)?,
+        None => vec![0.0; channels],
+    };
+    let mut attributes = op.get_attribute().to_vec();
+    match op.get_op_type() {
+        "Conv" => {
+            // Weights have shape M x C/group x kH x kW, where M is the number of output channels
+            if dims.first() != Some(&(channels as i64)) || bias.len() != channels {
+                return None;
             }
-            _ => Ok(false),
+            let channel_size = weights.len() / channels;
+            for (channel_weights, factor) in weights.chunks_mut(channel_size).zip(&factors) {
+                channel_weights
+                    .iter_mut()
+                    .for_each(|weight| *weight *= factor);
+            }
+        }
+        "Gemm" => {
+            // B has shape K x N (or N x K when transposed), where N is the number of output channels
+            let transposed = op.get_attribute_value("transB", Some(0)).ok()? != 0;
+            if dims.len() != 2 || dims[if transposed { 0 } else { 1 }] != channels as i64 {
+                return None;
+            }
+            let k = (dims[0] * dims[1]) as usize / channels;
+            for (index, weight) in weights.iter_mut().enumerate() {
+                *weight *= factors[if transposed {
+                    index / k
+                } else {
+                    index % channels
+                }];
+            }
+
+            // C is multiplied by beta, and must be broadcast along the rows (a scalar or a row of N values)
+            let beta: f32 = op.get_attribute_value("beta", Some(1.0)).ok()?;
+            bias = match bias.len() {
+                1 => vec![bias[0] * beta; channels],
+                n if n == channels
+                    && bias_tensor
+                        .iter()
+                        .all(|tensor| tensor.get_dims().last() == Some(&(channels as i64))) =>
+                {
+                    bias.iter().map(|c| c * beta).collect()
+                }
+                _ => return None,
+            };
+            attributes.retain(|attribute| attribute.get_name() != "beta");
+            attributes.push(attribute("beta", 1.0));
+        }
+        _ => return None,
+    }
+    let bias: Vec<f32> = bias
+        .iter()
+        .zip(&factors)
+        .zip(offset.iter().zip(&mean))
+        .map(|((bias, factor), (offset, mean))| (bias - mean) * factor + offset)
+        .collect();
+
+    // The output of the normalization is unique in the graph, and so are the names of the new initializers
+    let output = &normalization.get_output()[0];
+    let weights_name = format!("{output}_weights");
+    let bias_name = format!("{output}_bias");
+    let mut proto = op.clone();
+    proto.set_input(RepeatedField::from(vec![
+        m.value("x")?.to_string(),
+        weights_name.clone(),
+        bias_name.clone(),
+    ]));
+    proto.set_output(normalization.get_output().into());
+    proto.set_attribute(RepeatedField::from(attributes));
+    proto.set_name(format!("{}+{}", op.get_name(), normalization.get_name()));
+    Some(
+        Replacement::nodes(vec![proto])
+            .with_initializer(initializer(&weights_name, weights, dims.to_vec()))
+            .with_initializer(initializer(&bias_name, bias, vec![channels as i64])),
+    )
+}
+
+/// Returns the data of a float initializer. The weights of a 3x3 convolution may have been padded to the stride of a
+/// mat3x3 in WGSL (four values for every three), which is undone.
+fn initializer_data(tensor: &TensorProto) -> Option<Vec<f32>> {
+    if ScalarType::from_i32(tensor.get_data_type()).ok()? != ScalarType::F32 {
+        return None;
+    }
+    let data: Vec<f32> = if tensor.get_float_data().is_empty() {
+        pod_collect_to_vec(tensor.get_raw_data())
+    } else {
+        tensor.get_float_data().to_vec()
+    };
+    let count = tensor.get_dims().iter().product::<i64>() as usize;
+    if data.len() == count {
+        Some(data)
+    } else if data.len() * 3 == count * 4 {
+        Some(
+            data.chunks(4)
+                .flat_map(|row| row[0..3].iter().copied())
+                .collect(),
+        )
+    } else {
+        None
+    }
+}
+
+/// Returns the shape of the value an input refers to
+fn input_shape(input: &Input) -> Option<Shape> {
+    match &input.source_node.definition {
+        NodeDefinition::Operator(op_def) => op_def.output_shapes.get(input.output_index).cloned(),
+        NodeDefinition::Input(info) => info.get_shape().ok(),
+        NodeDefinition::Tensor(tensor) => Some(Shape::from(
+            ScalarType::from_i32(tensor.get_data_type()).ok()?,
+            tensor.get_dims(),
+        )),
+        NodeDefinition::Outputs { .. } | NodeDefinition::Missing => None,
+    }
+}
+
+/// Returns the node that produces the given input if it is the (first output of an) operator of the given type
+fn operator_input<'a, 'model>(
+    input: &'a Input<'model>,
+    op_type: &str,
+) -> Option<&'a Arc<Node<'model>>> {
+    match &input.source_node.definition {
+        NodeDefinition::Operator(op_def)
+            if input.output_index == 0 && op_def.proto.get_op_type() == op_type =>
+        {
+            Some(&input.source_node)
+        }
+        _ => None,
+    }
+}
+
+fn same_source(a: &Input, b: &Input) -> bool {
+    Arc::ptr_eq(&a.source_node, &b.source_node) && a.output_index == b.output_index
+}
+
+/// Returns the values of an input that is a (static) tensor, converted to f32
+fn tensor_input_values(input: &Input) -> Option<Vec<f32>> {
+    match &input.source_node.definition {
+        NodeDefinition::Tensor(tensor) => {
+            OutputTensor::from(&InputTensor::try_from(&***tensor).ok()?)
+                .try_into()
+                .ok()
         }
+        _ => None,
+    }
+}
+
+/// Returns the axes reduced over by a Reduce* operator that keeps the reduced dimensions, either from the attribute or
+/// (for opset 18 and up) from the static second input
+fn reduced_axes(reduce: &Node) -> Option<Vec<i64>> {
+    let proto = match &reduce.definition {
+        NodeDefinition::Operator(op_def) => &op_def.proto,
+        _ => return None,
+    };
+    if proto.get_attribute_value("keepdims", Some(1)).ok()? != 1 {
+        return None;
+    }
+    match reduce.inputs.get(1) {
+        Some(axes) => Some(
+            tensor_input_values(axes)?
+                .into_iter()
+                .map(|axis| axis as i64)
+                .collect(),
+        ),
+        None => proto.get_attribute_value("axes", None).ok(),
     }
 }
 
@@ -817,6 +1906,61 @@ static RESHAPE_INPUT_NAMES: &[&str] = &["data", "shape"];
 static CLIP_INPUT_NAMES: &[&str] = &["input", "min", "max"];
 static REDUCE_OPS_INPUT_NAMES: &[&str] = &["input", "axes"];
 static PAD_INPUT_NAMES: &[&str] = &["data", "pads", "constant_value"];
+static SLICE_INPUT_NAMES: &[&str] = &["data", "starts", "ends", "axes", "steps"];
+static EXPAND_INPUT_NAMES: &[&str] = &["input", "shape"];
+static TILE_INPUT_NAMES: &[&str] = &["input", "repeats"];
+static TOPK_INPUT_NAMES: &[&str] = &["X", "K"];
+
+/// Names of the inputs of ops whose secondary inputs are moved to attributes (see ONNX operator spec)
+fn static_input_names(op_type: &str) -> Option<&'static [&'static str]> {
+    match op_type {
+        "Split" => Some(SPLIT_INPUT_NAMES),
+        "Resize" => Some(RESIZE_INPUT_NAMES),
+        "Reshape" => Some(RESHAPE_INPUT_NAMES),
+        "Clip" => Some(CLIP_INPUT_NAMES),
+        "Pad" => Some(PAD_INPUT_NAMES),
+        "Slice" => Some(SLICE_INPUT_NAMES),
+        "Expand" => Some(EXPAND_INPUT_NAMES),
+        "Tile" => Some(TILE_INPUT_NAMES),
+        "TopK" => Some(TOPK_INPUT_NAMES),
+        "ReduceSum" | "ReduceL1" | "ReduceL2" | "ReduceLogSum" | "ReduceLogSumExp"
+        | "ReduceMax" | "ReduceMean" | "ReduceMin" | "ReduceProd" | "ReduceSumSquare" => {
+            Some(REDUCE_OPS_INPUT_NAMES)
+        }
+        _ => None,
+    }
+}
+
+/// Names of the inputs of quantized ops. Inputs whose names end in `_scale` or `_zero_point` are moved to attributes.
+fn quantization_input_names(op_type: &str) -> Option<&'static [&'static str]> {
+    match op_type {
+        "QuantizeLinear" => Some(&["x", "y_scale", "y_zero_point"]),
+        "DequantizeLinear" => Some(&["x", "x_scale", "x_zero_point"]),
+        "MatMulInteger" => Some(&["A", "B", "a_zero_point", "b_zero_point"]),
+        "QLinearMatMul" => Some(&[
+            "a",
+            "a_scale",
+            "a_zero_point",
+            "b",
+            "b_scale",
+            "b_zero_point",
+            "y_scale",
+            "y_zero_point",
+        ]),
+        "QLinearConv" => Some(&[
+            "x",
+            "x_scale",
+            "x_zero_point",
+            "w",
+            "w_scale",
+            "w_zero_point",
+            "y_scale",
+            "y_zero_point",
+            "B",
+        ]),
+        _ => None,
+    }
+}
 
 /// Generate the output for a ConstantOfShape node
 pub fn constant_of_shape_output(
@@ -839,6 +1983,15 @@ pub fn constant_of_shape_output(
                 }
                 Ok(OutputTensor::F32(vec![fd[0]; element_count]))
             }
+            ScalarType::F16 => {
+                let fd = constant_value_tensor.get_f16_data();
+                if fd.is_empty() {
+                    return Err(OptimizerError::InvalidNode(
+                        "value tensor for ConstantOfShape is empty".to_string(),
+                    ));
+                }
+                Ok(OutputTensor::F16(vec![fd[0]; element_count]))
+            }
             ScalarType::I64 => {
                 let fd = constant_value_tensor.get_int64_data();
                 if fd.is_empty() {
@@ -866,6 +2019,36 @@ pub fn constant_of_shape_output(
                 }
                 Ok(OutputTensor::U8(vec![fd[0]; element_count]))
             }
+            scalar_type @ (ScalarType::F64
+            | ScalarType::I8
+            | ScalarType::U16
+            | ScalarType::I16
+            | ScalarType::Bool) => {
+                let value = OutputTensor::from(&InputTensor::try_from(&constant_value_tensor)?);
+                let repeated = match value {
+                    OutputTensor::F64(fd) if !fd.is_empty() => {
+                        OutputTensor::F64(vec![fd[0]; element_count])
+                    }
+                    OutputTensor::I8(fd) if !fd.is_empty() => {
+                        OutputTensor::I8(vec![fd[0]; element_count])
+                    }
+                    OutputTensor::U16(fd) if !fd.is_empty() => {
+                        OutputTensor::U16(vec![fd[0]; element_count])
+                    }
+                    OutputTensor::I16(fd) if !fd.is_empty() => {
+                        OutputTensor::I16(vec![fd[0]; element_count])
+                    }
+                    OutputTensor::Bool(fd) if !fd.is_empty() => {
+                        OutputTensor::Bool(vec![fd[0]; element_count])
+                    }
+                    _ => {
+                        return Err(OptimizerError::InvalidNode(format!(
+                            "value tensor for ConstantOfShape is empty or not of type {scalar_type}"
+                        )))
+                    }
+                };
+                Ok(repeated)
+            }
         }
     } else {
         // The default value is a zero f32
@@ -877,10 +2060,16 @@ pub fn constant_of_shape_output(
 mod test {
     use std::sync::Arc;
 
+    use protobuf::ProtobufEnum;
+
     use crate::{
         ir::{self, Node, NodeDefinition},
-        onnx::AttributeProto,
-        utils::{attribute, graph, initializer, model, node, tensor},
+        onnx::{AttributeProto, ModelProto, TensorProto_DataType},
+        rewrite::{Pattern, Replacement, RewriteRule},
+        utils::{
+            attribute, graph, initializer, initializer_int64, model, node, tensor, tensor_of_type,
+            NodeAttributes,
+        },
     };
 
     use super::Optimizer;
@@ -1089,6 +2278,232 @@ mod test {
         });
     }
 
+    /// Rule that replaces X * HardSigmoid(X) (where HardSigmoid has alpha set to 0.5) with a (fictional) Swish op
+    fn swish_rule() -> RewriteRule {
+        RewriteRule::new(
+            "Swish",
+            Pattern::op("Mul")
+                .with_inputs(vec![
+                    Pattern::value("x"),
+                    Pattern::op("HardSigmoid")
+                        .with_attribute("alpha", 0.5_f32)
+                        .with_inputs(vec![Pattern::value("x")])
+                        .into(),
+                ])
+                .commutative(),
+            |m| {
+                let x = m.value("x")?;
+                let y = m.root().get_output()[0].as_str();
+                Some(Replacement::nodes(vec![node(
+                    vec![x],
+                    vec![y],
+                    "swish",
+                    "Swish",
+                    vec![],
+                )]))
+            },
+        )
+    }
+
+    fn swish_model(alpha: f32, outputs: &[&str]) -> ModelProto {
+        model(graph(
+            vec![tensor("X", &[1])],
+            outputs.iter().map(|name| tensor(name, &[1])).collect(),
+            vec![tensor("S", &[1])],
+            vec![],
+            vec![
+                node(
+                    vec!["X"],
+                    vec!["S"],
+                    "s",
+                    "HardSigmoid",
+                    vec![attribute("alpha", alpha)],
+                ),
+                node(vec!["S", "X"], vec!["Y"], "y", "Mul", vec![]),
+            ],
+        ))
+    }
+
+    // Test: X -> [HardSigmoid] S, X -> [Mul] -> Y => X -> [Swish] -> Y
+    #[test]
+    pub fn test_rewrite_rule() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        pollster::block_on(async {
+            let m = swish_model(0.5, &["Y"]);
+            let root = ir::Node::from_model(&m, None).unwrap();
+            let mut opt = Optimizer::new(13).with_rewrite_rules(vec![swish_rule()]);
+            let new_root = opt.optimize(root).await.unwrap();
+            let mut new_pairs = vec![];
+            traverse(new_root, &mut new_pairs);
+            assert_eq!(
+                new_pairs,
+                vec![
+                    ("Swish_swish".to_string(), "<outputs>".to_string()),
+                    ("X".to_string(), "Swish_swish".to_string())
+                ]
+            );
+        });
+    }
+
+    // Test: rules do not match when an attribute differs, or when an op in the pattern is also used elsewhere
+    #[test]
+    pub fn test_rewrite_rule_no_match() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        pollster::block_on(async {
+            for (alpha, outputs) in [(0.2, vec!["Y"]), (0.5, vec!["Y", "S"])] {
+                let m = swish_model(alpha, &outputs);
+                let root = ir::Node::from_model(&m, None).unwrap();
+                let mut opt = Optimizer::new(13).with_rewrite_rules(vec![swish_rule()]);
+                let new_root = opt.optimize(root).await.unwrap();
+                let mut new_pairs = vec![];
+                traverse(new_root, &mut new_pairs);
+                assert!(new_pairs
+                    .iter()
+                    .any(|(source, _)| source == "HardSigmoid_s"));
+                assert!(!new_pairs.iter().any(|(source, _)| source == "Swish_swish"));
+            }
+        });
+    }
+
+    // Test: X -> [Conv] Y -> [Add] A -> [Softplus] S -> [Tanh] T, A -> [Mul] -> Z => X -> [ConvAddMish] -> Z
+    #[test]
+    pub fn test_fuse_conv_add_mish() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        pollster::block_on(async {
+            let shape = [1, 4, 2, 2];
+            let m = model(graph(
+                vec![tensor("X", &shape), tensor("R", &shape)],
+                vec![tensor("Z", &shape)],
+                vec![
+                    tensor("Y", &shape),
+                    tensor("A", &shape),
+                    tensor("S", &shape),
+                    tensor("T", &shape),
+                ],
+                vec![initializer("W", vec![1.0; 16], vec![4, 4, 1, 1])],
+                vec![
+                    node(
+                        vec!["X", "W"],
+                        vec!["Y"],
+                        "c",
+                        "Conv",
+                        vec![attribute("kernel_shape", vec![1, 1])],
+                    ),
+                    node(vec!["Y", "R"], vec!["A"], "add", "Add", vec![]),
+                    node(vec!["A"], vec!["S"], "softplus", "Softplus", vec![]),
+                    node(vec!["S"], vec!["T"], "tanh", "Tanh", vec![]),
+                    node(vec!["T", "A"], vec!["Z"], "mul", "Mul", vec![]),
+                ],
+            ));
+            let root = ir::Node::from_model(&m, None).unwrap();
+            let new_root = Optimizer::new(13).optimize(root).await.unwrap();
+            let mut new_pairs = vec![];
+            traverse(new_root, &mut new_pairs);
+            assert_eq!(
+                new_pairs,
+                vec![
+                    ("ConvAddMish_c+add+mul".to_string(), "<outputs>".to_string()),
+                    ("X".to_string(), "ConvAddMish_c+add+mul".to_string()),
+                    ("W".to_string(), "ConvAddMish_c+add+mul".to_string()),
+                    ("R".to_string(), "ConvAddMish_c+add+mul".to_string()),
+                ]
+            );
+        });
+    }
+
+    // Test: X -> [Conv] A -> [BatchNormalization] B -> [Relu] -> Y => X -> [ConvRelu] -> Y, with folded weights and bias
+    #[test]
+    pub fn test_fold_batch_normalization() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        pollster::block_on(async {
+            let shape = [1, 2, 2, 2];
+            let m = model(graph(
+                vec![tensor("X", &shape)],
+                vec![tensor("Y", &shape)],
+                vec![tensor("A", &shape), tensor("B", &shape)],
+                vec![
+                    initializer("W", vec![1.0, 2.0, 3.0, 4.0], vec![2, 2, 1, 1]),
+                    initializer("scale", vec![2.0, 0.5], vec![2]),
+                    initializer("offset", vec![1.0, -1.0], vec![2]),
+                    initializer("mean", vec![0.5, 1.0], vec![2]),
+                    initializer("var", vec![4.0, 1.0], vec![2]),
+                ],
+                vec![
+                    node(
+                        vec!["X", "W"],
+                        vec!["A"],
+                        "c",
+                        "Conv",
+                        vec![attribute("kernel_shape", vec![1, 1])],
+                    ),
+                    node(
+                        vec!["A", "scale", "offset", "mean", "var"],
+                        vec!["B"],
+                        "bn",
+                        "BatchNormalization",
+                        vec![attribute("epsilon", 0.0)],
+                    ),
+                    node(vec!["B"], vec!["Y"], "relu", "Relu", vec![]),
+                ],
+            ));
+            let root = ir::Node::from_model(&m, None).unwrap();
+            let new_root = Optimizer::new(13).optimize(root).await.unwrap();
+            let mut new_pairs = vec![];
+            traverse(new_root.clone(), &mut new_pairs);
+            assert_eq!(
+                new_pairs,
+                vec![
+                    ("ConvRelu_c+bn+relu".to_string(), "<outputs>".to_string()),
+                    ("X".to_string(), "ConvRelu_c+bn+relu".to_string()),
+                    ("B_weights".to_string(), "ConvRelu_c+bn+relu".to_string()),
+                    ("B_bias".to_string(), "ConvRelu_c+bn+relu".to_string()),
+                ]
+            );
+
+            // Weights are scaled by scale / sqrt(var) = [1, 0.5], the bias is offset - mean * scale / sqrt(var)
+            let conv = &new_root.inputs[0].source_node;
+            let data = |index: usize| match &conv.inputs[index].source_node.definition {
+                NodeDefinition::Tensor(tensor) => tensor.get_float_data().to_vec(),
+                _ => panic!("expected an initializer"),
+            };
+            assert_eq!(data(1), vec![1.0, 2.0, 1.5, 2.0]);
+            assert_eq!(data(2), vec![0.5, -1.5]);
+        });
+    }
+
+    // Test: X -> [Conv] Y -> [Sigmoid] S, Y -> [Mul] -> Z is not fused, as the output of the Conv is also used by Mul
+    #[test]
+    pub fn test_fuse_conv_shared_output() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        pollster::block_on(async {
+            let shape = [1, 4, 2, 2];
+            let m = model(graph(
+                vec![tensor("X", &shape)],
+                vec![tensor("Z", &shape)],
+                vec![tensor("Y", &shape), tensor("S", &shape)],
+                vec![initializer("W", vec![1.0; 16], vec![4, 4, 1, 1])],
+                vec![
+                    node(
+                        vec!["X", "W"],
+                        vec!["Y"],
+                        "c",
+                        "Conv",
+                        vec![attribute("kernel_shape", vec![1, 1])],
+                    ),
+                    node(vec!["Y"], vec!["S"], "sigmoid", "Sigmoid", vec![]),
+                    node(vec!["Y", "S"], vec!["Z"], "mul", "Mul", vec![]),
+                ],
+            ));
+            let root = ir::Node::from_model(&m, None).unwrap();
+            let new_root = Optimizer::new(13).optimize(root).await.unwrap();
+            let mut new_pairs = vec![];
+            traverse(new_root, &mut new_pairs);
+            assert!(new_pairs
+                .iter()
+                .any(|(source, target)| source == "Conv_c" && target == "Sigmoid_sigmoid"));
+        });
+    }
+
     // Test: X -> [Neg] A -> [Identity] Z -> [Identity] -> Y with Y and Z output => X -> Y, Z
     #[test]
     pub fn test_optimize_identity_identity_two_outputs() {
@@ -1150,6 +2565,49 @@ mod test {
         });
     }
 
+    // Test: A, B -> [Mul] -> [Reshape] -> [Cast] -> Y where A, B are int64 initializers => [initializer] -> Y. The values
+    // do not fit in 32 bits, so this requires folding on the CPU.
+    #[test]
+    pub fn test_constant_folding_on_cpu() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        pollster::block_on(async {
+            let m = model(graph(
+                vec![],
+                vec![tensor_of_type("Y", &[2, 1], TensorProto_DataType::DOUBLE)],
+                vec![
+                    tensor_of_type("C", &[2], TensorProto_DataType::INT64),
+                    tensor_of_type("D", &[2, 1], TensorProto_DataType::INT64),
+                ],
+                vec![
+                    initializer_int64("A", vec![3_000_000_000, -5], vec![2]),
+                    initializer_int64("B", vec![2, 3], vec![2]),
+                    initializer_int64("shape", vec![2, 1], vec![2]),
+                ],
+                vec![
+                    node(vec!["A", "B"], vec!["C"], "c", "Mul", vec![]),
+                    node(vec!["C", "shape"], vec!["D"], "d", "Reshape", vec![]),
+                    node(
+                        vec!["D"],
+                        vec!["Y"],
+                        "y",
+                        "Cast",
+                        vec![attribute("to", TensorProto_DataType::DOUBLE.value() as i64)],
+                    ),
+                ],
+            ));
+
+            let root = ir::Node::from_model(&m, None).unwrap();
+            let mut opt = Optimizer::new(13);
+            let new_root = opt.optimize(root).await.unwrap();
+            let NodeDefinition::Tensor(y) = new_root.inputs[0].source_node.definition() else {
+                panic!("output should be folded to an initializer");
+            };
+            assert_eq!(y.get_dims(), &[2, 1]);
+            assert_eq!(y.get_double_data(), &[6_000_000_000.0, -15.0]);
+        });
+    }
+
     // Test: [Constant] -> Y => [initializer] -> Y
     #[test]
     pub fn test_constant_node_to_tensor() {
@@ -1268,4 +2726,85 @@ mod test {
             assert_eq!(t.get_int64_data(), expected);
         });
     }
+
+    // Test: Div(Sub(X, ReduceMean(X)), Sqrt(Add(ReduceMean(Pow(Sub, 2)), eps))) * scale + bias => LayerNormalization
+    #[test]
+    pub fn test_optimize_decomposed_layer_normalization() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        pollster::block_on(async {
+            let axes = vec![attribute("axes", vec![-1])];
+            let m = model(graph(
+                vec![tensor("X", &[2, 3])],
+                vec![tensor("Y", &[2, 3])],
+                vec![
+                    tensor("M", &[2, 1]),
+                    tensor("D", &[2, 3]),
+                    tensor("P", &[2, 3]),
+                    tensor("V", &[2, 1]),
+                    tensor("VE", &[2, 1]),
+                    tensor("S", &[2, 1]),
+                    tensor("N", &[2, 3]),
+                    tensor("NS", &[2, 3]),
+                ],
+                vec![
+                    initializer("two", vec![2.0], vec![]),
+                    initializer("epsilon", vec![1e-5], vec![]),
+                    initializer("scale", vec![1.0, 2.0, 3.0], vec![3]),
+                    initializer("bias", vec![0.5, 0.5, 0.5], vec![1, 3]),
+                ],
+                vec![
+                    node(vec!["X"], vec!["M"], "mean", "ReduceMean", axes.clone()),
+                    node(vec!["X", "M"], vec!["D"], "sub", "Sub", vec![]),
+                    node(vec!["D", "two"], vec!["P"], "pow", "Pow", vec![]),
+                    node(vec!["P"], vec!["V"], "variance", "ReduceMean", axes),
+                    node(
+                        vec!["V", "epsilon"],
+                        vec!["VE"],
+                        "add_epsilon",
+                        "Add",
+                        vec![],
+                    ),
+                    node(vec!["VE"], vec!["S"], "sqrt", "Sqrt", vec![]),
+                    node(vec!["D", "S"], vec!["N"], "div", "Div", vec![]),
+                    node(vec!["N", "scale"], vec!["NS"], "mul", "Mul", vec![]),
+                    node(vec!["NS", "bias"], vec!["Y"], "add", "Add", vec![]),
+                ],
+            ));
+
+            let root = ir::Node::from_model(&m, None).unwrap();
+            let mut opt = Optimizer::new(13);
+            let new_root = opt.optimize(root).await.unwrap();
+            let mut new_pairs = vec![];
+            traverse(new_root.clone(), &mut new_pairs);
+            let fused_name = "LayerNormalization_N+NS+Y".to_string();
+            assert_eq!(
+                new_pairs,
+                vec![
+                    (fused_name.clone(), "<outputs>".to_string()),
+                    ("X".to_string(), fused_name.clone()),
+                    ("scale".to_string(), fused_name.clone()),
+                    ("bias".to_string(), fused_name),
+                ]
+            );
+
+            let NodeDefinition::Operator(op_def) = new_root.inputs[0].source_node.definition()
+            else {
+                panic!("should be an operator");
+            };
+            assert_eq!(
+                op_def
+                    .proto
+                    .get_attribute_value::<i64>("axis", None)
+                    .unwrap(),
+                1
+            );
+            assert_eq!(
+                op_def
+                    .proto
+                    .get_attribute_value::<f32>("epsilon", None)
+                    .unwrap(),
+                1e-5
+            );
+        });
+    }
 }
@@ -1,7 +1,23 @@
+use thiserror::Error;
 use wgpu::{util::DeviceExt, BufferUsages};
 
+#[derive(Error, Debug)]
+pub enum DeviceError {
+    #[error("no GPU adapter found for backends {backends:?}")]
+    AdapterNotFound { backends: wgpu::Backends },
+
+    #[error(
+        "could not create GPU device with features {features:?} and limits {limits:?}: {source}"
+    )]
+    RequestDeviceFailed {
+        source: wgpu::RequestDeviceError,
+        features: wgpu::Features,
+        limits: Box<wgpu::Limits>,
+    },
+}
+
 // Get a device and a queue, honoring WGPU_ADAPTER_NAME and WGPU_BACKEND environment variables
-pub async fn request_device_queue() -> (wgpu::Device, wgpu::Queue) {
+pub async fn request_device_queue() -> Result<(wgpu::Device, wgpu::Queue), DeviceError> {
     let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::PRIMARY);
     let instance_descriptor = wgpu::InstanceDescriptor {
         backends,
@@ -11,14 +27,22 @@ pub async fn request_device_queue() -> (wgpu::Device, wgpu::Queue) {
     let instance = wgpu::Instance::new(instance_descriptor);
     let adapter = wgpu::util::initialize_adapter_from_env_or_default(&instance, None)
         .await
-        .expect("No GPU found given preference");
+        .ok_or(DeviceError::AdapterNotFound { backends })?;
 
     // `request_device` instantiates the feature specific connection to the GPU, defining some parameters,
-    //  `features` being the available features.
+    //  `features` being the available features. Half-precision floats are used in shaders when available.
+    let descriptor = wgpu::DeviceDescriptor {
+        required_features: adapter.features() & wgpu::Features::SHADER_F16,
+        ..Default::default()
+    };
     adapter
-        .request_device(&wgpu::DeviceDescriptor::default(), None)
+        .request_device(&descriptor, None)
         .await
-        .expect("Could not create adapter for GPU device")
+        .map_err(|source| DeviceError::RequestDeviceFailed {
+            source,
+            features: descriptor.required_features,
+            limits: Box::new(descriptor.required_limits),
+        })
 }
 
 pub fn create_buffer_init<T: Clone + bytemuck::Pod>(
@@ -75,12 +99,12 @@ pub fn padding(data: &[u8], chunk_size: usize, padding_size: usize) -> Vec<u8> {
 mod tests {
     #[test]
     fn test_request_device_queue() {
-        pollster::block_on(crate::resource::request_device_queue());
+        pollster::block_on(crate::resource::request_device_queue()).unwrap();
     }
 
     #[test]
     fn test_create_buffer_init() {
-        let (device, _) = pollster::block_on(crate::resource::request_device_queue());
+        let (device, _) = pollster::block_on(crate::resource::request_device_queue()).unwrap();
         let data = [1.0, 2.0, 3.0, 4.0];
         let _ = crate::resource::create_buffer_init(
             &device,
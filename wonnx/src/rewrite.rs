@@ -0,0 +1,468 @@
+//! Declarative rewrite rules for the optimizer. A rule consists of a pattern that is matched against the graph, and a
+//! function that provides a replacement for the matched ops.
+use crate::ir::{value_shape, Input, Node, NodeDefinition};
+use crate::onnx::{AttributeProto, NodeProto, TensorProto};
+use crate::utils::{NodeAttributes, Shape};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type Predicate = Arc<dyn Fn(&NodeProto) -> bool + Send + Sync>;
+type RewriteFn = Arc<dyn Fn(&Match) -> Option<Replacement> + Send + Sync>;
+
+/// Describes a (sub)graph of ops to look for. A pattern matches the first output of an op (see [`OpPattern`], which
+/// converts into a pattern), or any value.
+///
+/// # Examples
+///
+/// A pattern that matches `x * Sigmoid(x)` (in either order of the operands of `Mul`):
+///
+/// ```
+/// # use wonnx::Pattern;
+/// let pattern = Pattern::op("Mul")
+///     .with_inputs(vec![
+///         Pattern::value("x"),
+///         Pattern::op("Sigmoid").with_inputs(vec![Pattern::value("x")]).into(),
+///     ])
+///     .commutative();
+/// ```
+#[derive(Clone)]
+pub struct Pattern {
+    kind: PatternKind,
+}
+
+#[derive(Clone)]
+enum PatternKind {
+    Value { name: String, initializer: bool },
+    Op(OpPattern),
+}
+
+/// A pattern that matches the first output of an op, which can be refined further (e.g. by the inputs of the op). Rewrite
+/// rules are built from these patterns (see [`RewriteRule::new`]).
+#[derive(Clone)]
+pub struct OpPattern {
+    op_types: Vec<String>,
+    name: Option<String>,
+    inputs: Option<Vec<Pattern>>,
+    predicates: Vec<Predicate>,
+    commutative: bool,
+    shared: bool,
+}
+
+impl Pattern {
+    /// Matches any value and captures it under the given name. When a name is used more than once in a pattern, all
+    /// occurrences must match the same value.
+    pub fn value(name: &str) -> Pattern {
+        Pattern {
+            kind: PatternKind::Value {
+                name: name.to_string(),
+                initializer: false,
+            },
+        }
+    }
+
+    /// Matches an initializer (or a value that was folded to one) and captures it under the given name
+    pub fn initializer(name: &str) -> Pattern {
+        Pattern {
+            kind: PatternKind::Value {
+                name: name.to_string(),
+                initializer: true,
+            },
+        }
+    }
+
+    /// Matches the output of an op of the given type, regardless of its inputs
+    pub fn op(op_type: &str) -> OpPattern {
+        Pattern::ops(&[op_type])
+    }
+
+    /// Matches the output of an op of any of the given types, regardless of its inputs
+    pub fn ops(op_types: &[&str]) -> OpPattern {
+        OpPattern {
+            op_types: op_types.iter().map(|op_type| op_type.to_string()).collect(),
+            name: None,
+            inputs: None,
+            predicates: vec![],
+            commutative: false,
+            shared: false,
+        }
+    }
+
+    fn matches<'model>(&self, input: &Input<'model>, bindings: &mut Bindings<'model>) -> bool {
+        match &self.kind {
+            PatternKind::Value { name, initializer } => match &input.source_node.definition {
+                NodeDefinition::Missing => false,
+                NodeDefinition::Tensor(_) => bindings.bind_value(name, input),
+                _ if *initializer => false,
+                _ => bindings.bind_value(name, input),
+            },
+            PatternKind::Op(op) => {
+                if input.output_index != 0 || !op.matches(&input.source_node, bindings) {
+                    return false;
+                }
+                bindings.ops.push((input.source_node.clone(), op.shared));
+                true
+            }
+        }
+    }
+}
+
+impl From<OpPattern> for Pattern {
+    fn from(op: OpPattern) -> Pattern {
+        Pattern {
+            kind: PatternKind::Op(op),
+        }
+    }
+}
+
+impl OpPattern {
+    /// Captures the matched op under the given name (see [`Match::node`]). Its output can be referred to as a value of
+    /// the same name.
+    pub fn named(mut self, name: &str) -> OpPattern {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Only match ops that have exactly the given inputs
+    pub fn with_inputs(mut self, inputs: Vec<Pattern>) -> OpPattern {
+        self.inputs = Some(inputs);
+        self
+    }
+
+    /// Only match ops for which the predicate returns true
+    pub fn with_predicate(
+        mut self,
+        predicate: impl Fn(&NodeProto) -> bool + Send + Sync + 'static,
+    ) -> OpPattern {
+        self.predicates.push(Arc::new(predicate));
+        self
+    }
+
+    /// Only match ops that have the attribute set to the given value
+    pub fn with_attribute<T>(self, name: &str, value: T) -> OpPattern
+    where
+        T: From<AttributeProto> + PartialEq + Send + Sync + 'static,
+    {
+        let name = name.to_string();
+        self.with_predicate(move |node| {
+            node.has_attribute(&name)
+                && node
+                    .get_attribute_value::<T>(&name, None)
+                    .is_ok_and(|actual| actual == value)
+        })
+    }
+
+    /// Also match the op when its two inputs are swapped
+    pub fn commutative(mut self) -> OpPattern {
+        self.commutative = true;
+        self
+    }
+
+    /// Allow the matched op to have consumers outside of the match. Such ops are kept in the graph for their other
+    /// consumers. By default, ops other than the root of a match must not be used elsewhere, as they are removed.
+    pub fn shared(mut self) -> OpPattern {
+        self.shared = true;
+        self
+    }
+
+    fn matches<'model>(&self, node: &Arc<Node<'model>>, bindings: &mut Bindings<'model>) -> bool {
+        let op_def = match &node.definition {
+            NodeDefinition::Operator(op_def) => op_def,
+            _ => return false,
+        };
+        if !self
+            .op_types
+            .iter()
+            .any(|op_type| op_type == op_def.proto.get_op_type())
+            || !self
+                .predicates
+                .iter()
+                .all(|predicate| predicate(&op_def.proto))
+        {
+            return false;
+        }
+
+        let saved = bindings.clone();
+        if let Some(name) = &self.name {
+            let output = Input {
+                source_node: node.clone(),
+                output_index: 0,
+            };
+            if !bindings.bind_value(name, &output) {
+                return false;
+            }
+            bindings.names.insert(name.clone(), node.clone());
+        }
+
+        let patterns = match &self.inputs {
+            Some(patterns) => patterns,
+            None => return true,
+        };
+        if patterns.len() == node.inputs.len() {
+            let named = bindings.clone();
+            if patterns
+                .iter()
+                .zip(node.inputs.iter())
+                .all(|(pattern, input)| pattern.matches(input, bindings))
+            {
+                return true;
+            }
+
+            *bindings = named;
+            if self.commutative
+                && patterns.len() == 2
+                && patterns
+                    .iter()
+                    .zip(node.inputs.iter().rev())
+                    .all(|(pattern, input)| pattern.matches(input, bindings))
+            {
+                return true;
+            }
+        }
+        *bindings = saved;
+        false
+    }
+}
+
+/// The values and ops captured while matching a pattern
+#[derive(Clone, Default)]
+pub(crate) struct Bindings<'model> {
+    /// Captured values by name
+    values: HashMap<String, Input<'model>>,
+
+    /// Captured ops by name
+    names: HashMap<String, Arc<Node<'model>>>,
+
+    /// The matched ops (except for the root), and whether they may be used outside of the match
+    ops: Vec<(Arc<Node<'model>>, bool)>,
+}
+
+impl<'model> Bindings<'model> {
+    fn bind_value(&mut self, name: &str, input: &Input<'model>) -> bool {
+        match self.values.get(name) {
+            Some(bound) => {
+                Arc::ptr_eq(&bound.source_node, &input.source_node)
+                    && bound.output_index == input.output_index
+            }
+            None => {
+                self.values.insert(name.to_string(), input.clone());
+                true
+            }
+        }
+    }
+
+    /// The matched ops that are removed from the graph when the match is replaced (the root is replaced separately)
+    pub(crate) fn removed_ops(&self) -> Vec<Arc<Node<'model>>> {
+        let mut removed: Vec<Arc<Node<'model>>> = vec![];
+        for (node, shared) in &self.ops {
+            if !shared && !removed.iter().any(|r| Arc::ptr_eq(r, node)) {
+                removed.push(node.clone());
+            }
+        }
+        removed
+    }
+
+    /// The values that a replacement can use: the captured values and the inputs of the ops that are removed (including
+    /// the root), as well as the outputs of matched ops that are kept, by name.
+    pub(crate) fn available_values(
+        &self,
+        root: &Arc<Node<'model>>,
+    ) -> HashMap<String, Input<'model>> {
+        let removed = self.removed_ops();
+        let mut values = HashMap::new();
+        for input in removed
+            .iter()
+            .chain(std::iter::once(root))
+            .flat_map(|node| &node.inputs)
+            .chain(self.values.values())
+        {
+            if !removed.iter().any(|r| Arc::ptr_eq(r, &input.source_node)) {
+                values.insert(input.name().to_string(), input.clone());
+            }
+        }
+        for (node, shared) in &self.ops {
+            if let (true, NodeDefinition::Operator(op_def)) = (shared, &node.definition) {
+                for output_index in 0..op_def.proto.get_output().len() {
+                    let output = Input {
+                        source_node: node.clone(),
+                        output_index,
+                    };
+                    values.insert(output.name().to_string(), output);
+                }
+            }
+        }
+        values
+    }
+}
+
+/// A match of the pattern of a [`RewriteRule`], which is passed to its rewrite function
+pub struct Match<'a> {
+    root: &'a NodeProto,
+    nodes: HashMap<&'a str, &'a Arc<Node<'a>>>,
+    values: HashMap<&'a str, &'a Input<'a>>,
+}
+
+impl<'a> Match<'a> {
+    pub(crate) fn new(root: &'a NodeProto, bindings: &'a Bindings<'a>) -> Match<'a> {
+        let nodes = bindings
+            .names
+            .iter()
+            .map(|(name, node)| (name.as_str(), node))
+            .collect();
+        let values = bindings
+            .values
+            .iter()
+            .map(|(name, input)| (name.as_str(), input))
+            .collect();
+        Match {
+            root,
+            nodes,
+            values,
+        }
+    }
+
+    /// The op at the root of the match, whose outputs are replaced
+    pub fn root(&self) -> &NodeProto {
+        self.root
+    }
+
+    /// The op captured under the given name (see [`OpPattern::named`])
+    pub fn node(&self, name: &str) -> Option<&NodeProto> {
+        match &self.nodes.get(name)?.definition {
+            NodeDefinition::Operator(op_def) => Some(op_def.proto.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// The names of the values that are the inputs of the op captured under the given name. These may differ from the
+    /// input names in its definition, as the optimizer may have replaced the inputs.
+    pub fn inputs(&self, name: &str) -> Option<Vec<&str>> {
+        Some(
+            self.nodes
+                .get(name)?
+                .inputs
+                .iter()
+                .map(|input| input.name())
+                .collect(),
+        )
+    }
+
+    /// The name of the value captured under the given name. Replacement nodes use this name to refer to the value.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|input| input.name())
+    }
+
+    /// The data of the value captured under the given name, when it is an initializer
+    pub fn initializer(&self, name: &str) -> Option<&TensorProto> {
+        match &self.values.get(name)?.source_node.definition {
+            NodeDefinition::Tensor(tensor) => Some(tensor.as_ref().as_ref()),
+            _ => None,
+        }
+    }
+
+    /// The shape of the value captured under the given name
+    pub fn shape(&self, name: &str) -> Option<Shape> {
+        value_shape(self.values.get(name)?)
+    }
+}
+
+/// The ops (or value) that replace the root of a match
+pub struct Replacement {
+    pub(crate) nodes: Vec<NodeProto>,
+    pub(crate) value: Option<String>,
+    pub(crate) shapes: HashMap<String, Shape>,
+    pub(crate) initializers: Vec<TensorProto>,
+}
+
+impl Replacement {
+    /// Replace the root of the match with the given ops, which must be in execution order. The op that produces the first
+    /// output of the root must produce all its outputs (in the same order). Ops can use the values of the match (see
+    /// [`Match::value`]) and the outputs of preceding ops as input.
+    pub fn nodes(nodes: Vec<NodeProto>) -> Replacement {
+        Replacement {
+            nodes,
+            value: None,
+            shapes: HashMap::new(),
+            initializers: vec![],
+        }
+    }
+
+    /// Replace the (single) output of the root of the match with a value of the match
+    pub fn value(name: &str) -> Replacement {
+        Replacement {
+            nodes: vec![],
+            value: Some(name.to_string()),
+            shapes: HashMap::new(),
+            initializers: vec![],
+        }
+    }
+
+    /// Sets the shape of a value produced by the replacement ops. This is required for all values other than the
+    /// outputs of the root of the match.
+    pub fn with_shape(mut self, value: &str, shape: Shape) -> Replacement {
+        self.shapes.insert(value.to_string(), shape);
+        self
+    }
+
+    /// Adds an initializer, which the replacement ops can use as input by its name. The name must not be the name of a
+    /// value of the match.
+    pub fn with_initializer(mut self, initializer: TensorProto) -> Replacement {
+        self.initializers.push(initializer);
+        self
+    }
+}
+
+/// A rule that replaces subgraphs matching an [`OpPattern`] with other ops. Rules are applied by the optimizer to each op
+/// after its inputs have been optimized, so a pattern sees the result of rewriting the ops it consists of.
+///
+/// # Examples
+///
+/// A rule that replaces `x * Sigmoid(x)` with a custom op:
+///
+/// ```
+/// # use wonnx::{Pattern, Replacement, RewriteRule, utils::node};
+/// let pattern = Pattern::op("Mul")
+///     .with_inputs(vec![
+///         Pattern::value("x"),
+///         Pattern::op("Sigmoid").with_inputs(vec![Pattern::value("x")]).into(),
+///     ])
+///     .commutative();
+/// let rule = RewriteRule::new("swish", pattern, |m| {
+///     let x = m.value("x")?;
+///     let y = m.root().get_output()[0].as_str();
+///     Some(Replacement::nodes(vec![node(vec![x], vec![y], "swish", "Swish", vec![])]))
+/// });
+/// ```
+#[derive(Clone)]
+pub struct RewriteRule {
+    pub(crate) name: String,
+    pattern: OpPattern,
+    rewrite: RewriteFn,
+}
+
+impl RewriteRule {
+    /// Creates a rule that calls `rewrite` for each match of `pattern`. The function returns the replacement, or None
+    /// when the match should be left alone.
+    pub fn new(
+        name: &str,
+        pattern: OpPattern,
+        rewrite: impl Fn(&Match) -> Option<Replacement> + Send + Sync + 'static,
+    ) -> RewriteRule {
+        RewriteRule {
+            name: name.to_string(),
+            pattern,
+            rewrite: Arc::new(rewrite),
+        }
+    }
+
+    /// Matches the pattern of this rule against the given node
+    pub(crate) fn find<'model>(&self, root: &Arc<Node<'model>>) -> Option<Bindings<'model>> {
+        let mut bindings = Bindings::default();
+        self.pattern
+            .matches(root, &mut bindings)
+            .then_some(bindings)
+    }
+
+    pub(crate) fn rewrite(&self, found: &Match) -> Option<Replacement> {
+        (self.rewrite)(found)
+    }
+}
@@ -14,6 +14,7 @@ use crate::onnx::TypeProto;
 use crate::onnx::TypeProto_Tensor;
 use crate::onnx::TypeProto_oneof_value;
 use crate::onnx::ValueInfoProto;
+use half::f16;
 use num::FromPrimitive;
 use std::borrow::Cow;
 use std::convert::From;
@@ -60,7 +61,9 @@ impl Shape {
             (n + 15) / 16 * 16
         }
 
-        round_to_next_multiple_of_16((self.element_count() as usize) * self.data_type.stride())
+        round_to_next_multiple_of_16(
+            (self.element_count() as usize) * self.data_type.gpu_type().stride(),
+        )
     }
 
     pub fn dim(&self, idx: usize) -> u64 {
@@ -132,9 +135,15 @@ impl Shape {
 #[derive(Clone)]
 pub enum InputTensor<'a> {
     F32(Cow<'a, [f32]>),
+    F16(Cow<'a, [f16]>),
+    F64(Cow<'a, [f64]>),
     I32(Cow<'a, [i32]>),
     I64(Cow<'a, [i64]>),
     U8(Cow<'a, [u8]>),
+    I8(Cow<'a, [i8]>),
+    U16(Cow<'a, [u16]>),
+    I16(Cow<'a, [i16]>),
+    Bool(Cow<'a, [bool]>),
 }
 
 impl<'a> From<&'a [f32]> for InputTensor<'a> {
@@ -143,6 +152,12 @@ impl<'a> From<&'a [f32]> for InputTensor<'a> {
     }
 }
 
+impl<'a> From<&'a [f16]> for InputTensor<'a> {
+    fn from(a: &'a [f16]) -> Self {
+        InputTensor::F16(Cow::Borrowed(a))
+    }
+}
+
 impl<'a> From<&'a [i32]> for InputTensor<'a> {
     fn from(a: &'a [i32]) -> Self {
         InputTensor::I32(Cow::Borrowed(a))
@@ -155,15 +170,57 @@ impl<'a> From<&'a [i64]> for InputTensor<'a> {
     }
 }
 
+impl<'a> From<&'a [f64]> for InputTensor<'a> {
+    fn from(a: &'a [f64]) -> Self {
+        InputTensor::F64(Cow::Borrowed(a))
+    }
+}
+
+impl<'a> From<&'a [u8]> for InputTensor<'a> {
+    fn from(a: &'a [u8]) -> Self {
+        InputTensor::U8(Cow::Borrowed(a))
+    }
+}
+
+impl<'a> From<&'a [i8]> for InputTensor<'a> {
+    fn from(a: &'a [i8]) -> Self {
+        InputTensor::I8(Cow::Borrowed(a))
+    }
+}
+
+impl<'a> From<&'a [u16]> for InputTensor<'a> {
+    fn from(a: &'a [u16]) -> Self {
+        InputTensor::U16(Cow::Borrowed(a))
+    }
+}
+
+impl<'a> From<&'a [i16]> for InputTensor<'a> {
+    fn from(a: &'a [i16]) -> Self {
+        InputTensor::I16(Cow::Borrowed(a))
+    }
+}
+
+impl<'a> From<&'a [bool]> for InputTensor<'a> {
+    fn from(a: &'a [bool]) -> Self {
+        InputTensor::Bool(Cow::Borrowed(a))
+    }
+}
+
 impl<'a> TryFrom<&'a TensorProto> for InputTensor<'a> {
     type Error = DataTypeError;
 
     fn try_from(value: &'a TensorProto) -> Result<Self, Self::Error> {
         Ok(match ScalarType::from_i32(value.get_data_type())? {
             ScalarType::F32 => InputTensor::F32(Cow::Borrowed(value.get_float_data())),
+            ScalarType::F16 => InputTensor::F16(Cow::Owned(value.get_f16_data())),
+            ScalarType::F64 => InputTensor::F64(Cow::Owned(value.get_f64_data())),
             ScalarType::I64 => InputTensor::I64(Cow::Borrowed(value.get_int64_data())),
             ScalarType::I32 => InputTensor::I32(Cow::Borrowed(value.get_int32_data())),
             ScalarType::U8 => InputTensor::U8(Cow::Borrowed(value.get_raw_data())),
+            ScalarType::I8 => InputTensor::I8(Cow::Owned(value.get_small_int_data(|x| x as i8))),
+            ScalarType::U16 => InputTensor::U16(Cow::Owned(value.get_small_int_data(|x| x as u16))),
+            ScalarType::I16 => InputTensor::I16(Cow::Owned(value.get_small_int_data(|x| x as i16))),
+            ScalarType::Bool => InputTensor::Bool(Cow::Owned(value.get_bool_data())),
         })
     }
 }
@@ -181,9 +238,15 @@ pub enum TensorConversionError {
 #[serde(untagged)]
 pub enum OutputTensor {
     F32(Vec<f32>),
+    F16(Vec<f16>),
+    F64(Vec<f64>),
     I32(Vec<i32>),
     I64(Vec<i64>),
     U8(Vec<u8>),
+    I8(Vec<i8>),
+    U16(Vec<u16>),
+    I16(Vec<i16>),
+    Bool(Vec<bool>),
 }
 
 impl TryFrom<OutputTensor> for Vec<f32> {
@@ -193,6 +256,11 @@ impl TryFrom<OutputTensor> for Vec<f32> {
     fn try_from(value: OutputTensor) -> Result<Self, Self::Error> {
         match value {
             OutputTensor::F32(floats) => Ok(floats),
+            OutputTensor::F16(floats) => Ok(floats.into_iter().map(f32::from).collect()),
+            OutputTensor::F64(floats) => floats
+                .into_iter()
+                .map(|f| f32::from_f64(f).ok_or(TensorConversionError::OutOfBoundsError))
+                .collect::<Result<_, _>>(),
             OutputTensor::I32(ints) => ints
                 .into_iter()
                 .map(|i| f32::from_i32(i).ok_or(TensorConversionError::OutOfBoundsError))
@@ -201,10 +269,11 @@ impl TryFrom<OutputTensor> for Vec<f32> {
                 .into_iter()
                 .map(|i| f32::from_i64(i).ok_or(TensorConversionError::OutOfBoundsError))
                 .collect::<Result<_, _>>(),
-            OutputTensor::U8(ints) => ints
-                .into_iter()
-                .map(|i| f32::from_u8(i).ok_or(TensorConversionError::OutOfBoundsError))
-                .collect::<Result<_, _>>(),
+            OutputTensor::U8(ints) => Ok(ints.into_iter().map(f32::from).collect()),
+            OutputTensor::I8(ints) => Ok(ints.into_iter().map(f32::from).collect()),
+            OutputTensor::U16(ints) => Ok(ints.into_iter().map(f32::from).collect()),
+            OutputTensor::I16(ints) => Ok(ints.into_iter().map(f32::from).collect()),
+            OutputTensor::Bool(bools) => Ok(bools.into_iter().map(|b| b as u8 as f32).collect()),
         }
     }
 }
@@ -217,9 +286,7 @@ impl<'a> TryFrom<&'a OutputTensor> for &'a [f32] {
     fn try_from(value: &'a OutputTensor) -> Result<Self, Self::Error> {
         match value {
             OutputTensor::F32(floats) => Ok(floats.as_slice()),
-            OutputTensor::I32(_) | OutputTensor::I64(_) | OutputTensor::U8(_) => {
-                Err(TensorConversionError::DataTypeError)
-            }
+            _ => Err(TensorConversionError::DataTypeError),
         }
     }
 }
@@ -228,9 +295,57 @@ impl<'a> From<&InputTensor<'a>> for OutputTensor {
     fn from(input: &InputTensor<'a>) -> Self {
         match input {
             InputTensor::F32(fs) => OutputTensor::F32(fs.to_vec()),
+            InputTensor::F16(fs) => OutputTensor::F16(fs.to_vec()),
+            InputTensor::F64(fs) => OutputTensor::F64(fs.to_vec()),
             InputTensor::I32(fs) => OutputTensor::I32(fs.to_vec()),
             InputTensor::I64(fs) => OutputTensor::I64(fs.to_vec()),
             InputTensor::U8(fs) => OutputTensor::U8(fs.to_vec()),
+            InputTensor::I8(fs) => OutputTensor::I8(fs.to_vec()),
+            InputTensor::U16(fs) => OutputTensor::U16(fs.to_vec()),
+            InputTensor::I16(fs) => OutputTensor::I16(fs.to_vec()),
+            InputTensor::Bool(fs) => OutputTensor::Bool(fs.to_vec()),
+        }
+    }
+}
+
+impl<'a> From<&'a OutputTensor> for InputTensor<'a> {
+    fn from(output: &'a OutputTensor) -> Self {
+        match output {
+            OutputTensor::F32(fs) => InputTensor::F32(Cow::Borrowed(fs)),
+            OutputTensor::F16(fs) => InputTensor::F16(Cow::Borrowed(fs)),
+            OutputTensor::F64(fs) => InputTensor::F64(Cow::Borrowed(fs)),
+            OutputTensor::I32(fs) => InputTensor::I32(Cow::Borrowed(fs)),
+            OutputTensor::I64(fs) => InputTensor::I64(Cow::Borrowed(fs)),
+            OutputTensor::U8(fs) => InputTensor::U8(Cow::Borrowed(fs)),
+            OutputTensor::I8(fs) => InputTensor::I8(Cow::Borrowed(fs)),
+            OutputTensor::U16(fs) => InputTensor::U16(Cow::Borrowed(fs)),
+            OutputTensor::I16(fs) => InputTensor::I16(Cow::Borrowed(fs)),
+            OutputTensor::Bool(fs) => InputTensor::Bool(Cow::Borrowed(fs)),
+        }
+    }
+}
+
+impl OutputTensor {
+    /// Keeps the first `rows` rows of a tensor that has `max_rows` rows (i.e. the first dimension of its shape). Used to
+    /// remove the padding from outputs with a data-dependent number of rows.
+    pub(crate) fn truncated_rows(self, rows: usize, max_rows: usize) -> OutputTensor {
+        fn truncated<T>(mut values: Vec<T>, rows: usize, max_rows: usize) -> Vec<T> {
+            let row_size = values.len() / max_rows.max(1);
+            values.truncate(rows.min(max_rows) * row_size);
+            values
+        }
+
+        match self {
+            OutputTensor::F32(v) => OutputTensor::F32(truncated(v, rows, max_rows)),
+            OutputTensor::F16(v) => OutputTensor::F16(truncated(v, rows, max_rows)),
+            OutputTensor::F64(v) => OutputTensor::F64(truncated(v, rows, max_rows)),
+            OutputTensor::I32(v) => OutputTensor::I32(truncated(v, rows, max_rows)),
+            OutputTensor::I64(v) => OutputTensor::I64(truncated(v, rows, max_rows)),
+            OutputTensor::U8(v) => OutputTensor::U8(truncated(v, rows, max_rows)),
+            OutputTensor::I8(v) => OutputTensor::I8(truncated(v, rows, max_rows)),
+            OutputTensor::U16(v) => OutputTensor::U16(truncated(v, rows, max_rows)),
+            OutputTensor::I16(v) => OutputTensor::I16(truncated(v, rows, max_rows)),
+            OutputTensor::Bool(v) => OutputTensor::Bool(truncated(v, rows, max_rows)),
         }
     }
 }
@@ -243,6 +358,10 @@ impl TensorProto {
                 tensor.set_data_type(ScalarType::F32.to_datatype().value());
                 tensor.set_float_data(v);
             }
+            OutputTensor::F16(v) => {
+                tensor.set_data_type(ScalarType::F16.to_datatype().value());
+                tensor.set_raw_data(bytemuck::cast_slice(&v).to_vec());
+            }
             OutputTensor::I32(v) => {
                 tensor.set_data_type(ScalarType::I32.to_datatype().value());
                 tensor.set_int32_data(v);
@@ -255,10 +374,68 @@ impl TensorProto {
                 tensor.set_data_type(ScalarType::U8.to_datatype().value());
                 tensor.set_raw_data(v);
             }
+            OutputTensor::F64(v) => {
+                tensor.set_data_type(ScalarType::F64.to_datatype().value());
+                tensor.set_double_data(v);
+            }
+            // Small integer types are stored in int32_data
+            OutputTensor::I8(v) => {
+                tensor.set_data_type(ScalarType::I8.to_datatype().value());
+                tensor.set_int32_data(v.into_iter().map(i32::from).collect());
+            }
+            OutputTensor::U16(v) => {
+                tensor.set_data_type(ScalarType::U16.to_datatype().value());
+                tensor.set_int32_data(v.into_iter().map(i32::from).collect());
+            }
+            OutputTensor::I16(v) => {
+                tensor.set_data_type(ScalarType::I16.to_datatype().value());
+                tensor.set_int32_data(v.into_iter().map(i32::from).collect());
+            }
+            OutputTensor::Bool(v) => {
+                tensor.set_data_type(ScalarType::Bool.to_datatype().value());
+                tensor.set_int32_data(v.into_iter().map(i32::from).collect());
+            }
         }
         tensor.set_dims(dims);
         tensor
     }
+
+    /// Returns the data of a FLOAT16 tensor, which is stored either as raw (little-endian) data or as the bit patterns
+    /// of the values in `int32_data`.
+    pub fn get_f16_data(&self) -> Vec<f16> {
+        self.get_small_int_data(|bits| f16::from_bits(bits as u16))
+    }
+
+    /// Returns the data of a DOUBLE tensor, which is stored either as raw data or in `double_data`.
+    pub fn get_f64_data(&self) -> Vec<f64> {
+        let raw_data = self.get_raw_data();
+        if !raw_data.is_empty() {
+            bytemuck::pod_collect_to_vec(raw_data)
+        } else {
+            self.get_double_data().to_vec()
+        }
+    }
+
+    /// Returns the data of a BOOL tensor, which is stored either as raw data (one byte per value) or in `int32_data`.
+    pub fn get_bool_data(&self) -> Vec<bool> {
+        let raw_data = self.get_raw_data();
+        if !raw_data.is_empty() {
+            raw_data.iter().map(|x| *x != 0).collect()
+        } else {
+            self.get_int32_data().iter().map(|x| *x != 0).collect()
+        }
+    }
+
+    /// Returns the data of a tensor with values smaller than 32 bits (e.g. INT8, UINT16, INT16 or FLOAT16), which is
+    /// stored either as raw data or in `int32_data` (converted to `T` using `from_i32`).
+    pub fn get_small_int_data<T: bytemuck::Pod>(&self, from_i32: fn(i32) -> T) -> Vec<T> {
+        let raw_data = self.get_raw_data();
+        if !raw_data.is_empty() {
+            bytemuck::pod_collect_to_vec(raw_data)
+        } else {
+            self.get_int32_data().iter().map(|x| from_i32(*x)).collect()
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -280,9 +457,15 @@ pub enum DataTypeError {
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ScalarType {
     F32,
+    F16,
+    F64,
     I64,
     I32,
     U8,
+    I8,
+    U16,
+    I16,
+    Bool,
 }
 
 impl ScalarType {
@@ -295,9 +478,15 @@ impl ScalarType {
     pub fn from(onnx: TensorProto_DataType) -> Result<ScalarType, DataTypeError> {
         Ok(match onnx {
             TensorProto_DataType::FLOAT => ScalarType::F32,
+            TensorProto_DataType::FLOAT16 => ScalarType::F16,
+            TensorProto_DataType::DOUBLE => ScalarType::F64,
             TensorProto_DataType::INT64 => ScalarType::I64,
             TensorProto_DataType::INT32 => ScalarType::I32,
             TensorProto_DataType::UINT8 => ScalarType::U8,
+            TensorProto_DataType::INT8 => ScalarType::I8,
+            TensorProto_DataType::UINT16 => ScalarType::U16,
+            TensorProto_DataType::INT16 => ScalarType::I16,
+            TensorProto_DataType::BOOL => ScalarType::Bool,
             _ => return Err(DataTypeError::NotSupported(onnx)),
         })
     }
@@ -305,43 +494,85 @@ impl ScalarType {
     pub fn to_datatype(&self) -> TensorProto_DataType {
         match self {
             ScalarType::F32 => TensorProto_DataType::FLOAT,
+            ScalarType::F16 => TensorProto_DataType::FLOAT16,
+            ScalarType::F64 => TensorProto_DataType::DOUBLE,
             ScalarType::I64 => TensorProto_DataType::INT64,
             ScalarType::I32 => TensorProto_DataType::INT32,
             ScalarType::U8 => TensorProto_DataType::UINT8,
+            ScalarType::I8 => TensorProto_DataType::INT8,
+            ScalarType::U16 => TensorProto_DataType::UINT16,
+            ScalarType::I16 => TensorProto_DataType::INT16,
+            ScalarType::Bool => TensorProto_DataType::BOOL,
         }
     }
 
     pub fn stride(&self) -> usize {
         match self {
             ScalarType::F32 => 4,
+            ScalarType::F16 => 2,
+            ScalarType::F64 => 8,
             ScalarType::I32 => 4,
             ScalarType::I64 => 8,
-            ScalarType::U8 => 1, // ! TODO check this
+            ScalarType::U8 | ScalarType::I8 | ScalarType::Bool => 1,
+            ScalarType::U16 | ScalarType::I16 => 2,
+        }
+    }
+
+    /// The type that is used to store (and calculate with) values of this type on the GPU. Types that are not supported
+    /// by WGSL are stored as 32-bit types; integers and booleans as i32 (possibly overflowing) and f64 as f32.
+    pub fn gpu_type(&self) -> ScalarType {
+        match self {
+            ScalarType::F32 | ScalarType::F16 | ScalarType::I32 => *self,
+            ScalarType::F64 => ScalarType::F32,
+            ScalarType::I64
+            | ScalarType::U8
+            | ScalarType::I8
+            | ScalarType::U16
+            | ScalarType::I16
+            | ScalarType::Bool => ScalarType::I32,
         }
     }
 
     pub fn wgsl_supported(&self) -> bool {
         match self {
             ScalarType::F32 => true,
+            ScalarType::F16 => true, // Requires the 'shader-f16' feature
             ScalarType::I32 => true,
-            ScalarType::I64 => false,
-            ScalarType::U8 => false, // ! TODO check this
+            ScalarType::F64
+            | ScalarType::I64
+            | ScalarType::U8
+            | ScalarType::I8
+            | ScalarType::U16
+            | ScalarType::I16
+            | ScalarType::Bool => false,
         }
     }
 
     pub fn wgsl_type_name(&self) -> &'static str {
         match self {
             ScalarType::F32 => "f32",
+            ScalarType::F16 => "f16",
+            ScalarType::F64 => "f64",
             ScalarType::I32 => "i32",
             ScalarType::I64 => "i64",
-            ScalarType::U8 => "u8", // ! TODO check this
+            ScalarType::U8 => "u8",
+            ScalarType::I8 => "i8",
+            ScalarType::U16 => "u16",
+            ScalarType::I16 => "i16",
+            ScalarType::Bool => "bool",
         }
     }
 
     pub fn is_float(&self) -> bool {
         match self {
-            ScalarType::F32 => true,
-            ScalarType::I32 | ScalarType::I64 | ScalarType::U8 => false,
+            ScalarType::F32 | ScalarType::F16 | ScalarType::F64 => true,
+            ScalarType::I32
+            | ScalarType::I64
+            | ScalarType::U8
+            | ScalarType::I8
+            | ScalarType::U16
+            | ScalarType::I16
+            | ScalarType::Bool => false,
         }
     }
 }
@@ -700,6 +931,14 @@ impl From<String> for onnx::AttributeProto {
     }
 }
 
+impl From<Vec<String>> for onnx::AttributeProto {
+    fn from(value: Vec<String>) -> Self {
+        let mut attributes = crate::onnx::AttributeProto::new();
+        attributes.set_strings(value.into_iter().map(|s| s.into_bytes()).collect());
+        attributes
+    }
+}
+
 impl From<&str> for onnx::AttributeProto {
     fn from(value: &str) -> Self {
         let mut attributes = crate::onnx::AttributeProto::new();
@@ -716,6 +955,20 @@ impl From<TensorProto> for onnx::AttributeProto {
     }
 }
 
+impl From<onnx::GraphProto> for onnx::AttributeProto {
+    fn from(value: onnx::GraphProto) -> Self {
+        let mut attributes = crate::onnx::AttributeProto::new();
+        attributes.set_g(value);
+        attributes
+    }
+}
+
+impl From<onnx::AttributeProto> for onnx::GraphProto {
+    fn from(value: onnx::AttributeProto) -> Self {
+        value.get_g().clone()
+    }
+}
+
 impl From<onnx::AttributeProto> for Vec<i64> {
     fn from(value: onnx::AttributeProto) -> Self {
         value.get_ints().to_vec()
@@ -752,6 +1005,16 @@ impl From<onnx::AttributeProto> for String {
     }
 }
 
+impl From<onnx::AttributeProto> for Vec<String> {
+    fn from(value: onnx::AttributeProto) -> Self {
+        value
+            .get_strings()
+            .iter()
+            .map(|s| from_utf8(s).unwrap().to_string())
+            .collect()
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum OpsetError {
     #[error("more than one ONNX opset was specified: {0} and {1}")]
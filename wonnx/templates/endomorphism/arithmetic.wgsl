@@ -1,5 +1,9 @@
 {%- include "structs.wgsl" -%}
 
+struct OutputArrayVector {
+	data: array<vec4<{{ output_type }}>>
+};
+
 @group(0) @binding(0)
 var<storage, read> input_0: ArrayVector;
 
@@ -9,12 +13,12 @@ var<storage, read> input_0: ArrayVector;
 var<storage, read> input_1: ArrayVector;
 
 @group(0) @binding(2)
-var<storage, read_write> output_0: ArrayVector;
+var<storage, read_write> output_0: OutputArrayVector;
 
 {% else %}
 
 @group(0) @binding(1)
-var<storage, read_write> output_0: ArrayVector;
+var<storage, read_write> output_0: OutputArrayVector;
 
 {% endif %}
 
@@ -28,6 +32,8 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 		{% elif op_type == "PRelu" %}
 			output_0.data[gidx] = max(input_0.data[gidx], Vec4(Scalar(), Scalar(), Scalar(), Scalar()))
 	                            + min(input_0.data[gidx], Vec4(Scalar(), Scalar(), Scalar(), Scalar())) * input_1.data[gidx];
+		{% elif comparison %}
+			output_0.data[gidx] = vec4<{{ output_type }}>(input_0.data[gidx] {{ op_type }} input_1.data[gidx]);
 		{% else %}
 			output_0.data[gidx] = input_0.data[gidx] {{ op_type }} input_1.data[gidx];
 		{% endif %}
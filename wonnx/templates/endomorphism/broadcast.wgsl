@@ -1,48 +1,99 @@
 {%- include "structs.wgsl" -%}
 
-@group(0) @binding(0)
-var<storage, read> input_0: Array;
+struct OutputArray {
+	data: array<{{ output_type }}>
+};
 
-@group(0) @binding(1)
-var<storage, read> input_1: Array;
+{% if op_type == "Where" %}
+	struct Conditions {
+		data: array<i32>
+	};
 
-@group(0) @binding(2)
-var<storage, read_write> output_0: Array;
+	@group(0) @binding(0)
+	var<storage, read> input_0: Conditions;
+
+	@group(0) @binding(1)
+	var<storage, read> input_1: Array;
+
+	@group(0) @binding(2)
+	var<storage, read> input_2: Array;
+{% else %}
+	{% for shape in input_padded_shapes %}
+		@group({{ loop.index0 / 4 | int }}) @binding({{ loop.index0 % 4 }})
+		var<storage, read> input_{{ loop.index0 }}: Array;
+	{% endfor %}
+{% endif %}
+
+{% set input_count = input_padded_shapes | length %}
+@group({{ input_count / 4 | int }}) @binding({{ input_count % 4 }})
+var<storage, read_write> output_0: OutputArray;
 
 @compute @workgroup_size({{ workgroup_size_x }}, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 	let gidx = global_id.x;
 
-	{# We will be called for each element in the output tensor. Determine the corresponding indices in the source tensors #}
-	var lhs_index = 0u;
-	var rhs_index = 0u;
-	var rest = gidx;
-	{% for dim in o_shape[0] %}
-		{% if dim > 1 %}
-		{
-			let out_index = rest / {{ o_chunks[0][loop.index0] }}u;
-
-			{% if lhs_padded_shape[loop.index0] > 1 %}
-				lhs_index = lhs_index + (out_index * {{ lhs_padded_chunks[loop.index0] }}u);
+	if (gidx < {{ o_lens[0] }}u) {
+		{# We will be called for each element in the output tensor. Determine the corresponding indices in the source tensors #}
+		{% for shape in input_padded_shapes %}
+			var index_{{ loop.index0 }} = 0u;
+		{% endfor %}
+		var rest = gidx;
+		{% for dim in o_shape[0] %}
+			{% if dim > 1 %}
+			{
+				{% set axis = loop.index0 %}
+				let out_index = rest / {{ o_chunks[0][axis] }}u;
+
+				{% for shape in input_padded_shapes %}
+					{% if shape[axis] > 1 %}
+						{% set chunks = input_padded_chunks[loop.index0] %}
+						{% if op_type == "Tile" %}
+							index_{{ loop.index0 }} = index_{{ loop.index0 }} + ((out_index % {{ shape[axis] }}u) * {{ chunks[axis] }}u);
+						{% else %}
+							index_{{ loop.index0 }} = index_{{ loop.index0 }} + (out_index * {{ chunks[axis] }}u);
+						{% endif %}
+					{% endif %}
+				{% endfor %}
+				rest = rest % {{ o_chunks[0][axis] }}u;
+			}
 			{% endif %}
+		{% endfor %}
 
-			{% if rhs_padded_shape[loop.index0] > 1 %}
-				rhs_index = rhs_index + (out_index * {{ rhs_padded_chunks[loop.index0] }}u);
+		{% if op_type == "Where" %}
+			output_0.data[gidx] = select(input_2.data[index_2], input_1.data[index_1], input_0.data[index_0] != 0);
+		{% elif op_type == "Expand" or op_type == "Tile" %}
+			output_0.data[gidx] = input_0.data[index_0];
+		{% elif op_type == "Sum" or op_type == "Mean" or op_type == "Max" or op_type == "Min" %}
+			var result = input_0.data[index_0];
+			{% for shape in input_padded_shapes %}
+				{% if not loop.first %}
+					{% if op_type == "Max" %}
+						result = max(result, input_{{ loop.index0 }}.data[index_{{ loop.index0 }}]);
+					{% elif op_type == "Min" %}
+						result = min(result, input_{{ loop.index0 }}.data[index_{{ loop.index0 }}]);
+					{% else %}
+						result = result + input_{{ loop.index0 }}.data[index_{{ loop.index0 }}];
+					{% endif %}
+				{% endif %}
+			{% endfor %}
+			{% if op_type == "Mean" %}
+				result = result / Scalar({{ input_count }});
 			{% endif %}
-			rest = rest % {{ o_chunks[0][loop.index0] }}u;
-		}
-		{% endif %}
-	{% endfor %}
+			output_0.data[gidx] = result;
+		{% else %}
+			let lhs = input_0.data[index_0];
+			let rhs = input_1.data[index_1];
 
-	let lhs = input_0.data[lhs_index];
-	let rhs = input_1.data[rhs_index];
-
-	{% if op_type == "Pow" %}
-		output_0.data[gidx] = pow(lhs, rhs);
-	{% elif op_type == "PRelu" %}
-		output_0.data[gidx] = max(lhs, Scalar())
-							+ min(lhs, Scalar()) * rhs;
-	{% else %}
-		output_0.data[gidx] = (lhs {{ op_type }} rhs);
-	{% endif %}
+			{% if op_type == "Pow" %}
+				output_0.data[gidx] = pow(lhs, rhs);
+			{% elif op_type == "PRelu" %}
+				output_0.data[gidx] = max(lhs, Scalar())
+									+ min(lhs, Scalar()) * rhs;
+			{% elif comparison %}
+				output_0.data[gidx] = {{ output_type }}(lhs {{ op_type }} rhs);
+			{% else %}
+				output_0.data[gidx] = (lhs {{ op_type }} rhs);
+			{% endif %}
+		{% endif %}
+	}
 }
@@ -0,0 +1,42 @@
+{%- include "structs.wgsl" -%}
+
+struct Indices {
+	data: array<i32>
+};
+
+@group(0) @binding(0)
+var<storage, read> input_0: Array; // data
+
+@group(0) @binding(1)
+var<storage, read> input_1: Indices; // indices
+
+@group(0) @binding(2)
+var<storage, read_write> output_0: Array;
+
+@compute @workgroup_size({{ workgroup_size_x }}, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ o_lens[0] }}u) {
+		var index = input_1.data[gidx];
+		if (index < 0) {
+			index = {{ axis_size }} + index;
+		}
+
+		{# The data element has the same coordinates as the output element, except along the axis (where the index is used) #}
+		var data_index = 0u;
+		var rest = gidx;
+		{% for chunk in o_chunks[0] %}
+		{
+			var coordinate = rest / {{ chunk }}u;
+			rest = rest % {{ chunk }}u;
+			{% if loop.index0 == axis %}
+				coordinate = u32(index);
+			{% endif %}
+			data_index = data_index + coordinate * {{ i_chunks[0][loop.index0] }}u;
+		}
+		{% endfor %}
+
+		output_0.data[gidx] = input_0.data[data_index];
+	}
+}
@@ -0,0 +1,38 @@
+{%- include "structs.wgsl" -%}
+
+struct Indices {
+	data: array<i32>
+};
+
+@group(0) @binding(0)
+var<storage, read> input_0: Array; // data
+
+@group(0) @binding(1)
+var<storage, read> input_1: Indices; // indices
+
+@group(0) @binding(2)
+var<storage, read_write> output_0: Array;
+
+@compute @workgroup_size({{ workgroup_size_x }}, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ o_lens[0] }}u) {
+		{# Each output element is part of the slice selected by an index tuple (within a batch) #}
+		let tuple = gidx / {{ slice_size }}u;
+		let batch = tuple / {{ tuples_per_batch }}u;
+		var data_index = batch * {{ batch_stride }}u + gidx % {{ slice_size }}u;
+
+		{% for stride in index_strides %}
+		{
+			var index = input_1.data[tuple * {{ index_strides | length }}u + {{ loop.index0 }}u];
+			if (index < 0) {
+				index = {{ index_dims[loop.index0] }} + index;
+			}
+			data_index = data_index + u32(index) * {{ stride }}u;
+		}
+		{% endfor %}
+
+		output_0.data[gidx] = input_0.data[data_index];
+	}
+}
@@ -0,0 +1,45 @@
+{%- include "structs.wgsl" -%}
+
+@group(0) @binding(0)
+var<storage, read> input_0: Array;
+
+{% if op_type == "BiasGelu" %}
+	@group(0) @binding(1)
+	var<storage, read> input_1: Array;
+
+	@group(0) @binding(2)
+	var<storage, read_write> output_0: Array;
+{% else %}
+	@group(0) @binding(1)
+	var<storage, read_write> output_0: Array;
+{% endif %}
+
+{# WGSL has no erf; this approximation (Abramowitz and Stegun 7.1.26) has a maximum error of 1.5e-7 #}
+fn erf(x: f32) -> f32 {
+	let t = 1.0 / (1.0 + 0.3275911 * abs(x));
+	let polynomial = ((((1.061405429 * t - 1.453152027) * t + 1.421413741) * t - 0.284496736) * t + 0.254829592) * t;
+	let y = 1.0 - polynomial * exp(-x * x);
+	return select(-y, y, x >= 0.0);
+}
+
+@compute @workgroup_size({{ workgroup_size_x }})
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ o_lens[0] }}u) {
+		{% if op_type == "BiasGelu" %}
+			{# The bias is broadcast along the last dimension #}
+			let x = f32(input_0.data[gidx]) + f32(input_1.data[gidx % {{ i_lens[1] }}u]);
+		{% else %}
+			let x = f32(input_0.data[gidx]);
+		{% endif %}
+
+		{% if approximate == "tanh" %}
+			{# Tanh produces NaNs for large inputs, but converges to -1 and 1 #}
+			let inner = clamp(0.7978845608 * (x + 0.044715 * x * x * x), -10.0, 10.0);
+			output_0.data[gidx] = Scalar(0.5 * x * (1.0 + tanh(inner)));
+		{% else %}
+			output_0.data[gidx] = Scalar(0.5 * x * (1.0 + erf(x * 0.7071067812)));
+		{% endif %}
+	}
+}
@@ -0,0 +1,44 @@
+{%- include "structs.wgsl" -%}
+
+{# InstanceNormalization is GroupNormalization with a group for each channel #}
+@group(0) @binding(0)
+var<storage, read> input_0: Array; // X
+
+@group(0) @binding(1)
+var<storage, read> input_1: Array; // scale
+
+@group(0) @binding(2)
+var<storage, read> input_2: Array; // B (bias)
+
+@group(0) @binding(3)
+var<storage, read_write> output_0: Array;
+
+@compute @workgroup_size({{ workgroup_size_x }})
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	{# Each thread normalizes the (contiguous) values of one group of channels in one batch #}
+	let row = global_id.x;
+
+	if (row < {{ rows }}u) {
+		let start = row * {{ group_size }}u;
+		var mean = 0.0;
+		for(var i: u32 = 0u; i < {{ group_size }}u; i = i + 1u) {
+			mean = mean + f32(input_0.data[start + i]);
+		}
+		mean = mean / {{ group_size }}.0;
+
+		var variance = 0.0;
+		for(var i: u32 = 0u; i < {{ group_size }}u; i = i + 1u) {
+			let deviation = f32(input_0.data[start + i]) - mean;
+			variance = variance + deviation * deviation;
+		}
+		let inverse_std_dev = 1.0 / sqrt(variance / {{ group_size }}.0 + {{ epsilon }});
+
+		let first_channel = (row % {{ num_groups }}u) * {{ channels_per_group }}u;
+		for(var i: u32 = 0u; i < {{ group_size }}u; i = i + 1u) {
+			{# Scale and bias are either per channel or per group #}
+			let parameter = (first_channel + i / {{ spatial_size }}u) / {{ channels_per_parameter }}u;
+			let normalized = (f32(input_0.data[start + i]) - mean) * inverse_std_dev;
+			output_0.data[start + i] = Scalar(normalized * f32(input_1.data[parameter]) + f32(input_2.data[parameter]));
+		}
+	}
+}
@@ -0,0 +1,66 @@
+{%- include "structs.wgsl" -%}
+
+{# LayerNormalization: X, Scale, B (the fused form created by the optimizer may lack Scale and B)
+   SkipLayerNormalization: input, skip, gamma, beta, bias #}
+{% set input_count = i_lens | length %}
+{% for input in i_lens %}
+	@group({{ loop.index0 / 4 | int }}) @binding({{ loop.index0 % 4 }})
+	var<storage, read> input_{{ loop.index0 }}: Array;
+{% endfor %}
+
+@group({{ input_count / 4 | int }}) @binding({{ input_count % 4 }})
+var<storage, read_write> output_0: Array;
+
+{% if op_type == "SkipLayerNormalization" %}
+	fn value(index: u32) -> f32 {
+		{# The skip input may be broadcast along the batch dimension #}
+		var value = f32(input_0.data[index]) + f32(input_1.data[index % {{ i_lens[1] }}u]);
+		{% if input_count > 4 %}
+			value = value + f32(input_4.data[index % {{ i_lens[4] }}u]);
+		{% endif %}
+		return value;
+	}
+{% else %}
+	fn value(index: u32) -> f32 {
+		return f32(input_0.data[index]);
+	}
+{% endif %}
+
+@compute @workgroup_size({{ workgroup_size_x }})
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let row = global_id.x;
+
+	if (row < {{ i_lens[0] / normalized_size | int }}u) {
+		let start = row * {{ normalized_size }}u;
+		var mean = 0.0;
+		for(var i: u32 = 0u; i < {{ normalized_size }}u; i = i + 1u) {
+			mean = mean + value(start + i);
+		}
+		mean = mean / {{ normalized_size }}.0;
+
+		var variance = 0.0;
+		for(var i: u32 = 0u; i < {{ normalized_size }}u; i = i + 1u) {
+			let deviation = value(start + i) - mean;
+			variance = variance + deviation * deviation;
+		}
+		let inverse_std_dev = 1.0 / sqrt(variance / {{ normalized_size }}.0 + {{ epsilon }});
+
+		{% if op_type == "SkipLayerNormalization" %}
+			{% set scale_index = 2 %}
+			{% set bias_index = 3 %}
+		{% else %}
+			{% set scale_index = 1 %}
+			{% set bias_index = 2 %}
+		{% endif %}
+		for(var i: u32 = 0u; i < {{ normalized_size }}u; i = i + 1u) {
+			var normalized = (value(start + i) - mean) * inverse_std_dev;
+			{% if input_count > scale_index %}
+				normalized = normalized * f32(input_{{ scale_index }}.data[i % {{ i_lens[scale_index] }}u]);
+			{% endif %}
+			{% if input_count > bias_index %}
+				normalized = normalized + f32(input_{{ bias_index }}.data[i % {{ i_lens[bias_index] }}u]);
+			{% endif %}
+			output_0.data[start + i] = Scalar(normalized);
+		}
+	}
+}
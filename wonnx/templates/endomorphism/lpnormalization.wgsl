@@ -0,0 +1,39 @@
+{%- include "structs.wgsl" -%}
+
+@group(0) @binding(0)
+var<storage, read> input_0: Array;
+
+@group(0) @binding(1)
+var<storage, read_write> output_0: Array;
+
+@compute @workgroup_size({{ workgroup_size_x }})
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	{# Each thread normalizes the values along the axis that start at one position in the outer and inner dimensions #}
+	let line = global_id.x;
+
+	if (line < {{ lines }}u) {
+		let start = (line / {{ inner_size }}u) * {{ outer_stride }}u + line % {{ inner_size }}u;
+		var norm = 0.0;
+		for(var i: u32 = 0u; i < {{ axis_size }}u; i = i + 1u) {
+			let value = f32(input_0.data[start + i * {{ inner_size }}u]);
+			{% if p == 1 %}
+				norm = norm + abs(value);
+			{% else %}
+				norm = norm + value * value;
+			{% endif %}
+		}
+		{% if p == 2 %}
+			norm = sqrt(norm);
+		{% endif %}
+
+		{# Values that are all zero are left unchanged #}
+		if (norm == 0.0) {
+			norm = 1.0;
+		}
+
+		for(var i: u32 = 0u; i < {{ axis_size }}u; i = i + 1u) {
+			let index = start + i * {{ inner_size }}u;
+			output_0.data[index] = Scalar(f32(input_0.data[index]) / norm);
+		}
+	}
+}
@@ -0,0 +1,51 @@
+{%- include "structs.wgsl" -%}
+
+struct Integers {
+	data: array<i32>
+};
+
+struct Floats {
+	data: array<{{ float_type }}>
+};
+
+{% if op_type == "QuantizeLinear" %}
+	{% set scale = y_scale %}
+	{% set zero_point = y_zero_point %}
+
+	@group(0) @binding(0)
+	var<storage, read> input_0: Floats;
+
+	@group(0) @binding(1)
+	var<storage, read_write> output_0: Integers;
+{% else %}
+	{% set scale = x_scale %}
+	{% set zero_point = x_zero_point %}
+
+	@group(0) @binding(0)
+	var<storage, read> input_0: Integers;
+
+	@group(0) @binding(1)
+	var<storage, read_write> output_0: Floats;
+{% endif %}
+
+var<private> scale: array<f32, {{ scale | length }}> = array<f32, {{ scale | length }}>({{ scale | join(sep=", ") }});
+var<private> zero_point: array<i32, {{ zero_point | length }}> = array<i32, {{ zero_point | length }}>({{ zero_point | join(sep=", ") }});
+
+@compute @workgroup_size({{ workgroup_size_x }})
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ o_lens[0] }}u) {
+		{# Scales and zero points either are scalars or apply to slices along an axis #}
+		let axis_index = (gidx / {{ axis_chunk }}u) % {{ axis_dim }}u;
+		let s = scale[{% if scale | length > 1 %}axis_index{% else %}0u{% endif %}];
+		let z = zero_point[{% if zero_point | length > 1 %}axis_index{% else %}0u{% endif %}];
+
+		{% if op_type == "QuantizeLinear" %}
+			let quantized = round(f32(input_0.data[gidx]) / s) + f32(z);
+			output_0.data[gidx] = i32(clamp(quantized, f32({{ q_min }}), f32({{ q_max }})));
+		{% else %}
+			output_0.data[gidx] = {{ float_type }}(f32(input_0.data[gidx] - z) * s);
+		{% endif %}
+	}
+}
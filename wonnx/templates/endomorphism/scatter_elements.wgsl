@@ -0,0 +1,74 @@
+{%- include "structs.wgsl" -%}
+
+struct Indices {
+	data: array<i32>
+};
+
+@group(0) @binding(0)
+var<storage, read> input_0: Array; // data
+
+@group(0) @binding(1)
+var<storage, read> input_1: Indices; // indices
+
+@group(0) @binding(2)
+var<storage, read> input_2: Array; // updates
+
+@group(0) @binding(3)
+var<storage, read_write> output_0: Array;
+
+@compute @workgroup_size({{ workgroup_size_x }}, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ o_lens[0] }}u) {
+		var result = input_0.data[gidx];
+
+		{# 
+			Each thread gathers the updates for its own output element (so that reductions need no atomics). Only updates
+			with the same coordinates (except along the axis) can target this element.
+		#}
+		var update_index = 0u;
+		var position = 0;
+		var in_range = true;
+		var rest = gidx;
+		{% for chunk in o_chunks[0] %}
+		{
+			let coordinate = rest / {{ chunk }}u;
+			rest = rest % {{ chunk }}u;
+			{% if loop.index0 == axis %}
+				position = i32(coordinate);
+			{% else %}
+				in_range = in_range && coordinate < {{ i_shape[1][loop.index0] }}u;
+				update_index = update_index + coordinate * {{ i_chunks[1][loop.index0] }}u;
+			{% endif %}
+		}
+		{% endfor %}
+
+		if (in_range) {
+			for (var i = 0u; i < {{ updates_along_axis }}u; i = i + 1u) {
+				let u = update_index + i * {{ i_chunks[1][axis] }}u;
+				var index = input_1.data[u];
+				if (index < 0) {
+					index = {{ axis_size }} + index;
+				}
+
+				if (index == position) {
+					let update = input_2.data[u];
+					{% if reduction == "add" %}
+						result = result + update;
+					{% elif reduction == "mul" %}
+						result = result * update;
+					{% elif reduction == "max" %}
+						result = max(result, update);
+					{% elif reduction == "min" %}
+						result = min(result, update);
+					{% else %}
+						result = update;
+					{% endif %}
+				}
+			}
+		}
+
+		output_0.data[gidx] = result;
+	}
+}
@@ -0,0 +1,62 @@
+{%- include "structs.wgsl" -%}
+
+struct Indices {
+	data: array<i32>
+};
+
+@group(0) @binding(0)
+var<storage, read> input_0: Array; // data
+
+@group(0) @binding(1)
+var<storage, read> input_1: Indices; // indices
+
+@group(0) @binding(2)
+var<storage, read> input_2: Array; // updates
+
+@group(0) @binding(3)
+var<storage, read_write> output_0: Array;
+
+@compute @workgroup_size({{ workgroup_size_x }}, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ o_lens[0] }}u) {
+		var result = input_0.data[gidx];
+
+		{# 
+			Each thread gathers the updates for its own output element (so that reductions need no atomics) by checking
+			which index tuples select the slice this element is part of.
+		#}
+		let slice_offset = gidx % {{ slice_size }}u;
+		let slice_start = gidx - slice_offset;
+		for (var tuple = 0u; tuple < {{ tuples }}u; tuple = tuple + 1u) {
+			var start = 0u;
+			{% for stride in index_strides %}
+			{
+				var index = input_1.data[tuple * {{ index_strides | length }}u + {{ loop.index0 }}u];
+				if (index < 0) {
+					index = {{ index_dims[loop.index0] }} + index;
+				}
+				start = start + u32(index) * {{ stride }}u;
+			}
+			{% endfor %}
+
+			if (start == slice_start) {
+				let update = input_2.data[tuple * {{ slice_size }}u + slice_offset];
+				{% if reduction == "add" %}
+					result = result + update;
+				{% elif reduction == "mul" %}
+					result = result * update;
+				{% elif reduction == "max" %}
+					result = max(result, update);
+				{% elif reduction == "min" %}
+					result = min(result, update);
+				{% else %}
+					result = update;
+				{% endif %}
+			}
+		}
+
+		output_0.data[gidx] = result;
+	}
+}
@@ -0,0 +1,117 @@
+{%- include "structs.wgsl" -%}
+
+@group(0) @binding(0)
+var<storage, read> input_0: Array; // input (batch_size, sequence_length, input_size)
+
+@group(0) @binding(1)
+var<storage, read> input_1: Array; // weights (input_size, 3 * hidden_size)
+
+@group(0) @binding(2)
+var<storage, read> input_2: Array; // bias (3 * hidden_size)
+
+{% if i_lens | length > 3 %}
+	struct Integers {
+		data: array<i32>
+	};
+
+	@group(0) @binding(3)
+	var<storage, read> input_3: Integers; // mask (batch_size, sequence_length)
+
+	@group(1) @binding(0)
+	var<storage, read_write> output_0: Array;
+{% else %}
+	@group(0) @binding(3)
+	var<storage, read_write> output_0: Array;
+{% endif %}
+
+{# Scores are calculated without materializing the keys and values: q.k_t = x_t.(W_k q) + b_k.q, and the weighted sum
+of values is (sum_t p_t x_t).W_v + b_v #}
+var<private> query: array<f32, {{ head_size }}>;
+var<private> weighted_query: array<f32, {{ input_size }}>;
+var<private> weighted_input: array<f32, {{ input_size }}>;
+
+fn score(batch: u32, t: u32, query_bias: f32) -> f32 {
+	let base = (batch * {{ sequence_length }}u + t) * {{ input_size }}u;
+	var score = query_bias;
+	for(var i: u32 = 0u; i < {{ input_size }}u; i = i + 1u) {
+		score = score + f32(input_0.data[base + i]) * weighted_query[i];
+	}
+	return score * {{ scale }};
+}
+
+fn attends(batch: u32, s: u32, t: u32) -> bool {
+	var attends = true;
+	{% if unidirectional %}
+		attends = attends && t <= s;
+	{% endif %}
+	{% if i_lens | length > 3 %}
+		attends = attends && input_3.data[batch * {{ sequence_length }}u + t] != 0;
+	{% endif %}
+	return attends;
+}
+
+@compute @workgroup_size({{ workgroup_size_x }})
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ o_lens[0] / head_size | int }}u) {
+		let batch = gidx / {{ num_heads * sequence_length }}u;
+		let head = (gidx / {{ sequence_length }}u) % {{ num_heads }}u;
+		let s = gidx % {{ sequence_length }}u;
+		let input_base = (batch * {{ sequence_length }}u + s) * {{ input_size }}u;
+		let head_offset = head * {{ head_size }}u;
+
+		// Query for this element and head
+		for(var j: u32 = 0u; j < {{ head_size }}u; j = j + 1u) {
+			var q = f32(input_2.data[head_offset + j]);
+			for(var i: u32 = 0u; i < {{ input_size }}u; i = i + 1u) {
+				q = q + f32(input_0.data[input_base + i]) * f32(input_1.data[i * {{ 3 * hidden_size }}u + head_offset + j]);
+			}
+			query[j] = q;
+		}
+
+		// Project the query onto the key weights
+		var query_bias = 0.0;
+		for(var j: u32 = 0u; j < {{ head_size }}u; j = j + 1u) {
+			query_bias = query_bias + query[j] * f32(input_2.data[{{ hidden_size }}u + head_offset + j]);
+		}
+		for(var i: u32 = 0u; i < {{ input_size }}u; i = i + 1u) {
+			var w = 0.0;
+			for(var j: u32 = 0u; j < {{ head_size }}u; j = j + 1u) {
+				w = w + query[j] * f32(input_1.data[i * {{ 3 * hidden_size }}u + {{ hidden_size }}u + head_offset + j]);
+			}
+			weighted_query[i] = w;
+			weighted_input[i] = 0.0;
+		}
+
+		// Softmax over the scores of all attended elements
+		var max_score = -3.402823e+38;
+		for(var t: u32 = 0u; t < {{ sequence_length }}u; t = t + 1u) {
+			if (attends(batch, s, t)) {
+				max_score = max(max_score, score(batch, t, query_bias));
+			}
+		}
+		var sum = 0.0;
+		for(var t: u32 = 0u; t < {{ sequence_length }}u; t = t + 1u) {
+			if (attends(batch, s, t)) {
+				let p = exp(score(batch, t, query_bias) - max_score);
+				sum = sum + p;
+				let base = (batch * {{ sequence_length }}u + t) * {{ input_size }}u;
+				for(var i: u32 = 0u; i < {{ input_size }}u; i = i + 1u) {
+					weighted_input[i] = weighted_input[i] + p * f32(input_0.data[base + i]);
+				}
+			}
+		}
+		let normalizer = select(0.0, 1.0 / sum, sum > 0.0);
+
+		// Weighted sum of the values
+		let output_base = (batch * {{ sequence_length }}u + s) * {{ hidden_size }}u + head_offset;
+		for(var j: u32 = 0u; j < {{ head_size }}u; j = j + 1u) {
+			var v = 0.0;
+			for(var i: u32 = 0u; i < {{ input_size }}u; i = i + 1u) {
+				v = v + weighted_input[i] * f32(input_1.data[i * {{ 3 * hidden_size }}u + {{ 2 * hidden_size }}u + head_offset + j]);
+			}
+			output_0.data[output_base + j] = Scalar(f32(input_2.data[{{ 2 * hidden_size }}u + head_offset + j]) + v * normalizer);
+		}
+	}
+}
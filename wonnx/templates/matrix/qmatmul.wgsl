@@ -0,0 +1,53 @@
+{%- include "structs.wgsl" -%}
+
+struct Integers {
+	data: array<i32>
+};
+
+@group(0) @binding(0)
+var<storage, read> input_0: Integers; // A
+
+@group(0) @binding(1)
+var<storage, read> input_1: Integers; // B
+
+@group(0) @binding(2)
+var<storage, read_write> output_0: Integers;
+
+var<private> a_zero_point: array<i32, {{ a_zero_point | length }}> = array<i32, {{ a_zero_point | length }}>({{ a_zero_point | join(sep=", ") }});
+var<private> b_zero_point: array<i32, {{ b_zero_point | length }}> = array<i32, {{ b_zero_point | length }}>({{ b_zero_point | join(sep=", ") }});
+
+{% if op_type == "QLinearMatMul" %}
+	var<private> a_scale: array<f32, {{ a_scale | length }}> = array<f32, {{ a_scale | length }}>({{ a_scale | join(sep=", ") }});
+	var<private> b_scale: array<f32, {{ b_scale | length }}> = array<f32, {{ b_scale | length }}>({{ b_scale | join(sep=", ") }});
+{% endif %}
+
+@compute @workgroup_size({{ workgroup_size_x }})
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ o_lens[0] }}u) {
+		let stack_index = gidx / {{ dim_m * dim_n }}u;
+		let row = (gidx / {{ dim_n }}u) % {{ dim_m }}u;
+		let column = gidx % {{ dim_n }}u;
+
+		{# Zero points (and scales) of A may be specified per row, those of B per column #}
+		let a_zp = a_zero_point[{% if a_zero_point | length > 1 %}row{% else %}0u{% endif %}];
+		let b_zp = b_zero_point[{% if b_zero_point | length > 1 %}column{% else %}0u{% endif %}];
+
+		let a_base = stack_index * {{ a_batch_stride }}u + row * {{ dim_k }}u;
+		let b_base = stack_index * {{ b_batch_stride }}u + column;
+		var accumulator: i32 = 0;
+		for(var k: u32 = 0u; k < {{ dim_k }}u; k = k + 1u) {
+			accumulator = accumulator + (input_0.data[a_base + k] - a_zp) * (input_1.data[b_base + k * {{ dim_n }}u] - b_zp);
+		}
+
+		{% if op_type == "QLinearMatMul" %}
+			let a_s = a_scale[{% if a_scale | length > 1 %}row{% else %}0u{% endif %}];
+			let b_s = b_scale[{% if b_scale | length > 1 %}column{% else %}0u{% endif %}];
+			let quantized = round(f32(accumulator) * a_s * b_s / f32({{ y_scale[0] }})) + f32({{ y_zero_point[0] }});
+			output_0.data[gidx] = i32(clamp(quantized, f32({{ q_min }}), f32({{ q_max }})));
+		{% else %}
+			output_0.data[gidx] = accumulator;
+		{% endif %}
+	}
+}
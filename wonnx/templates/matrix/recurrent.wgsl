@@ -0,0 +1,272 @@
+{%- include "structs.wgsl" -%}
+
+struct Integers {
+	data: array<i32>
+};
+
+{# Inputs are X, W, R and the optional inputs that are present (B, sequence_lens, initial_h, initial_c, P) #}
+{% for role in input_roles %}
+	@group({{ loop.index0 / 4 | int }}) @binding({{ loop.index0 % 4 }})
+	var<storage, read> input_{{ role }}: {% if role == "sequence_lens" %}Integers{% else %}Array{% endif %};
+{% endfor %}
+
+{# Outputs are Y, Y_h and Y_c (optional outputs that are left out still have a binding, but are not written to) #}
+{% set input_count = input_roles | length %}
+{% for output in o_lens %}
+	{% set binding = input_count + loop.index0 %}
+	@group({{ binding / 4 | int }}) @binding({{ binding % 4 }})
+	var<storage, read_write> output_{{ loop.index0 }}: Array;
+{% endfor %}
+
+{% set gate_rows = gates * hidden_size %}
+
+{# The hidden state of the previous step is read by all threads in the workgroup #}
+var<workgroup> hidden: array<f32, {{ hidden_size }}>;
+{% if op_type == "GRU" and not linear_before_reset %}
+	var<workgroup> reset_hidden: array<f32, {{ hidden_size }}>;
+{% endif %}
+
+var<private> next_hidden: array<f32, {{ units_per_thread }}>;
+var<private> cell: array<f32, {{ units_per_thread }}>;
+
+{% for activation in activations %}
+	fn activation_{{ loop.index0 }}(x: f32) -> f32 {
+		let alpha = f32({{ activation.alpha }});
+		let beta = f32({{ activation.beta }});
+		{% if activation.name == "Sigmoid" %}
+			return 1.0 / (1.0 + exp(-x));
+		{% elif activation.name == "Tanh" %}
+			{# Tanh will produce NaNs for large inputs, but converges to -1 and 1 #}
+			return tanh(clamp(x, -10.0, 10.0));
+		{% elif activation.name == "Relu" %}
+			return max(x, 0.0);
+		{% elif activation.name == "LeakyRelu" %}
+			return select(alpha * x, x, x >= 0.0);
+		{% elif activation.name == "ThresholdedRelu" %}
+			return select(0.0, x, x > alpha);
+		{% elif activation.name == "ScaledTanh" %}
+			return alpha * tanh(clamp(beta * x, -10.0, 10.0));
+		{% elif activation.name == "HardSigmoid" %}
+			return clamp(alpha * x + beta, 0.0, 1.0);
+		{% elif activation.name == "Elu" %}
+			return select(alpha * (exp(x) - 1.0), x, x >= 0.0);
+		{% elif activation.name == "Softsign" %}
+			return x / (1.0 + abs(x));
+		{% elif activation.name == "Softplus" %}
+			return log(1.0 + exp(x));
+		{% elif activation.name == "Affine" %}
+			return alpha * x + beta;
+		{% endif %}
+	}
+{% endfor %}
+
+{# Applies the activation function in the specified slot (e.g. f, g or h for LSTM) for a direction #}
+fn activate(slot: u32, direction: u32, x: f32) -> f32 {
+	switch (direction * {{ activation_slots }}u + slot) {
+		{% for activation in activations %}
+			case {{ loop.index0 }}u: {
+				return activation_{{ loop.index0 }}(x);
+			}
+		{% endfor %}
+		default: {}
+	}
+	return x;
+}
+
+fn clipped(x: f32) -> f32 {
+	{% if has_clip %}
+		return clamp(x, -f32({{ clip }}), f32({{ clip }}));
+	{% else %}
+		return x;
+	{% endif %}
+}
+
+fn input_product(direction: u32, row: u32, x_base: u32) -> f32 {
+	let w_base = (direction * {{ gate_rows }}u + row) * {{ input_size }}u;
+	var sum = 0.0;
+	for(var i: u32 = 0u; i < {{ input_size }}u; i = i + 1u) {
+		sum = sum + f32(input_X.data[x_base + i]) * f32(input_W.data[w_base + i]);
+	}
+	{% if "B" in input_roles %}
+		sum = sum + f32(input_B.data[direction * {{ 2 * gate_rows }}u + row]);
+	{% endif %}
+	return sum;
+}
+
+fn hidden_product(direction: u32, row: u32) -> f32 {
+	let r_base = (direction * {{ gate_rows }}u + row) * {{ hidden_size }}u;
+	var sum = 0.0;
+	for(var k: u32 = 0u; k < {{ hidden_size }}u; k = k + 1u) {
+		sum = sum + hidden[k] * f32(input_R.data[r_base + k]);
+	}
+	{% if "B" in input_roles %}
+		sum = sum + f32(input_B.data[direction * {{ 2 * gate_rows }}u + {{ gate_rows }}u + row]);
+	{% endif %}
+	return sum;
+}
+
+{% if op_type == "GRU" and not linear_before_reset %}
+	fn reset_hidden_product(direction: u32, row: u32) -> f32 {
+		let r_base = (direction * {{ gate_rows }}u + row) * {{ hidden_size }}u;
+		var sum = 0.0;
+		for(var k: u32 = 0u; k < {{ hidden_size }}u; k = k + 1u) {
+			sum = sum + reset_hidden[k] * f32(input_R.data[r_base + k]);
+		}
+		{% if "B" in input_roles %}
+			sum = sum + f32(input_B.data[direction * {{ 2 * gate_rows }}u + {{ gate_rows }}u + row]);
+		{% endif %}
+		return sum;
+	}
+{% endif %}
+
+{% if "P" in input_roles %}
+	fn peephole(direction: u32, row: u32) -> f32 {
+		return f32(input_P.data[direction * {{ 3 * hidden_size }}u + row]);
+	}
+{% endif %}
+
+{# The loop over the sequence runs inside the shader, so that all steps are performed in a single dispatch #}
+@compute @workgroup_size({{ workgroup_size_x }})
+fn main(@builtin(workgroup_id) workgroup_id: vec3<u32>, @builtin(local_invocation_id) local_id: vec3<u32>) {
+	{# Outputs that are left out still have a binding that needs to be used (their buffers hold at least one element).
+	A single invocation writes to them, so that workgroups do not race on the same element. #}
+	if (workgroup_id.x == 0u && local_id.x == 0u) {
+		{% if not has_y %}
+			output_0.data[0] = Scalar();
+		{% endif %}
+		{% if o_lens | length > 1 and not has_y_h %}
+			output_1.data[0] = Scalar();
+		{% endif %}
+		{% if o_lens | length > 2 and not has_y_c %}
+			output_2.data[0] = Scalar();
+		{% endif %}
+	}
+
+	let direction = workgroup_id.x / {{ batch_size }}u;
+	let batch = workgroup_id.x % {{ batch_size }}u;
+	{% if reverse %}
+		let reverse = true;
+	{% else %}
+		let reverse = direction == 1u;
+	{% endif %}
+	{% if "sequence_lens" in input_roles %}
+		let sequence_length = min(u32(max(input_sequence_lens.data[batch], 0)), {{ sequence_length }}u);
+	{% else %}
+		let sequence_length = {{ sequence_length }}u;
+	{% endif %}
+	{% if layout == 0 %}
+		let state_base = (direction * {{ batch_size }}u + batch) * {{ hidden_size }}u;
+	{% else %}
+		let state_base = (batch * {{ num_directions }}u + direction) * {{ hidden_size }}u;
+	{% endif %}
+
+	for(var unit: u32 = 0u; unit < {{ units_per_thread }}u; unit = unit + 1u) {
+		let j = unit * {{ workgroup_size_x }}u + local_id.x;
+		if (j < {{ hidden_size }}u) {
+			{% if "initial_h" in input_roles %}
+				hidden[j] = f32(input_initial_h.data[state_base + j]);
+			{% else %}
+				hidden[j] = 0.0;
+			{% endif %}
+			{% if "initial_c" in input_roles %}
+				cell[unit] = f32(input_initial_c.data[state_base + j]);
+			{% else %}
+				cell[unit] = 0.0;
+			{% endif %}
+		}
+	}
+	workgroupBarrier();
+
+	for(var step: u32 = 0u; step < {{ sequence_length }}u; step = step + 1u) {
+		{# Steps beyond the sequence length of this batch element only write (zero) padding to the output #}
+		let in_sequence = step < sequence_length;
+		var t = step;
+		if (in_sequence && reverse) {
+			t = sequence_length - 1u - step;
+		}
+		{% if layout == 0 %}
+			let x_base = (t * {{ batch_size }}u + batch) * {{ input_size }}u;
+			let y_base = ((t * {{ num_directions }}u + direction) * {{ batch_size }}u + batch) * {{ hidden_size }}u;
+		{% else %}
+			let x_base = (batch * {{ sequence_length }}u + t) * {{ input_size }}u;
+			let y_base = ((batch * {{ sequence_length }}u + t) * {{ num_directions }}u + direction) * {{ hidden_size }}u;
+		{% endif %}
+
+		{% if op_type == "GRU" and not linear_before_reset %}
+			{# The reset gate is applied to the hidden state before the recurrence weights, so it is needed for all units #}
+			for(var unit: u32 = 0u; unit < {{ units_per_thread }}u; unit = unit + 1u) {
+				let j = unit * {{ workgroup_size_x }}u + local_id.x;
+				if (in_sequence && j < {{ hidden_size }}u) {
+					let r = activate(0u, direction, clipped(input_product(direction, {{ hidden_size }}u + j, x_base) + hidden_product(direction, {{ hidden_size }}u + j)));
+					reset_hidden[j] = r * hidden[j];
+				}
+			}
+			workgroupBarrier();
+		{% endif %}
+
+		for(var unit: u32 = 0u; unit < {{ units_per_thread }}u; unit = unit + 1u) {
+			let j = unit * {{ workgroup_size_x }}u + local_id.x;
+			if (j < {{ hidden_size }}u) {
+				if (in_sequence) {
+					{% if op_type == "LSTM" %}
+						var i = input_product(direction, j, x_base) + hidden_product(direction, j);
+						var f = input_product(direction, {{ 2 * hidden_size }}u + j, x_base) + hidden_product(direction, {{ 2 * hidden_size }}u + j);
+						var o = input_product(direction, {{ hidden_size }}u + j, x_base) + hidden_product(direction, {{ hidden_size }}u + j);
+						let c = activate(1u, direction, clipped(input_product(direction, {{ 3 * hidden_size }}u + j, x_base) + hidden_product(direction, {{ 3 * hidden_size }}u + j)));
+						{% if "P" in input_roles %}
+							i = i + peephole(direction, j) * cell[unit];
+							f = f + peephole(direction, {{ 2 * hidden_size }}u + j) * cell[unit];
+						{% endif %}
+						i = activate(0u, direction, clipped(i));
+						f = activate(0u, direction, clipped(f));
+						cell[unit] = f * cell[unit] + i * c;
+						{% if "P" in input_roles %}
+							o = o + peephole(direction, {{ hidden_size }}u + j) * cell[unit];
+						{% endif %}
+						o = activate(0u, direction, clipped(o));
+						next_hidden[unit] = o * activate(2u, direction, cell[unit]);
+					{% elif op_type == "GRU" %}
+						let z = activate(0u, direction, clipped(input_product(direction, j, x_base) + hidden_product(direction, j)));
+						{% if linear_before_reset %}
+							let r = activate(0u, direction, clipped(input_product(direction, {{ hidden_size }}u + j, x_base) + hidden_product(direction, {{ hidden_size }}u + j)));
+							let h = activate(1u, direction, clipped(input_product(direction, {{ 2 * hidden_size }}u + j, x_base) + r * hidden_product(direction, {{ 2 * hidden_size }}u + j)));
+						{% else %}
+							let h = activate(1u, direction, clipped(input_product(direction, {{ 2 * hidden_size }}u + j, x_base) + reset_hidden_product(direction, {{ 2 * hidden_size }}u + j)));
+						{% endif %}
+						next_hidden[unit] = (1.0 - z) * h + z * hidden[j];
+					{% else %}
+						next_hidden[unit] = activate(0u, direction, clipped(input_product(direction, j, x_base) + hidden_product(direction, j)));
+					{% endif %}
+					{% if has_y %}
+						output_0.data[y_base + j] = Scalar(next_hidden[unit]);
+					{% endif %}
+				} else {
+					{% if has_y %}
+						output_0.data[y_base + j] = Scalar();
+					{% endif %}
+				}
+			}
+		}
+		workgroupBarrier();
+
+		for(var unit: u32 = 0u; unit < {{ units_per_thread }}u; unit = unit + 1u) {
+			let j = unit * {{ workgroup_size_x }}u + local_id.x;
+			if (in_sequence && j < {{ hidden_size }}u) {
+				hidden[j] = next_hidden[unit];
+			}
+		}
+		workgroupBarrier();
+	}
+
+	for(var unit: u32 = 0u; unit < {{ units_per_thread }}u; unit = unit + 1u) {
+		let j = unit * {{ workgroup_size_x }}u + local_id.x;
+		if (j < {{ hidden_size }}u) {
+			{% if has_y_h %}
+				output_1.data[state_base + j] = Scalar(hidden[j]);
+			{% endif %}
+			{% if has_y_c %}
+				output_2.data[state_base + j] = Scalar(cell[unit]);
+			{% endif %}
+		}
+	}
+}
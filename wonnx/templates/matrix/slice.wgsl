@@ -0,0 +1,27 @@
+{%- include "structs.wgsl" -%}
+
+@group(0) @binding(0)
+var<storage, read> input_0: Array;
+
+@group(0) @binding(1)
+var<storage, read_write> output_0: Array;
+
+@compute @workgroup_size({{ workgroup_size_x }}, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ o_lens[0] }}u) {
+		{# Each output element is taken from index start + out_index * step along each axis (steps may be negative) #}
+		var index = 0;
+		var rest = gidx;
+		{% for chunk in o_chunks[0] %}
+		{
+			let out_index = i32(rest / {{ chunk }}u);
+			rest = rest % {{ chunk }}u;
+			index = index + ({{ slice_starts[loop.index0] }} + out_index * {{ slice_steps[loop.index0] }}) * {{ i_chunks[0][loop.index0] }};
+		}
+		{% endfor %}
+
+		output_0.data[gidx] = input_0.data[u32(index)];
+	}
+}
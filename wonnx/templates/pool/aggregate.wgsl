@@ -3,9 +3,13 @@
 {# 
 // The smallest floating point number that can be represented in IEEE-754. This should be -3.40282347E+38. However, Google 
 // Chrome's WGSL compiler (as of July 2022) complains that number cannot be represented in f32. Hence we are using +37f,
-// which should be sufficiently low.
+// which should be sufficiently low. For f16 the lowest finite value is -65504.
 #}
-{% set_global min_float = scalar_type ~ "(-3.40282347E+37f)" %}
+{% if scalar_type == "f16" %}
+	{% set_global min_float = "f16(-65504.0)" %}
+{% else %}
+	{% set_global min_float = scalar_type ~ "(-3.40282347E+37f)" %}
+{% endif %}
 
 @group(0) @binding(0)
 var<storage, read> input_0: Array;
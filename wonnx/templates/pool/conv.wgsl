@@ -6,19 +6,20 @@ var<storage, read> input_0: Array;
 @group(0) @binding(1)
 var<storage, read> input_1: Array;
 
-{% if i_lens | length == 3 -%} // Bias
-	@group(0) @binding(2)
-	var<storage, read> input_2: Array;
-
-	@group(0) @binding(3)
-	var<storage, read_write> output_0: Array;
-
-{%- else -%}
-	@group(0) @binding(2)
-	var<storage, read_write> output_0: Array;
+{%- if bias %}
+@group(0) @binding(2)
+var<storage, read> input_2: Array;
+{%- endif %}
 
+{%- if residual %}
+@group(0) @binding({% if bias %}3{% else %}2{% endif %})
+var<storage, read> residual: Array;
 {%- endif %}
 
+{% set output_binding = i_lens | length -%}
+@group({{ output_binding / 4 | int }}) @binding({{ output_binding % 4 }})
+var<storage, read_write> output_0: Array;
+
 @compute @workgroup_size(256, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 	let gidx = global_id.x;
@@ -38,20 +39,44 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 		var result: Scalar = Scalar();
 
 		let root_index = batch * {{ i_chunks[0][0] }}u;
-		let root_kernel_index = m * {{ kernel_channel_len }}u;
+		{% if op_type == "ConvTranspose" %}
+			{# Weights have shape (C x M/group x kH x kW) #}
+			let root_kernel_index = m % {{ output_channels_per_group }}u * {{ kernel_length }}u;
+		{% else %}
+			let root_kernel_index = m * {{ kernel_channel_len }}u;
+		{% endif %}
 
 		for(var c: u32 = current_group * {{ channels_per_group }}u; c < (current_group + 1u) * {{ channels_per_group }}u; c = c + 1u) {
 			let base_index = root_index + c * {{ i_chunks[0][1] }}u;
-			let base_kernel_index = root_kernel_index + c % {{ channels_per_group }}u * {{ kernel_length }}u;
+			{% if op_type == "ConvTranspose" %}
+				let base_kernel_index = root_kernel_index + c * {{ output_channels_per_group * kernel_length }}u;
+			{% else %}
+				let base_kernel_index = root_kernel_index + c % {{ channels_per_group }}u * {{ kernel_length }}u;
+			{% endif %}
 
 			for(var i: u32 = 0u; i < {{ kernel_shape[0] }}u; i = i + 1u) {
-				let tmp_y = i32(y) * {{ stride[0] }}i + i32(i) * {{ dilation[0] }}i - {{ pad[0] }}i; 
-
-				if ((tmp_y < {{ original_height }}i) && (tmp_y >= 0i)) {
+				{% if op_type == "ConvTranspose" %}
+					{# The input element at tmp_y contributes to this output element if tmp_y * stride + i * dilation - pad = y #}
+					let strided_y = i32(y) + {{ pad[0] }}i - i32(i) * {{ dilation[0] }}i;
+					let tmp_y = strided_y / {{ stride[0] }}i;
+					let valid_y = strided_y >= 0i && strided_y % {{ stride[0] }}i == 0i;
+				{% else %}
+					let tmp_y = i32(y) * {{ stride[0] }}i + i32(i) * {{ dilation[0] }}i - {{ pad[0] }}i; 
+					let valid_y = true;
+				{% endif %}
+
+				if (valid_y && (tmp_y < {{ original_height }}i) && (tmp_y >= 0i)) {
 					for(var j: u32 = 0u; j < {{ kernel_shape[1] }}u; j = j + 1u) { 
-						let tmp_x = i32(x) * {{ stride[1] }}i + i32(j) * {{ dilation[1] }}i - {{ pad[1] }}i;
-
-						if ((tmp_x < {{ original_width }}i) && (tmp_x >= 0i)) {
+						{% if op_type == "ConvTranspose" %}
+							let strided_x = i32(x) + {{ pad[1] }}i - i32(j) * {{ dilation[1] }}i;
+							let tmp_x = strided_x / {{ stride[1] }}i;
+							let valid_x = strided_x >= 0i && strided_x % {{ stride[1] }}i == 0i;
+						{% else %}
+							let tmp_x = i32(x) * {{ stride[1] }}i + i32(j) * {{ dilation[1] }}i - {{ pad[1] }}i;
+							let valid_x = true;
+						{% endif %}
+
+						if (valid_x && (tmp_x < {{ original_width }}i) && (tmp_x >= 0i)) {
 							let tmp_index = base_index + u32(tmp_y) * {{ original_width }}u + u32(tmp_x);
 							let index_kernel = base_kernel_index + i * {{ kernel_shape[1] }}u + j;
 							result = input_0.data[tmp_index] * input_1.data[index_kernel] + result;
@@ -61,13 +86,17 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 			}
 		}
 
-		{% if i_lens | length == 3 -%}
+		{% if bias -%}
 			result = result + input_2.data[m];
 		{%- endif %}
 
+		{% if residual -%}
+			result = result + residual.data[gidx];
+		{%- endif %}
+
 		{% set activation_input = "result" -%}
 		{% set activation_output = "output_0.data[gidx]" -%}
-		{% set activation_type = op_type | replace(from="Conv", to="") -%}
+		{% set activation_type = op_type | replace(from="ConvAdd", to="") | replace(from="Conv", to="") -%}
 		{% include "snippets/activation_scalar.wgsl" %}
 	}
 }
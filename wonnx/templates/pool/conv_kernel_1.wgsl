@@ -6,19 +6,20 @@ var<storage, read> input_0: Array;
 @group(0) @binding(1)
 var<storage, read> input_1: ArrayMatrix;
 
-{%- if i_lens | length == 3 -%} // Bias
-	@group(0) @binding(2)
-	var<storage, read> input_2: ArrayVector;
-
-	@group(0) @binding(3)
-	var<storage, read_write> output_0: Array;
-
-{%- else -%}
-	@group(0) @binding(2)
-	var<storage, read_write> output_0: Array;
+{%- if bias %}
+@group(0) @binding(2)
+var<storage, read> input_2: ArrayVector;
+{%- endif %}
 
+{%- if residual %}
+@group(0) @binding({% if bias %}3{% else %}2{% endif %})
+var<storage, read> residual: Array;
 {%- endif %}
 
+{% set output_binding = i_lens | length -%}
+@group({{ output_binding / 4 | int }}) @binding({{ output_binding % 4 }})
+var<storage, read_write> output_0: Array;
+
 
 @compute @workgroup_size(256, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
@@ -63,16 +64,23 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 			}
 		}
 
-		{% if i_lens | length == 3 -%}
+		{% if bias -%}
 			result = result + input_2.data[m];
 		{%- endif %}
 
+		let base_index_3 = batch * {{ o_chunks[0][0] }}u + m * {{ o_chunks[0][1] * 4 }}u + xy;
+
+		{% if residual -%}
+			for(var index_vec: u32 = 0u; index_vec < 4u; index_vec = index_vec + 1u) {
+				result[index_vec] = result[index_vec] + residual.data[base_index_3 + index_vec * {{ o_chunks[0][1] }}u];
+			}
+		{%- endif %}
+
 		{% set activation_input = "result" %}
 		{% set activation_output = "result" %}
-		{% set activation_type = op_type | replace(from="Conv", to="") %}
+		{% set activation_type = op_type | replace(from="ConvAdd", to="") | replace(from="Conv", to="") %}
 		{%- include "snippets/activation_vec.wgsl" %}
 
-		let base_index_3 = batch * {{ o_chunks[0][0] }}u + m * {{ o_chunks[0][1] * 4 }}u + xy;
 		for(var index_vec: u32 = 0u; index_vec < 4u; index_vec = index_vec + 1u) {
 			let index = base_index_3 + index_vec * {{ o_chunks[0][1] }}u;
 			output_0.data[index] = result[index_vec];
@@ -0,0 +1,120 @@
+{%- include "structs.wgsl" -%}
+
+struct Indices {
+	data: array<i32>
+};
+
+@group(0) @binding(0)
+var<storage, read> input_0: Array; // X
+
+@group(0) @binding(1)
+var<storage, read> input_1: Array; // rois
+
+@group(0) @binding(2)
+var<storage, read> input_2: Indices; // batch_indices
+
+@group(0) @binding(3)
+var<storage, read_write> output_0: Array;
+
+{# Returns the four terms of the bilinear interpolation of the plane starting at `start` at the specified position #}
+fn sample(start: u32, sample_y: f32, sample_x: f32) -> vec4<f32> {
+	if (sample_y < -1.0 || sample_y > f32({{ height }}) || sample_x < -1.0 || sample_x > f32({{ width }})) {
+		return vec4<f32>(0.0);
+	}
+
+	var y = max(sample_y, 0.0);
+	var y_low = u32(y);
+	var y_high = y_low + 1u;
+	if (y_low >= {{ height - 1 }}u) {
+		y_low = {{ height - 1 }}u;
+		y_high = y_low;
+		y = f32(y_low);
+	}
+
+	var x = max(sample_x, 0.0);
+	var x_low = u32(x);
+	var x_high = x_low + 1u;
+	if (x_low >= {{ width - 1 }}u) {
+		x_low = {{ width - 1 }}u;
+		x_high = x_low;
+		x = f32(x_low);
+	}
+
+	let ly = y - f32(y_low);
+	let lx = x - f32(x_low);
+	let hy = 1.0 - ly;
+	let hx = 1.0 - lx;
+	return vec4<f32>(
+		hy * hx * f32(input_0.data[start + y_low * {{ width }}u + x_low]),
+		hy * lx * f32(input_0.data[start + y_low * {{ width }}u + x_high]),
+		ly * hx * f32(input_0.data[start + y_high * {{ width }}u + x_low]),
+		ly * lx * f32(input_0.data[start + y_high * {{ width }}u + x_high])
+	);
+}
+
+@compute @workgroup_size({{ workgroup_size_x }}, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ o_lens[0] }}u) {
+		{# Output is [num_rois, C, output_height, output_width] #}
+		let roi = gidx / {{ o_chunks[0][0] }}u;
+		let channel = (gidx % {{ o_chunks[0][0] }}u) / {{ o_chunks[0][1] }}u;
+		let output_y = (gidx % {{ o_chunks[0][1] }}u) / {{ o_chunks[0][2] }}u;
+		let output_x = gidx % {{ o_chunks[0][2] }}u;
+
+		var batch = input_2.data[roi];
+		if (batch < 0) {
+			batch = batch + {{ i_shape[0][0] }};
+		}
+		let start = (u32(batch) * {{ i_shape[0][1] }}u + channel) * {{ height * width }}u;
+
+		let start_x = f32(input_1.data[roi * 4u]) * f32({{ roi_align.spatial_scale }}) - {{ offset }};
+		let start_y = f32(input_1.data[roi * 4u + 1u]) * f32({{ roi_align.spatial_scale }}) - {{ offset }};
+		var roi_width = f32(input_1.data[roi * 4u + 2u]) * f32({{ roi_align.spatial_scale }}) - {{ offset }} - start_x;
+		var roi_height = f32(input_1.data[roi * 4u + 3u]) * f32({{ roi_align.spatial_scale }}) - {{ offset }} - start_y;
+		{% if not roi_align.half_pixel %}
+			roi_width = max(roi_width, 1.0);
+			roi_height = max(roi_height, 1.0);
+		{% endif %}
+		let bin_height = roi_height / f32({{ o_shape[0][2] }});
+		let bin_width = roi_width / f32({{ o_shape[0][3] }});
+
+		{# The number of samples per bin is either fixed or depends on the size of the RoI #}
+		{% if roi_align.sampling_ratio > 0 %}
+			let grid_height = {{ roi_align.sampling_ratio }}u;
+			let grid_width = {{ roi_align.sampling_ratio }}u;
+		{% else %}
+			let grid_height = u32(max(ceil(bin_height), 0.0));
+			let grid_width = u32(max(ceil(bin_width), 0.0));
+		{% endif %}
+
+		var value = 0.0;
+		{% if roi_align.max %}
+			var first = true;
+		{% endif %}
+		for (var grid_y = 0u; grid_y < grid_height; grid_y = grid_y + 1u) {
+			let y = start_y + f32(output_y) * bin_height + (f32(grid_y) + 0.5) * bin_height / f32(grid_height);
+			for (var grid_x = 0u; grid_x < grid_width; grid_x = grid_x + 1u) {
+				let x = start_x + f32(output_x) * bin_width + (f32(grid_x) + 0.5) * bin_width / f32(grid_width);
+				let terms = sample(start, y, x);
+				{% if roi_align.max %}
+					let term = max(max(terms.x, terms.y), max(terms.z, terms.w));
+					if (first) {
+						value = term;
+						first = false;
+					} else {
+						value = max(value, term);
+					}
+				{% else %}
+					value = value + terms.x + terms.y + terms.z + terms.w;
+				{% endif %}
+			}
+		}
+
+		{% if not roi_align.max %}
+			value = value / f32(max(grid_height * grid_width, 1u));
+		{% endif %}
+		output_0.data[gidx] = Scalar(value);
+	}
+}
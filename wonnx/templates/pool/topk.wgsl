@@ -0,0 +1,65 @@
+{%- include "structs.wgsl" -%}
+
+struct Indices {
+	data: array<i32>
+};
+
+@group(0) @binding(0)
+var<storage, read> input_0: Array;
+
+{% if op_type == "TopK" %}
+	@group(0) @binding(1)
+	var<storage, read_write> output_0: Array;
+
+	@group(0) @binding(2)
+	var<storage, read_write> output_1: Indices;
+{% else %}
+	@group(0) @binding(1)
+	var<storage, read_write> output_0: Indices;
+{% endif %}
+
+{# Returns whether the element with value a and index a_index is to be selected before the element with value b and
+index b_index #}
+fn precedes(a: Scalar, a_index: u32, b: Scalar, b_index: u32) -> bool {
+	if (a != b) {
+		return a {% if largest %}>{% else %}<{% endif %} b;
+	}
+	return a_index {% if last_index_first %}>{% else %}<{% endif %} b_index;
+}
+
+@compute @workgroup_size({{ workgroup_size_x }})
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let gidx = global_id.x;
+
+	if (gidx < {{ i_lens[0] / axis_size | int }}u) {
+		let outer = gidx / {{ axis_chunk }}u;
+		let inner = gidx % {{ axis_chunk }}u;
+		let input_base = outer * {{ axis_size * axis_chunk }}u + inner;
+		let output_base = outer * {{ k * axis_chunk }}u + inner;
+
+		// Select elements in order: each is the first element that comes after the previously selected one
+		var previous = Scalar();
+		var previous_index = 0u;
+		for(var r: u32 = 0u; r < {{ k }}u; r = r + 1u) {
+			var best = Scalar();
+			var best_index = {{ axis_size }}u;
+			for(var j: u32 = 0u; j < {{ axis_size }}u; j = j + 1u) {
+				let value = input_0.data[input_base + j * {{ axis_chunk }}u];
+				if ((r == 0u || precedes(previous, previous_index, value, j))
+					&& (best_index == {{ axis_size }}u || precedes(value, j, best, best_index))) {
+					best = value;
+					best_index = j;
+				}
+			}
+
+			{% if op_type == "TopK" %}
+				output_0.data[output_base + r * {{ axis_chunk }}u] = best;
+				output_1.data[output_base + r * {{ axis_chunk }}u] = i32(best_index);
+			{% else %}
+				output_0.data[output_base] = i32(best_index);
+			{% endif %}
+			previous = best;
+			previous_index = best_index;
+		}
+	}
+}
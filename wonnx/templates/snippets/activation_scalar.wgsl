@@ -47,6 +47,24 @@
 		)
 	);
 
+{%- elif activation_type == "Mish" -%}
+	let input = {{ activation_input }};
+	{{ activation_output }} = input * tanh(log({{ scalar_type }}(1) + exp(input)));
+
+{%- elif activation_type == "LeakyRelu" -%}
+	let input = {{ activation_input }};
+	{{ activation_output }} = max(input, Scalar()) + min({{ scalar_type }}({{ alpha }}) * input, Scalar());
+
+{%- elif activation_type == "HardSwish" -%}
+	let input = {{ activation_input }};
+	{{ activation_output }} = input * max(
+		{{ scalar_type }}(0),
+		min(
+			{{ scalar_type }}(1),
+			{{ scalar_type }}({{ alpha }}) * input + {{ scalar_type }}({{ beta }})
+		)
+	);
+
 {%- elif activation_output != activation_input -%}
 	{{ activation_output }} = {{ activation_input }};
 
@@ -55,6 +55,16 @@
 		)
 	);
 
+{%- elif activation_type == "HardSwish" -%}
+	let input_vec = {{ activation_input }};
+	{{ activation_output }} = input_vec * max(
+		Vec4(Scalar(), Scalar(), Scalar(), Scalar()),
+		min(
+			Vec4({{ scalar_type }}(1), {{ scalar_type }}(1), {{ scalar_type }}(1), {{ scalar_type }}(1)),
+			{{ scalar_type }}({{ alpha }}) * input_vec + {{ scalar_type }}({{ beta }})
+		)
+	);
+
 {%- elif activation_output != activation_input -%}
 	{{ activation_output }} = {{ activation_input }};
 
@@ -324,3 +324,66 @@ fn test_clip() {
     let result = pollster::block_on(session.run(&input_data)).unwrap();
     assert_eq!(result["Y"], OutputTensor::F32(vec![0.0, 0.0, 1.0, 1.0]));
 }
+
+#[test]
+fn test_variadic() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Five inputs (more than fit in a single bind group) of different but broadcastable shapes
+    let inputs: [(&str, Vec<f32>, Vec<i64>); 5] = [
+        ("A", vec![1.0, -2.0, 3.0, -4.0, 5.0, -6.0], vec![2, 3]),
+        ("B", vec![0.5, 1.5, -2.5], vec![3]),
+        ("C", vec![10.0, -10.0], vec![2, 1]),
+        ("D", vec![2.0], vec![]),
+        ("E", vec![6.0, 5.0, 4.0, 3.0, 2.0, 1.0], vec![2, 3]),
+    ];
+    let mut input_data = HashMap::new();
+    for (name, data, _) in &inputs {
+        input_data.insert(name.to_string(), data.as_slice().into());
+    }
+
+    let expected: [(&str, [f32; 6]); 4] = [
+        ("Sum", [19.5, 16.5, 16.5, -8.5, 0.5, -15.5]),
+        ("Mean", [3.9, 3.3, 3.3, -1.7, 0.1, -3.1]),
+        ("Max", [10.0, 10.0, 10.0, 3.0, 5.0, 2.0]),
+        ("Min", [0.5, -2.0, -2.5, -10.0, -10.0, -10.0]),
+    ];
+
+    for (op, expected) in expected {
+        // Model: (A, B, C, D, E) -> op -> Y
+        let model = model(graph(
+            inputs
+                .iter()
+                .map(|(name, _, shape)| tensor(name, shape))
+                .collect(),
+            vec![tensor("Y", &[2, 3])],
+            vec![],
+            vec![],
+            vec![node(
+                inputs.iter().map(|(name, _, _)| *name).collect(),
+                vec!["Y"],
+                "variadic",
+                op,
+                vec![],
+            )],
+        ));
+
+        for result in common::run_on_all_backends(&model, &input_data) {
+            let result: &[f32] = (&result["Y"]).try_into().unwrap();
+            assert_abs_diff_eq!(result, expected.as_slice(), epsilon = 1e-5);
+        }
+    }
+
+    // A single input is passed through
+    let model = model(graph(
+        vec![tensor("A", &[2, 3])],
+        vec![tensor("Y", &[2, 3])],
+        vec![],
+        vec![],
+        vec![node(vec!["A"], vec!["Y"], "sum", "Sum", vec![])],
+    ));
+    let session =
+        pollster::block_on(wonnx::Session::from_model(model)).expect("Session did not create");
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(result["Y"], OutputTensor::F32(inputs[0].1.clone()));
+}
@@ -1,5 +1,8 @@
+use approx::assert_abs_diff_eq;
 use std::{collections::HashMap, convert::TryInto};
-use wonnx::utils::{attribute, graph, initializer, model, node, tensor};
+use wonnx::onnx::ModelProto;
+use wonnx::utils::{attribute, graph, initializer, model, node, tensor, InputTensor};
+use wonnx::Backend;
 mod common;
 
 #[test]
@@ -67,3 +70,148 @@ fn batch_normalization() {
         ],
     );
 }
+
+#[test]
+fn conv_batch_normalization() {
+    // A BatchNormalization with static parameters is folded into the preceding Conv; compare with one whose parameters
+    // are inputs, which is executed separately
+    let shape = vec![1, 2, 4, 4];
+    let output_shape = vec![1, 4, 4, 4];
+    let x: Vec<f32> = (0..32).map(|i| (i as f32 * 0.37).sin()).collect();
+    let parameters = [
+        ("scale", vec![1.0, 2.0, 0.5, -1.0]),
+        ("B", vec![0.5, -0.5, 1.0, 0.0]),
+        ("input_mean", vec![0.1, -0.2, 0.3, 0.0]),
+        ("input_var", vec![1.0, 0.5, 2.0, 0.25]),
+    ];
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x.as_slice().into());
+
+    let bn_model = |static_parameters: bool| {
+        let mut inputs = vec![tensor("X", &shape)];
+        let mut initializers = vec![
+            initializer(
+                "W",
+                (0..72).map(|i| (i as f32 * 0.11).cos()).collect(),
+                vec![4, 2, 3, 3],
+            ),
+            initializer("C", vec![0.1, 0.2, 0.3, 0.4], vec![4]),
+        ];
+        for (name, data) in &parameters {
+            if static_parameters {
+                initializers.push(initializer(name, data.clone(), vec![4]));
+            } else {
+                inputs.push(tensor(name, &[4]));
+            }
+        }
+        model(graph(
+            inputs,
+            vec![tensor("Y", &output_shape)],
+            vec![tensor("Z", &output_shape)],
+            initializers,
+            vec![
+                node(
+                    vec!["X", "W", "C"],
+                    vec!["Z"],
+                    "conv",
+                    "Conv",
+                    vec![
+                        attribute("kernel_shape", vec![3, 3]),
+                        attribute("pads", vec![1, 1, 1, 1]),
+                    ],
+                ),
+                node(
+                    vec!["Z", "scale", "B", "input_mean", "input_var"],
+                    vec!["Y"],
+                    "bn",
+                    "BatchNormalization",
+                    vec![attribute("epsilon", 0.1)],
+                ),
+            ],
+        ))
+    };
+
+    let mut parameter_data = input_data.clone();
+    for (name, data) in &parameters {
+        parameter_data.insert(name.to_string(), data.as_slice().into());
+    }
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let folded = run(bn_model(true), &input_data, backend);
+        let expected = run(bn_model(false), &parameter_data, backend);
+        assert_abs_diff_eq!(folded.as_slice(), expected.as_slice(), epsilon = 1e-4);
+    }
+}
+
+fn run(model: ModelProto, input_data: &HashMap<String, InputTensor>, backend: Backend) -> Vec<f32> {
+    let result = common::run_with_backend(&model, input_data, backend).unwrap();
+    result["Y"].clone().try_into().unwrap()
+}
+
+#[test]
+fn gemm_batch_normalization() {
+    let mut input_data = HashMap::new();
+    input_data.insert("A".to_string(), [1.0, 2.0, -1.0, 0.5][..].into());
+
+    // Z = A * B + 2 * C, with B (K x N) stored either as is or transposed (N x K)
+    for transposed in [false, true] {
+        let b = if transposed {
+            initializer(
+                "B",
+                vec![1.0, 0.0, 0.0, 1.0, 1.0, -1.0, 1.0, 1.0],
+                vec![4, 2],
+            )
+        } else {
+            initializer(
+                "B",
+                vec![1.0, 0.0, 1.0, 1.0, 0.0, 1.0, -1.0, 1.0],
+                vec![2, 4],
+            )
+        };
+        let bn_model = model(graph(
+            vec![tensor("A", &[2, 2])],
+            vec![tensor("Y", &[2, 4])],
+            vec![tensor("Z", &[2, 4])],
+            vec![
+                b,
+                initializer("C", vec![1.0, 2.0, 3.0, 0.5], vec![4]),
+                initializer("scale", vec![1.0, 2.0, 0.5, 1.0], vec![4]),
+                initializer("offset", vec![0.0, 1.0, -1.0, 0.0], vec![4]),
+                initializer("input_mean", vec![1.0, 1.0, 2.0, 0.0], vec![4]),
+                initializer("input_var", vec![4.0, 1.0, 0.25, 1.0], vec![4]),
+            ],
+            vec![
+                node(
+                    vec!["A", "B", "C"],
+                    vec!["Z"],
+                    "gemm",
+                    "Gemm",
+                    vec![
+                        attribute("transB", transposed as i64),
+                        attribute("beta", 2.0),
+                    ],
+                ),
+                node(
+                    vec!["Z", "scale", "offset", "input_mean", "input_var"],
+                    vec!["Y"],
+                    "bn",
+                    "BatchNormalization",
+                    vec![attribute("epsilon", 0.0)],
+                ),
+            ],
+        ));
+
+        // The GPU implementation of Gemm does not support transB
+        let backends = if transposed {
+            vec![Backend::Cpu]
+        } else {
+            vec![Backend::Gpu, Backend::Cpu]
+        };
+        for backend in backends {
+            // Z = [[3, 6, 5, 4], [1, 4.5, 4.5, 0.5]], Y = (Z - mean) / sqrt(var) * scale + offset
+            common::assert_eq_vector(
+                &run(bn_model.clone(), &input_data, backend),
+                &[1.0, 11.0, 2.0, 4.0, 0.0, 8.0, 1.5, 0.5],
+            );
+        }
+    }
+}
@@ -1,4 +1,13 @@
+// Not every test uses every helper
+#![allow(dead_code)]
+
 use approx::assert_ulps_eq;
+use std::collections::HashMap;
+use wonnx::{
+    onnx::ModelProto,
+    utils::{InputTensor, OutputTensor},
+    wgpu, Backend, Session, SessionConfig, SessionError,
+};
 
 /// Assert two vectors are equal up to a specific number of units in last place (ULPS)
 pub fn assert_eq_vector(xs: &[f32], ys: &[f32]) {
@@ -7,3 +16,42 @@ pub fn assert_eq_vector(xs: &[f32], ys: &[f32]) {
         assert_ulps_eq!(xs[i], ys[i], max_ulps = 2);
     }
 }
+
+/// Run a model with the specified backend. The session is dropped before returning, so that only one device exists at a
+/// time.
+pub fn run_with_backend(
+    model: &ModelProto,
+    input_data: &HashMap<String, InputTensor>,
+    backend: Backend,
+) -> Result<HashMap<String, OutputTensor>, SessionError> {
+    let config = SessionConfig::new().with_backend(backend);
+    let session = pollster::block_on(Session::from_model_with_config(model.clone(), &config))?;
+    pollster::block_on(session.run(input_data))
+}
+
+/// Run a model with each backend (the GPU first, then the CPU) and return the outputs of each
+pub fn run_on_all_backends(
+    model: &ModelProto,
+    input_data: &HashMap<String, InputTensor>,
+) -> Vec<HashMap<String, OutputTensor>> {
+    [Backend::Gpu, Backend::Cpu]
+        .iter()
+        .map(|backend| run_with_backend(model, input_data, *backend).unwrap())
+        .collect()
+}
+
+/// Request a device and queue from the adapter selected by the environment (as the tests of wgpu do)
+pub async fn request_device() -> (wgpu::Device, wgpu::Queue) {
+    let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::PRIMARY);
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    let adapter = wgpu::util::initialize_adapter_from_env_or_default(&instance, None)
+        .await
+        .expect("no adapter found");
+    adapter
+        .request_device(&wgpu::DeviceDescriptor::default(), None)
+        .await
+        .expect("could not create device")
+}
@@ -0,0 +1,109 @@
+use protobuf::Message;
+use std::{collections::HashMap, sync::Arc};
+use wonnx::{
+    onnx::ModelProto,
+    utils::{attribute, graph, initializer, model, node, tensor},
+    Backend, Session, SessionConfig, SessionError,
+};
+mod common;
+
+fn conv_relu_model() -> ModelProto {
+    // Model: X -> Conv -> C -> Relu -> Y, X -> Neg -> Z
+    let shape = vec![1, 1, 5, 5];
+    let data_w: Vec<f32> = (0..9).map(|_| 1.0f32).collect();
+    model(graph(
+        vec![tensor("X", &shape)],
+        vec![tensor("Y", &shape), tensor("Z", &shape)],
+        vec![tensor("C", &shape)],
+        vec![initializer("W", data_w, vec![1, 1, 3, 3])],
+        vec![
+            node(
+                vec!["X", "W"],
+                vec!["C"],
+                "conv",
+                "Conv",
+                vec![
+                    attribute("kernel_shape", vec![3, 3]),
+                    attribute("auto_pad", "SAME_UPPER"),
+                ],
+            ),
+            node(vec!["C"], vec!["Y"], "relu", "Relu", vec![]),
+            node(vec!["X"], vec!["Z"], "neg", "Neg", vec![]),
+        ],
+    ))
+}
+
+#[test]
+fn test_compiled_model() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let data: Vec<f32> = (0..25).map(|x| x as f32 - 12.0).collect();
+    let input_data = HashMap::from([("X".to_string(), data.as_slice().into())]);
+
+    let model = conv_relu_model();
+    let (device, queue) = pollster::block_on(common::request_device());
+    let config = SessionConfig::new().with_device(Arc::new(device), Arc::new(queue));
+    let compiled = pollster::block_on(Session::compile_model(&model, &config)).unwrap();
+    assert!(compiled.get_graph().get_node().iter().all(|node| node
+        .get_attribute()
+        .iter()
+        .any(|a| a.get_name() == "wonnx.shader")));
+    let bytes = compiled.write_to_bytes().unwrap();
+
+    let session = pollster::block_on(Session::from_model_with_config(model, &config)).unwrap();
+    let expected = pollster::block_on(session.run(&input_data)).unwrap();
+
+    // The restored model gives the same results on both backends
+    for config in [config, SessionConfig::new().with_backend(Backend::Cpu)] {
+        let restored =
+            pollster::block_on(Session::from_bytes_with_config(&bytes, &config)).unwrap();
+        let result = pollster::block_on(restored.run(&input_data)).unwrap();
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+fn test_compiled_model_version() {
+    let config = SessionConfig::new().with_backend(Backend::Cpu);
+    let mut compiled =
+        pollster::block_on(Session::compile_model(&conv_relu_model(), &config)).unwrap();
+    for prop in compiled.mut_metadata_props().iter_mut() {
+        if prop.get_key() == "wonnx.version" {
+            prop.set_value("0.0.0".to_string());
+        }
+    }
+
+    assert!(matches!(
+        pollster::block_on(Session::from_model_with_config(compiled, &config)),
+        Err(SessionError::IncompatibleCompiledModel { .. })
+    ));
+}
+
+#[test]
+fn test_stored_shaders_require_compiled_model() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let data: Vec<f32> = (0..25).map(|x| x as f32 - 12.0).collect();
+    let input_data = HashMap::from([("X".to_string(), data.as_slice().into())]);
+
+    // Shaders stored in a model that is not marked as compiled are not used
+    let config = SessionConfig::new().with_backend(Backend::Cpu);
+    let mut model =
+        pollster::block_on(Session::compile_model(&conv_relu_model(), &config)).unwrap();
+    model.clear_metadata_props();
+    for node in model.mut_graph().mut_node().iter_mut() {
+        for attribute in node.mut_attribute().iter_mut() {
+            if attribute.get_name() == "wonnx.shader" {
+                attribute.set_s(b"not a shader".to_vec());
+            }
+        }
+    }
+
+    let expected = {
+        let session =
+            pollster::block_on(Session::from_model_with_config(conv_relu_model(), &config))
+                .unwrap();
+        pollster::block_on(session.run(&input_data)).unwrap()
+    };
+    let session = pollster::block_on(Session::from_model(model)).unwrap();
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(result, expected);
+}
@@ -0,0 +1,306 @@
+use std::{collections::HashMap, convert::TryInto};
+use wonnx::{
+    onnx::{ModelProto, TensorProto_DataType},
+    utils::{
+        attribute, graph, initializer, initializer_int64, model, node, tensor, tensor_of_type,
+        OutputTensor,
+    },
+    Backend, CpuError, GpuError, SessionError,
+};
+mod common;
+
+fn run(
+    model: &ModelProto,
+    inputs: &[(&str, &[f32])],
+    backend: Backend,
+) -> Result<HashMap<String, OutputTensor>, SessionError> {
+    let input_data: HashMap<String, _> = inputs
+        .iter()
+        .map(|(name, data)| (name.to_string(), (*data).into()))
+        .collect();
+    common::run_with_backend(model, &input_data, backend)
+}
+
+/// Model: Y = If(C > 0, X + X, X * K) where K is an initializer of the main graph
+fn if_model(else_shape: &[i64]) -> ModelProto {
+    let then_branch = graph(
+        vec![],
+        vec![tensor("then_y", &[4])],
+        vec![],
+        vec![],
+        vec![node(vec!["X", "X"], vec!["then_y"], "add", "Add", vec![])],
+    );
+    let else_branch = graph(
+        vec![],
+        vec![tensor("else_y", else_shape)],
+        vec![],
+        vec![],
+        vec![node(vec!["X", "K"], vec!["else_y"], "mul", "Mul", vec![])],
+    );
+
+    model(graph(
+        vec![tensor("X", &[4]), tensor("C", &[1])],
+        vec![tensor("Y", &[4])],
+        vec![tensor_of_type("cond", &[1], TensorProto_DataType::BOOL)],
+        vec![
+            initializer("zero", vec![0.0], vec![1]),
+            initializer("K", vec![3.0], vec![1]),
+        ],
+        vec![
+            node(
+                vec!["C", "zero"],
+                vec!["cond"],
+                "greater",
+                "Greater",
+                vec![],
+            ),
+            node(
+                vec!["cond"],
+                vec!["Y"],
+                "if",
+                "If",
+                vec![
+                    attribute("then_branch", then_branch),
+                    attribute("else_branch", else_branch),
+                ],
+            ),
+        ],
+    ))
+}
+
+#[test]
+fn test_if() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let model = if_model(&[4]);
+    let x = [1.0, 2.0, 3.0, 4.0];
+
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let result = run(&model, &[("X", &x), ("C", &[1.0])], backend).unwrap();
+        common::assert_eq_vector((&result["Y"]).try_into().unwrap(), &[2.0, 4.0, 6.0, 8.0]);
+
+        let result = run(&model, &[("X", &x), ("C", &[-1.0])], backend).unwrap();
+        common::assert_eq_vector((&result["Y"]).try_into().unwrap(), &[3.0, 6.0, 9.0, 12.0]);
+    }
+}
+
+#[test]
+fn test_if_invalid_branch() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // The else branch produces a value of a different shape than the output of the op
+    let model = if_model(&[2, 2]);
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        assert!(matches!(
+            run(&model, &[("X", &[0.0; 4]), ("C", &[1.0])], backend),
+            Err(SessionError::IrError(_))
+        ));
+    }
+}
+
+/// Model: a Loop with trip count M (and no condition) that adds X to an accumulator in each iteration, and outputs the
+/// value of the accumulator after each iteration as scan output
+fn accumulating_loop_model(trip_count: i64, iterations: i64) -> ModelProto {
+    let body = graph(
+        vec![
+            tensor_of_type("i", &[], TensorProto_DataType::INT64),
+            tensor_of_type("cond_in", &[], TensorProto_DataType::BOOL),
+            tensor("acc_in", &[2]),
+        ],
+        vec![
+            tensor_of_type("cond_out", &[], TensorProto_DataType::BOOL),
+            tensor("acc_out", &[2]),
+            tensor("acc_scan", &[2]),
+        ],
+        vec![],
+        vec![],
+        vec![
+            node(
+                vec!["cond_in"],
+                vec!["cond_out"],
+                "cond",
+                "Identity",
+                vec![],
+            ),
+            node(vec!["acc_in", "X"], vec!["acc_out"], "add", "Add", vec![]),
+            node(
+                vec!["acc_out"],
+                vec!["acc_scan"],
+                "scan",
+                "Identity",
+                vec![],
+            ),
+        ],
+    );
+
+    model(graph(
+        vec![tensor("X", &[2]), tensor("V", &[2])],
+        vec![tensor("acc", &[2]), tensor("scan", &[iterations, 2])],
+        vec![],
+        vec![initializer_int64("M", vec![trip_count], vec![])],
+        vec![node(
+            vec!["M", "", "V"],
+            vec!["acc", "scan"],
+            "loop",
+            "Loop",
+            vec![attribute("body", body)],
+        )],
+    ))
+}
+
+#[test]
+fn test_loop_trip_count() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let model = accumulating_loop_model(3, 3);
+
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let result = run(&model, &[("X", &[1.0, 2.0]), ("V", &[0.5, 0.0])], backend).unwrap();
+        common::assert_eq_vector((&result["acc"]).try_into().unwrap(), &[3.5, 6.0]);
+        common::assert_eq_vector(
+            (&result["scan"]).try_into().unwrap(),
+            &[1.5, 2.0, 2.5, 4.0, 3.5, 6.0],
+        );
+    }
+}
+
+#[test]
+fn test_loop_iteration_count_mismatch() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // The scan output has room for more iterations than the loop performs
+    let model = accumulating_loop_model(2, 3);
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let result = run(&model, &[("X", &[1.0, 2.0]), ("V", &[0.0, 0.0])], backend);
+        assert!(matches!(
+            result,
+            Err(SessionError::GpuError(GpuError::IterationCountMismatch {
+                expected: 3,
+                actual: 2,
+                ..
+            })) | Err(SessionError::CpuError(CpuError::IterationCountMismatch {
+                expected: 3,
+                actual: 2,
+                ..
+            }))
+        ));
+    }
+}
+
+#[test]
+fn test_loop_condition() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Model: a Loop without trip count that increments a value by the iteration number plus one while it is below L
+    let body = graph(
+        vec![
+            tensor_of_type("i", &[1], TensorProto_DataType::INT64),
+            tensor_of_type("cond_in", &[1], TensorProto_DataType::BOOL),
+            tensor("x_in", &[1]),
+        ],
+        vec![
+            tensor_of_type("cond_out", &[1], TensorProto_DataType::BOOL),
+            tensor("x_out", &[1]),
+        ],
+        vec![tensor("i_float", &[1]), tensor("step", &[1])],
+        vec![initializer("one", vec![1.0], vec![1])],
+        vec![
+            node(
+                vec!["i"],
+                vec!["i_float"],
+                "cast",
+                "Cast",
+                vec![attribute("to", TensorProto_DataType::FLOAT as i64)],
+            ),
+            node(vec!["i_float", "one"], vec!["step"], "step", "Add", vec![]),
+            node(vec!["x_in", "step"], vec!["x_out"], "add", "Add", vec![]),
+            node(vec!["x_out", "L"], vec!["cond_out"], "less", "Less", vec![]),
+        ],
+    );
+
+    let model = model(graph(
+        vec![tensor("X", &[1]), tensor("L", &[1])],
+        vec![tensor("Y", &[1])],
+        vec![tensor_of_type("cond", &[1], TensorProto_DataType::BOOL)],
+        vec![],
+        vec![
+            node(vec!["X", "L"], vec!["cond"], "less", "Less", vec![]),
+            node(
+                vec!["", "cond", "X"],
+                vec!["Y"],
+                "loop",
+                "Loop",
+                vec![attribute("body", body)],
+            ),
+        ],
+    ));
+
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        // 0 + 1 + 2 + 3 + 4 = 10
+        let result = run(&model, &[("X", &[0.0]), ("L", &[8.0])], backend).unwrap();
+        common::assert_eq_vector((&result["Y"]).try_into().unwrap(), &[10.0]);
+
+        // The condition is false initially
+        let result = run(&model, &[("X", &[9.0]), ("L", &[8.0])], backend).unwrap();
+        common::assert_eq_vector((&result["Y"]).try_into().unwrap(), &[9.0]);
+    }
+}
+
+/// Model: a Scan that calculates the cumulative sum of the rows of X
+fn cumulative_sum_model(direction: i64) -> ModelProto {
+    let body = graph(
+        vec![tensor("sum_in", &[2]), tensor("row", &[2])],
+        vec![tensor("sum_out", &[2]), tensor("sum_scan", &[2])],
+        vec![],
+        vec![],
+        vec![
+            node(vec!["sum_in", "row"], vec!["sum_out"], "add", "Add", vec![]),
+            node(
+                vec!["sum_out"],
+                vec!["sum_scan"],
+                "scan",
+                "Identity",
+                vec![],
+            ),
+        ],
+    );
+
+    model(graph(
+        vec![tensor("X", &[3, 2])],
+        vec![tensor("sum", &[2]), tensor("Y", &[3, 2])],
+        vec![],
+        vec![initializer("zeros", vec![0.0, 0.0], vec![2])],
+        vec![node(
+            vec!["zeros", "X"],
+            vec!["sum", "Y"],
+            "scan",
+            "Scan",
+            vec![
+                attribute("body", body),
+                attribute("num_scan_inputs", 1),
+                attribute("scan_input_directions", vec![direction]),
+            ],
+        )],
+    ))
+}
+
+#[test]
+fn test_scan() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let result = run(&cumulative_sum_model(0), &[("X", &x)], backend).unwrap();
+        common::assert_eq_vector((&result["sum"]).try_into().unwrap(), &[9.0, 12.0]);
+        common::assert_eq_vector(
+            (&result["Y"]).try_into().unwrap(),
+            &[1.0, 2.0, 4.0, 6.0, 9.0, 12.0],
+        );
+
+        // Rows are taken in reverse order
+        let result = run(&cumulative_sum_model(1), &[("X", &x)], backend).unwrap();
+        common::assert_eq_vector((&result["sum"]).try_into().unwrap(), &[9.0, 12.0]);
+        common::assert_eq_vector(
+            (&result["Y"]).try_into().unwrap(),
+            &[5.0, 6.0, 8.0, 10.0, 9.0, 12.0],
+        );
+    }
+}
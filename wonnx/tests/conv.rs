@@ -1,6 +1,10 @@
+use approx::assert_abs_diff_eq;
 use std::collections::HashMap;
 use std::convert::TryInto;
-use wonnx::utils::{attribute, graph, initializer, model, node, tensor, OutputTensor};
+use wonnx::onnx::{NodeProto, TensorProto};
+use wonnx::utils::{
+    attribute, graph, initializer, model, model_with_opset, node, tensor, OutputTensor,
+};
 use wonnx::*;
 mod common;
 
@@ -204,6 +208,195 @@ fn conv_asymetric_stride() {
     );
 }
 
+/// A chain of ops that follows a convolution (which outputs Y) and outputs Z, with the function it computes of Y and the
+/// residual input R
+type Tail = (Vec<NodeProto>, Vec<TensorProto>, fn(f32, f32) -> f32);
+
+fn tails() -> Vec<Tail> {
+    vec![
+        (
+            vec![node(vec!["Y"], vec!["Z"], "relu", "Relu", vec![])],
+            vec![],
+            |y, _| y.max(0.0),
+        ),
+        (
+            vec![node(
+                vec!["Y"],
+                vec!["Z"],
+                "leaky",
+                "LeakyRelu",
+                vec![attribute("alpha", 0.1)],
+            )],
+            vec![],
+            |y, _| if y < 0.0 { 0.1 * y } else { y },
+        ),
+        (
+            vec![node(vec!["Y"], vec!["Z"], "sigmoid", "Sigmoid", vec![])],
+            vec![],
+            |y, _| 1.0 / (1.0 + (-y).exp()),
+        ),
+        (
+            vec![node(
+                vec!["Y", "min", "max"],
+                vec!["Z"],
+                "clip",
+                "Clip",
+                vec![],
+            )],
+            vec![
+                initializer("min", vec![0.0], vec![]),
+                initializer("max", vec![1.0], vec![]),
+            ],
+            |y, _| y.clamp(0.0, 1.0),
+        ),
+        (
+            vec![node(
+                vec!["Y"],
+                vec!["Z"],
+                "hard_swish",
+                "HardSwish",
+                vec![],
+            )],
+            vec![],
+            |y, _| y * (y / 6.0 + 0.5).clamp(0.0, 1.0),
+        ),
+        (
+            vec![
+                node(
+                    vec!["Y"],
+                    vec!["S"],
+                    "hard_sigmoid",
+                    "HardSigmoid",
+                    vec![attribute("alpha", 0.25)],
+                ),
+                node(vec!["S", "Y"], vec!["Z"], "mul", "Mul", vec![]),
+            ],
+            vec![],
+            |y, _| y * (0.25 * y + 0.5).clamp(0.0, 1.0),
+        ),
+        (
+            vec![
+                node(vec!["Y"], vec!["S"], "softplus", "Softplus", vec![]),
+                node(vec!["S"], vec!["T"], "tanh", "Tanh", vec![]),
+                node(vec!["Y", "T"], vec!["Z"], "mul", "Mul", vec![]),
+            ],
+            vec![],
+            |y, _| y * (1.0 + y.exp()).ln().tanh(),
+        ),
+        (
+            vec![node(vec!["R", "Y"], vec!["Z"], "add", "Add", vec![])],
+            vec![],
+            |y, r| y + r,
+        ),
+        (
+            vec![
+                node(vec!["Y", "R"], vec!["S"], "add", "Add", vec![]),
+                node(vec!["S"], vec!["Z"], "relu", "Relu", vec![]),
+            ],
+            vec![],
+            |y, r| (y + r).max(0.0),
+        ),
+    ]
+}
+
+#[test]
+fn conv_fused_activations() {
+    // The shapes select the generic shader, the shader for 1x1 kernels and the shader for 3x3 kernels respectively
+    for (channels, output_channels, kernel) in [(3, 2, 3), (16, 4, 1), (2, 4, 3)] {
+        let input_shape = vec![1, channels, 4, 4];
+        let output_shape = vec![1, output_channels, 4, 4];
+        let weights_shape = vec![output_channels, channels, kernel, kernel];
+        let pad = (kernel - 1) / 2;
+        let count = |shape: &[i64]| shape.iter().product::<i64>() as usize;
+        let x: Vec<f32> = (0..count(&input_shape))
+            .map(|i| (i as f32 * 0.37).sin() * 2.0)
+            .collect();
+        let r: Vec<f32> = (0..count(&output_shape))
+            .map(|i| (i as f32 * 0.23).cos())
+            .collect();
+        let mut input_data = HashMap::new();
+        input_data.insert("X".to_string(), x.as_slice().into());
+        input_data.insert("R".to_string(), r.as_slice().into());
+
+        // A plain convolution that outputs Y, and for each tail a separate convolution (outputting Y<i>) followed by the
+        // tail (outputting Z<i>), so that each of these can be fused
+        let conv = |output: &str| {
+            node(
+                vec!["X", "W", "B"],
+                vec![output],
+                output,
+                "Conv",
+                vec![
+                    attribute("kernel_shape", vec![kernel, kernel]),
+                    attribute("pads", vec![pad, pad, pad, pad]),
+                ],
+            )
+        };
+        let tails = tails();
+        let mut nodes = vec![conv("Y")];
+        let mut initializers = vec![
+            initializer(
+                "W",
+                (0..count(&weights_shape))
+                    .map(|i| (i as f32 * 0.11).cos() * 0.5)
+                    .collect(),
+                weights_shape.clone(),
+            ),
+            initializer(
+                "B",
+                (0..output_channels)
+                    .map(|i| i as f32 * 0.25 - 0.5)
+                    .collect(),
+                vec![output_channels],
+            ),
+        ];
+        let mut infos = vec![];
+        let mut outputs = vec![tensor("Y", &output_shape)];
+        for (index, (tail, tail_initializers, _)) in tails.iter().enumerate() {
+            let rename = |value: &str| match value {
+                "Y" | "S" | "T" | "Z" => format!("{value}{index}"),
+                _ => value.to_string(),
+            };
+            nodes.push(conv(&rename("Y")));
+            for tail_node in tail {
+                let mut tail_node = tail_node.clone();
+                let inputs = tail_node.get_input().iter().map(|v| rename(v)).collect();
+                let outputs = tail_node.get_output().iter().map(|v| rename(v)).collect();
+                tail_node.set_input(inputs);
+                tail_node.set_output(outputs);
+                tail_node.set_name(format!("{}{index}", tail_node.get_name()));
+                nodes.push(tail_node);
+            }
+            initializers.extend(tail_initializers.iter().cloned());
+            infos.extend(
+                ["Y", "S", "T"]
+                    .iter()
+                    .map(|value| tensor(&rename(value), &output_shape)),
+            );
+            outputs.push(tensor(&rename("Z"), &output_shape));
+        }
+        let conv_model = model_with_opset(
+            graph(
+                vec![tensor("X", &input_shape), tensor("R", &output_shape)],
+                outputs,
+                infos,
+                initializers,
+                nodes,
+            ),
+            14,
+        );
+
+        for result in common::run_on_all_backends(&conv_model, &input_data) {
+            let y: Vec<f32> = result["Y"].clone().try_into().unwrap();
+            for (index, (_, _, function)) in tails.iter().enumerate() {
+                let z: Vec<f32> = result[&format!("Z{index}")].clone().try_into().unwrap();
+                let expected: Vec<f32> = y.iter().zip(&r).map(|(y, r)| function(*y, *r)).collect();
+                assert_abs_diff_eq!(z.as_slice(), expected.as_slice(), epsilon = 1e-4);
+            }
+        }
+    }
+}
+
 fn _conv_kernel_3() {
     let n: usize = 4;
     let c = 1;
@@ -0,0 +1,124 @@
+use std::{collections::HashMap, convert::TryInto};
+
+use wonnx::{
+    onnx::{AttributeProto, ModelProto},
+    utils::{attribute, graph, initializer, model, node, tensor, OutputTensor},
+};
+mod common;
+
+fn conv_transpose_model(
+    x_dims: &[i64],
+    w: Vec<f32>,
+    w_dims: &[i64],
+    y_dims: &[i64],
+    attributes: Vec<AttributeProto>,
+) -> ModelProto {
+    model(graph(
+        vec![tensor("X", x_dims)],
+        vec![tensor("Y", y_dims)],
+        vec![],
+        vec![initializer("W", w, w_dims.to_vec())],
+        vec![node(
+            vec!["X", "W"],
+            vec!["Y"],
+            "conv_transpose",
+            "ConvTranspose",
+            attributes,
+        )],
+    ))
+}
+
+// Examples from https://github.com/onnx/onnx/blob/main/docs/Operators.md#ConvTranspose
+#[test]
+fn test_conv_transpose() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x: Vec<f32> = (0..9).map(|i| i as f32).collect();
+    let input_data = HashMap::from([("X".to_string(), x.as_slice().into())]);
+
+    let model = conv_transpose_model(
+        &[1, 1, 3, 3],
+        vec![1.0; 18],
+        &[1, 2, 3, 3],
+        &[1, 2, 5, 5],
+        vec![],
+    );
+    let channel = [
+        0.0, 1.0, 3.0, 3.0, 2.0, 3.0, 8.0, 15.0, 12.0, 7.0, 9.0, 21.0, 36.0, 27.0, 15.0, 9.0, 20.0,
+        33.0, 24.0, 13.0, 6.0, 13.0, 21.0, 15.0, 8.0,
+    ];
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(result["Y"], OutputTensor::F32([channel, channel].concat()));
+    }
+
+    // Strides and pads
+    let model = conv_transpose_model(
+        &[1, 1, 3, 3],
+        vec![1.0; 18],
+        &[1, 2, 3, 3],
+        &[1, 2, 7, 3],
+        vec![
+            attribute("strides", vec![3, 2]),
+            attribute("pads", vec![1, 2, 1, 2]),
+        ],
+    );
+    let channel = [
+        1.0, 1.0, 3.0, 1.0, 1.0, 3.0, 7.0, 4.0, 9.0, 7.0, 4.0, 9.0, 7.0, 4.0, 9.0, 13.0, 7.0, 15.0,
+        13.0, 7.0, 15.0,
+    ];
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(result["Y"], OutputTensor::F32([channel, channel].concat()));
+    }
+
+    // The same output follows from specifying the output shape (the padding is then calculated)
+    let model = conv_transpose_model(
+        &[1, 1, 3, 3],
+        vec![1.0; 18],
+        &[1, 2, 3, 3],
+        &[1, 2, 10, 8],
+        vec![
+            attribute("strides", vec![3, 2]),
+            attribute("output_shape", vec![10, 8]),
+        ],
+    );
+    let padded_model = conv_transpose_model(
+        &[1, 1, 3, 3],
+        vec![1.0; 18],
+        &[1, 2, 3, 3],
+        &[1, 2, 10, 8],
+        vec![
+            attribute("strides", vec![3, 2]),
+            attribute("output_padding", vec![1, 1]),
+        ],
+    );
+    let expected = &common::run_on_all_backends(&padded_model, &input_data)[1]["Y"];
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(&result["Y"], expected);
+    }
+}
+
+#[test]
+fn test_conv_transpose_group_dilation() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x: Vec<f32> = (0..32).map(|i| (i % 7) as f32 - 3.0).collect();
+    let input_data = HashMap::from([("X".to_string(), x.as_slice().into())]);
+    let w: Vec<f32> = (0..24).map(|i| (i % 5) as f32 * 0.5 - 1.0).collect();
+
+    // Input of 4 channels in two groups, each producing 3 output channels
+    let model = conv_transpose_model(
+        &[1, 4, 2, 4],
+        w,
+        &[4, 3, 1, 2],
+        &[1, 6, 4, 10],
+        vec![
+            attribute("group", 2),
+            attribute("strides", vec![2, 2]),
+            attribute("dilations", vec![1, 2]),
+            attribute("output_padding", vec![1, 1]),
+            attribute("pads", vec![0, 0, 0, 0]),
+        ],
+    );
+    let results = common::run_on_all_backends(&model, &input_data);
+    assert_eq!(results[0], results[1]);
+    let y: &[f32] = (&results[0]["Y"]).try_into().unwrap();
+    assert!(y.iter().any(|v| *v != 0.0));
+}
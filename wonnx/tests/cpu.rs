@@ -0,0 +1,394 @@
+use std::{collections::HashMap, convert::TryInto};
+use wonnx::{
+    onnx::{ModelProto, TensorProto_DataType},
+    utils::{
+        attribute, graph, initializer, initializer_int64, model, node, tensor, tensor_of_type,
+        InputTensor, OutputTensor,
+    },
+    Backend, Session, SessionConfig,
+};
+
+mod common;
+
+fn cpu_session(model: ModelProto) -> Session {
+    pollster::block_on(Session::from_model_with_config(
+        model,
+        &SessionConfig::new().with_backend(Backend::Cpu),
+    ))
+    .expect("Session did not create")
+}
+
+#[test]
+fn test_cpu_add_broadcast() {
+    let mut input_data = HashMap::new();
+    let data: Vec<f32> = (0..6).map(|x| x as f32).collect();
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    // Model: X + B -> Y, where B is broadcast over the rows of X
+    let model = model(graph(
+        vec![tensor("X", &[2, 3])],
+        vec![tensor("Y", &[2, 3])],
+        vec![],
+        vec![initializer("B", vec![10.0, 20.0, 30.0], vec![3])],
+        vec![node(vec!["X", "B"], vec!["Y"], "add", "Add", vec![])],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(
+        result["Y"],
+        OutputTensor::F32(vec![10.0, 21.0, 32.0, 13.0, 24.0, 35.0])
+    );
+}
+
+#[test]
+fn test_cpu_integer_mod() {
+    let mut input_data = HashMap::new();
+    let data = vec![-7i64, -3, 3, 7];
+    input_data.insert("X".to_string(), InputTensor::I64(data.as_slice().into()));
+
+    // Model: X mod 3 -> Y (the sign of the result follows the divisor)
+    let model = model(graph(
+        vec![tensor_of_type("X", &[4], TensorProto_DataType::INT64)],
+        vec![tensor_of_type("Y", &[4], TensorProto_DataType::INT64)],
+        vec![],
+        vec![initializer_int64("D", vec![3], vec![1])],
+        vec![node(vec!["X", "D"], vec!["Y"], "mod", "Mod", vec![])],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(result["Y"], OutputTensor::I64(vec![2, 0, 0, 1]));
+}
+
+#[test]
+fn test_cpu_conv() {
+    let n = 5;
+    let mut input_data = HashMap::new();
+    let data: Vec<f32> = (0..25).map(|x| x as f32).collect();
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    // Model: X -> Conv -> Y, with four identical 3x3 kernels. The optimizer pads the weights for this configuration.
+    let model = model(graph(
+        vec![tensor("X", &[1, 1, n, n])],
+        vec![tensor("Y", &[1, 4, n, n])],
+        vec![],
+        vec![
+            initializer("W", vec![1.0; 4 * 3 * 3], vec![4, 1, 3, 3]),
+            initializer("B", vec![0.0, 1.0, 2.0, 3.0], vec![4]),
+        ],
+        vec![node(
+            vec!["X", "W", "B"],
+            vec!["Y"],
+            "conv",
+            "Conv",
+            vec![
+                attribute("kernel_shape", vec![3, 3]),
+                attribute("pads", vec![1, 1, 1, 1]),
+            ],
+        )],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+
+    #[rustfmt::skip]
+    let channel = vec![
+        12.0, 21.0, 27.0, 33.0, 24.0,
+        33.0, 54.0, 63.0, 72.0, 51.0,
+        63.0, 99.0, 108.0, 117.0, 81.0,
+        93.0, 144.0, 153.0, 162.0, 111.0,
+        72.0, 111.0, 117.0, 123.0, 84.0,
+    ];
+    let expected: Vec<f32> = (0..4)
+        .flat_map(|bias| channel.iter().map(move |x| x + bias as f32))
+        .collect();
+    assert_eq!(result["Y"], OutputTensor::F32(expected));
+}
+
+#[test]
+fn test_cpu_pool() {
+    let mut input_data = HashMap::new();
+    let data: Vec<f32> = (0..16).map(|x| x as f32).collect();
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    // Model: X -> MaxPool -> Y, X -> AveragePool -> Z
+    let model = model(graph(
+        vec![tensor("X", &[1, 1, 4, 4])],
+        vec![tensor("Y", &[1, 1, 2, 2]), tensor("Z", &[1, 1, 2, 2])],
+        vec![],
+        vec![],
+        vec![
+            node(
+                vec!["X"],
+                vec!["Y"],
+                "maxpool",
+                "MaxPool",
+                vec![
+                    attribute("kernel_shape", vec![2, 2]),
+                    attribute("strides", vec![2, 2]),
+                ],
+            ),
+            node(
+                vec!["X"],
+                vec!["Z"],
+                "averagepool",
+                "AveragePool",
+                vec![
+                    attribute("kernel_shape", vec![2, 2]),
+                    attribute("strides", vec![2, 2]),
+                ],
+            ),
+        ],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(result["Y"], OutputTensor::F32(vec![5.0, 7.0, 13.0, 15.0]));
+    assert_eq!(result["Z"], OutputTensor::F32(vec![2.5, 4.5, 10.5, 12.5]));
+}
+
+#[test]
+fn test_cpu_matmul_batched() {
+    let mut input_data = HashMap::new();
+    let data: Vec<f32> = (0..12).map(|x| x as f32).collect();
+    input_data.insert("A".to_string(), data.as_slice().into());
+
+    // Model: A x B -> Y, where B is broadcast over both matrices in A
+    let model = model(graph(
+        vec![tensor("A", &[2, 2, 3])],
+        vec![tensor("Y", &[2, 2, 2])],
+        vec![],
+        vec![initializer(
+            "B",
+            vec![1.0, 0.0, 0.0, 1.0, 1.0, 1.0],
+            vec![3, 2],
+        )],
+        vec![node(vec!["A", "B"], vec!["Y"], "matmul", "MatMul", vec![])],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(
+        result["Y"],
+        OutputTensor::F32(vec![2.0, 3.0, 8.0, 9.0, 14.0, 15.0, 20.0, 21.0])
+    );
+}
+
+#[test]
+fn test_cpu_gemm() {
+    let mut input_data = HashMap::new();
+    let data: Vec<f32> = (0..6).map(|x| x as f32).collect();
+    input_data.insert("A".to_string(), data.as_slice().into());
+
+    // Model: alpha * A' x B + beta * C -> Y
+    let model = model(graph(
+        vec![tensor("A", &[3, 2])],
+        vec![tensor("Y", &[2, 2])],
+        vec![],
+        vec![
+            initializer("B", vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0], vec![3, 2]),
+            initializer("C", vec![1.0, -1.0], vec![2]),
+        ],
+        vec![node(
+            vec!["A", "B", "C"],
+            vec!["Y"],
+            "gemm",
+            "Gemm",
+            vec![
+                attribute("transA", 1),
+                attribute("alpha", 2.0),
+                attribute("beta", 3.0),
+            ],
+        )],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(result["Y"], OutputTensor::F32(vec![55.0, 61.0, 73.0, 85.0]));
+}
+
+#[test]
+fn test_cpu_softmax() {
+    let mut input_data = HashMap::new();
+    let data = vec![0.0f32, 1.0, 2.0, 0.0, 0.0, 0.0];
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    // Model: X -> Softmax (over the last axis) -> Y
+    let model = model(graph(
+        vec![tensor("X", &[2, 3])],
+        vec![tensor("Y", &[2, 3])],
+        vec![],
+        vec![],
+        vec![node(vec!["X"], vec!["Y"], "softmax", "Softmax", vec![])],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    common::assert_eq_vector(
+        (&result["Y"]).try_into().unwrap(),
+        &[
+            0.09003057, 0.24472848, 0.66524094, 0.33333334, 0.33333334, 0.33333334,
+        ],
+    );
+}
+
+#[test]
+fn test_cpu_gather() {
+    let mut input_data = HashMap::new();
+    let data: Vec<f32> = (0..6).map(|x| x as f32).collect();
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    // Model: X -> Gather (columns, with a negative index) -> Y
+    let model = model(graph(
+        vec![tensor("X", &[2, 3])],
+        vec![tensor("Y", &[2, 2])],
+        vec![],
+        vec![initializer_int64("I", vec![-1, 0], vec![2])],
+        vec![node(
+            vec!["X", "I"],
+            vec!["Y"],
+            "gather",
+            "Gather",
+            vec![attribute("axis", 1)],
+        )],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(result["Y"], OutputTensor::F32(vec![2.0, 0.0, 5.0, 3.0]));
+}
+
+#[test]
+fn test_cpu_transpose_reduce() {
+    let mut input_data = HashMap::new();
+    let data: Vec<f32> = (0..6).map(|x| x as f32).collect();
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    // Model: X -> Transpose -> T -> ReduceSum (over the last axis) -> Y
+    let model = model(graph(
+        vec![tensor("X", &[2, 3])],
+        vec![tensor("T", &[3, 2]), tensor("Y", &[3, 1])],
+        vec![],
+        vec![],
+        vec![
+            node(vec!["X"], vec!["T"], "transpose", "Transpose", vec![]),
+            node(
+                vec!["T"],
+                vec!["Y"],
+                "reduce",
+                "ReduceSum",
+                vec![attribute("axes", vec![-1])],
+            ),
+        ],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(
+        result["T"],
+        OutputTensor::F32(vec![0.0, 3.0, 1.0, 4.0, 2.0, 5.0])
+    );
+    assert_eq!(result["Y"], OutputTensor::F32(vec![3.0, 5.0, 7.0]));
+}
+
+#[test]
+fn test_cpu_concat_split() {
+    let mut input_data = HashMap::new();
+    let data_a: Vec<f32> = (0..4).map(|x| x as f32).collect();
+    let data_b: Vec<f32> = (4..6).map(|x| x as f32).collect();
+    input_data.insert("A".to_string(), data_a.as_slice().into());
+    input_data.insert("B".to_string(), data_b.as_slice().into());
+
+    // Model: (A, B) -> Concat -> C -> Split -> (D, E)
+    let model = model(graph(
+        vec![tensor("A", &[2, 2]), tensor("B", &[2, 1])],
+        vec![tensor("D", &[2, 1]), tensor("E", &[2, 2])],
+        vec![tensor("C", &[2, 3])],
+        vec![initializer_int64("S", vec![1, 2], vec![2])],
+        vec![
+            node(
+                vec!["A", "B"],
+                vec!["C"],
+                "concat",
+                "Concat",
+                vec![attribute("axis", 1)],
+            ),
+            node(
+                vec!["C", "S"],
+                vec!["D", "E"],
+                "split",
+                "Split",
+                vec![attribute("axis", 1)],
+            ),
+        ],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(result["D"], OutputTensor::F32(vec![0.0, 2.0]));
+    assert_eq!(result["E"], OutputTensor::F32(vec![1.0, 4.0, 3.0, 5.0]));
+}
+
+#[test]
+fn test_cpu_pad_reflect() {
+    let mut input_data = HashMap::new();
+    let data = vec![1.0f32, 2.0, 3.0];
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    // Model: X -> Pad (reflect) -> Y
+    let model = model(graph(
+        vec![tensor("X", &[1, 3])],
+        vec![tensor("Y", &[1, 7])],
+        vec![],
+        vec![initializer_int64("P", vec![0, 2, 0, 2], vec![4])],
+        vec![node(
+            vec!["X", "P"],
+            vec!["Y"],
+            "pad",
+            "Pad",
+            vec![attribute("mode", "reflect")],
+        )],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+    assert_eq!(
+        result["Y"],
+        OutputTensor::F32(vec![3.0, 2.0, 1.0, 2.0, 3.0, 2.0, 1.0])
+    );
+}
+
+#[test]
+fn test_cpu_resize() {
+    let mut input_data = HashMap::new();
+    let data = (1..=4).map(|x| x as f32).collect::<Vec<f32>>();
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    // Model: X -> Resize (nearest, upsampling) -> Y
+    let model = model(graph(
+        vec![tensor("X", &[1, 1, 2, 2])],
+        vec![tensor("Y", &[1, 1, 4, 6])],
+        vec![],
+        vec![initializer("scales", vec![1., 1., 2., 3.], vec![4])],
+        vec![node(
+            vec!["X", "" /* roi */, "scales"],
+            vec!["Y"],
+            "Resize",
+            "Resize",
+            vec![],
+        )],
+    ));
+
+    let session = cpu_session(model);
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+
+    #[rustfmt::skip]
+    let test_y = vec![
+        1., 1., 1., 2., 2., 2.,
+        1., 1., 1., 2., 2., 2.,
+        3., 3., 3., 4., 4., 4.,
+        3., 3., 3., 4., 4., 4.,
+    ];
+    assert_eq!(result["Y"], OutputTensor::F32(test_y));
+}
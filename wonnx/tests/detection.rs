@@ -0,0 +1,431 @@
+use std::{collections::HashMap, convert::TryInto};
+use wonnx::{
+    onnx::{ModelProto, TensorProto_DataType, ValueInfoProto},
+    utils::{
+        attribute, graph, initializer, initializer_int64, model, model_with_opset, node, tensor,
+        tensor_of_type, OutputTensor,
+    },
+    Backend, SessionError,
+};
+mod common;
+
+/// Declares the selected indices output of NonMaxSuppression with a symbolic number of boxes, as exporters do
+fn selected_indices(name: &str) -> ValueInfoProto {
+    let mut output = tensor_of_type(name, &[1, 3], TensorProto_DataType::INT64);
+    output
+        .mut_field_type()
+        .mut_tensor_type()
+        .mut_shape()
+        .mut_dim()[0]
+        .set_dim_param("num_selected".to_string());
+    output
+}
+
+fn nms_model(
+    boxes_shape: &[i64],
+    scores_shape: &[i64],
+    max_output_boxes_per_class: i64,
+    score_threshold: Option<f32>,
+    center_point_box: i64,
+) -> ModelProto {
+    let mut inputs = vec!["boxes", "scores", "max_output", "iou_threshold"];
+    let mut initializers = vec![
+        initializer_int64("max_output", vec![max_output_boxes_per_class], vec![1]),
+        initializer("iou_threshold", vec![0.5], vec![1]),
+    ];
+    if let Some(score_threshold) = score_threshold {
+        inputs.push("score_threshold");
+        initializers.push(initializer(
+            "score_threshold",
+            vec![score_threshold],
+            vec![1],
+        ));
+    }
+
+    // The upper bound of the number of selected boxes, as determined by shape inference
+    let max_selected = scores_shape[0] * scores_shape[1] * max_output_boxes_per_class;
+    model(graph(
+        vec![tensor("boxes", boxes_shape), tensor("scores", scores_shape)],
+        vec![selected_indices("Y")],
+        vec![tensor_of_type(
+            "Y",
+            &[max_selected, 3],
+            TensorProto_DataType::INT64,
+        )],
+        initializers,
+        vec![node(
+            inputs,
+            vec!["Y"],
+            "nms",
+            "NonMaxSuppression",
+            vec![attribute("center_point_box", center_point_box)],
+        )],
+    ))
+}
+
+fn run(
+    model: &ModelProto,
+    inputs: &[(&str, &[f32])],
+    backend: Backend,
+) -> Result<OutputTensor, SessionError> {
+    let input_data: HashMap<String, _> = inputs
+        .iter()
+        .map(|(name, data)| (name.to_string(), (*data).into()))
+        .collect();
+    let mut result = common::run_with_backend(model, &input_data, backend)?;
+    Ok(result.remove("Y").unwrap())
+}
+
+const BOXES: [f32; 24] = [
+    0.0, 0.0, 1.0, 1.0, //
+    0.0, 0.1, 1.0, 1.1, //
+    0.0, -0.1, 1.0, 0.9, //
+    0.0, 10.0, 1.0, 11.0, //
+    0.0, 10.1, 1.0, 11.1, //
+    0.0, 100.0, 1.0, 101.0,
+];
+
+const SCORES: [f32; 6] = [0.9, 0.75, 0.6, 0.95, 0.5, 0.3];
+
+fn assert_nms(model: &ModelProto, boxes: &[f32], scores: &[f32], expected: &[i64]) {
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let result = run(model, &[("boxes", boxes), ("scores", scores)], backend).unwrap();
+        assert!(matches!(result, OutputTensor::I64(result) if result == expected));
+    }
+}
+
+#[test]
+fn test_non_max_suppression() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Suppression by IoU
+    assert_nms(
+        &nms_model(&[1, 6, 4], &[1, 1, 6], 3, Some(0.0), 0),
+        &BOXES,
+        &SCORES,
+        &[0, 0, 3, 0, 0, 0, 0, 0, 5],
+    );
+
+    // Suppression by IoU and scores: fewer boxes are selected than the upper bound of the output
+    assert_nms(
+        &nms_model(&[1, 6, 4], &[1, 1, 6], 3, Some(0.4), 0),
+        &BOXES,
+        &SCORES,
+        &[0, 0, 3, 0, 0, 0],
+    );
+
+    // Limited number of boxes per class, without score threshold
+    assert_nms(
+        &nms_model(&[1, 6, 4], &[1, 1, 6], 2, None, 0),
+        &BOXES,
+        &SCORES,
+        &[0, 0, 3, 0, 0, 0],
+    );
+
+    // Flipped coordinates
+    let flipped_boxes = [
+        1.0, 1.0, 0.0, 0.0, //
+        0.0, 0.1, 1.0, 1.1, //
+        0.0, 0.9, 1.0, -0.1, //
+        0.0, 10.0, 1.0, 11.0, //
+        1.0, 10.1, 0.0, 11.1, //
+        1.0, 101.0, 0.0, 100.0,
+    ];
+    assert_nms(
+        &nms_model(&[1, 6, 4], &[1, 1, 6], 3, Some(0.0), 0),
+        &flipped_boxes,
+        &SCORES,
+        &[0, 0, 3, 0, 0, 0, 0, 0, 5],
+    );
+
+    // Center point format
+    let center_boxes = [
+        0.5, 0.5, 1.0, 1.0, //
+        0.5, 0.6, 1.0, 1.0, //
+        0.5, 0.4, 1.0, 1.0, //
+        0.5, 10.5, 1.0, 1.0, //
+        0.5, 10.6, 1.0, 1.0, //
+        0.5, 100.5, 1.0, 1.0,
+    ];
+    assert_nms(
+        &nms_model(&[1, 6, 4], &[1, 1, 6], 3, Some(0.0), 1),
+        &center_boxes,
+        &SCORES,
+        &[0, 0, 3, 0, 0, 0, 0, 0, 5],
+    );
+
+    // Two classes and two batches
+    let boxes: Vec<f32> = BOXES.iter().chain(BOXES.iter()).copied().collect();
+    let scores: Vec<f32> = (0..4).flat_map(|_| SCORES).collect();
+    assert_nms(
+        &nms_model(&[2, 6, 4], &[2, 2, 6], 2, Some(0.0), 0),
+        &boxes,
+        &scores,
+        &[
+            0, 0, 3, 0, 0, 0, 0, 1, 3, 0, 1, 0, //
+            1, 0, 3, 1, 0, 0, 1, 1, 3, 1, 1, 0,
+        ],
+    );
+}
+
+#[test]
+fn test_non_max_suppression_computed_inputs() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // The scores are computed on the GPU before suppression, and the selected indices are passed through an op that
+    // forwards its input
+    let model = model(graph(
+        vec![tensor("boxes", &[1, 6, 4]), tensor("scores", &[1, 1, 6])],
+        vec![selected_indices("Y")],
+        vec![
+            tensor("relu_scores", &[1, 1, 6]),
+            tensor_of_type("selected", &[3, 3], TensorProto_DataType::INT64),
+            tensor_of_type("Y", &[3, 3], TensorProto_DataType::INT64),
+        ],
+        vec![
+            initializer_int64("max_output", vec![3], vec![1]),
+            initializer("iou_threshold", vec![0.5], vec![1]),
+            initializer("score_threshold", vec![0.4], vec![1]),
+        ],
+        vec![
+            node(vec!["scores"], vec!["relu_scores"], "relu", "Relu", vec![]),
+            node(
+                vec![
+                    "boxes",
+                    "relu_scores",
+                    "max_output",
+                    "iou_threshold",
+                    "score_threshold",
+                ],
+                vec!["selected"],
+                "nms",
+                "NonMaxSuppression",
+                vec![],
+            ),
+            node(vec!["selected"], vec!["Y"], "identity", "Identity", vec![]),
+        ],
+    ));
+    assert_nms(&model, &BOXES, &SCORES, &[0, 0, 3, 0, 0, 0]);
+}
+
+#[test]
+fn test_non_max_suppression_post_processing() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // The selected boxes are looked up using the box index column of the selected indices. Only the rows for the boxes
+    // that are actually selected are returned (there is room for four, but three are selected).
+    let model = model(graph(
+        vec![tensor("boxes", &[1, 6, 4]), tensor("scores", &[1, 1, 6])],
+        vec![tensor("Y", &[4, 1, 4])],
+        vec![
+            tensor_of_type("selected", &[4, 3], TensorProto_DataType::INT64),
+            tensor_of_type("box_index", &[4, 1], TensorProto_DataType::INT64),
+            tensor("boxes_2d", &[6, 4]),
+        ],
+        vec![
+            initializer_int64("max_output", vec![4], vec![1]),
+            initializer("iou_threshold", vec![0.5], vec![1]),
+            initializer("score_threshold", vec![0.0], vec![1]),
+            initializer_int64("starts", vec![2], vec![1]),
+            initializer_int64("ends", vec![3], vec![1]),
+            initializer_int64("axes", vec![1], vec![1]),
+            initializer_int64("shape", vec![6, 4], vec![2]),
+        ],
+        vec![
+            node(
+                vec![
+                    "boxes",
+                    "scores",
+                    "max_output",
+                    "iou_threshold",
+                    "score_threshold",
+                ],
+                vec!["selected"],
+                "nms",
+                "NonMaxSuppression",
+                vec![],
+            ),
+            node(
+                vec!["selected", "starts", "ends", "axes"],
+                vec!["box_index"],
+                "slice",
+                "Slice",
+                vec![],
+            ),
+            node(
+                vec!["boxes", "shape"],
+                vec!["boxes_2d"],
+                "reshape",
+                "Reshape",
+                vec![],
+            ),
+            node(
+                vec!["boxes_2d", "box_index"],
+                vec!["Y"],
+                "gather",
+                "Gather",
+                vec![],
+            ),
+        ],
+    ));
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let result: Vec<f32> = run(&model, &[("boxes", &BOXES), ("scores", &SCORES)], backend)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            result,
+            [&BOXES[12..16], &BOXES[0..4], &BOXES[20..24]].concat()
+        );
+    }
+}
+
+#[test]
+fn test_non_max_suppression_data_dependent_shape_unsupported() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // The selected indices cannot be used by ops that do not keep their rows, as the number of rows is not known in
+    // advance
+    let model = model(graph(
+        vec![tensor("boxes", &[1, 6, 4]), tensor("scores", &[1, 1, 6])],
+        vec![tensor_of_type("Y", &[3, 3], TensorProto_DataType::INT64)],
+        vec![tensor_of_type(
+            "selected",
+            &[3, 3],
+            TensorProto_DataType::INT64,
+        )],
+        vec![initializer_int64("max_output", vec![3], vec![1])],
+        vec![
+            node(
+                vec!["boxes", "scores", "max_output"],
+                vec!["selected"],
+                "nms",
+                "NonMaxSuppression",
+                vec![],
+            ),
+            node(
+                vec!["selected"],
+                vec!["Y"],
+                "transpose",
+                "Transpose",
+                vec![],
+            ),
+        ],
+    ));
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        assert!(matches!(
+            run(&model, &[("boxes", &BOXES), ("scores", &SCORES)], backend),
+            Err(SessionError::IrError(_))
+        ));
+    }
+}
+
+fn roi_align_model(
+    x_shape: &[i64],
+    num_rois: i64,
+    output_size: i64,
+    attributes: Vec<(&str, &str)>,
+    sampling_ratio: i64,
+    opset_version: i64,
+) -> ModelProto {
+    let mut node_attributes = vec![
+        attribute("output_height", output_size),
+        attribute("output_width", output_size),
+        attribute("sampling_ratio", sampling_ratio),
+    ];
+    for (name, value) in attributes {
+        node_attributes.push(attribute(name, value));
+    }
+    model_with_opset(
+        graph(
+            vec![
+                tensor("X", x_shape),
+                tensor("rois", &[num_rois, 4]),
+                tensor_of_type("batch_indices", &[num_rois], TensorProto_DataType::INT64),
+            ],
+            vec![tensor(
+                "Y",
+                &[num_rois, x_shape[1], output_size, output_size],
+            )],
+            vec![],
+            vec![],
+            vec![node(
+                vec!["X", "rois", "batch_indices"],
+                vec!["Y"],
+                "roi_align",
+                "RoiAlign",
+                node_attributes,
+            )],
+        ),
+        opset_version,
+    )
+}
+
+fn run_roi_align(
+    model: &ModelProto,
+    x: &[f32],
+    rois: &[f32],
+    batch_indices: &[i64],
+    backend: Backend,
+) -> Vec<f32> {
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x.into());
+    input_data.insert("rois".to_string(), rois.into());
+    input_data.insert("batch_indices".to_string(), batch_indices.into());
+    let mut result = common::run_with_backend(model, &input_data, backend).unwrap();
+    result.remove("Y").unwrap().try_into().unwrap()
+}
+
+#[test]
+fn test_roi_align() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Each plane is a ramp (value = 4y + x, plus 100 for the second batch), which bilinear interpolation reproduces
+    // exactly, so that the average of a bin is the value at its center
+    let x: Vec<f32> = (0..32)
+        .map(|i| (i % 16) as f32 + (i / 16) as f32 * 100.0)
+        .collect();
+
+    // Half pixel: the roi starts at (0.5, 0.5) in pixel coordinates. The second roi lies outside of the image.
+    let model = roi_align_model(&[2, 1, 4, 4], 3, 2, vec![("mode", "avg")], 2, 16);
+    let rois = [
+        1.0, 1.0, 3.0, 3.0, //
+        1.0, 1.0, 3.0, 3.0, //
+        -4.0, -4.0, -2.0, -2.0,
+    ];
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let result = run_roi_align(&model, &x, &rois, &[0, 1, 0], backend);
+        common::assert_eq_vector(
+            &result,
+            &[
+                5.0, 6.0, 9.0, 10.0, //
+                105.0, 106.0, 109.0, 110.0, //
+                0.0, 0.0, 0.0, 0.0,
+            ],
+        );
+    }
+
+    // Before opset 16 the default is to not offset coordinates by half a pixel
+    let model = roi_align_model(&[2, 1, 4, 4], 1, 2, vec![], 2, 10);
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let result = run_roi_align(&model, &x, &[0.0, 0.0, 2.0, 2.0], &[0], backend);
+        common::assert_eq_vector(&result, &[2.5, 3.5, 6.5, 7.5]);
+    }
+
+    // Samples at pixel centers select single pixels. The mode selects the maximum or average of the samples in a bin.
+    let attributes = |mode| {
+        vec![
+            ("mode", mode),
+            ("coordinate_transformation_mode", "output_half_pixel"),
+        ]
+    };
+    let max_model = roi_align_model(&[2, 1, 4, 4], 2, 1, attributes("max"), 2, 16);
+    let avg_model = roi_align_model(&[2, 1, 4, 4], 2, 1, attributes("avg"), 2, 16);
+    let rois = [0.0, 0.0, 4.0, 4.0, 0.0, 0.0, 4.0, 4.0];
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let result = run_roi_align(&max_model, &x, &rois, &[0, 1], backend);
+        common::assert_eq_vector(&result, &[15.0, 115.0]);
+        let result = run_roi_align(&avg_model, &x, &rois, &[0, 1], backend);
+        common::assert_eq_vector(&result, &[10.0, 110.0]);
+    }
+}
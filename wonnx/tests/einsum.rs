@@ -0,0 +1,150 @@
+use approx::assert_abs_diff_eq;
+use std::{collections::HashMap, convert::TryInto};
+use wonnx::{
+    onnx::ModelProto,
+    utils::{attribute, graph, model, node, tensor},
+    Backend, CompileError, GpuError, SessionError,
+};
+mod common;
+
+fn einsum_model(equation: &str, input_shapes: &[&[i64]], output_shape: &[i64]) -> ModelProto {
+    let names: Vec<String> = (0..input_shapes.len()).map(|i| format!("X{}", i)).collect();
+    model(graph(
+        names
+            .iter()
+            .zip(input_shapes)
+            .map(|(name, shape)| tensor(name, shape))
+            .collect(),
+        vec![tensor("Y", output_shape)],
+        vec![],
+        vec![],
+        vec![node(
+            names.iter().map(|name| name.as_str()).collect(),
+            vec!["Y"],
+            "einsum",
+            "Einsum",
+            vec![attribute("equation", equation)],
+        )],
+    ))
+}
+
+fn run(
+    model: &ModelProto,
+    inputs: &[Vec<f32>],
+    backend: Backend,
+) -> Result<Vec<f32>, SessionError> {
+    let mut input_data = HashMap::new();
+    for (index, input) in inputs.iter().enumerate() {
+        input_data.insert(format!("X{}", index), input.as_slice().into());
+    }
+
+    let mut result = common::run_with_backend(model, &input_data, backend)?;
+    Ok(result.remove("Y").unwrap().try_into().unwrap())
+}
+
+/// Straightforward evaluation of an Einsum equation (without ellipsis) as a reference
+fn reference(equation: &str, inputs: &[Vec<f32>], input_shapes: &[&[i64]]) -> Vec<f32> {
+    let (lhs, output) = equation.split_once("->").unwrap();
+    let terms: Vec<Vec<char>> = lhs.split(',').map(|term| term.chars().collect()).collect();
+    let output: Vec<char> = output.chars().collect();
+    let mut dims: HashMap<char, usize> = HashMap::new();
+    for (term, shape) in terms.iter().zip(input_shapes) {
+        for (label, dim) in term.iter().zip(shape.iter()) {
+            dims.insert(*label, *dim as usize);
+        }
+    }
+    let mut labels: Vec<char> = dims.keys().copied().collect();
+    labels.sort_unstable();
+
+    let offset = |term: &[char], values: &HashMap<char, usize>| {
+        term.iter()
+            .fold(0, |offset, label| offset * dims[label] + values[label])
+    };
+    let mut result = vec![0.0; output.iter().map(|label| dims[label]).product()];
+    let combinations: usize = labels.iter().map(|label| dims[label]).product();
+    for combination in 0..combinations {
+        let mut rest = combination;
+        let mut values = HashMap::new();
+        for label in &labels {
+            values.insert(*label, rest % dims[label]);
+            rest /= dims[label];
+        }
+        let product: f32 = terms
+            .iter()
+            .zip(inputs)
+            .map(|(term, input)| input[offset(term, &values)])
+            .product();
+        result[offset(&output, &values)] += product;
+    }
+    result
+}
+
+fn assert_einsum(equation: &str, input_shapes: &[&[i64]], output_shape: &[i64]) {
+    let inputs: Vec<Vec<f32>> = input_shapes
+        .iter()
+        .enumerate()
+        .map(|(index, shape)| {
+            (0..shape.iter().product::<i64>())
+                .map(|i| ((i * 7 + index as i64 * 3) % 11) as f32 * 0.25 - 1.0)
+                .collect()
+        })
+        .collect();
+    let expected = reference(equation, &inputs, input_shapes);
+
+    let model = einsum_model(equation, input_shapes, output_shape);
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let result = run(&model, &inputs, backend).unwrap();
+        assert_abs_diff_eq!(result.as_slice(), expected.as_slice(), epsilon = 1e-4);
+    }
+}
+
+#[test]
+fn test_einsum() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Transpose and reductions
+    assert_einsum("ij->ji", &[&[2, 3]], &[3, 2]);
+    assert_einsum("bij->bji", &[&[2, 3, 4]], &[2, 4, 3]);
+    assert_einsum("ij->i", &[&[2, 3]], &[2]);
+    assert_einsum("ijk->ki", &[&[2, 3, 4]], &[4, 2]);
+
+    // (Batched) matrix multiplication
+    assert_einsum("ij,jk->ik", &[&[4, 8], &[8, 4]], &[4, 4]);
+    assert_einsum("bij,bjk->bik", &[&[2, 4, 4], &[2, 4, 8]], &[2, 4, 8]);
+    assert_einsum(
+        "bhqd,bhkd->bhqk",
+        &[&[1, 2, 4, 8], &[1, 2, 6, 8]],
+        &[1, 2, 4, 6],
+    );
+    assert_einsum(
+        "bhqk,bhkd->bqhd",
+        &[&[1, 2, 4, 6], &[1, 2, 6, 8]],
+        &[1, 4, 2, 8],
+    );
+
+    // Dimensions the matrix multiplication templates do not support, and outer products
+    assert_einsum("ij,jk->ik", &[&[3, 5], &[5, 3]], &[3, 3]);
+    assert_einsum("i,j->ij", &[&[2], &[3]], &[2, 3]);
+    assert_einsum("bi,bj->bij", &[&[2, 3], &[2, 4]], &[2, 3, 4]);
+
+    // Three inputs, and labels summed over within a single input
+    assert_einsum("ij,jk,kl->il", &[&[2, 4], &[4, 4], &[4, 2]], &[2, 2]);
+    assert_einsum("ij,k->i", &[&[2, 3], &[4]], &[2]);
+}
+
+#[test]
+fn test_einsum_not_lowered() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Diagonals cannot be lowered for the GPU, but are supported by the CPU backend
+    let model = einsum_model("ii->i", &[&[3, 3]], &[3]);
+    let x: Vec<f32> = (0..9).map(|x| x as f32).collect();
+    assert!(matches!(
+        run(&model, std::slice::from_ref(&x), Backend::Gpu),
+        Err(SessionError::GpuError(GpuError::CompileError {
+            error: CompileError::Einsum(_),
+            ..
+        }))
+    ));
+    assert_eq!(run(&model, &[x], Backend::Cpu).unwrap(), [0.0, 4.0, 8.0]);
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use wonnx::utils::{graph, initializer_int64, model, node, tensor, OutputTensor};
+mod common;
+
+fn assert_output(model: wonnx::onnx::ModelProto, data: &[f32], expected: &[f32]) {
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), data.into());
+
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(result["Y"], OutputTensor::F32(expected.to_vec()));
+    }
+}
+
+#[test]
+fn test_expand() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // The shape may have a lower rank than the input, and dimensions of 1 are kept
+    let model = model(graph(
+        vec![tensor("X", &[3, 1])],
+        vec![tensor("Y", &[2, 3, 2])],
+        vec![],
+        vec![initializer_int64("shape", vec![2, 1, 2], vec![3])],
+        vec![node(
+            vec!["X", "shape"],
+            vec!["Y"],
+            "expand",
+            "Expand",
+            vec![],
+        )],
+    ));
+    assert_output(
+        model,
+        &[1.0, 2.0, 3.0],
+        &[1.0, 1.0, 2.0, 2.0, 3.0, 3.0, 1.0, 1.0, 2.0, 2.0, 3.0, 3.0],
+    );
+}
+
+#[test]
+fn test_tile() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let model = model(graph(
+        vec![tensor("X", &[2, 2])],
+        vec![tensor("Y", &[4, 4])],
+        vec![],
+        vec![initializer_int64("repeats", vec![2, 2], vec![2])],
+        vec![node(
+            vec!["X", "repeats"],
+            vec!["Y"],
+            "tile",
+            "Tile",
+            vec![],
+        )],
+    ));
+    assert_output(
+        model,
+        &[1.0, 2.0, 3.0, 4.0],
+        &[
+            1.0, 2.0, 1.0, 2.0, 3.0, 4.0, 3.0, 4.0, 1.0, 2.0, 1.0, 2.0, 3.0, 4.0, 3.0, 4.0,
+        ],
+    );
+}
@@ -0,0 +1,97 @@
+use std::{collections::HashMap, convert::TryInto};
+
+use protobuf::ProtobufEnum;
+use wonnx::{
+    half::f16,
+    onnx::{TensorProto, TensorProto_DataType},
+    utils::{attribute, graph, model, node, tensor, tensor_of_type, OutputTensor},
+};
+mod common;
+
+#[test]
+fn test_f16_model() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let n: usize = 7;
+    let dims = vec![n as i64];
+    let data: Vec<f16> = (0..n).map(|x| f16::from_f32(x as f32 - 3.5)).collect();
+    let bias: Vec<f16> = (0..n).map(|x| f16::from_f32(x as f32 * 0.25)).collect();
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    let mut bias_initializer = TensorProto::from(OutputTensor::F16(bias.clone()), dims.clone());
+    bias_initializer.set_name("B".to_string());
+
+    // Model: X, B -> Add -> Relu -> Y
+    let model = model(graph(
+        vec![tensor_of_type("X", &dims, TensorProto_DataType::FLOAT16)],
+        vec![tensor_of_type("Y", &dims, TensorProto_DataType::FLOAT16)],
+        vec![tensor_of_type("S", &dims, TensorProto_DataType::FLOAT16)],
+        vec![bias_initializer],
+        vec![
+            node(vec!["X", "B"], vec!["S"], "add", "Add", vec![]),
+            node(vec!["S"], vec!["Y"], "relu", "Relu", vec![]),
+        ],
+    ));
+
+    let expected: Vec<f16> = data
+        .iter()
+        .zip(&bias)
+        .map(|(x, b)| f16::from_f32((x.to_f32() + b.to_f32()).max(0.0)))
+        .collect();
+
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(result["Y"], OutputTensor::F16(expected.clone()));
+    }
+}
+
+#[test]
+fn test_cast_f16() {
+    let n: usize = 16;
+    let dims = vec![n as i64];
+    let data: Vec<f32> = (0..n).map(|x| x as f32 / 3.0).collect();
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    // Model: X -> Cast -> Y (f16) -> Cast -> Z (f32)
+    let model = model(graph(
+        vec![tensor("X", &dims)],
+        vec![
+            tensor_of_type("Y", &dims, TensorProto_DataType::FLOAT16),
+            tensor("Z", &dims),
+        ],
+        vec![],
+        vec![],
+        vec![
+            node(
+                vec!["X"],
+                vec!["Y"],
+                "to_f16",
+                "Cast",
+                vec![attribute(
+                    "to",
+                    TensorProto_DataType::FLOAT16.value() as i64,
+                )],
+            ),
+            node(
+                vec!["Y"],
+                vec!["Z"],
+                "to_f32",
+                "Cast",
+                vec![attribute("to", TensorProto_DataType::FLOAT.value() as i64)],
+            ),
+        ],
+    ));
+
+    let session =
+        pollster::block_on(wonnx::Session::from_model(model)).expect("Session did not create");
+    let result = pollster::block_on(session.run(&input_data)).unwrap();
+
+    let expected: Vec<f16> = data.iter().map(|x| f16::from_f32(*x)).collect();
+    assert_eq!(result["Y"], OutputTensor::F16(expected.clone()));
+
+    // When the device does not support f16, calculations are performed with f32, so values are not necessarily rounded
+    let z: Vec<f32> = result["Z"].clone().try_into().unwrap();
+    for (z, y) in z.iter().zip(expected) {
+        assert!((z - y.to_f32()).abs() <= 0.01, "{} != {}", z, y);
+    }
+}
@@ -1,5 +1,8 @@
 use std::{collections::HashMap, convert::TryInto};
-use wonnx::utils::{attribute, graph, model, node, tensor};
+use wonnx::{
+    onnx::AttributeProto,
+    utils::{attribute, graph, initializer_int64, model, node, tensor},
+};
 mod common;
 
 fn assert_gather(
@@ -88,3 +91,127 @@ fn gather() {
         0,
     );
 }
+
+/// Runs GatherElements or GatherND (with constant indices) on all backends and checks the output
+#[allow(clippy::too_many_arguments)]
+fn assert_gather_op(
+    op: &str,
+    data: &[f32],
+    data_shape: &[i64],
+    indices: Vec<i64>,
+    indices_shape: &[i64],
+    output: &[f32],
+    output_shape: &[i64],
+    attributes: Vec<AttributeProto>,
+) {
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), data.into());
+
+    // Model: (X, I) -> op -> Y
+    let model = model(graph(
+        vec![tensor("X", data_shape)],
+        vec![tensor("Y", output_shape)],
+        vec![],
+        vec![initializer_int64("I", indices, indices_shape.to_vec())],
+        vec![node(vec!["X", "I"], vec!["Y"], "gather", op, attributes)],
+    ));
+
+    for result in common::run_on_all_backends(&model, &input_data) {
+        common::assert_eq_vector((&result["Y"]).try_into().unwrap(), output);
+    }
+}
+
+// Examples from https://github.com/onnx/onnx/blob/main/docs/Operators.md#GatherElements
+#[test]
+fn gather_elements() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    assert_gather_op(
+        "GatherElements",
+        &[1.0, 2.0, 3.0, 4.0],
+        &[2, 2],
+        vec![0, 0, 1, 0],
+        &[2, 2],
+        &[1.0, 1.0, 4.0, 3.0],
+        &[2, 2],
+        vec![attribute("axis", 1)],
+    );
+
+    let data: Vec<f32> = (1..=9).map(|x| x as f32).collect();
+    assert_gather_op(
+        "GatherElements",
+        &data,
+        &[3, 3],
+        vec![1, 2, 0, 2, 0, 0],
+        &[2, 3],
+        &[4.0, 8.0, 3.0, 7.0, 2.0, 3.0],
+        &[2, 3],
+        vec![attribute("axis", 0)],
+    );
+
+    // Negative indices
+    assert_gather_op(
+        "GatherElements",
+        &data,
+        &[3, 3],
+        vec![-1, -2, 0, -2, 0, 0],
+        &[2, 3],
+        &[7.0, 5.0, 3.0, 4.0, 2.0, 3.0],
+        &[2, 3],
+        vec![attribute("axis", 0)],
+    );
+}
+
+// Examples from https://github.com/onnx/onnx/blob/main/docs/Operators.md#GatherND
+#[test]
+fn gather_nd() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // Index tuples select single elements
+    assert_gather_op(
+        "GatherND",
+        &[0.0, 1.0, 2.0, 3.0],
+        &[2, 2],
+        vec![0, 0, 1, 1],
+        &[2, 2],
+        &[0.0, 3.0],
+        &[2],
+        vec![],
+    );
+
+    // Index tuples select rows
+    assert_gather_op(
+        "GatherND",
+        &[0.0, 1.0, 2.0, 3.0],
+        &[2, 2],
+        vec![1, 0],
+        &[2, 1],
+        &[2.0, 3.0, 0.0, 1.0],
+        &[2, 2],
+        vec![],
+    );
+
+    let data: Vec<f32> = (0..8).map(|x| x as f32).collect();
+    assert_gather_op(
+        "GatherND",
+        &data,
+        &[2, 2, 2],
+        vec![0, 1, 1, 0],
+        &[2, 2],
+        &[2.0, 3.0, 4.0, 5.0],
+        &[2, 2],
+        vec![],
+    );
+
+    // Negative indices within batches
+    assert_gather_op(
+        "GatherND",
+        &data,
+        &[2, 2, 2],
+        vec![-1, 0],
+        &[2, 1],
+        &[2.0, 3.0, 4.0, 5.0],
+        &[2, 2],
+        vec![attribute("batch_dims", 1)],
+    );
+}
@@ -0,0 +1,132 @@
+use approx::assert_abs_diff_eq;
+use std::{collections::HashMap, convert::TryInto};
+use wonnx::{
+    onnx::{AttributeProto, ModelProto},
+    utils::{attribute, graph, initializer, model, node, tensor},
+};
+mod common;
+
+fn normalization_model(
+    op: &str,
+    shape: &[i64],
+    parameters: Option<(Vec<f32>, Vec<f32>)>,
+    attributes: Vec<AttributeProto>,
+) -> ModelProto {
+    let (inputs, initializers) = match parameters {
+        Some((scale, bias)) => {
+            let parameter_shape = vec![scale.len() as i64];
+            (
+                vec!["X", "scale", "B"],
+                vec![
+                    initializer("scale", scale, parameter_shape.clone()),
+                    initializer("B", bias, parameter_shape),
+                ],
+            )
+        }
+        None => (vec!["X"], vec![]),
+    };
+
+    model(graph(
+        vec![tensor("X", shape)],
+        vec![tensor("Y", shape)],
+        vec![],
+        initializers,
+        vec![node(inputs, vec!["Y"], "normalization", op, attributes)],
+    ))
+}
+
+#[test]
+fn test_instance_normalization() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x: &[f32] = &[1.0, 2.0, 3.0, 2.0, 4.0, 6.0];
+    let input_data = HashMap::from([("X".to_string(), x.into())]);
+
+    let model = normalization_model(
+        "InstanceNormalization",
+        &[1, 2, 1, 3],
+        Some((vec![1.0, 2.0], vec![0.0, 1.0])),
+        vec![],
+    );
+    let expected = [-1.22474, 0.0, 1.22474, -1.44949, 1.0, 3.44949];
+    for result in common::run_on_all_backends(&model, &input_data) {
+        let y: &[f32] = (&result["Y"]).try_into().unwrap();
+        assert_abs_diff_eq!(y, &expected[..], epsilon = 1e-4);
+    }
+}
+
+#[test]
+fn test_group_normalization() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // A single group containing both channels (scale and bias per channel)
+    let x: &[f32] = &[1.0, 2.0, 3.0, 2.0, 4.0, 6.0];
+    let input_data = HashMap::from([("X".to_string(), x.into())]);
+    let model = normalization_model(
+        "GroupNormalization",
+        &[1, 2, 1, 3],
+        Some((vec![1.0, 2.0], vec![0.0, 1.0])),
+        vec![attribute("num_groups", 1)],
+    );
+    let expected = [-1.22474, -0.61237, 0.0, -0.22474, 2.22474, 4.67423];
+    for result in common::run_on_all_backends(&model, &input_data) {
+        let y: &[f32] = (&result["Y"]).try_into().unwrap();
+        assert_abs_diff_eq!(y, &expected[..], epsilon = 1e-4);
+    }
+
+    // Scale and bias per group (before opset 21) are the same as scale and bias repeated for each channel in the group
+    let x: Vec<f32> = (0..48).map(|i| ((i * 7) % 11) as f32 - 5.0).collect();
+    let input_data = HashMap::from([("X".to_string(), x.as_slice().into())]);
+    let per_group = normalization_model(
+        "GroupNormalization",
+        &[2, 4, 2, 3],
+        Some((vec![0.5, 2.0], vec![1.0, -1.0])),
+        vec![attribute("num_groups", 2), attribute("epsilon", 1e-3)],
+    );
+    let per_channel = normalization_model(
+        "GroupNormalization",
+        &[2, 4, 2, 3],
+        Some((vec![0.5, 0.5, 2.0, 2.0], vec![1.0, 1.0, -1.0, -1.0])),
+        vec![attribute("num_groups", 2), attribute("epsilon", 1e-3)],
+    );
+    let expected: Vec<f32> = common::run_on_all_backends(&per_channel, &input_data)[1]["Y"]
+        .clone()
+        .try_into()
+        .unwrap();
+    for result in common::run_on_all_backends(&per_group, &input_data) {
+        let y: &[f32] = (&result["Y"]).try_into().unwrap();
+        assert_abs_diff_eq!(y, expected.as_slice(), epsilon = 1e-4);
+    }
+}
+
+#[test]
+fn test_lp_normalization() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x: &[f32] = &[3.0, 4.0, 6.0, 8.0];
+    let input_data = HashMap::from([("X".to_string(), x.into())]);
+
+    let cases: [(i64, i64, [f32; 4]); 3] = [
+        (-1, 2, [0.6, 0.8, 0.6, 0.8]),
+        (1, 1, [3.0 / 7.0, 4.0 / 7.0, 6.0 / 14.0, 8.0 / 14.0]),
+        (0, 2, [0.44721, 0.44721, 0.89443, 0.89443]),
+    ];
+    for (axis, p, expected) in cases {
+        let model = normalization_model(
+            "LpNormalization",
+            &[2, 2],
+            None,
+            vec![attribute("axis", axis), attribute("p", p)],
+        );
+        for result in common::run_on_all_backends(&model, &input_data) {
+            let y: &[f32] = (&result["Y"]).try_into().unwrap();
+            assert_abs_diff_eq!(y, &expected[..], epsilon = 1e-4);
+        }
+    }
+
+    // Values that are all zero are left unchanged
+    let model = normalization_model("LpNormalization", &[2, 2], None, vec![]);
+    let input_data = HashMap::from([("X".to_string(), [0.0f32, 0.0, 3.0, 4.0][..].into())]);
+    for result in common::run_on_all_backends(&model, &input_data) {
+        let y: &[f32] = (&result["Y"]).try_into().unwrap();
+        assert_abs_diff_eq!(y, &[0.0, 0.0, 0.6, 0.8][..], epsilon = 1e-4);
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use wonnx::{
+    onnx::{TensorProto, TensorProto_DataType},
+    utils::{attribute, graph, initializer, model, node, tensor, tensor_of_type, OutputTensor},
+};
+mod common;
+
+fn quantized_initializer(name: &str, data: OutputTensor, dims: &[i64]) -> TensorProto {
+    let mut initializer = TensorProto::from(data, dims.to_vec());
+    initializer.set_name(name.to_string());
+    initializer
+}
+
+#[test]
+fn test_quantize_dequantize_linear() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x = [-3.0f32, -0.75, -0.25, 0.25, 0.75, 1.25, 60.0, 70.0];
+    let w = [-4i8, 2, 6, -8, 0, 8];
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x[..].into());
+    input_data.insert("W".to_string(), w[..].into());
+
+    let u8_type = TensorProto_DataType::UINT8;
+    let model = model(graph(
+        vec![
+            tensor("X", &[2, 4]),
+            tensor_of_type("W", &[2, 3], TensorProto_DataType::INT8),
+        ],
+        vec![
+            tensor_of_type("Q", &[2, 4], u8_type),
+            tensor("D", &[2, 4]),
+            tensor_of_type("QN", &[2, 4], u8_type),
+            tensor("WD", &[2, 3]),
+        ],
+        vec![],
+        vec![
+            initializer("scale", vec![0.5], vec![]),
+            quantized_initializer("zero_point", OutputTensor::U8(vec![128]), &[]),
+            initializer("w_scale", vec![0.5, 0.25], vec![2]),
+        ],
+        vec![
+            node(
+                vec!["X", "scale", "zero_point"],
+                vec!["Q"],
+                "quantize",
+                "QuantizeLinear",
+                vec![],
+            ),
+            node(
+                vec!["Q", "scale", "zero_point"],
+                vec!["D"],
+                "dequantize",
+                "DequantizeLinear",
+                vec![],
+            ),
+            node(
+                vec!["X", "scale"],
+                vec!["QN"],
+                "quantize_no_zero_point",
+                "QuantizeLinear",
+                vec![],
+            ),
+            node(
+                vec!["W", "w_scale"],
+                vec!["WD"],
+                "dequantize_per_axis",
+                "DequantizeLinear",
+                vec![attribute("axis", 0)],
+            ),
+        ],
+    ));
+
+    for result in common::run_on_all_backends(&model, &input_data) {
+        // Rounding is to the nearest even integer, and values are saturated
+        assert_eq!(
+            result["Q"],
+            OutputTensor::U8(vec![122, 126, 128, 128, 130, 130, 248, 255])
+        );
+        assert_eq!(
+            result["D"],
+            OutputTensor::F32(vec![-3.0, -1.0, 0.0, 0.0, 1.0, 1.0, 60.0, 63.5])
+        );
+        assert_eq!(
+            result["QN"],
+            OutputTensor::U8(vec![0, 0, 0, 0, 2, 2, 120, 140])
+        );
+        assert_eq!(
+            result["WD"],
+            OutputTensor::F32(vec![-2.0, 1.0, 3.0, -2.0, 0.0, 2.0])
+        );
+    }
+}
+
+#[test]
+fn test_quantized_matmul() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let a = [1u8, 2, 3, 4, 5, 6];
+    let mut input_data = HashMap::new();
+    input_data.insert("A".to_string(), a[..].into());
+
+    let model = model(graph(
+        vec![tensor_of_type("A", &[2, 3], TensorProto_DataType::UINT8)],
+        vec![
+            tensor_of_type("Y", &[2, 2], TensorProto_DataType::INT32),
+            tensor_of_type("QY", &[2, 2], TensorProto_DataType::UINT8),
+        ],
+        vec![],
+        vec![
+            quantized_initializer("B", OutputTensor::U8(vec![7, 8, 9, 10, 11, 12]), &[3, 2]),
+            quantized_initializer("a_zero_point", OutputTensor::U8(vec![2]), &[]),
+            quantized_initializer("b_zero_point", OutputTensor::U8(vec![8]), &[]),
+            initializer("a_scale", vec![0.5], vec![]),
+            initializer("b_scale", vec![0.25], vec![]),
+            initializer("y_scale", vec![0.25], vec![]),
+            quantized_initializer("y_zero_point", OutputTensor::U8(vec![100]), &[]),
+        ],
+        vec![
+            node(
+                vec!["A", "B", "a_zero_point", "b_zero_point"],
+                vec!["Y"],
+                "matmul_integer",
+                "MatMulInteger",
+                vec![],
+            ),
+            node(
+                vec![
+                    "A",
+                    "a_scale",
+                    "a_zero_point",
+                    "B",
+                    "b_scale",
+                    "b_zero_point",
+                    "y_scale",
+                    "y_zero_point",
+                ],
+                vec!["QY"],
+                "qlinear_matmul",
+                "QLinearMatMul",
+                vec![],
+            ),
+        ],
+    ));
+
+    for result in common::run_on_all_backends(&model, &input_data) {
+        // [[-1, 0, 1], [2, 3, 4]] x [[-1, 0], [1, 2], [3, 4]]
+        assert_eq!(result["Y"], OutputTensor::I32(vec![4, 4, 13, 22]));
+
+        // Y * 0.5 * 0.25 / 0.25 + 100
+        assert_eq!(result["QY"], OutputTensor::U8(vec![102, 102, 106, 111]));
+    }
+}
+
+#[test]
+fn test_qlinear_conv() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x: Vec<u8> = (0..16).map(|i| i * 3).collect();
+    let w: Vec<i8> = (0..18).map(|i| (i % 5) - 2).collect();
+    let bias = [4i32, -8];
+    let (x_scale, x_zero_point, w_scales) = (0.5, 6.0, [0.5, 0.25]);
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x.as_slice().into());
+
+    let model = model(graph(
+        vec![tensor_of_type(
+            "X",
+            &[1, 1, 4, 4],
+            TensorProto_DataType::UINT8,
+        )],
+        vec![tensor_of_type(
+            "Y",
+            &[1, 2, 4, 4],
+            TensorProto_DataType::INT8,
+        )],
+        vec![],
+        vec![
+            quantized_initializer("W", OutputTensor::I8(w.clone()), &[2, 1, 3, 3]),
+            quantized_initializer("B", OutputTensor::I32(bias.to_vec()), &[2]),
+            initializer("x_scale", vec![x_scale], vec![]),
+            quantized_initializer("x_zero_point", OutputTensor::U8(vec![6]), &[]),
+            initializer("w_scale", w_scales.to_vec(), vec![2]),
+            quantized_initializer("w_zero_point", OutputTensor::I8(vec![0]), &[]),
+            initializer("y_scale", vec![1.0], vec![]),
+            quantized_initializer("y_zero_point", OutputTensor::I8(vec![0]), &[]),
+        ],
+        vec![node(
+            vec![
+                "X",
+                "x_scale",
+                "x_zero_point",
+                "W",
+                "w_scale",
+                "w_zero_point",
+                "y_scale",
+                "y_zero_point",
+                "B",
+            ],
+            vec!["Y"],
+            "qlinear_conv",
+            "QLinearConv",
+            vec![
+                attribute("kernel_shape", vec![3, 3]),
+                attribute("pads", vec![1, 1, 1, 1]),
+            ],
+        )],
+    ));
+
+    let mut expected = vec![];
+    for m in 0..2 {
+        for y in 0..4i64 {
+            for x_index in 0..4i64 {
+                let mut sum = bias[m] as f32;
+                for ky in 0..3 {
+                    for kx in 0..3 {
+                        let (iy, ix) = (y + ky - 1, x_index + kx - 1);
+                        if (0..4).contains(&iy) && (0..4).contains(&ix) {
+                            let x_value = x[(iy * 4 + ix) as usize] as f32 - x_zero_point;
+                            sum += x_value * w[m * 9 + (ky * 3 + kx) as usize] as f32;
+                        }
+                    }
+                }
+                let y_value = (sum * x_scale * w_scales[m]).round_ties_even();
+                expected.push(y_value.clamp(-128.0, 127.0) as i8);
+            }
+        }
+    }
+
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(result["Y"], OutputTensor::I8(expected.clone()));
+    }
+}
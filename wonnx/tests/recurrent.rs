@@ -0,0 +1,376 @@
+use std::{collections::HashMap, convert::TryInto};
+
+use approx::assert_abs_diff_eq;
+use wonnx::{
+    onnx::{ModelProto, NodeProto, TensorProto},
+    utils::{attribute, graph, initializer, model, node, tensor, OutputTensor},
+};
+mod common;
+
+fn sequence_lengths(name: &str, lengths: Vec<i32>) -> TensorProto {
+    let dims = vec![lengths.len() as i64];
+    let mut tensor = TensorProto::from(OutputTensor::I32(lengths), dims);
+    tensor.set_name(name.to_string());
+    tensor
+}
+
+/// Deterministic pseudo-random values in [-1, 1)
+fn values(count: usize, seed: usize) -> Vec<f32> {
+    (0..count)
+        .map(|i| ((i * 7919 + seed * 104729) % 2000) as f32 / 1000.0 - 1.0)
+        .collect()
+}
+
+/// An RNN node with a single hidden unit and ReLU activation, for which h_t = relu(x_t + 0.5 * h_t-1)
+fn relu_rnn_model(
+    x_dims: &[i64],
+    direction: &str,
+    extra_inputs: Vec<&str>,
+    extra_initializers: Vec<TensorProto>,
+    y_dims: &[i64],
+) -> ModelProto {
+    let num_directions = y_dims[1];
+    let mut inputs = vec!["X", "W", "R"];
+    inputs.extend(extra_inputs);
+    let mut initializers = vec![
+        initializer(
+            "W",
+            vec![1.0; num_directions as usize],
+            vec![num_directions, 1, 1],
+        ),
+        initializer(
+            "R",
+            vec![0.5; num_directions as usize],
+            vec![num_directions, 1, 1],
+        ),
+    ];
+    initializers.extend(extra_initializers);
+    model(graph(
+        vec![tensor("X", x_dims)],
+        vec![
+            tensor("Y", y_dims),
+            tensor("Y_h", &[num_directions, x_dims[1], 1]),
+        ],
+        vec![],
+        initializers,
+        vec![node(
+            inputs,
+            vec!["Y", "Y_h"],
+            "rnn",
+            "RNN",
+            vec![
+                attribute("hidden_size", 1),
+                attribute("direction", direction),
+                attribute(
+                    "activations",
+                    vec!["Relu".to_string(); num_directions as usize],
+                ),
+            ],
+        )],
+    ))
+}
+
+#[test]
+fn test_rnn_directions() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x = [1.0, 2.0, 3.0];
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x[..].into());
+
+    let model = relu_rnn_model(&[3, 1, 1], "forward", vec![], vec![], &[3, 1, 1, 1]);
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(result["Y"], OutputTensor::F32(vec![1.0, 2.5, 4.25]));
+        assert_eq!(result["Y_h"], OutputTensor::F32(vec![4.25]));
+    }
+
+    let model = relu_rnn_model(&[3, 1, 1], "reverse", vec![], vec![], &[3, 1, 1, 1]);
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(result["Y"], OutputTensor::F32(vec![2.75, 3.5, 3.0]));
+        assert_eq!(result["Y_h"], OutputTensor::F32(vec![2.75]));
+    }
+
+    let model = relu_rnn_model(&[3, 1, 1], "bidirectional", vec![], vec![], &[3, 2, 1, 1]);
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(
+            result["Y"],
+            OutputTensor::F32(vec![1.0, 2.75, 2.5, 3.5, 4.25, 3.0])
+        );
+        assert_eq!(result["Y_h"], OutputTensor::F32(vec![4.25, 2.75]));
+    }
+}
+
+#[test]
+fn test_rnn_sequence_lengths_and_initial_state() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // Two batch elements with sequences [1, 2, 3] and [1, 2] (the last step is padding)
+    let x = [1.0, 1.0, 2.0, 2.0, 3.0, 100.0];
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x[..].into());
+
+    let model = relu_rnn_model(
+        &[3, 2, 1],
+        "forward",
+        vec!["B", "sequence_lens", "initial_h"],
+        vec![
+            initializer("B", vec![0.0, 1.0], vec![1, 2]),
+            sequence_lengths("sequence_lens", vec![3, 2]),
+            initializer("initial_h", vec![0.0, 2.0], vec![1, 2, 1]),
+        ],
+        &[3, 1, 2, 1],
+    );
+    // Batch 0: h = 2, 4, 6; batch 1: h = 1 + 1 + 1 = 3, 2 + 1.5 + 1 = 4.5
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(
+            result["Y"],
+            OutputTensor::F32(vec![2.0, 3.0, 4.0, 4.5, 6.0, 0.0])
+        );
+        assert_eq!(result["Y_h"], OutputTensor::F32(vec![6.0, 4.5]));
+    }
+
+    // Optional inputs can be left out by name (here the bias), and reversed sequences start at the last step within the
+    // sequence length
+    let model = relu_rnn_model(
+        &[3, 2, 1],
+        "reverse",
+        vec!["", "sequence_lens"],
+        vec![sequence_lengths("sequence_lens", vec![3, 2])],
+        &[3, 1, 2, 1],
+    );
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(
+            result["Y"],
+            OutputTensor::F32(vec![2.75, 2.0, 3.5, 2.0, 3.0, 0.0])
+        );
+        assert_eq!(result["Y_h"], OutputTensor::F32(vec![2.75, 2.0]));
+    }
+}
+
+#[test]
+fn test_lstm_cell_state() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // With zero weights all gates are 0.5 and the cell input is zero, so the cell state halves at each step
+    let (hidden_size, input_size) = (3, 2);
+    let x = [1.0, 2.0, 3.0, 4.0];
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x[..].into());
+
+    let model = model(graph(
+        vec![tensor("X", &[2, 1, input_size])],
+        vec![
+            tensor("Y_h", &[1, 1, hidden_size]),
+            tensor("Y_c", &[1, 1, hidden_size]),
+        ],
+        vec![],
+        vec![
+            initializer(
+                "W",
+                vec![0.0; (4 * hidden_size * input_size) as usize],
+                vec![1, 4 * hidden_size, input_size],
+            ),
+            initializer(
+                "R",
+                vec![0.0; (4 * hidden_size * hidden_size) as usize],
+                vec![1, 4 * hidden_size, hidden_size],
+            ),
+            initializer("initial_c", vec![1.0, 2.0, -4.0], vec![1, 1, hidden_size]),
+        ],
+        vec![node(
+            vec!["X", "W", "R", "", "", "", "initial_c"],
+            vec!["", "Y_h", "Y_c"],
+            "lstm",
+            "LSTM",
+            vec![attribute("hidden_size", hidden_size)],
+        )],
+    ));
+
+    let expected_cell = [0.25f32, 0.5, -1.0];
+    let expected_hidden: Vec<f32> = expected_cell.iter().map(|c| 0.5 * c.tanh()).collect();
+    for result in common::run_on_all_backends(&model, &input_data) {
+        let (y_h, y_c): (&[f32], &[f32]) = (
+            (&result["Y_h"]).try_into().unwrap(),
+            (&result["Y_c"]).try_into().unwrap(),
+        );
+        assert_abs_diff_eq!(y_h, expected_hidden.as_slice(), epsilon = 1e-5);
+        assert_abs_diff_eq!(y_c, &expected_cell[..], epsilon = 1e-5);
+    }
+}
+
+fn recurrent_node(
+    op: &str,
+    inputs: Vec<&str>,
+    outputs: Vec<&str>,
+    attributes: Vec<wonnx::onnx::AttributeProto>,
+) -> NodeProto {
+    node(inputs, outputs, "recurrent", op, attributes)
+}
+
+#[test]
+fn test_lstm_bidirectional() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let (sequence_length, batch_size, input_size, hidden_size) = (4, 2, 3, 5);
+    let x = values((sequence_length * batch_size * input_size) as usize, 1);
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x[..].into());
+
+    // Batch-first layout with all optional inputs present
+    let model = model(graph(
+        vec![tensor("X", &[batch_size, sequence_length, input_size])],
+        vec![
+            tensor("Y", &[batch_size, sequence_length, 2, hidden_size]),
+            tensor("Y_h", &[batch_size, 2, hidden_size]),
+            tensor("Y_c", &[batch_size, 2, hidden_size]),
+        ],
+        vec![],
+        vec![
+            initializer(
+                "W",
+                values((2 * 4 * hidden_size * input_size) as usize, 2),
+                vec![2, 4 * hidden_size, input_size],
+            ),
+            initializer(
+                "R",
+                values((2 * 4 * hidden_size * hidden_size) as usize, 3),
+                vec![2, 4 * hidden_size, hidden_size],
+            ),
+            initializer(
+                "B",
+                values((2 * 8 * hidden_size) as usize, 4),
+                vec![2, 8 * hidden_size],
+            ),
+            sequence_lengths("sequence_lens", vec![4, 3]),
+            initializer(
+                "initial_h",
+                values((batch_size * 2 * hidden_size) as usize, 5),
+                vec![batch_size, 2, hidden_size],
+            ),
+            initializer(
+                "initial_c",
+                values((batch_size * 2 * hidden_size) as usize, 6),
+                vec![batch_size, 2, hidden_size],
+            ),
+            initializer(
+                "P",
+                values((2 * 3 * hidden_size) as usize, 7),
+                vec![2, 3 * hidden_size],
+            ),
+        ],
+        vec![recurrent_node(
+            "LSTM",
+            vec![
+                "X",
+                "W",
+                "R",
+                "B",
+                "sequence_lens",
+                "initial_h",
+                "initial_c",
+                "P",
+            ],
+            vec!["Y", "Y_h", "Y_c"],
+            vec![
+                attribute("hidden_size", hidden_size),
+                attribute("direction", "bidirectional"),
+                attribute("layout", 1),
+                attribute("clip", 2.0),
+                attribute(
+                    "activations",
+                    [
+                        "Sigmoid",
+                        "Tanh",
+                        "Tanh",
+                        "HardSigmoid",
+                        "Softsign",
+                        "LeakyRelu",
+                    ]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<String>>(),
+                ),
+                attribute("activation_alpha", vec![0.3, 0.1]),
+            ],
+        )],
+    ));
+
+    let results = common::run_on_all_backends(&model, &input_data);
+    let output = |backend: usize, name: &str| -> Vec<f32> {
+        results[backend][name].clone().try_into().unwrap()
+    };
+    for name in ["Y", "Y_h", "Y_c"] {
+        assert_abs_diff_eq!(
+            output(0, name).as_slice(),
+            output(1, name).as_slice(),
+            epsilon = 1e-5
+        );
+    }
+    // The padding beyond the sequence length of the second batch element is zero
+    let y = output(0, "Y");
+    let padding = &y[(7 * 2 * hidden_size) as usize..];
+    assert!(padding.iter().all(|y| *y == 0.0));
+}
+
+#[test]
+fn test_gru() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let (sequence_length, batch_size, input_size, hidden_size) = (3, 2, 4, 300);
+    let x = values((sequence_length * batch_size * input_size) as usize, 1);
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x[..].into());
+
+    // A hidden size larger than the workgroup size, so that each thread calculates several units
+    for linear_before_reset in [0, 1] {
+        let model = model(graph(
+            vec![tensor("X", &[sequence_length, batch_size, input_size])],
+            vec![
+                tensor("Y", &[sequence_length, 1, batch_size, hidden_size]),
+                tensor("Y_h", &[1, batch_size, hidden_size]),
+            ],
+            vec![],
+            vec![
+                initializer(
+                    "W",
+                    values((3 * hidden_size * input_size) as usize, 2),
+                    vec![1, 3 * hidden_size, input_size],
+                ),
+                initializer(
+                    "R",
+                    values((3 * hidden_size * hidden_size) as usize, 3)
+                        .iter()
+                        .map(|r| r * 0.05)
+                        .collect(),
+                    vec![1, 3 * hidden_size, hidden_size],
+                ),
+                initializer(
+                    "B",
+                    values((6 * hidden_size) as usize, 4),
+                    vec![1, 6 * hidden_size],
+                ),
+            ],
+            vec![recurrent_node(
+                "GRU",
+                vec!["X", "W", "R", "B"],
+                vec!["Y", "Y_h"],
+                vec![
+                    attribute("hidden_size", hidden_size),
+                    attribute("linear_before_reset", linear_before_reset),
+                ],
+            )],
+        ));
+
+        let results = common::run_on_all_backends(&model, &input_data);
+        let output = |backend: usize, name: &str| -> Vec<f32> {
+            results[backend][name].clone().try_into().unwrap()
+        };
+        for name in ["Y", "Y_h"] {
+            assert_abs_diff_eq!(
+                output(0, name).as_slice(),
+                output(1, name).as_slice(),
+                epsilon = 1e-4
+            );
+        }
+        // The last step of Y is the final hidden state
+        assert_eq!(
+            &output(0, "Y")[((sequence_length - 1) * batch_size * hidden_size) as usize..],
+            output(0, "Y_h").as_slice()
+        );
+    }
+}
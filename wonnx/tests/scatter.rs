@@ -0,0 +1,135 @@
+use std::{collections::HashMap, convert::TryInto};
+use wonnx::{
+    onnx::AttributeProto,
+    utils::{attribute, graph, initializer_int64, model, node, tensor},
+};
+mod common;
+
+/// Runs ScatterElements or ScatterND (with constant indices) on all backends and checks the output
+#[allow(clippy::too_many_arguments)]
+fn assert_scatter(
+    op: &str,
+    data: &[f32],
+    data_shape: &[i64],
+    indices: Vec<i64>,
+    indices_shape: &[i64],
+    updates: &[f32],
+    updates_shape: &[i64],
+    output: &[f32],
+    attributes: Vec<AttributeProto>,
+) {
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), data.into());
+    input_data.insert("U".to_string(), updates.into());
+
+    // Model: (X, I, U) -> op -> Y
+    let model = model(graph(
+        vec![tensor("X", data_shape), tensor("U", updates_shape)],
+        vec![tensor("Y", data_shape)],
+        vec![],
+        vec![initializer_int64("I", indices, indices_shape.to_vec())],
+        vec![node(
+            vec!["X", "I", "U"],
+            vec!["Y"],
+            "scatter",
+            op,
+            attributes,
+        )],
+    ));
+
+    for result in common::run_on_all_backends(&model, &input_data) {
+        common::assert_eq_vector((&result["Y"]).try_into().unwrap(), output);
+    }
+}
+
+// Examples from https://github.com/onnx/onnx/blob/main/docs/Operators.md#ScatterElements
+#[test]
+fn scatter_elements() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    assert_scatter(
+        "ScatterElements",
+        &[0.0; 9],
+        &[3, 3],
+        vec![1, 0, 2, 0, 2, 1],
+        &[2, 3],
+        &[1.0, 1.1, 1.2, 2.0, 2.1, 2.2],
+        &[2, 3],
+        &[2.0, 1.1, 0.0, 1.0, 0.0, 2.2, 0.0, 2.1, 1.2],
+        vec![],
+    );
+
+    let data = [1.0, 2.0, 3.0, 4.0, 5.0];
+    assert_scatter(
+        "ScatterElements",
+        &data,
+        &[1, 5],
+        vec![1, -2],
+        &[1, 2],
+        &[1.1, 2.1],
+        &[1, 2],
+        &[1.0, 1.1, 3.0, 2.1, 5.0],
+        vec![attribute("axis", 1)],
+    );
+
+    // Duplicate indices are combined using the reduction
+    for (reduction, expected) in [("add", 6.0), ("mul", 7.5), ("max", 2.5), ("min", 1.5)] {
+        assert_scatter(
+            "ScatterElements",
+            &data,
+            &[1, 5],
+            vec![1, 1],
+            &[1, 2],
+            &[1.5, 2.5],
+            &[1, 2],
+            &[1.0, expected, 3.0, 4.0, 5.0],
+            vec![attribute("axis", 1), attribute("reduction", reduction)],
+        );
+    }
+}
+
+// Examples from https://github.com/onnx/onnx/blob/main/docs/Operators.md#ScatterND
+#[test]
+fn scatter_nd() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let data: Vec<f32> = (1..=8).map(|x| x as f32).collect();
+    assert_scatter(
+        "ScatterND",
+        &data,
+        &[8],
+        vec![4, 3, 1, 7],
+        &[4, 1],
+        &[9.0, 10.0, 11.0, 12.0],
+        &[4],
+        &[1.0, 11.0, 3.0, 10.0, 9.0, 6.0, 7.0, 12.0],
+        vec![],
+    );
+
+    // Index tuples select rows (and may count from the back)
+    let data: Vec<f32> = (1..=6).map(|x| x as f32).collect();
+    assert_scatter(
+        "ScatterND",
+        &data,
+        &[3, 2],
+        vec![-1, 0],
+        &[2, 1],
+        &[10.0, 20.0, 30.0, 40.0],
+        &[2, 2],
+        &[30.0, 40.0, 3.0, 4.0, 10.0, 20.0],
+        vec![],
+    );
+
+    // Index tuples select single elements, duplicates are combined using the reduction
+    assert_scatter(
+        "ScatterND",
+        &data,
+        &[3, 2],
+        vec![0, 1, 2, 0, 0, 1],
+        &[3, 2],
+        &[10.0, 20.0, 30.0],
+        &[3],
+        &[1.0, 600.0, 3.0, 4.0, 100.0, 6.0],
+        vec![attribute("reduction", "mul")],
+    );
+}
@@ -0,0 +1,218 @@
+use std::{collections::HashMap, convert::TryInto, sync::Arc};
+use wonnx::{
+    utils::{
+        graph, initializer_int64, model, node, tensor, InputTensor, OutputTensor, ScalarType, Shape,
+    },
+    wgpu::{self, util::DeviceExt},
+    Backend, GpuTensor, Pattern, Replacement, RewriteRule, Session, SessionConfig,
+};
+mod common;
+
+#[test]
+fn test_session_with_device() {
+    let n: usize = 16;
+    let mut input_data = HashMap::new();
+
+    let data: Vec<f32> = (0..n).map(|x| x as f32).collect();
+    let dims = vec![n as i64];
+    input_data.insert("X".to_string(), data.as_slice().into());
+
+    // Model: X -> Neg -> Y
+    let model = model(graph(
+        vec![tensor("X", &dims)],
+        vec![tensor("Y", &dims)],
+        vec![],
+        vec![],
+        vec![node(vec!["X"], vec!["Y"], "neg", "Neg", vec![])],
+    ));
+
+    // Two sessions that share the same device
+    let (device, queue) = pollster::block_on(common::request_device());
+    let config = SessionConfig::new().with_device(Arc::new(device), Arc::new(queue));
+    let first = pollster::block_on(Session::from_model_with_config(model.clone(), &config))
+        .expect("Session did not create");
+    let second = pollster::block_on(Session::from_model_with_config(model, &config))
+        .expect("Session did not create");
+
+    let expected: Vec<f32> = data.iter().map(|x| -x).collect();
+    for session in [first, second] {
+        let result = pollster::block_on(session.run(&input_data)).unwrap();
+        common::assert_eq_vector((&result["Y"]).try_into().unwrap(), &expected);
+    }
+}
+
+#[test]
+fn test_session_run_gpu() {
+    let n: usize = 16;
+    let data: Vec<f32> = (0..n).map(|x| x as f32).collect();
+    let dims = vec![n as i64];
+
+    // Model: X -> Neg -> Y
+    let model = model(graph(
+        vec![tensor("X", &dims)],
+        vec![tensor("Y", &dims)],
+        vec![],
+        vec![],
+        vec![node(vec!["X"], vec!["Y"], "neg", "Neg", vec![])],
+    ));
+
+    // Two sessions that share the same device, so the output of the first can be used as input for the second
+    let (device, queue) = pollster::block_on(common::request_device());
+    let (device, queue) = (Arc::new(device), Arc::new(queue));
+    let config = SessionConfig::new().with_device(device.clone(), queue);
+    let first = pollster::block_on(Session::from_model_with_config(model.clone(), &config))
+        .expect("Session did not create");
+    let second = pollster::block_on(Session::from_model_with_config(model, &config))
+        .expect("Session did not create");
+
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("X"),
+        contents: bytemuck::cast_slice(&data),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+    });
+    let shape = Shape::from(ScalarType::F32, &dims);
+    let mut inputs = HashMap::new();
+    inputs.insert("X".to_string(), GpuTensor::new(Arc::new(buffer), shape));
+
+    let intermediate = first.run_gpu(&inputs).unwrap();
+    let outputs = second
+        .run_gpu(&HashMap::from([(
+            "X".to_string(),
+            intermediate["Y"].clone(),
+        )]))
+        .unwrap();
+    let result = pollster::block_on(second.read_tensor(&outputs["Y"])).unwrap();
+    assert_eq!(result, OutputTensor::F32(data));
+}
+
+#[test]
+fn test_session_run_gpu_bound_input() {
+    let n: usize = 16;
+    let data: Vec<f32> = (0..n).map(|x| x as f32).collect();
+
+    // Model: X -> Reshape -> R -> Neg -> Y
+    let model = model(graph(
+        vec![tensor("X", &[n as i64])],
+        vec![tensor("R", &[4, 4]), tensor("Y", &[4, 4])],
+        vec![],
+        vec![initializer_int64("S", vec![4, 4], vec![2])],
+        vec![
+            node(vec!["X", "S"], vec!["R"], "reshape", "Reshape", vec![]),
+            node(vec!["R"], vec!["Y"], "neg", "Neg", vec![]),
+        ],
+    ));
+    let session = pollster::block_on(Session::from_model(model)).expect("Session did not create");
+    let (device, _) = session.device().unwrap();
+
+    // A buffer that cannot be copied from (it lacks the COPY_SRC usage) has to be bound in place of the input buffer
+    for run in 1..3 {
+        let input: Vec<f32> = data.iter().map(|x| x * run as f32).collect();
+        let buffer = Arc::new(
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("X"),
+                contents: bytemuck::cast_slice(&input),
+                usage: wgpu::BufferUsages::STORAGE,
+            }),
+        );
+        let inputs = HashMap::from([(
+            "X".to_string(),
+            GpuTensor::new(buffer.clone(), Shape::from(ScalarType::F32, &[n as i64])),
+        )]);
+        let outputs = session.run_gpu(&inputs).unwrap();
+        assert!(Arc::ptr_eq(outputs["R"].buffer(), &buffer));
+        let result = pollster::block_on(session.read_tensor(&outputs["Y"])).unwrap();
+        assert_eq!(
+            result,
+            OutputTensor::F32(input.iter().map(|x| -x).collect())
+        );
+    }
+}
+
+#[test]
+fn test_session_run_batch() {
+    let n: usize = 16;
+    let dims = vec![n as i64];
+
+    // Model: X -> Neg -> Y -> Neg -> Z
+    let model = model(graph(
+        vec![tensor("X", &dims)],
+        vec![tensor("Y", &dims), tensor("Z", &dims)],
+        vec![],
+        vec![],
+        vec![
+            node(vec!["X"], vec!["Y"], "neg1", "Neg", vec![]),
+            node(vec!["Y"], vec!["Z"], "neg2", "Neg", vec![]),
+        ],
+    ));
+
+    let session = pollster::block_on(Session::from_model_with_config(
+        model,
+        &SessionConfig::new().with_lanes(3),
+    ))
+    .expect("Session did not create");
+
+    // More requests than lanes, so that lanes are re-used
+    let data: Vec<Vec<f32>> = (0..5)
+        .map(|request| (0..n).map(|x| (x * request) as f32).collect())
+        .collect();
+    let batch: Vec<HashMap<String, InputTensor>> = data
+        .iter()
+        .map(|data| HashMap::from([("X".to_string(), data.as_slice().into())]))
+        .collect();
+
+    let results = pollster::block_on(session.run_batch(&batch)).unwrap();
+    assert_eq!(results.len(), data.len());
+    for (result, data) in results.iter().zip(data) {
+        let negated: Vec<f32> = data.iter().map(|x| -x).collect();
+        assert_eq!(result["Y"], OutputTensor::F32(negated));
+        assert_eq!(result["Z"], OutputTensor::F32(data));
+    }
+}
+
+#[test]
+fn test_session_rewrite_rule() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let dims = vec![4];
+
+    // Model: X, Y -> Sub -> Z
+    let model = model(graph(
+        vec![tensor("X", &dims), tensor("Y", &dims)],
+        vec![tensor("Z", &dims)],
+        vec![],
+        vec![],
+        vec![node(vec!["X", "Y"], vec!["Z"], "sub", "Sub", vec![])],
+    ));
+
+    // Rule: A - B => A + (-B)
+    let rule = RewriteRule::new(
+        "Sub",
+        Pattern::op("Sub").with_inputs(vec![Pattern::value("a"), Pattern::value("b")]),
+        |m| {
+            let (a, b) = (m.value("a")?, m.value("b")?);
+            let output = m.root().get_output()[0].as_str();
+            Some(
+                Replacement::nodes(vec![
+                    node(vec![b], vec!["negated"], "neg", "Neg", vec![]),
+                    node(vec![a, "negated"], vec![output], "add", "Add", vec![]),
+                ])
+                .with_shape("negated", m.shape("b")?),
+            )
+        },
+    );
+
+    let x = [1.0, 2.0, 3.0, 4.0];
+    let y = [4.0, 3.0, 2.0, 1.0];
+    let input_data: HashMap<String, InputTensor> = HashMap::from([
+        ("X".to_string(), x.as_slice().into()),
+        ("Y".to_string(), y.as_slice().into()),
+    ]);
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        let config = SessionConfig::new()
+            .with_backend(backend)
+            .with_rewrite_rule(rule.clone());
+        let session = pollster::block_on(Session::from_model_with_config(model.clone(), &config))
+            .expect("Session did not create");
+        let result = pollster::block_on(session.run(&input_data)).unwrap();
+        common::assert_eq_vector((&result["Z"]).try_into().unwrap(), &[-3.0, -1.0, 1.0, 3.0]);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use wonnx::{
+    onnx::{ModelProto, TensorProto, TensorProto_DataType},
+    utils::{
+        graph, initializer, initializer_int64, model, node, tensor, tensor_of_type, OutputTensor,
+    },
+    Backend, SessionError,
+};
+mod common;
+
+fn slice_model(
+    input_shape: &[i64],
+    output_shape: &[i64],
+    parameters: Vec<TensorProto>,
+) -> ModelProto {
+    let mut inputs = vec!["X"];
+    inputs.extend(parameters.iter().map(|p| p.get_name()));
+    model(graph(
+        vec![tensor("X", input_shape)],
+        vec![tensor("Y", output_shape)],
+        vec![],
+        parameters.clone(),
+        vec![node(inputs, vec!["Y"], "slice", "Slice", vec![])],
+    ))
+}
+
+fn assert_slice(model: &ModelProto, data: &[f32], expected: &[f32]) {
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), data.into());
+
+    for result in common::run_on_all_backends(model, &input_data) {
+        assert_eq!(result["Y"], OutputTensor::F32(expected.to_vec()));
+    }
+}
+
+#[test]
+fn test_slice() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let data: Vec<f32> = (0..12).map(|x| x as f32).collect();
+
+    // Axes default to the first axes
+    let model = slice_model(
+        &[3, 4],
+        &[2, 2],
+        vec![
+            initializer_int64("starts", vec![0, 1], vec![2]),
+            initializer_int64("ends", vec![2, 3], vec![2]),
+        ],
+    );
+    assert_slice(&model, &data, &[1.0, 2.0, 5.0, 6.0]);
+
+    // Negative steps, with a start that counts from the back and an end that is out of bounds
+    let model = slice_model(
+        &[3, 4],
+        &[3, 2],
+        vec![
+            initializer_int64("starts", vec![-1], vec![1]),
+            initializer_int64("ends", vec![i64::MIN], vec![1]),
+            initializer_int64("axes", vec![1], vec![1]),
+            initializer_int64("steps", vec![-2], vec![1]),
+        ],
+    );
+    assert_slice(&model, &data, &[3.0, 1.0, 7.0, 5.0, 11.0, 9.0]);
+
+    // Indices may be 32-bit integers
+    let mut starts = TensorProto::from(OutputTensor::I32(vec![1]), vec![1]);
+    starts.set_name("starts".to_string());
+    let mut ends = TensorProto::from(OutputTensor::I32(vec![100]), vec![1]);
+    ends.set_name("ends".to_string());
+    let mut axes = TensorProto::from(OutputTensor::I32(vec![0]), vec![1]);
+    axes.set_name("axes".to_string());
+    let model = slice_model(&[3, 4], &[2, 4], vec![starts, ends, axes]);
+    assert_slice(&model, &data, &data[4..]);
+}
+
+#[test]
+fn test_slice_constant() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // The slice of an initializer is folded by the optimizer
+    let model = model(graph(
+        vec![tensor("X", &[2])],
+        vec![tensor("Y", &[2])],
+        vec![tensor("S", &[2])],
+        vec![
+            initializer("C", vec![1.0, 2.0, 3.0, 4.0], vec![4]),
+            initializer_int64("starts", vec![1], vec![1]),
+            initializer_int64("ends", vec![4], vec![1]),
+            initializer_int64("steps", vec![2], vec![1]),
+        ],
+        vec![
+            node(
+                vec!["C", "starts", "ends", "", "steps"],
+                vec!["S"],
+                "slice",
+                "Slice",
+                vec![],
+            ),
+            node(vec!["X", "S"], vec!["Y"], "add", "Add", vec![]),
+        ],
+    ));
+    assert_slice(&model, &[10.0, 20.0], &[12.0, 24.0]);
+}
+
+#[test]
+fn test_slice_dynamic_starts() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    // The output shape depends on the starts, which are not known until inference
+    let model = model(graph(
+        vec![
+            tensor("X", &[3, 4]),
+            tensor_of_type("starts", &[1], TensorProto_DataType::INT64),
+        ],
+        vec![tensor("Y", &[2, 4])],
+        vec![],
+        vec![initializer_int64("ends", vec![3], vec![1])],
+        vec![node(
+            vec!["X", "starts", "ends"],
+            vec!["Y"],
+            "slice",
+            "Slice",
+            vec![],
+        )],
+    ));
+    for backend in [Backend::Gpu, Backend::Cpu] {
+        assert!(matches!(
+            common::run_with_backend(&model, &HashMap::new(), backend),
+            Err(SessionError::OptimizerError(_))
+        ));
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use wonnx::{
+    onnx::TensorProto_DataType,
+    utils::{
+        attribute, graph, initializer_int64, model, node, tensor, tensor_of_type, OutputTensor,
+    },
+};
+mod common;
+
+#[test]
+fn test_argmax_argmin() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // [[[3, 1, 3], [0, 2, 2]], [[5, 5, 1], [1, 4, 4]]]
+    let x: &[f32] = &[3.0, 1.0, 3.0, 0.0, 2.0, 2.0, 5.0, 5.0, 1.0, 1.0, 4.0, 4.0];
+    let input_data = HashMap::from([("X".to_string(), x.into())]);
+    let i64_type = TensorProto_DataType::INT64;
+    let model = model(graph(
+        vec![tensor("X", &[2, 2, 3])],
+        vec![
+            tensor_of_type("MAX", &[2, 2, 1], i64_type),
+            tensor_of_type("MAX_LAST", &[2, 2], i64_type),
+            tensor_of_type("MIN", &[1, 2, 3], i64_type),
+        ],
+        vec![],
+        vec![],
+        vec![
+            node(
+                vec!["X"],
+                vec!["MAX"],
+                "argmax",
+                "ArgMax",
+                vec![attribute("axis", -1)],
+            ),
+            node(
+                vec!["X"],
+                vec!["MAX_LAST"],
+                "argmax_last",
+                "ArgMax",
+                vec![
+                    attribute("axis", 2),
+                    attribute("keepdims", 0),
+                    attribute("select_last_index", 1),
+                ],
+            ),
+            node(vec!["X"], vec!["MIN"], "argmin", "ArgMin", vec![]),
+        ],
+    ));
+
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(result["MAX"], OutputTensor::I64(vec![0, 1, 0, 1]));
+        assert_eq!(result["MAX_LAST"], OutputTensor::I64(vec![2, 2, 1, 2]));
+        assert_eq!(result["MIN"], OutputTensor::I64(vec![0, 0, 1, 0, 0, 0]));
+    }
+}
+
+#[test]
+fn test_topk() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    // [[0, 4, 2, 4, 1], [7, -1, 3, 3, 5]]
+    let x: &[f32] = &[0.0, 4.0, 2.0, 4.0, 1.0, 7.0, -1.0, 3.0, 3.0, 5.0];
+    let input_data = HashMap::from([("X".to_string(), x.into())]);
+    let i64_type = TensorProto_DataType::INT64;
+    let model = model(graph(
+        vec![tensor("X", &[2, 5])],
+        vec![
+            tensor("V", &[2, 3]),
+            tensor_of_type("I", &[2, 3], i64_type),
+            tensor("SV", &[2, 2]),
+            tensor_of_type("SI", &[2, 2], i64_type),
+            tensor("AV", &[1, 5]),
+            tensor_of_type("AI", &[1, 5], i64_type),
+        ],
+        vec![],
+        vec![
+            initializer_int64("k", vec![3], vec![1]),
+            initializer_int64("k_smallest", vec![2], vec![1]),
+            initializer_int64("k_axis", vec![1], vec![1]),
+        ],
+        vec![
+            node(vec!["X", "k"], vec!["V", "I"], "topk", "TopK", vec![]),
+            node(
+                vec!["X", "k_smallest"],
+                vec!["SV", "SI"],
+                "topk_smallest",
+                "TopK",
+                vec![attribute("largest", 0)],
+            ),
+            node(
+                vec!["X", "k_axis"],
+                vec!["AV", "AI"],
+                "topk_axis",
+                "TopK",
+                vec![attribute("axis", 0)],
+            ),
+        ],
+    ));
+
+    for result in common::run_on_all_backends(&model, &input_data) {
+        // Equal values are ordered by index
+        assert_eq!(
+            result["V"],
+            OutputTensor::F32(vec![4.0, 4.0, 2.0, 7.0, 5.0, 3.0])
+        );
+        assert_eq!(result["I"], OutputTensor::I64(vec![1, 3, 2, 0, 4, 2]));
+        assert_eq!(result["SV"], OutputTensor::F32(vec![0.0, 1.0, -1.0, 3.0]));
+        assert_eq!(result["SI"], OutputTensor::I64(vec![0, 4, 1, 2]));
+        assert_eq!(
+            result["AV"],
+            OutputTensor::F32(vec![7.0, 4.0, 3.0, 4.0, 5.0])
+        );
+        assert_eq!(result["AI"], OutputTensor::I64(vec![1, 0, 1, 0, 1]));
+    }
+}
@@ -0,0 +1,323 @@
+use std::{collections::HashMap, convert::TryInto};
+
+use approx::assert_abs_diff_eq;
+use wonnx::{
+    onnx::NodeProto,
+    utils::{attribute, graph, initializer, model, node, tensor},
+};
+mod common;
+
+fn contrib_node(
+    inputs: Vec<&str>,
+    outputs: Vec<&str>,
+    name: &str,
+    op_type: &str,
+    attributes: Vec<wonnx::onnx::AttributeProto>,
+) -> NodeProto {
+    let mut node = node(inputs, outputs, name, op_type, attributes);
+    node.set_domain("com.microsoft".to_string());
+    node
+}
+
+fn assert_close(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len());
+    for (a, e) in actual.iter().zip(expected.iter()) {
+        assert_abs_diff_eq!(a, e, epsilon = 1e-4);
+    }
+}
+
+/// Reference implementation of layer normalization over rows of the given size
+fn layer_norm(x: &[f32], size: usize, scale: &[f32], bias: &[f32], epsilon: f32) -> Vec<f32> {
+    let mut output = vec![];
+    for row in x.chunks(size) {
+        let mean = row.iter().sum::<f32>() / size as f32;
+        let variance = row.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / size as f32;
+        for (i, v) in row.iter().enumerate() {
+            output.push((v - mean) / (variance + epsilon).sqrt() * scale[i] + bias[i]);
+        }
+    }
+    output
+}
+
+#[test]
+fn test_layer_normalization() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x: Vec<f32> = (0..12).map(|i| ((i * 7) % 5) as f32 - 1.5).collect();
+    let scale = [1.0, 0.5, 2.0, -1.0, 1.5, 0.25];
+    let bias = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x.as_slice().into());
+
+    let model = model(graph(
+        vec![tensor("X", &[2, 2, 3])],
+        vec![tensor("Y", &[2, 2, 3]), tensor("Z", &[2, 2, 3])],
+        vec![],
+        vec![
+            initializer("scale", scale[0..3].to_vec(), vec![3]),
+            initializer("bias", bias[0..3].to_vec(), vec![3]),
+            initializer("scale_2d", scale.to_vec(), vec![2, 3]),
+            initializer("bias_2d", bias.to_vec(), vec![2, 3]),
+        ],
+        vec![
+            node(
+                vec!["X", "scale", "bias"],
+                vec!["Y"],
+                "layer_norm",
+                "LayerNormalization",
+                vec![],
+            ),
+            node(
+                vec!["X", "scale_2d", "bias_2d"],
+                vec!["Z"],
+                "layer_norm_axis",
+                "LayerNormalization",
+                vec![attribute("axis", 1), attribute("epsilon", 0.01)],
+            ),
+        ],
+    ));
+
+    let expected_y = layer_norm(&x, 3, &scale[0..3], &bias[0..3], 1e-5);
+    let expected_z = layer_norm(&x, 6, &scale, &bias, 0.01);
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_close((&result["Y"]).try_into().unwrap(), &expected_y);
+        assert_close((&result["Z"]).try_into().unwrap(), &expected_z);
+    }
+}
+
+#[test]
+fn test_skip_layer_normalization() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x: Vec<f32> = (0..8).map(|i| i as f32 * 0.5).collect();
+    let skip: Vec<f32> = (0..8).map(|i| (i % 3) as f32).collect();
+    let (gamma, beta, bias) = (
+        [1.0, 2.0, 0.5, 1.0],
+        [0.0, 0.1, 0.2, 0.3],
+        [1.0, -1.0, 0.0, 0.5],
+    );
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x.as_slice().into());
+    input_data.insert("S".to_string(), skip.as_slice().into());
+
+    let model = model(graph(
+        vec![tensor("X", &[1, 2, 4]), tensor("S", &[1, 2, 4])],
+        vec![tensor("Y", &[1, 2, 4])],
+        vec![],
+        vec![
+            initializer("gamma", gamma.to_vec(), vec![4]),
+            initializer("beta", beta.to_vec(), vec![4]),
+            initializer("bias", bias.to_vec(), vec![4]),
+        ],
+        vec![contrib_node(
+            vec!["X", "S", "gamma", "beta", "bias"],
+            vec!["Y"],
+            "skip_layer_norm",
+            "SkipLayerNormalization",
+            vec![attribute("epsilon", 1e-5)],
+        )],
+    ));
+
+    let sum: Vec<f32> = (0..8).map(|i| x[i] + skip[i] + bias[i % 4]).collect();
+    let expected = layer_norm(&sum, 4, &gamma, &beta, 1e-5);
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_close((&result["Y"]).try_into().unwrap(), &expected);
+    }
+}
+
+#[test]
+fn test_decomposed_layer_normalization() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x: Vec<f32> = (0..8).map(|i| ((i * 3) % 7) as f32).collect();
+    let (scale, bias) = ([2.0, 1.0, 0.5, -1.0], [0.5, 0.0, -0.5, 1.0]);
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x.as_slice().into());
+
+    let axes = vec![attribute("axes", vec![-1])];
+    let model = model(graph(
+        vec![tensor("X", &[2, 4])],
+        vec![tensor("Y", &[2, 4])],
+        vec![
+            tensor("M", &[2, 1]),
+            tensor("D", &[2, 4]),
+            tensor("P", &[2, 4]),
+            tensor("V", &[2, 1]),
+            tensor("VE", &[2, 1]),
+            tensor("S", &[2, 1]),
+            tensor("N", &[2, 4]),
+            tensor("NS", &[2, 4]),
+        ],
+        vec![
+            initializer("two", vec![2.0], vec![]),
+            initializer("epsilon", vec![1e-5], vec![]),
+            initializer("scale", scale.to_vec(), vec![4]),
+            initializer("bias", bias.to_vec(), vec![4]),
+        ],
+        vec![
+            node(vec!["X"], vec!["M"], "mean", "ReduceMean", axes.clone()),
+            node(vec!["X", "M"], vec!["D"], "sub", "Sub", vec![]),
+            node(vec!["D", "two"], vec!["P"], "pow", "Pow", vec![]),
+            node(vec!["P"], vec!["V"], "variance", "ReduceMean", axes),
+            node(
+                vec!["V", "epsilon"],
+                vec!["VE"],
+                "add_epsilon",
+                "Add",
+                vec![],
+            ),
+            node(vec!["VE"], vec!["S"], "sqrt", "Sqrt", vec![]),
+            node(vec!["D", "S"], vec!["N"], "div", "Div", vec![]),
+            node(vec!["N", "scale"], vec!["NS"], "mul", "Mul", vec![]),
+            node(vec!["NS", "bias"], vec!["Y"], "add", "Add", vec![]),
+        ],
+    ));
+
+    let expected = layer_norm(&x, 4, &scale, &bias, 1e-5);
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_close((&result["Y"]).try_into().unwrap(), &expected);
+    }
+}
+
+#[test]
+fn test_gelu() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let x: Vec<f32> = (0..16).map(|i| i as f32 * 0.5 - 4.0).collect();
+    let bias = [0.5f32, -0.5, 1.0, 0.0];
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x.as_slice().into());
+
+    let model = model(graph(
+        vec![tensor("X", &[4, 4])],
+        vec![
+            tensor("Y", &[4, 4]),
+            tensor("T", &[4, 4]),
+            tensor("B", &[4, 4]),
+        ],
+        vec![],
+        vec![initializer("bias", bias.to_vec(), vec![4])],
+        vec![
+            node(vec!["X"], vec!["Y"], "gelu", "Gelu", vec![]),
+            node(
+                vec!["X"],
+                vec!["T"],
+                "gelu_tanh",
+                "Gelu",
+                vec![attribute("approximate", "tanh")],
+            ),
+            contrib_node(
+                vec!["X", "bias"],
+                vec!["B"],
+                "bias_gelu",
+                "BiasGelu",
+                vec![],
+            ),
+        ],
+    ));
+
+    // Values for erf(x/sqrt(2)) follow from the standard normal distribution
+    let gelu = |x: f32| {
+        let cdf = normal_cdf(x as f64);
+        (x as f64 * cdf) as f32
+    };
+    let gelu_tanh = |x: f32| {
+        0.5 * x * (1.0 + ((2.0 / std::f32::consts::PI).sqrt() * (x + 0.044715 * x.powi(3))).tanh())
+    };
+    let expected_y: Vec<f32> = x.iter().map(|x| gelu(*x)).collect();
+    let expected_t: Vec<f32> = x.iter().map(|x| gelu_tanh(*x)).collect();
+    let expected_b: Vec<f32> = (0..16).map(|i| gelu(x[i] + bias[i % 4])).collect();
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_close((&result["Y"]).try_into().unwrap(), &expected_y);
+        assert_close((&result["T"]).try_into().unwrap(), &expected_t);
+        assert_close((&result["B"]).try_into().unwrap(), &expected_b);
+    }
+}
+
+/// Cumulative distribution function of the standard normal distribution, by numerical integration of its density
+fn normal_cdf(x: f64) -> f64 {
+    let steps = 100_000;
+    let (from, to) = (-12.0, x);
+    let h = (to - from) / steps as f64;
+    let density = |t: f64| (-t * t / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt();
+    let interior: f64 = (1..steps).map(|i| density(from + i as f64 * h)).sum();
+    h * ((density(from) + density(to)) / 2.0 + interior)
+}
+
+#[test]
+fn test_attention() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let (batch_size, sequence_length, input_size, hidden_size) = (2, 3, 4, 4);
+    let x: Vec<f32> = (0..batch_size * sequence_length * input_size)
+        .map(|i| ((i * 5) % 9) as f32 * 0.25 - 1.0)
+        .collect();
+    let weights: Vec<f32> = (0..input_size * 3 * hidden_size)
+        .map(|i| ((i * 7) % 11) as f32 * 0.1 - 0.5)
+        .collect();
+    let bias: Vec<f32> = (0..3 * hidden_size).map(|i| (i % 4) as f32 * 0.1).collect();
+    let mask = [1i32, 1, 1, 1, 1, 0];
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x.as_slice().into());
+    input_data.insert("M".to_string(), mask[..].into());
+
+    let shape = [batch_size as i64, sequence_length as i64, input_size as i64];
+    let model = model(graph(
+        vec![
+            tensor("X", &shape),
+            wonnx::utils::tensor_of_type(
+                "M",
+                &shape[0..2],
+                wonnx::onnx::TensorProto_DataType::INT32,
+            ),
+        ],
+        vec![tensor("Y", &shape), tensor("U", &shape)],
+        vec![],
+        vec![
+            initializer(
+                "weights",
+                weights.clone(),
+                vec![input_size as i64, 3 * hidden_size as i64],
+            ),
+            initializer("bias", bias.clone(), vec![3 * hidden_size as i64]),
+        ],
+        vec![
+            contrib_node(
+                vec!["X", "weights", "bias", "M"],
+                vec!["Y"],
+                "attention",
+                "Attention",
+                vec![attribute("num_heads", 2)],
+            ),
+            contrib_node(
+                vec!["X", "weights", "bias"],
+                vec!["U"],
+                "attention_unidirectional",
+                "Attention",
+                vec![attribute("num_heads", 2), attribute("unidirectional", 1)],
+            ),
+        ],
+    ));
+
+    let results = common::run_on_all_backends(&model, &input_data);
+    let (gpu, cpu) = (&results[0], &results[1]);
+    assert_close(
+        (&gpu["Y"]).try_into().unwrap(),
+        (&cpu["Y"]).try_into().unwrap(),
+    );
+    let cpu_u: &[f32] = (&cpu["U"]).try_into().unwrap();
+    assert_close((&gpu["U"]).try_into().unwrap(), cpu_u);
+
+    // With unidirectional attention, the first element of each sequence only attends to itself, so its output is the
+    // value projection of its input
+    for b in 0..batch_size {
+        let row = &x[(b * sequence_length * input_size)..][..input_size];
+        let value: Vec<f32> = (0..hidden_size)
+            .map(|j| {
+                bias[2 * hidden_size + j]
+                    + (0..input_size)
+                        .map(|i| row[i] * weights[i * 3 * hidden_size + 2 * hidden_size + j])
+                        .sum::<f32>()
+            })
+            .collect();
+        assert_close(
+            &cpu_u[(b * sequence_length * hidden_size)..][..hidden_size],
+            &value,
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::{collections::HashMap, convert::TryInto};
+
+use protobuf::ProtobufEnum;
+use wonnx::{
+    onnx::{TensorProto, TensorProto_DataType},
+    utils::{attribute, graph, model, node, tensor, tensor_of_type, OutputTensor},
+};
+mod common;
+
+#[test]
+fn test_data_types() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let n: usize = 8;
+    let dims = vec![n as i64];
+    let x: Vec<f32> = (0..n).map(|i| i as f32 - 3.5).collect();
+    let y: Vec<f32> = (0..n).map(|i| (n - i) as f32 - 4.0).collect();
+    let s = [0.0f32];
+    let a: Vec<i8> = (0..n).map(|i| i as i8 * 10 - 40).collect();
+    let b: Vec<i8> = (0..n).map(|i| -(i as i8)).collect();
+    let u: Vec<u16> = (0..n).map(|i| i as u16 * 10).collect();
+    let d: Vec<f64> = (0..n).map(|i| i as f64 * 0.5).collect();
+
+    let mut input_data = HashMap::new();
+    input_data.insert("X".to_string(), x.as_slice().into());
+    input_data.insert("Y".to_string(), y.as_slice().into());
+    input_data.insert("S".to_string(), s[..].into());
+    input_data.insert("A".to_string(), a.as_slice().into());
+    input_data.insert("U".to_string(), u.as_slice().into());
+    input_data.insert("D".to_string(), d.as_slice().into());
+
+    let mut b_initializer = TensorProto::from(OutputTensor::I8(b.clone()), dims.clone());
+    b_initializer.set_name("B".to_string());
+
+    let bool_type = TensorProto_DataType::BOOL;
+    let model = model(graph(
+        vec![
+            tensor("X", &dims),
+            tensor("Y", &dims),
+            tensor("S", &[1]),
+            tensor_of_type("A", &dims, TensorProto_DataType::INT8),
+            tensor_of_type("U", &dims, TensorProto_DataType::UINT16),
+            tensor_of_type("D", &dims, TensorProto_DataType::DOUBLE),
+        ],
+        vec![
+            tensor_of_type("G", &dims, bool_type),
+            tensor_of_type("L", &dims, bool_type),
+            tensor("GF", &dims),
+            tensor_of_type("XB", &dims, bool_type),
+            tensor_of_type("AB", &dims, TensorProto_DataType::INT8),
+            tensor_of_type("UU", &dims, TensorProto_DataType::UINT16),
+            tensor_of_type("DN", &dims, TensorProto_DataType::DOUBLE),
+        ],
+        vec![],
+        vec![b_initializer],
+        vec![
+            node(vec!["X", "Y"], vec!["G"], "greater", "Greater", vec![]),
+            node(vec!["X", "S"], vec!["L"], "less", "Less", vec![]),
+            node(
+                vec!["G"],
+                vec!["GF"],
+                "g_to_float",
+                "Cast",
+                vec![attribute("to", TensorProto_DataType::FLOAT.value() as i64)],
+            ),
+            node(
+                vec!["X"],
+                vec!["XB"],
+                "x_to_bool",
+                "Cast",
+                vec![attribute("to", bool_type.value() as i64)],
+            ),
+            node(vec!["A", "B"], vec!["AB"], "add", "Add", vec![]),
+            node(vec!["U", "U"], vec!["UU"], "mul", "Mul", vec![]),
+            node(vec!["D"], vec!["DN"], "neg", "Neg", vec![]),
+        ],
+    ));
+
+    let greater: Vec<bool> = x.iter().zip(&y).map(|(x, y)| x > y).collect();
+    let greater_float: Vec<f32> = greater.iter().map(|g| *g as u8 as f32).collect();
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(result["G"], OutputTensor::Bool(greater.clone()));
+        assert_eq!(
+            result["L"],
+            OutputTensor::Bool(x.iter().map(|x| *x < 0.0).collect())
+        );
+        let gf: Vec<f32> = result["GF"].clone().try_into().unwrap();
+        assert_eq!(gf, greater_float);
+        assert_eq!(result["XB"], OutputTensor::Bool(vec![true; n]));
+        assert_eq!(
+            result["AB"],
+            OutputTensor::I8(a.iter().zip(&b).map(|(a, b)| a + b).collect())
+        );
+        assert_eq!(
+            result["UU"],
+            OutputTensor::U16(u.iter().map(|u| u * u).collect())
+        );
+        assert_eq!(
+            result["DN"],
+            OutputTensor::F64(d.iter().map(|d| -d).collect())
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+use wonnx::{
+    onnx::TensorProto_DataType,
+    utils::{graph, model, node, tensor, tensor_of_type, OutputTensor},
+};
+mod common;
+
+#[test]
+fn test_where() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let condition = [true, false, true];
+    let x = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0];
+    let y = [-1.0f32, -2.0];
+    let mut input_data = HashMap::new();
+    input_data.insert("C".to_string(), condition[..].into());
+    input_data.insert("X".to_string(), x[..].into());
+    input_data.insert("Y".to_string(), y[..].into());
+
+    // The condition is broadcast along the rows, Y along the columns
+    let model = model(graph(
+        vec![
+            tensor_of_type("C", &[3], TensorProto_DataType::BOOL),
+            tensor("X", &[2, 3]),
+            tensor("Y", &[2, 1]),
+        ],
+        vec![tensor("Z", &[2, 3])],
+        vec![],
+        vec![],
+        vec![node(
+            vec!["C", "X", "Y"],
+            vec!["Z"],
+            "where",
+            "Where",
+            vec![],
+        )],
+    ));
+
+    for result in common::run_on_all_backends(&model, &input_data) {
+        assert_eq!(
+            result["Z"],
+            OutputTensor::F32(vec![1.0, -1.0, 3.0, 4.0, -2.0, 6.0])
+        );
+    }
+}